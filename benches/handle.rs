@@ -0,0 +1,43 @@
+//! Benchmarks of `Handler::handle` with signed `push` deliveries of GitHub of various sizes
+//!
+//! Run with `cargo bench --features bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use rifling::bench::SyntheticDelivery;
+use rifling::{Constructor, Delivery, Handler, Hook};
+
+const SECRET: &str = "secret";
+
+/// Handler with a single hook for `push`, and another one filtered out by its condition
+fn handler() -> Handler {
+    let mut cons = Constructor::new();
+    cons.register(Hook::new(
+        "push",
+        Some(SECRET.to_string()),
+        |_: &Delivery| (),
+    ));
+    cons.register(
+        Hook::new("*", Some(SECRET.to_string()), |_: &Delivery| ())
+            .when(|delivery: &Delivery| delivery.event() != "push"),
+    );
+    Handler::from(&cons)
+}
+
+fn handle(c: &mut Criterion) {
+    let handler = handler();
+    let mut group = c.benchmark_group("handle");
+    for size in [1024, 64 * 1024, 1024 * 1024] {
+        let delivery = SyntheticDelivery::github_push(SECRET, size);
+        group.throughput(Throughput::Bytes(delivery.body.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(size),
+            &delivery,
+            |b, delivery| b.iter(|| delivery.send(&handler)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, handle);
+criterion_main!(benches);
@@ -3,21 +3,18 @@
 #[cfg(feature = "logging")]
 #[macro_use]
 extern crate log;
-extern crate hyper;
 extern crate pretty_env_logger;
 
-#[macro_use]
+#[cfg_attr(not(feature = "logging"), macro_use)]
 extern crate rifling;
 
-use hyper::rt::Future;
-use hyper::Server;
-
+use rifling::server::Builder;
 use rifling::{Constructor, Delivery, DeliveryType, Hook};
 
 use std::env;
 
 fn main() {
-    if let Err(_) = env::var("RIFLING_LOG") {
+    if env::var("RIFLING_LOG").is_err() {
         env::set_var("RIFLING_LOG", "info")
     }
     pretty_env_logger::init_custom_env("RIFLING_LOG");
@@ -25,19 +22,19 @@ fn main() {
     let hook = Hook::new("*", Some(String::from("secret")), |delivery: &Delivery| {
         #[cfg(feature = "parse")]
         {
-            if let Some(payload) = &delivery.payload {
+            if let Some(payload) = delivery.payload() {
                 info!(
                     "Bazinga! Received \"{}\" action!",
-                    match delivery.delivery_type {
+                    match delivery.delivery_type() {
                         DeliveryType::GitHub => payload["action"].as_str().unwrap(),
                         DeliveryType::GitLab => payload["event_name"].as_str().unwrap(),
-                        _ => &delivery.event.as_str(),
+                        _ => delivery.event(),
                     }
                 );
             }
         }
         #[cfg(not(feature = "parse"))]
-        info!("Received \"{}\" action!", &delivery.event);
+        info!("Received \"{}\" action!", delivery.event());
     });
     let another_hook = Hook::new("push", Some(String::from("secret")), |_: &Delivery| {
         info!("Pushed!");
@@ -48,10 +45,11 @@ fn main() {
     cons.register(hook);
     cons.register(another_hook);
     cons.register(gitlab_push_hook);
-    let addr = "0.0.0.0:4567".parse().unwrap();
-    let server = Server::bind(&addr)
-        .serve(cons)
-        .map_err(|e| println!("Error: {:?}", e));
+    // Listen on `RIFLING_ADDR`, or on `HOST` and `PORT` (0.0.0.0:4567 by default), accept HTTP/2 too if
+    // `RIFLING_HTTP2` is set
+    let builder = Builder::from_env()
+        .unwrap()
+        .http1_only(env::var("RIFLING_HTTP2").is_err());
     info!("Starting up...");
-    hyper::rt::run(server);
+    builder.run(cons).unwrap();
 }
@@ -0,0 +1,69 @@
+//! Organization automation bot with the typed payloads of GitHub
+//!
+//! Welcomes new members of teams, keeps track of blocked users and thanks sponsors.
+
+extern crate rifling;
+
+use rifling::github::{MembershipEvent, OrgBlockEvent, OrganizationEvent, SponsorshipEvent};
+use rifling::server::Builder;
+use rifling::{Constructor, Delivery, Hook};
+
+fn main() {
+    let secret = Some(String::from("secret"));
+    let mut cons = Constructor::new();
+    cons.register(Hook::typed(
+        "membership",
+        secret.clone(),
+        |event: MembershipEvent, _: &Delivery| {
+            if event.is_added() {
+                println!(
+                    "Welcome @{} to {}/{}!",
+                    event.member.login, event.organization.login, event.team.slug
+                );
+            }
+        },
+    ));
+    cons.register(Hook::typed(
+        "organization",
+        secret.clone(),
+        |event: OrganizationEvent, _: &Delivery| {
+            if let (Some(member), "member_invited") = (event.member(), event.action.as_str()) {
+                println!(
+                    "@{} invited @{} to {}",
+                    event.sender.login, member.login, event.organization.login
+                );
+            }
+        },
+    ));
+    cons.register(Hook::typed(
+        "org_block",
+        secret.clone(),
+        |event: OrgBlockEvent, _: &Delivery| {
+            let verb = if event.is_blocked() {
+                "blocked"
+            } else {
+                "unblocked"
+            };
+            println!(
+                "@{} {} @{}",
+                event.sender.login, verb, event.blocked_user.login
+            );
+        },
+    ));
+    cons.register(Hook::typed(
+        "sponsorship",
+        secret,
+        |event: SponsorshipEvent, _: &Delivery| match event.action.as_str() {
+            "created" => println!(
+                "Thank you @{} for sponsoring ${}.{:02} a month!",
+                event.sponsor().login,
+                event.monthly_price_in_cents() / 100,
+                event.monthly_price_in_cents() % 100
+            ),
+            "cancelled" => println!("@{} stopped sponsoring", event.sponsor().login),
+            action => println!("Sponsorship of @{} {}", event.sponsor().login, action),
+        },
+    ));
+    // Listen on `RIFLING_ADDR`, or on `HOST` and `PORT` (0.0.0.0:4567 by default)
+    Builder::from_env().unwrap().run(cons).unwrap();
+}
@@ -0,0 +1,90 @@
+//! # rifling-macros
+//!
+//! Procedural macros for [rifling](https://crates.io/crates/rifling), use them through the `macros` feature
+//! of rifling rather than depending on this crate directly.
+//!
+//! ```ignore
+//! #[rifling::hook(event = "push", secret_env = "HOOK_SECRET")]
+//! fn on_push(delivery: &Delivery) {
+//!     println!("Pushed!");
+//! }
+//!
+//! let cons = rifling::collect_hooks!();
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr};
+
+/// Arguments of the `hook` attribute
+#[derive(Default)]
+struct HookArgs {
+    event: Option<LitStr>,
+    secret: Option<LitStr>,
+    secret_env: Option<LitStr>,
+}
+
+/// Declare a free function as a hook
+///
+/// Arguments:
+///
+///  - `event` (required): Event the hook listens to.
+///  - `secret`: Secret to authenticate the payload.
+///  - `secret_env`: Name of the environment variable containing the secret, read when the hooks are collected.
+///
+/// The function keeps being usable as a normal function, and is registered by `rifling::collect_hooks!()`.
+#[proc_macro_attribute]
+pub fn hook(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut hook_args = HookArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        let target = if meta.path.is_ident("event") {
+            &mut hook_args.event
+        } else if meta.path.is_ident("secret") {
+            &mut hook_args.secret
+        } else if meta.path.is_ident("secret_env") {
+            &mut hook_args.secret_env
+        } else {
+            return Err(meta.error("expected `event`, `secret` or `secret_env`"));
+        };
+        *target = Some(meta.value()?.parse()?);
+        Ok(())
+    });
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    let event = match hook_args.event {
+        Some(event) => event,
+        None => {
+            return syn::Error::new_spanned(&function.sig.ident, "missing `event = \"...\"`")
+                .to_compile_error()
+                .into()
+        }
+    };
+    let secret = match (hook_args.secret, hook_args.secret_env) {
+        (Some(_), Some(secret_env)) => {
+            return syn::Error::new_spanned(
+                secret_env,
+                "`secret` and `secret_env` can't be used together",
+            )
+            .to_compile_error()
+            .into()
+        }
+        (Some(secret), None) => {
+            quote!(::std::option::Option::Some(::std::string::String::from(#secret)))
+        }
+        (None, Some(secret_env)) => quote!(::std::env::var(#secret_env).ok()),
+        (None, None) => quote!(::std::option::Option::None),
+    };
+    let name = &function.sig.ident;
+    let expanded = quote! {
+        #function
+
+        ::rifling::inventory::submit! {
+            ::rifling::collect::HookRegistration::new(|| {
+                ::rifling::Hook::new(#event, #secret, #name)
+            })
+        }
+    };
+    expanded.into()
+}
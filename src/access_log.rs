@@ -0,0 +1,251 @@
+//! Access log
+//!
+//! With `Constructor::access_log` or `Constructor::access_log_writer`, one entry is emitted per request, with the
+//! timing of the request and of each hook run while handling it. Entries are formatted as JSON lines with
+//! `AccessLogEntry::to_json`, suitable for log collectors:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//!
+//! let mut cons = Constructor::new();
+//! cons.access_log_writer(std::io::stderr());
+//! ```
+//!
+//! ```text
+//! {"timestamp":"2019-03-01T12:00:00.000Z","remote_addr":"127.0.0.1","provider":"GitHub","event":"push","delivery_id":"72d3162e","status":200,"duration_ms":1.204,"hooks":[{"event":"push","duration_ms":0.931,"ok":true}]}
+//! ```
+//!
+//! Hooks retried after a delay run after the entry is emitted, so they're not included.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::io;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Callback receiving the entries of the access log
+pub type AccessLogCallback = Arc<dyn Fn(&AccessLogEntry) + Send + Sync>;
+
+/// Timing of a hook run
+#[derive(Clone, Debug, PartialEq)]
+pub struct HookTiming {
+    pub event: &'static str,
+    pub name: Option<String>, // set by `Hook::named`
+    pub duration: Duration,
+    pub ok: bool,
+}
+
+/// Entry of the access log, one per request
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessLogEntry {
+    /// Time the request was received
+    pub timestamp: SystemTime,
+    pub remote_addr: Option<IpAddr>,
+    /// Provider of the delivery (e.g. `GitHub`), if the request is recognized as one
+    pub provider: Option<String>,
+    pub event: Option<String>,
+    pub delivery_id: Option<String>,
+    /// See `Constructor::request_id`
+    pub request_id: Option<String>,
+    /// Status code of the response
+    pub status: u16,
+    /// Time taken to handle the request
+    pub duration: Duration,
+    /// Hooks run while handling the request
+    pub hooks: Vec<HookTiming>,
+}
+
+thread_local! {
+    /// Timings of the hooks run for the request being handled in this thread
+    static TIMINGS: RefCell<Option<Vec<HookTiming>>> = const { RefCell::new(None) };
+}
+
+/// Start collecting the timings of hooks run in this thread
+pub(crate) fn begin() {
+    TIMINGS.with(|timings| *timings.borrow_mut() = Some(Vec::new()));
+}
+
+/// Record the timing of the hook, if collecting
+pub(crate) fn record(event: &'static str, name: Option<&str>, duration: Duration, ok: bool) {
+    TIMINGS.with(|timings| {
+        if let Some(timings) = timings.borrow_mut().as_mut() {
+            timings.push(HookTiming {
+                event,
+                name: name.map(str::to_string),
+                duration,
+                ok,
+            });
+        }
+    });
+}
+
+/// Stop collecting and take the timings
+pub(crate) fn finish() -> Vec<HookTiming> {
+    TIMINGS.with(|timings| timings.borrow_mut().take().unwrap_or_default())
+}
+
+/// Callback writing JSON lines to the writer
+pub(crate) fn writer(writer: impl io::Write + Send + 'static) -> AccessLogCallback {
+    let writer = Mutex::new(writer);
+    Arc::new(move |entry: &AccessLogEntry| {
+        let mut writer = match writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Err(error) = writeln!(writer, "{}", entry.to_json()) {
+            debug!("Unable to write access log: {}", error);
+        }
+    })
+}
+
+/// Write the string as a JSON string
+pub(crate) fn write_string(json: &mut String, string: &str) {
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Write the optional string as a JSON string or null
+fn write_option(json: &mut String, string: Option<&str>) {
+    match string {
+        Some(string) => write_string(json, string),
+        None => json.push_str("null"),
+    }
+}
+
+/// Duration in milliseconds with microsecond precision
+fn millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// Format the time as RFC 3339 in UTC with milliseconds (e.g. `2019-03-01T12:00:00.000Z`)
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Convert days since the epoch to the civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// The main impl clause of `AccessLogEntry`
+impl AccessLogEntry {
+    /// Format the entry as a single line of JSON
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"timestamp\":");
+        write_string(&mut json, &rfc3339(self.timestamp));
+        json.push_str(",\"remote_addr\":");
+        let remote_addr = self.remote_addr.map(|addr| addr.to_string());
+        write_option(&mut json, remote_addr.as_deref());
+        json.push_str(",\"provider\":");
+        write_option(&mut json, self.provider.as_deref());
+        json.push_str(",\"event\":");
+        write_option(&mut json, self.event.as_deref());
+        json.push_str(",\"delivery_id\":");
+        write_option(&mut json, self.delivery_id.as_deref());
+        if let Some(request_id) = &self.request_id {
+            json.push_str(",\"request_id\":");
+            write_string(&mut json, request_id);
+        }
+        let _ = write!(
+            json,
+            ",\"status\":{},\"duration_ms\":{},\"hooks\":[",
+            self.status,
+            millis(self.duration)
+        );
+        for (index, hook) in self.hooks.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            json.push_str("{\"event\":");
+            write_string(&mut json, hook.event);
+            if let Some(name) = &hook.name {
+                json.push_str(",\"name\":");
+                write_string(&mut json, name);
+            }
+            let _ = write!(
+                json,
+                ",\"duration_ms\":{},\"ok\":{}}}",
+                millis(hook.duration),
+                hook.ok
+            );
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test formatting entries as JSON
+    #[test]
+    fn to_json() {
+        let entry = AccessLogEntry {
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_551_441_600_123),
+            remote_addr: Some("127.0.0.1".parse().unwrap()),
+            provider: Some("GitHub".to_string()),
+            event: Some("push".to_string()),
+            delivery_id: Some("\"quoted\"\n".to_string()),
+            request_id: None,
+            status: 200,
+            duration: Duration::from_micros(1204),
+            hooks: vec![
+                HookTiming {
+                    event: "push",
+                    name: None,
+                    duration: Duration::from_micros(931),
+                    ok: true,
+                },
+                HookTiming {
+                    event: "*",
+                    name: Some("deploy-prod".to_string()),
+                    duration: Duration::from_micros(12),
+                    ok: false,
+                },
+            ],
+        };
+        assert_eq!(
+            entry.to_json(),
+            r#"{"timestamp":"2019-03-01T12:00:00.123Z","remote_addr":"127.0.0.1","provider":"GitHub","event":"push","delivery_id":"\"quoted\"\n","status":200,"duration_ms":1.204,"hooks":[{"event":"push","duration_ms":0.931,"ok":true},{"event":"*","name":"deploy-prod","duration_ms":0.012,"ok":false}]}"#
+        );
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00.000Z"
+        );
+    }
+}
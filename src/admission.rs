@@ -0,0 +1,94 @@
+//! Kubernetes admission review
+//!
+//! With `Constructor::admission_review(true)` (requires `parse`), rifling serves the `AdmissionReview` requests
+//! sent by Kubernetes to validating admission webhooks. The envelope is available as the payload of the delivery,
+//! hooks are registered for `admission.<kind>.<operation>` (e.g. `admission.pod.create`).
+//!
+//! Every matched hook decides whether the object is admitted: returning `Err(reason)` denies it, and the reply is
+//! wrapped back into an `AdmissionReview`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.admission_review(true);
+//! cons.register(Hook::new("admission.pod.create", None, |delivery: &Delivery| {
+//!     let namespace = delivery
+//!         .payload()
+//!         .and_then(|payload| payload.pointer("/request/namespace"))
+//!         .and_then(|namespace| namespace.as_str());
+//!     match namespace {
+//!         Some("kube-system") => Err("Pods can't be created in kube-system"),
+//!         _ => Ok(()),
+//!     }
+//! }));
+//! ```
+//!
+//! Objects without matched hook are admitted. Reviews go through the same checks as other deliveries (rate limits,
+//! validators, sinks, response overrides, ...) before hooks are run. During maintenance they're answered with the
+//! maintenance status instead, Kubernetes applies the `failurePolicy` of the webhook then. Hooks are run synchronously and never retried.
+
+use serde_json::Value;
+
+use super::handler::Delivery;
+
+/// Reply to an `AdmissionReview` request
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdmissionResponse {
+    /// Version of the review, the same as the request's
+    pub api_version: String,
+    /// UID of the request
+    pub uid: String,
+    pub allowed: bool,
+    /// Reason of the denial
+    pub message: Option<String>,
+}
+
+/// The event of the admission review, `admission.<kind>.<operation>` (e.g. `admission.pod.create`)
+pub(crate) fn event(payload: &Value) -> Option<String> {
+    let kind = payload.pointer("/request/kind/kind")?.as_str()?;
+    let operation = payload.pointer("/request/operation")?.as_str()?;
+    Some(format!("admission.{}.{}", kind, operation))
+}
+
+/// The main impl clause of `AdmissionResponse`
+impl AdmissionResponse {
+    /// Admit the object of the review in the delivery
+    pub(crate) fn allow(delivery: &Delivery) -> Self {
+        let payload = delivery.payload.as_ref();
+        let api_version = payload
+            .and_then(|payload| payload.get("apiVersion"))
+            .and_then(Value::as_str)
+            .unwrap_or("admission.k8s.io/v1");
+        Self {
+            api_version: api_version.to_string(),
+            uid: delivery.id.clone().unwrap_or_default(),
+            allowed: true,
+            message: None,
+        }
+    }
+
+    /// Deny the object for the reason
+    pub(crate) fn deny(&mut self, message: String) {
+        self.allowed = false;
+        self.message = Some(message);
+    }
+
+    /// The `AdmissionReview` sent back to Kubernetes
+    pub fn to_review(&self) -> Value {
+        let mut response = serde_json::json!({
+            "uid": self.uid,
+            "allowed": self.allowed,
+        });
+        if let Some(message) = &self.message {
+            response["status"] = serde_json::json!({ "code": 403, "message": message });
+        }
+        serde_json::json!({
+            "apiVersion": self.api_version,
+            "kind": "AdmissionReview",
+            "response": response,
+        })
+    }
+}
@@ -0,0 +1,136 @@
+//! Benchmarks
+//!
+//! Helpers driving `Handler::handle` with synthetic signed deliveries, to measure the throughput of your own hooks
+//! (the benchmarks of rifling itself use them too, see `cargo bench --features bench`):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::bench::{self, SyntheticDelivery};
+//! use rifling::{Constructor, Delivery, Handler, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.register(Hook::new("push", Some("secret".to_string()), |_: &Delivery| ()));
+//! let handler = Handler::from(&cons);
+//!
+//! let delivery = SyntheticDelivery::github_push("secret", 64 * 1024);
+//! assert_eq!(delivery.send(&handler).status(), 200);
+//! let measurement = bench::measure(&handler, &delivery, 10);
+//! println!("{:.0} deliveries per second", measurement.per_second());
+//! ```
+//!
+//! Deliveries are signed with HMAC-SHA256 if a cryptography library is enabled.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use super::crypto::{self, Algorithm};
+use super::handler::Handler;
+use super::outcome::Response;
+
+/// Request carrying a synthetic delivery
+#[derive(Clone, Debug)]
+pub struct SyntheticDelivery {
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Result of `measure`
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    pub iterations: u32,
+    pub elapsed: Duration,
+}
+
+/// The main impl clause of `SyntheticDelivery`
+impl SyntheticDelivery {
+    /// Create a `push` delivery of GitHub with a payload of at least the given size in bytes, signed with the secret
+    pub fn github_push(secret: &str, size: usize) -> Self {
+        let body = payload(size);
+        let mut headers = vec![
+            ("X-GitHub-Event".to_string(), "push".to_string()),
+            (
+                "X-GitHub-Delivery".to_string(),
+                "72d3162e-cc78-11e3-81ab-4c9367dc0958".to_string(),
+            ),
+            ("Content-Type".to_string(), "application/json".to_string()),
+        ];
+        if let Some(signature) =
+            crypto::sign_hmac(Algorithm::Sha256, secret.as_bytes(), body.as_bytes())
+        {
+            headers.push((
+                "X-Hub-Signature-256".to_string(),
+                format!("sha256={}", hex::encode(signature)),
+            ));
+        }
+        Self { headers, body }
+    }
+
+    /// Handle the delivery with the handler
+    pub fn send(&self, handler: &Handler) -> Response {
+        handler.handle(
+            self.headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+            &self.body,
+        )
+    }
+}
+
+/// The main impl clause of `Measurement`
+impl Measurement {
+    /// Number of deliveries handled per second
+    pub fn per_second(&self) -> f64 {
+        f64::from(self.iterations) / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Payload of a `push` event of at least the given size in bytes, padded with commits
+pub fn payload(size: usize) -> String {
+    let mut payload = String::from(
+        r#"{"ref":"refs/heads/master","before":"6113728f27ae82c7b1a177c8d03f9e96e0adf246","after":"c0ffee0000000000000000000000000000000000","repository":{"full_name":"octocat/Hello-World","default_branch":"master"},"pusher":{"name":"octocat"},"commits":["#,
+    );
+    let mut commit = 0;
+    while payload.len() < size {
+        if commit > 0 {
+            payload.push(',');
+        }
+        let _ = write!(
+            payload,
+            r#"{{"id":"{:040x}","message":"Commit {}","author":{{"name":"octocat"}},"added":["src/file_{}.rs"],"removed":[],"modified":["README.md"]}}"#,
+            commit, commit, commit
+        );
+        commit += 1;
+    }
+    payload.push_str("]}");
+    payload
+}
+
+/// Handle the delivery with the handler for the number of iterations, and measure the time taken
+pub fn measure(handler: &Handler, delivery: &SyntheticDelivery, iterations: u32) -> Measurement {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        delivery.send(handler);
+    }
+    Measurement {
+        iterations,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test generating payloads of the requested size
+    #[test]
+    fn payload_size() {
+        for size in [0, 1024, 64 * 1024] {
+            let payload = payload(size);
+            assert!(payload.len() >= size);
+            assert!(payload.len() < size + 512);
+            #[cfg(feature = "parse")]
+            assert!(serde_json::from_str::<serde_json::Value>(&payload).is_ok());
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Collecting hooks declared with the `hook` attribute
+//!
+//! With the `macros` feature enabled, free functions can be declared as hooks with `#[rifling::hook]`,
+//! and gathered into a `Constructor` with `collect_hooks!()`:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rifling;
+//!
+//! use rifling::Delivery;
+//!
+//! #[rifling::hook(event = "push", secret_env = "HOOK_SECRET")]
+//! fn on_push(_delivery: &Delivery) {
+//!     println!("Pushed!");
+//! }
+//!
+//! #[rifling::hook(event = "issues", secret = "secret")]
+//! fn on_issue(_delivery: &Delivery) -> Result<(), String> {
+//!     Ok(())
+//! }
+//!
+//! fn main() {
+//!     let cons = collect_hooks!();
+//!     assert_eq!(cons.hooks.len(), 2);
+//! }
+//! ```
+
+use super::handler::Constructor;
+use super::hook::Hook;
+
+/// Registration of a hook declared with the `hook` attribute, created by the attribute
+#[doc(hidden)]
+pub struct HookRegistration {
+    build: fn() -> Hook,
+}
+
+inventory::collect!(HookRegistration);
+
+/// Collect hooks declared with the `hook` attribute into a new `Constructor`
+#[macro_export]
+macro_rules! collect_hooks {
+    () => {
+        $crate::collect::collect()
+    };
+}
+
+/// The main impl clause of `HookRegistration`
+impl HookRegistration {
+    pub const fn new(build: fn() -> Hook) -> Self {
+        Self { build }
+    }
+}
+
+/// Create a new `Constructor` with all the hooks declared with the `hook` attribute
+pub fn collect() -> Constructor {
+    let mut cons = Constructor::new();
+    cons.register_all(
+        inventory::iter::<HookRegistration>
+            .into_iter()
+            .map(|registration| (registration.build)()),
+    );
+    cons
+}
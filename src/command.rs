@@ -0,0 +1,360 @@
+//! Command hooks
+//!
+//! `CommandHook` runs a command for every delivery, the classic way of deploying on push:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use std::time::Duration;
+//!
+//! use rifling::command::CommandHook;
+//! use rifling::Hook;
+//!
+//! let deploy = CommandHook::shell("./deploy.sh \"$RIFLING_PAYLOAD_AFTER\"")
+//!     .current_dir("/srv/app")
+//!     .inherit_env(&["PATH", "HOME"])
+//!     .env("DEPLOY_ENV", "production")
+//!     .timeout(Duration::from_secs(300));
+//! let hook = Hook::new("push", Some("secret".to_string()), deploy).named("deploy");
+//! ```
+//!
+//! The environment of the command is explicit: nothing is inherited unless allowed with `CommandHook::inherit_env`
+//! (the program is still looked up in the `PATH` of the server). The delivery is described by variables prefixed
+//! with `RIFLING_` (see `CommandHook::env_prefix`):
+//!
+//!  - `RIFLING_EVENT`, `RIFLING_PROVIDER` (e.g. `GitHub`) and `RIFLING_DELIVERY_ID` (if any),
+//!  - `RIFLING_PAYLOAD_*` for the fields of the payload which are not arrays, with their path in upper case, e.g.
+//!    `RIFLING_PAYLOAD_REPOSITORY_FULL_NAME` for `repository.full_name`.
+//!
+//! The request body is written to the standard input of the command. The command fails the hook if it exits with a
+//! non-zero status or runs longer than its timeout (then it's killed). Its standard output and error are kept in
+//! the delivery store (see `HookRun.output`), up to 64 KiB each.
+
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use super::context::Context;
+use super::error::Error;
+use super::handler::Delivery;
+use super::hook::HookFunc;
+use super::repository::working_dir;
+
+/// Largest part of the standard output and error kept
+const OUTPUT_LIMIT: u64 = 64 * 1024;
+
+/// Deepest fields of the payload passed as variables
+const MAX_DEPTH: usize = 4;
+
+/// Interval between checks of commands with a timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Hook function running a command
+#[derive(Clone, Debug)]
+pub struct CommandHook {
+    program: String,
+    args: Vec<String>,
+    inherited_env: Vec<String>,
+    env: Vec<(String, String)>,
+    env_prefix: String,
+    current_dir: Option<PathBuf>,
+    timeout: Option<Duration>,
+}
+
+/// Output and exit status of a command
+struct Output {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// Name of the variable for the path of the field
+fn variable_name(prefix: &str, path: &[&str]) -> String {
+    let mut name = prefix.to_string();
+    for (index, segment) in path.iter().enumerate() {
+        if index > 0 {
+            name.push('_');
+        }
+        name.extend(segment.chars().map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        }));
+    }
+    name
+}
+
+/// Add the fields of the value which are not arrays as variables
+fn flatten<'a>(
+    prefix: &str,
+    path: &mut Vec<&'a str>,
+    value: &'a Value,
+    vars: &mut Vec<(String, String)>,
+) {
+    match value {
+        Value::Object(fields) if path.len() < MAX_DEPTH => {
+            for (key, field) in fields {
+                path.push(key);
+                flatten(prefix, path, field, vars);
+                path.pop();
+            }
+        }
+        Value::Object(_) | Value::Array(_) | Value::Null => (),
+        Value::String(string) => vars.push((variable_name(prefix, path), string.clone())),
+        value => vars.push((variable_name(prefix, path), value.to_string())),
+    }
+}
+
+/// Read at most `OUTPUT_LIMIT` bytes from the reader in a separate thread, discarding the rest
+fn read_limited(reader: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(reader) = reader {
+            let mut reader = reader.take(OUTPUT_LIMIT);
+            let _ = reader.read_to_end(&mut output);
+            let _ = io::copy(&mut reader.into_inner(), &mut io::sink());
+        }
+        output
+    })
+}
+
+/// The main impl clause of `CommandHook`
+impl CommandHook {
+    /// Create a new hook running the program
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+            inherited_env: Vec::new(),
+            env: Vec::new(),
+            env_prefix: "RIFLING_".to_string(),
+            current_dir: None,
+            timeout: None,
+        }
+    }
+
+    /// Create a new hook running the script with `sh -c`
+    pub fn shell(script: &str) -> Self {
+        Self::new("sh").arg("-c").arg(script)
+    }
+
+    /// Add an argument
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Pass the variables of the environment of the server (e.g. `PATH`, `HOME`) to the command
+    pub fn inherit_env(mut self, names: &[&str]) -> Self {
+        self.inherited_env
+            .extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Set the variable of the environment of the command
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the prefix of the variables describing the delivery (`RIFLING_` by default)
+    pub fn env_prefix(mut self, prefix: &str) -> Self {
+        self.env_prefix = prefix.to_string();
+        self
+    }
+
+    /// Run the command in the directory, `{repository}` is replaced by the full name of the repository (see
+    /// `rifling::repository`)
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Kill the command if it runs longer than the duration
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Variables of the environment of the command for the delivery
+    fn vars(&self, delivery: &Delivery) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = self
+            .inherited_env
+            .iter()
+            .filter_map(|name| Some((name.clone(), std::env::var(name).ok()?)))
+            .collect();
+        if let Some(payload) = &delivery.payload {
+            flatten(
+                &format!("{}PAYLOAD_", &self.env_prefix),
+                &mut Vec::new(),
+                payload,
+                &mut vars,
+            );
+        }
+        let prefix = &self.env_prefix;
+        vars.push((format!("{}EVENT", prefix), delivery.event.clone()));
+        vars.push((
+            format!("{}PROVIDER", prefix),
+            format!("{:?}", delivery.delivery_type),
+        ));
+        if let Some(id) = &delivery.id {
+            vars.push((format!("{}DELIVERY_ID", prefix), id.clone()));
+        }
+        vars.extend(self.env.iter().cloned());
+        vars
+    }
+
+    /// Wait for the command to exit, killing it after the timeout
+    fn wait(&self, child: &mut Child) -> Result<ExitStatus, Error> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return child.wait().map_err(|error| self.error(error)),
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = child.try_wait().map_err(|error| self.error(error))? {
+                return Ok(status);
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::HookFailed(format!(
+                    "{} timed out after {:?}",
+                    &self.program, timeout
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Error failing to run the command
+    fn error(&self, error: io::Error) -> Error {
+        Error::HookFailed(format!("Unable to run {}: {}", &self.program, error))
+    }
+
+    /// Run the command for the delivery
+    fn execute(&self, delivery: &Delivery) -> Result<Output, Error> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env_clear()
+            .envs(self.vars(delivery))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(working_dir(dir, delivery)?);
+        }
+        debug!("Running {} {:?}", &self.program, &self.args);
+        let mut child = command.spawn().map_err(|error| self.error(error))?;
+        let stdout = read_limited(child.stdout.take());
+        let stderr = read_limited(child.stderr.take());
+        if let Some(mut stdin) = child.stdin.take() {
+            let body = delivery.request_body.clone().unwrap_or_default();
+            // Commands may exit without reading it all
+            thread::spawn(move || stdin.write_all(body.as_bytes()));
+        }
+        let status = self.wait(&mut child);
+        let output = Output {
+            status: status?,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        };
+        Ok(output)
+    }
+}
+
+/// The main impl clause of `Output`
+impl Output {
+    /// Format the standard output and error
+    fn format(&self) -> String {
+        let mut output = String::from_utf8_lossy(&self.stdout).into_owned();
+        if !self.stderr.is_empty() {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(&String::from_utf8_lossy(&self.stderr));
+        }
+        output
+    }
+}
+
+/// Implement `HookFunc` to `CommandHook`
+impl HookFunc for CommandHook {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.run_with_context(&Context::new("*"), delivery)
+    }
+
+    fn run_with_context(&self, context: &Context, delivery: &Delivery) -> Result<(), Error> {
+        let output = self.execute(delivery)?;
+        context.record_output(output.format());
+        if !output.status.success() {
+            return Err(Error::HookFailed(format!(
+                "{} exited with {}",
+                &self.program, output.status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn delivery() -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        headers.insert("x-github-delivery".to_string(), "42".to_string());
+        let request_body = r#"{"ref": "refs/heads/master", "size": 2, "commits": [], "repository": {"full_name": "RedL0tus/rifling"}}"#;
+        Delivery::new(headers, Some(request_body.to_string())).unwrap()
+    }
+
+    /// Test passing the delivery to commands
+    #[test]
+    fn command_env() {
+        std::env::set_var("RIFLING_TEST_INHERITED", "yes");
+        let hook = CommandHook::shell(
+            "echo \"$RIFLING_EVENT $RIFLING_DELIVERY_ID $RIFLING_PAYLOAD_REF $RIFLING_PAYLOAD_SIZE\"; \
+             echo \"$RIFLING_PAYLOAD_REPOSITORY_FULL_NAME ${RIFLING_PAYLOAD_COMMITS:-none} $FOO\"; \
+             echo \"${HOME:-no home} $RIFLING_TEST_INHERITED\"; cat; echo oops >&2",
+        )
+        .inherit_env(&["RIFLING_TEST_INHERITED"])
+        .env("FOO", "bar")
+        .current_dir("/");
+        let context = Context::new("push");
+        hook.run_with_context(&context, &delivery()).unwrap();
+        let output = context.take_output().unwrap();
+        let request_body = delivery().request_body.unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "push 42 refs/heads/master 2\nRedL0tus/rifling none bar\nno home yes\n{}\noops\n",
+                request_body
+            )
+        );
+        let hook = CommandHook::shell("pwd; exit 3").current_dir("/");
+        let context = Context::new("push");
+        assert!(hook.run_with_context(&context, &delivery()).is_err());
+        assert_eq!(context.take_output().unwrap(), "/\n");
+    }
+
+    /// Test killing commands running longer than their timeout
+    #[test]
+    fn command_timeout() {
+        let start = Instant::now();
+        let hook = CommandHook::new("sleep")
+            .arg("5")
+            .timeout(Duration::from_millis(100));
+        match hook.run(&delivery()) {
+            Err(Error::HookFailed(message)) => assert!(message.contains("timed out")),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,202 @@
+//! Concurrency limit
+//!
+//! Limits the number of hook executions running simultaneously, configured on the `Constructor` with
+//! `Constructor::max_concurrent_hooks`.
+//!
+//! When the limit is reached, excess deliveries are handled according to the `ConcurrencyPolicy`:
+//!
+//!  - `Reject` (default): answered with `503 Service Unavailable` immediately.
+//!  - `Queue(n)`: answered with `202 Accepted` (`Outcome::Queued`) immediately, their hooks are run in the
+//!    background like retries once running executions finish, with at most `n` runs waiting. Deliveries
+//!    which would exceed it are rejected.
+//!
+//! Every hook run holds a permit of its own, deliveries matching several hooks run them one after another, each taking
+//! a permit when it starts. Hooks left once no permit is available (e.g. taken by other deliveries meanwhile) are
+//! queued or rejected following the policy.
+//!
+//! Neither policy blocks the threads handling requests (e.g. those of the runtime of hyper). Hooks streaming the
+//! response (`Hook::stream_response`) and admission reviews of Kubernetes have to run while the request is handled,
+//! they are rejected at the limit whatever the policy.
+//!
+//! Runs in the background (retries, hooks scheduled with `Hook::delay`, `Hook::debounce`, `Hook::throttle` or
+//! `Hook::batch`, and deliveries queued until the active window of their hook opens) count towards the limit too,
+//! they wait for a running execution to finish whatever the policy.
+//!
+//! Example:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//! use rifling::concurrency::ConcurrencyPolicy;
+//!
+//! let mut cons = Constructor::new();
+//! cons.max_concurrent_hooks(2);
+//! cons.concurrency_policy(ConcurrencyPolicy::Queue(16));
+//! ```
+
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// What to do with deliveries exceeding the limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConcurrencyPolicy {
+    Reject,
+    Queue(usize),
+}
+
+/// Numbers of running and waiting executions
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    waiting: usize,
+}
+
+/// Counting semaphore limiting concurrent hook executions
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    max: usize,
+    policy: ConcurrencyPolicy,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+/// Permission to run hooks, released when dropped
+#[derive(Debug)]
+pub struct Permit<'a> {
+    limiter: &'a ConcurrencyLimiter,
+}
+
+/// Default policy is `Reject`
+impl Default for ConcurrencyPolicy {
+    fn default() -> Self {
+        ConcurrencyPolicy::Reject
+    }
+}
+
+/// The main impl clause of `ConcurrencyLimiter`
+impl ConcurrencyLimiter {
+    /// Create a new limiter allowing `max` simultaneous executions
+    pub fn new(max: usize, policy: ConcurrencyPolicy) -> Self {
+        Self {
+            max: max.max(1),
+            policy,
+            state: Mutex::new(State::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Lock the state, ignoring poisoning since the counters are always consistent
+    fn lock(&self) -> MutexGuard<'_, State> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Acquire a permit without waiting, returns `None` if the limit is reached
+    pub fn acquire(&self) -> Option<Permit<'_>> {
+        let mut state = self.lock();
+        if state.running >= self.max {
+            return None;
+        }
+        state.running += 1;
+        Some(Permit { limiter: self })
+    }
+
+    /// Reserve places in the queue for runs waiting for a permit in the background, fails if the policy is `Reject`
+    /// or there are not enough places left
+    pub(crate) fn reserve(&self, runs: usize) -> bool {
+        let capacity = match self.policy {
+            ConcurrencyPolicy::Queue(capacity) => capacity,
+            ConcurrencyPolicy::Reject => return false,
+        };
+        let mut state = self.lock();
+        if state.waiting + runs > capacity {
+            return false;
+        }
+        state.waiting += runs;
+        true
+    }
+
+    /// Acquire a permit for a run in the background, waiting for it whatever the policy, and release its place in
+    /// the queue if it's reserved
+    pub(crate) fn wait(&self, reserved: bool) -> Permit<'_> {
+        let mut state = self.lock();
+        while state.running >= self.max {
+            state = match self.released.wait(state) {
+                Ok(state) => state,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+        }
+        if reserved {
+            state.waiting -= 1;
+        }
+        state.running += 1;
+        Permit { limiter: self }
+    }
+
+    /// Maximum number of simultaneous executions
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Number of running executions
+    pub fn running(&self) -> usize {
+        self.lock().running
+    }
+
+    /// Number of runs waiting in the queue
+    pub fn waiting(&self) -> usize {
+        self.lock().waiting
+    }
+}
+
+/// Release the permit when dropped
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        self.limiter.lock().running -= 1;
+        self.limiter.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Test rejecting executions exceeding the limit
+    #[test]
+    fn reject_policy() {
+        let limiter = ConcurrencyLimiter::new(1, ConcurrencyPolicy::Reject);
+        let permit = limiter.acquire();
+        assert!(permit.is_some());
+        assert!(limiter.acquire().is_none());
+        drop(permit);
+        assert!(limiter.acquire().is_some());
+    }
+
+    /// Test queueing executions exceeding the limit
+    #[test]
+    fn queue_policy() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, ConcurrencyPolicy::Queue(2)));
+        let permit = limiter.acquire();
+        assert!(permit.is_some());
+        assert!(limiter.acquire().is_none());
+        assert!(limiter.reserve(2));
+        // Queue is full
+        assert!(!limiter.reserve(1));
+        let waiting = {
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                drop(limiter.wait(true));
+                drop(limiter.wait(true));
+            })
+        };
+        drop(permit);
+        waiting.join().unwrap();
+        assert_eq!(limiter.waiting(), 0);
+        assert_eq!(limiter.running(), 0);
+        assert!(!ConcurrencyLimiter::new(1, ConcurrencyPolicy::Reject).reserve(1));
+    }
+}
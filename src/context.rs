@@ -0,0 +1,247 @@
+//! Context of hook runs
+//!
+//! Hooks created with `Hook::with_context` receive a `Context` along with the delivery, describing the run (the
+//! event the hook is registered for, its name, the attempt and when the delivery was received) and letting the hook
+//! add to the response or count custom metrics:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Context, Delivery, Hook};
+//!
+//! let hook = Hook::with_context("*", None, |context: &Context, delivery: &Delivery| {
+//!     if context.attempt() > 1 {
+//!         println!("Retrying '{}' for the {} time", delivery.event(), context.attempt());
+//!     }
+//!     context.count("builds", 1);
+//!     context.respond("Build queued");
+//! });
+//! ```
+//!
+//! Fragments passed to `Context::respond` are appended to the body of the response, one per line. Hooks run after
+//! the response was sent (retried, delayed or batched) can't respond anymore, their fragments are dropped. Hooks
+//! created with `Hook::stream_response` send their fragments as they add them, see `ResponseStream`.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::stats::StatsHandle;
+
+/// Fragments of a response, collected until it's sent or streamed to it
+enum Fragments {
+    Collected(Vec<String>),
+    Streamed(Sender<String>),
+}
+
+thread_local! {
+    /// Fragments of the response to the request being handled in this thread
+    static FRAGMENTS: RefCell<Option<Fragments>> = const { RefCell::new(None) };
+    /// Stream of the response to the request being handled in this thread, if hooks are run in another thread
+    static STREAM: RefCell<Option<ResponseStream>> = const { RefCell::new(None) };
+}
+
+/// Start collecting the fragments of the response added by hooks run in this thread
+pub(crate) fn begin() {
+    FRAGMENTS.with(|fragments| *fragments.borrow_mut() = Some(Fragments::Collected(Vec::new())));
+    STREAM.with(|stream| *stream.borrow_mut() = None);
+}
+
+/// Start streaming the fragments added by hooks run in this thread to the response
+pub(crate) fn begin_streaming(sender: Sender<String>) {
+    FRAGMENTS.with(|fragments| *fragments.borrow_mut() = Some(Fragments::Streamed(sender)));
+}
+
+/// Stop collecting and take the fragments, or stop streaming them
+pub(crate) fn finish() -> Vec<String> {
+    FRAGMENTS.with(|fragments| match fragments.borrow_mut().take() {
+        Some(Fragments::Collected(fragments)) => fragments,
+        Some(Fragments::Streamed(_)) | None => Vec::new(),
+    })
+}
+
+/// Stream the response to the request being handled in this thread, the hooks are run in another thread
+pub(crate) fn attach_stream(response_stream: ResponseStream) {
+    STREAM.with(|stream| *stream.borrow_mut() = Some(response_stream));
+}
+
+/// Take the stream of the response to the request being handled in this thread
+pub(crate) fn take_stream() -> Option<ResponseStream> {
+    STREAM.with(|stream| stream.borrow_mut().take())
+}
+
+/// Fragments added by hooks streaming the response (see `Hook::stream_response`) as they add them, it ends once all
+/// the hooks are run
+///
+/// Iterating blocks until the next fragment is added. Servers of rifling send them after the body of the response,
+/// one per line, with chunked transfer encoding.
+#[derive(Clone)]
+pub struct ResponseStream {
+    receiver: Arc<Mutex<Receiver<String>>>,
+}
+
+/// Context of a hook run
+pub struct Context<'a> {
+    pattern: &'static str,
+    name: Option<&'a str>,
+    attempt: u32,
+    received_at: SystemTime,
+    state: Option<&'a (dyn Any + Send + Sync)>,
+    stats: Option<&'a StatsHandle>,
+    output: Mutex<Option<String>>,
+}
+
+/// The main impl clause of `Context`
+impl<'a> Context<'a> {
+    /// Create the context of a first attempt for a hook registered for the event, e.g. to test hooks
+    pub fn new(pattern: &'static str) -> Self {
+        Self {
+            pattern,
+            name: None,
+            attempt: 1,
+            received_at: SystemTime::now(),
+            state: None,
+            stats: None,
+            output: Mutex::new(None),
+        }
+    }
+
+    /// Set the name of the hook
+    pub(crate) fn with_name(mut self, name: Option<&'a str>) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Set the attempt and when the delivery was received
+    pub(crate) fn with_run(mut self, attempt: u32, received_at: SystemTime) -> Self {
+        self.attempt = attempt;
+        self.received_at = received_at;
+        self
+    }
+
+    /// Set the state and statistics of the constructor
+    pub(crate) fn with_settings(
+        mut self,
+        state: Option<&'a (dyn Any + Send + Sync)>,
+        stats: &'a StatsHandle,
+    ) -> Self {
+        self.state = state;
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Event the hook is registered for, e.g. `*` for hooks matching every event
+    pub fn pattern(&self) -> &'static str {
+        self.pattern
+    }
+
+    /// Name of the hook, see `Hook::named`
+    pub fn name(&self) -> Option<&'a str> {
+        self.name
+    }
+
+    /// Number of the attempt, starting at 1 and increased by every retry
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Time the delivery was received
+    pub fn received_at(&self) -> SystemTime {
+        self.received_at
+    }
+
+    /// State set by `Constructor::with_state`, if it has the type
+    pub fn state<S: Any>(&self) -> Option<&'a S> {
+        self.state.and_then(|state| state.downcast_ref::<S>())
+    }
+
+    /// State set by `Constructor::with_state`, untyped
+    pub(crate) fn any_state(&self) -> Option<&'a (dyn Any + Send + Sync)> {
+        self.state
+    }
+
+    /// Append the fragment to the body of the response, if it's not sent yet
+    pub fn respond(&self, fragment: impl Into<String>) {
+        let fragment = fragment.into();
+        FRAGMENTS.with(|fragments| match fragments.borrow_mut().as_mut() {
+            Some(Fragments::Collected(fragments)) => fragments.push(fragment),
+            Some(Fragments::Streamed(sender)) => {
+                if let Err(error) = sender.send(fragment) {
+                    debug!("Response stream closed, dropping fragment: {}", error.0);
+                }
+            }
+            None => debug!("Response already sent, dropping fragment: {}", fragment),
+        });
+    }
+
+    /// Add the value to the custom metric, available in `Stats.metrics`
+    pub fn count(&self, metric: &str, value: u64) {
+        if let Some(stats) = self.stats {
+            stats.record_metric(metric, value);
+        }
+    }
+
+    /// Keep the output of the run (e.g. of a command) in the delivery store, see `HookRun.output`
+    pub fn record_output(&self, output: impl Into<String>) {
+        let mut recorded = match self.output.lock() {
+            Ok(recorded) => recorded,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *recorded = Some(output.into());
+    }
+
+    /// Take the output recorded by the run
+    pub(crate) fn take_output(&self) -> Option<String> {
+        match self.output.lock() {
+            Ok(mut recorded) => recorded.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        }
+    }
+}
+
+/// Implement `Debug` to `Context`, the state is elided
+impl fmt::Debug for Context<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("pattern", &self.pattern)
+            .field("name", &self.name)
+            .field("attempt", &self.attempt)
+            .field("received_at", &self.received_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The main impl clause of `ResponseStream`
+impl ResponseStream {
+    /// Create a new stream, ending once the sender is dropped
+    pub(crate) fn new() -> (Sender<String>, Self) {
+        let (sender, receiver) = mpsc::channel();
+        let stream = Self {
+            receiver: Arc::new(Mutex::new(receiver)),
+        };
+        (sender, stream)
+    }
+}
+
+/// Implement `Iterator` to `ResponseStream`, yielding the fragments as hooks add them
+impl Iterator for ResponseStream {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let receiver = match self.receiver.lock() {
+            Ok(receiver) => receiver,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        receiver.recv().ok()
+    }
+}
+
+/// Implement `Debug` to `ResponseStream`
+impl fmt::Debug for ResponseStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResponseStream").finish_non_exhaustive()
+    }
+}
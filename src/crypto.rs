@@ -0,0 +1,397 @@
+//! Cryptography helpers
+//!
+//! Signatures sent by GitHub (and other senders following the same convention) are HMAC digests of the
+//! request body, carried in a header like `sha256=<hex digest>`.
+//!
+//! `verify_hmac` checks such a digest with whichever cryptography library is enabled (`ring` is preferred
+//! when both are), `sign_hmac` computes it (e.g. to send test deliveries).
+//!
+//! Example:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::crypto::{split_signature, verify_hmac, Algorithm, SignatureEncoding};
+//!
+//! let header = "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8";
+//! let (algorithm, digest) = split_signature(header);
+//! assert_eq!(algorithm, Some(Algorithm::Sha256));
+//! let signature = SignatureEncoding::Hex.decode(digest).unwrap();
+//! # if rifling::crypto::is_available() {
+//! assert!(verify_hmac(Algorithm::Sha256, b"key", b"The quick brown fox jumps over the lazy dog", &signature));
+//! # }
+//! ```
+//!
+//! Ed25519 signatures (e.g. from Discord) and RSA signatures (e.g. of JSON Web Tokens) can be verified with
+//! `verify_ed25519` and `verify_rsa_sha256`, which are only supported by `ring`.
+
+use hex::FromHex;
+#[cfg(feature = "crypto-use-rustcrypto")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "crypto-use-ring")]
+use ring::digest;
+#[cfg(feature = "crypto-use-ring")]
+use ring::hmac as ring_hmac;
+#[cfg(feature = "crypto-use-ring")]
+use ring::signature as ring_signature;
+#[cfg(feature = "crypto-use-rustcrypto")]
+use sha1::Sha1;
+#[cfg(feature = "crypto-use-rustcrypto")]
+use sha2::{Sha256, Sha512};
+
+/// Backend selected by enabled features, `ring` is preferred if both libraries are enabled
+#[cfg(feature = "crypto-use-ring")]
+pub(crate) type Backend = RingBackend;
+#[cfg(all(feature = "crypto-use-rustcrypto", not(feature = "crypto-use-ring")))]
+pub(crate) type Backend = RustCryptoBackend;
+#[cfg(all(
+    not(feature = "crypto-use-rustcrypto"),
+    not(feature = "crypto-use-ring")
+))]
+pub(crate) type Backend = UnsupportedBackend;
+
+/// Cryptography library used to verify signatures
+pub(crate) trait HmacBackend {
+    /// Whether this backend is able to verify anything at all
+    const AVAILABLE: bool = true;
+
+    /// Whether this backend is able to verify Ed25519 signatures
+    const ED25519_AVAILABLE: bool = false;
+
+    /// Whether this backend is able to verify RSA signatures
+    const RSA_AVAILABLE: bool = false;
+
+    /// Verify HMAC signature of the body
+    fn verify(algorithm: Algorithm, secret: &[u8], body: &[u8], signature: &[u8]) -> bool;
+
+    /// Sign the body with HMAC, not supported by default
+    fn sign(_algorithm: Algorithm, _secret: &[u8], _body: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Verify Ed25519 signature of the message, not supported by default
+    fn verify_ed25519(_public_key: &[u8], _message: &[u8], _signature: &[u8]) -> bool {
+        false
+    }
+
+    /// Verify RSASSA-PKCS1-v1_5 signature of the message with SHA-256, not supported by default
+    fn verify_rsa_sha256(
+        _modulus: &[u8],
+        _exponent: &[u8],
+        _message: &[u8],
+        _signature: &[u8],
+    ) -> bool {
+        false
+    }
+}
+
+/// Backend using `ring`
+#[cfg(feature = "crypto-use-ring")]
+pub(crate) struct RingBackend;
+
+/// Backend using crates provided by RustCrypto team
+#[cfg(feature = "crypto-use-rustcrypto")]
+#[cfg_attr(feature = "crypto-use-ring", allow(dead_code))] // `ring` is preferred when both are enabled
+pub(crate) struct RustCryptoBackend;
+
+/// Fallback when no cryptography library is enabled, it rejects every signature
+#[cfg(all(
+    not(feature = "crypto-use-rustcrypto"),
+    not(feature = "crypto-use-ring")
+))]
+pub(crate) struct UnsupportedBackend;
+
+/// HMAC algorithms that can be used to sign a payload
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// Encoding of the digest in the signature header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureEncoding {
+    Hex,
+    Base64,
+}
+
+/// The main impl clause of `Algorithm`
+impl Algorithm {
+    /// All supported algorithms
+    pub const ALL: [Algorithm; 3] = [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512];
+
+    /// Name of the algorithm, as used in the prefix of the signature (e.g. `sha256=...`)
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Find algorithm by its name, case insensitive
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        Self::ALL
+            .iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
+
+/// The main impl clause of `SignatureEncoding`
+impl SignatureEncoding {
+    /// Decode the digest, returns `None` if it's malformed
+    pub fn decode(self, signature: &str) -> Option<Vec<u8>> {
+        match self {
+            SignatureEncoding::Hex => Vec::from_hex(signature.trim()).ok(),
+            SignatureEncoding::Base64 => base64::decode(signature.trim()).ok(),
+        }
+    }
+}
+
+/// Split the signature into algorithm and digest (e.g. `sha1=abcdef` -> `(Some(Sha1), "abcdef")`)
+///
+/// If the part before the first `=` is not a known algorithm, the whole string is returned as the digest,
+/// since base64 digests may contain `=` as padding.
+pub fn split_signature(signature: &str) -> (Option<Algorithm>, &str) {
+    if let Some(index) = signature.find('=') {
+        if let Some(algorithm) = Algorithm::from_name(&signature[..index]) {
+            return (Some(algorithm), &signature[index + 1..]);
+        }
+    }
+    (None, signature)
+}
+
+/// Verify HMAC signature of the body with the enabled cryptography library
+///
+/// Always returns `false` if no cryptography library is enabled.
+pub fn verify_hmac(algorithm: Algorithm, secret: &[u8], body: &[u8], signature: &[u8]) -> bool {
+    Backend::verify(algorithm, secret, body, signature)
+}
+
+/// Sign the body with HMAC with the enabled cryptography library, e.g. to send test deliveries
+///
+/// Returns `None` if no cryptography library is enabled.
+pub fn sign_hmac(algorithm: Algorithm, secret: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+    Backend::sign(algorithm, secret, body)
+}
+
+/// Whether signatures can be verified with enabled features
+pub fn is_available() -> bool {
+    Backend::AVAILABLE
+}
+
+/// Verify Ed25519 signature of the message with the raw 32 bytes public key
+///
+/// Always returns `false` if `ring` is not enabled.
+pub fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    Backend::verify_ed25519(public_key, message, signature)
+}
+
+/// Whether Ed25519 signatures can be verified with enabled features
+pub fn is_ed25519_available() -> bool {
+    Backend::ED25519_AVAILABLE
+}
+
+/// Verify RSASSA-PKCS1-v1_5 signature (2048 to 8192 bits) of the message with SHA-256, the public key is given by
+/// its big-endian modulus and exponent
+///
+/// Always returns `false` if `ring` is not enabled.
+pub fn verify_rsa_sha256(
+    modulus: &[u8],
+    exponent: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    Backend::verify_rsa_sha256(modulus, exponent, message, signature)
+}
+
+/// Whether RSA signatures can be verified with enabled features
+pub fn is_rsa_available() -> bool {
+    Backend::RSA_AVAILABLE
+}
+
+#[cfg(feature = "crypto-use-ring")]
+impl HmacBackend for RingBackend {
+    const ED25519_AVAILABLE: bool = true;
+    const RSA_AVAILABLE: bool = true;
+
+    fn verify(algorithm: Algorithm, secret: &[u8], body: &[u8], signature: &[u8]) -> bool {
+        let key = ring_hmac::SigningKey::new(ring_digest(algorithm), secret);
+        ring_hmac::verify_with_own_key(&key, body, signature).is_ok()
+    }
+
+    fn sign(algorithm: Algorithm, secret: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+        let key = ring_hmac::SigningKey::new(ring_digest(algorithm), secret);
+        Some(ring_hmac::sign(&key, body).as_ref().to_vec())
+    }
+
+    fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        ring_signature::verify(
+            &ring_signature::ED25519,
+            untrusted::Input::from(public_key),
+            untrusted::Input::from(message),
+            untrusted::Input::from(signature),
+        )
+        .is_ok()
+    }
+
+    fn verify_rsa_sha256(
+        modulus: &[u8],
+        exponent: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> bool {
+        ring_signature::primitive::verify_rsa(
+            &ring_signature::RSA_PKCS1_2048_8192_SHA256,
+            (
+                untrusted::Input::from(modulus),
+                untrusted::Input::from(exponent),
+            ),
+            untrusted::Input::from(message),
+            untrusted::Input::from(signature),
+        )
+        .is_ok()
+    }
+}
+
+/// Digest algorithm of `ring` for the HMAC algorithm
+#[cfg(feature = "crypto-use-ring")]
+fn ring_digest(algorithm: Algorithm) -> &'static digest::Algorithm {
+    match algorithm {
+        Algorithm::Sha1 => &digest::SHA1,
+        Algorithm::Sha256 => &digest::SHA256,
+        Algorithm::Sha512 => &digest::SHA512,
+    }
+}
+
+#[cfg(feature = "crypto-use-rustcrypto")]
+impl HmacBackend for RustCryptoBackend {
+    fn verify(algorithm: Algorithm, secret: &[u8], body: &[u8], signature: &[u8]) -> bool {
+        macro_rules! verify_with {
+            ($digest:ty) => {
+                match Hmac::<$digest>::new_varkey(secret) {
+                    Ok(mut mac) => {
+                        mac.input(body);
+                        mac.verify(signature).is_ok()
+                    }
+                    Err(_) => false,
+                }
+            };
+        }
+        match algorithm {
+            Algorithm::Sha1 => verify_with!(Sha1),
+            Algorithm::Sha256 => verify_with!(Sha256),
+            Algorithm::Sha512 => verify_with!(Sha512),
+        }
+    }
+
+    fn sign(algorithm: Algorithm, secret: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+        macro_rules! sign_with {
+            ($digest:ty) => {{
+                let mut mac = Hmac::<$digest>::new_varkey(secret).ok()?;
+                mac.input(body);
+                Some(mac.result().code().to_vec())
+            }};
+        }
+        match algorithm {
+            Algorithm::Sha1 => sign_with!(Sha1),
+            Algorithm::Sha256 => sign_with!(Sha256),
+            Algorithm::Sha512 => sign_with!(Sha512),
+        }
+    }
+}
+
+#[cfg(all(
+    not(feature = "crypto-use-rustcrypto"),
+    not(feature = "crypto-use-ring")
+))]
+impl HmacBackend for UnsupportedBackend {
+    const AVAILABLE: bool = false;
+
+    fn verify(_algorithm: Algorithm, _secret: &[u8], _body: &[u8], _signature: &[u8]) -> bool {
+        false
+    }
+}
+
+#[cfg(any(feature = "crypto-use-ring", feature = "crypto-use-rustcrypto"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"key";
+    const BODY: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+    /// Check a backend against known digests from RFC 4231 style examples
+    fn check_backend<B: HmacBackend>() {
+        let digests = [
+            (Algorithm::Sha1, "de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9"),
+            (
+                Algorithm::Sha256,
+                "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8",
+            ),
+            (
+                Algorithm::Sha512,
+                "b42af09057bac1e2d41708e48a902e09b5ff7f12ab428a4fe86653c73dd248fb82f948a549f7b791a5b41915ee4d1ec3935357e4e2317250d0372afa2ebeeb3a",
+            ),
+        ];
+        for (algorithm, digest) in digests.iter() {
+            let signature = SignatureEncoding::Hex.decode(digest).unwrap();
+            assert!(B::verify(*algorithm, KEY, BODY, &signature));
+            assert!(!B::verify(*algorithm, b"another key", BODY, &signature));
+            assert_eq!(B::sign(*algorithm, KEY, BODY), Some(signature));
+        }
+    }
+
+    /// Test HMAC verification with `ring`
+    #[cfg(feature = "crypto-use-ring")]
+    #[test]
+    fn verify_known_digests_ring() {
+        check_backend::<RingBackend>();
+    }
+
+    /// Test HMAC verification with crates from RustCrypto team
+    #[cfg(feature = "crypto-use-rustcrypto")]
+    #[test]
+    fn verify_known_digests_rustcrypto() {
+        check_backend::<RustCryptoBackend>();
+    }
+
+    /// Test HMAC verification with base64 encoded digest
+    #[test]
+    fn verify_base64_digest() {
+        let signature = SignatureEncoding::Base64
+            .decode("97yD9DBThCSxMpjmqm+xQ+9NWaFJRhdZl0edvC0aPNg=")
+            .unwrap();
+        assert!(verify_hmac(Algorithm::Sha256, KEY, BODY, &signature));
+    }
+
+    /// Test Ed25519 verification with the test vector from RFC 8032
+    #[cfg(feature = "crypto-use-ring")]
+    #[test]
+    fn verify_ed25519_rfc8032() {
+        let public_key = SignatureEncoding::Hex
+            .decode("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+            .unwrap();
+        let signature = SignatureEncoding::Hex
+            .decode("e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b")
+            .unwrap();
+        assert!(verify_ed25519(&public_key, b"", &signature));
+        assert!(!verify_ed25519(&public_key, b"tampered", &signature));
+    }
+
+    /// Test splitting signature into algorithm and digest
+    #[test]
+    fn split_signature_prefix() {
+        assert_eq!(
+            split_signature("sha1=abcd"),
+            (Some(Algorithm::Sha1), "abcd")
+        );
+        assert_eq!(
+            split_signature("SHA512=ab="),
+            (Some(Algorithm::Sha512), "ab=")
+        );
+        assert_eq!(split_signature("q83v="), (None, "q83v="));
+    }
+}
@@ -0,0 +1,143 @@
+//! Error
+//!
+//! Errors that may occur while handling a delivery.
+//!
+//! They are passed to the error callback registered with `Constructor::on_error`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Error};
+//!
+//! let mut cons = Constructor::new();
+//! cons.on_error(|error: &Error| eprintln!("Failed to handle delivery: {}", error));
+//! ```
+
+use std::error;
+use std::fmt;
+use std::time::Duration;
+
+/// Errors during the handling of a delivery
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The request is not a delivery from a known sender, or its payload is malformed
+    InvalidDelivery(&'static str),
+    /// The signature can't be verified because no cryptography library is enabled
+    CryptoUnavailable,
+    /// A secret is configured but the delivery carries no signature or token
+    MissingSignature,
+    /// The signature or token does not match the secret
+    InvalidSignature,
+    /// The claims of the JSON Web Token are not accepted (e.g. it is expired)
+    InvalidToken(&'static str),
+    /// The keys verifying JSON Web Tokens couldn't be loaded
+    Jwks(String),
+    /// The client is not allowed by `Constructor::allowed_clients`
+    UntrustedClient,
+    /// The delivery was not sent by a GitHub Enterprise Server instance required by
+    /// `Constructor::require_enterprise_host`, its host is given (empty if missing)
+    UntrustedHost(String),
+    /// The user agent of the delivery is not allowed by `Constructor::allow_user_agent` or
+    /// `Constructor::deny_user_agent`, it is given (empty if missing)
+    UntrustedUserAgent(String),
+    /// The delivery lacks headers the provider sends, or they're malformed, see `Constructor::strict_github`
+    InvalidHeaders(&'static str),
+    /// Too many deliveries, should be retried after the given duration
+    RateLimited(Duration),
+    /// The delivery was sent too long ago (or too far in the future), possibly replayed
+    StaleDelivery(Duration),
+    /// The delivery failed the validation set by `Constructor::validate`
+    ValidationFailed(String),
+    /// The body of the request wasn't received within the read or idle timeout
+    RequestTimeout(Duration),
+    /// The body of the request is larger than the given number of bytes
+    PayloadTooLarge(usize),
+    /// Too many hooks are running
+    Overloaded,
+    /// The hook needs a state of the given type, which is not set by `Constructor::with_state`
+    StateUnavailable(&'static str),
+    /// The hook function returned an error
+    HookFailed(String),
+    /// The delivery was received outside the active window of the hook, see `Hook::active_between`
+    OutsideWindow,
+    /// The time of an active window is not `HH:MM`, see `Hook::active_between`
+    InvalidWindow(String),
+    /// The hook function still failed after all the retries
+    RetriesExhausted { attempts: u32, error: String },
+    /// The outcome of the hook couldn't be reported as a commit status
+    StatusReport(String),
+    /// The delivery couldn't be published by a sink (see `Constructor::sink`)
+    Sink(String),
+    /// The template couldn't be rendered (see `rifling::template`)
+    Template(String),
+    /// The hook with the name (see `Hook::named`) failed with the error
+    Hook { name: String, error: Box<Error> },
+    /// A downstream target is unreachable, see `rifling::readiness`
+    Unreachable(String),
+}
+
+/// Implement `Display` for `Error`
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidDelivery(reason) => write!(f, "{}", reason),
+            Error::CryptoUnavailable => write!(
+                f,
+                "Unable to verify signature: no cryptography library enabled"
+            ),
+            Error::MissingSignature => write!(f, "Missing signature"),
+            Error::InvalidSignature => write!(f, "Invalid signature"),
+            Error::InvalidToken(reason) => write!(f, "Invalid token: {}", reason),
+            Error::Jwks(error) => write!(f, "Unable to load JWKS: {}", error),
+            Error::UntrustedClient => write!(f, "Client is not allowed"),
+            Error::UntrustedHost(host) => {
+                write!(f, "GitHub Enterprise host '{}' is not allowed", host)
+            }
+            Error::UntrustedUserAgent(user_agent) => {
+                write!(f, "User agent '{}' is not allowed", user_agent)
+            }
+            Error::InvalidHeaders(reason) => write!(f, "Invalid headers: {}", reason),
+            Error::RateLimited(retry_after) => write!(
+                f,
+                "Rate limit exceeded, retry after {} second(s)",
+                retry_after_secs(*retry_after)
+            ),
+            Error::StaleDelivery(age) => write!(f, "Delivery is {} second(s) old", age.as_secs()),
+            Error::ValidationFailed(reason) => write!(f, "Validation failed: {}", reason),
+            Error::RequestTimeout(timeout) => {
+                write!(f, "Request body not received within {:?}", timeout)
+            }
+            Error::PayloadTooLarge(limit) => {
+                write!(f, "Request body larger than {} bytes", limit)
+            }
+            Error::Overloaded => write!(f, "Too many running hooks"),
+            Error::StateUnavailable(state_type) => {
+                write!(f, "State of type {} is not set", state_type)
+            }
+            Error::HookFailed(error) => write!(f, "Hook failed: {}", error),
+            Error::OutsideWindow => write!(f, "Received outside the active window of the hook"),
+            Error::InvalidWindow(time) => {
+                write!(
+                    f,
+                    "Invalid time '{}' of active window, expected HH:MM",
+                    time
+                )
+            }
+            Error::RetriesExhausted { attempts, error } => {
+                write!(f, "Hook failed after {} attempt(s): {}", attempts, error)
+            }
+            Error::StatusReport(error) => write!(f, "Unable to report commit status: {}", error),
+            Error::Sink(error) => write!(f, "Unable to publish delivery: {}", error),
+            Error::Template(error) => write!(f, "Unable to render template: {}", error),
+            Error::Hook { name, error } => write!(f, "{}: {}", name, error),
+            Error::Unreachable(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Round the duration up to whole seconds, as used in the `Retry-After` header
+pub(crate) fn retry_after_secs(duration: Duration) -> u64 {
+    duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 }
+}
@@ -0,0 +1,362 @@
+//! Filter
+//!
+//! Hooks can be restricted to deliveries satisfying conditions with `Hook::when`, hooks whose conditions don't hold
+//! are not run, as if they were not registered for the event. Conditions on fields of the payload are expressed
+//! with JSON pointers (RFC 6901, e.g. `/pull_request/head/ref`), requires `parse`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::filter::{when_eq, when_one_of};
+//! use rifling::{Delivery, Hook};
+//!
+//! let hook = Hook::new("pull_request", None, |delivery: &Delivery| {
+//!     println!("Pull request targeting {:?}", delivery.field("/pull_request/base/ref"));
+//! })
+//! .when(when_eq("/pull_request/base/ref", "master"))
+//! .when(when_one_of("/action", &["opened", "synchronize"]));
+//! ```
+//!
+//! Any `Fn(&Delivery) -> bool` can be used as a condition too, multiple conditions of a hook must all hold.
+//!
+//! Events of GitHub Actions and checks have helpers understanding their payloads, e.g. to notify failed runs:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::filter::{when_eq, workflow_conclusion};
+//! use rifling::{Delivery, Hook};
+//!
+//! let hook = Hook::new("workflow_run", None, |delivery: &Delivery| {
+//!     println!("Workflow failed: {:?}", delivery.field("/workflow_run/html_url"));
+//! })
+//! .when(when_eq("/action", "completed"))
+//! .when(workflow_conclusion("failure"));
+//! ```
+//!
+//! Pipeline and job events of GitLab CI (`Pipeline Hook` and `Job Hook`) have helpers too, e.g. for notification bots:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::filter::{job_stage, job_status};
+//! use rifling::{Delivery, Hook};
+//!
+//! let hook = Hook::new("job_hook", None, |delivery: &Delivery| {
+//!     println!("Deployment failed: {:?}", delivery.field("/build_name"));
+//! })
+//! .when(job_stage("deploy"))
+//! .when(job_status("failed"));
+//! ```
+//!
+//! Triage bots can filter issues and pull requests by label, author or title (`title_matches` requires
+//! `regex-filter`):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::filter::{author, has_label};
+//! use rifling::{Delivery, Hook};
+//!
+//! let hook = Hook::new("pull_request", None, |_: &Delivery| println!("Merging..."))
+//!     .when(has_label("dependencies"))
+//!     .when(author("dependabot[bot]"));
+//! ```
+
+#[cfg(feature = "regex-filter")]
+use regex::Regex;
+#[cfg(feature = "parse")]
+use serde_json::Value;
+
+use super::handler::Delivery;
+
+/// Condition on deliveries
+/// You can implement this trait to your own struct
+pub trait Condition: Sync + Send {
+    fn matches(&self, delivery: &Delivery) -> bool;
+}
+
+/// Implement `Condition` to `Fn(&Delivery) -> bool`.
+impl<F> Condition for F
+where
+    F: Fn(&Delivery) -> bool + Sync + Send,
+{
+    fn matches(&self, delivery: &Delivery) -> bool {
+        self(delivery)
+    }
+}
+
+/// The field at the JSON pointer equals the value
+#[cfg(feature = "parse")]
+pub fn when_eq(pointer: &str, value: impl Into<Value>) -> impl Condition + Clone {
+    let pointer = pointer.to_string();
+    let value = value.into();
+    move |delivery: &Delivery| delivery.field(&pointer) == Some(&value)
+}
+
+/// The field at the JSON pointer is missing or doesn't equal the value
+#[cfg(feature = "parse")]
+pub fn when_ne(pointer: &str, value: impl Into<Value>) -> impl Condition + Clone {
+    let pointer = pointer.to_string();
+    let value = value.into();
+    move |delivery: &Delivery| delivery.field(&pointer) != Some(&value)
+}
+
+/// The field at the JSON pointer equals one of the values
+#[cfg(feature = "parse")]
+pub fn when_one_of<V: Clone + Into<Value>>(pointer: &str, values: &[V]) -> impl Condition + Clone {
+    let pointer = pointer.to_string();
+    let values: Vec<Value> = values.iter().cloned().map(Into::into).collect();
+    move |delivery: &Delivery| {
+        delivery
+            .field(&pointer)
+            .is_some_and(|field| values.contains(field))
+    }
+}
+
+/// The field at the JSON pointer exists and is not null
+#[cfg(feature = "parse")]
+pub fn when_exists(pointer: &str) -> impl Condition + Clone {
+    let pointer = pointer.to_string();
+    move |delivery: &Delivery| {
+        delivery
+            .field(&pointer)
+            .is_some_and(|field| !field.is_null())
+    }
+}
+
+/// Field of the workflow run or job in `workflow_run` and `workflow_job` events
+#[cfg(feature = "parse")]
+fn workflow_field<'a>(delivery: &'a Delivery, field: &str) -> Option<&'a Value> {
+    delivery
+        .field(&format!("/workflow_run/{}", field))
+        .or_else(|| delivery.field(&format!("/workflow_job/{}", field)))
+}
+
+/// The workflow run or job (`workflow_run` and `workflow_job` events) concluded as given (e.g. `success`, `failure`,
+/// `cancelled` or `timed_out`)
+#[cfg(feature = "parse")]
+pub fn workflow_conclusion(conclusion: &str) -> impl Condition + Clone {
+    let conclusion = Value::from(conclusion);
+    move |delivery: &Delivery| workflow_field(delivery, "conclusion") == Some(&conclusion)
+}
+
+/// The workflow run or job (`workflow_run` and `workflow_job` events) has the status (e.g. `queued`, `in_progress`
+/// or `completed`)
+#[cfg(feature = "parse")]
+pub fn workflow_status(status: &str) -> impl Condition + Clone {
+    let status = Value::from(status);
+    move |delivery: &Delivery| workflow_field(delivery, "status") == Some(&status)
+}
+
+/// The check suite (`check_suite` events) has the status (e.g. `requested`, `in_progress` or `completed`)
+#[cfg(feature = "parse")]
+pub fn check_suite_status(status: &str) -> impl Condition + Clone {
+    when_eq("/check_suite/status", status.to_string())
+}
+
+/// The check suite (`check_suite` events) concluded as given (e.g. `success`, `failure` or `timed_out`)
+#[cfg(feature = "parse")]
+pub fn check_suite_conclusion(conclusion: &str) -> impl Condition + Clone {
+    when_eq("/check_suite/conclusion", conclusion.to_string())
+}
+
+/// The check run (`check_run` events) concluded as given (e.g. `success`, `failure` or `action_required`)
+#[cfg(feature = "parse")]
+pub fn check_run_conclusion(conclusion: &str) -> impl Condition + Clone {
+    when_eq("/check_run/conclusion", conclusion.to_string())
+}
+
+/// The delivery of GitLab has the object kind (e.g. `push`, `merge_request`, `pipeline` or `build` for jobs)
+#[cfg(feature = "parse")]
+pub fn object_kind(kind: &str) -> impl Condition + Clone {
+    when_eq("/object_kind", kind.to_string())
+}
+
+/// The pipeline of GitLab (`Pipeline Hook` events) has the status (e.g. `running`, `success` or `failed`)
+#[cfg(feature = "parse")]
+pub fn pipeline_status(status: &str) -> impl Condition + Clone {
+    let status = Value::from(status);
+    move |delivery: &Delivery| {
+        delivery.field("/object_kind").and_then(Value::as_str) == Some("pipeline")
+            && delivery.field("/object_attributes/status") == Some(&status)
+    }
+}
+
+/// The job of GitLab (`Job Hook` events) has the status (e.g. `running`, `success` or `failed`)
+#[cfg(feature = "parse")]
+pub fn job_status(status: &str) -> impl Condition + Clone {
+    let status = Value::from(status);
+    move |delivery: &Delivery| {
+        delivery.field("/object_kind").and_then(Value::as_str) == Some("build")
+            && delivery.field("/build_status") == Some(&status)
+    }
+}
+
+/// The job of GitLab (`Job Hook` events) is in the stage, or the pipeline (`Pipeline Hook` events) has the stage
+#[cfg(feature = "parse")]
+pub fn job_stage(stage: &str) -> impl Condition + Clone {
+    let stage = Value::from(stage);
+    move |delivery: &Delivery| match delivery.field("/object_kind").and_then(Value::as_str) {
+        Some("build") => delivery.field("/build_stage") == Some(&stage),
+        Some("pipeline") => delivery
+            .field("/object_attributes/stages")
+            .and_then(Value::as_array)
+            .is_some_and(|stages| stages.contains(&stage)),
+        _ => false,
+    }
+}
+
+/// The issue or pull request of `issues`, `issue_comment` and `pull_request` events
+#[cfg(feature = "parse")]
+fn issue(delivery: &Delivery) -> Option<&Value> {
+    delivery
+        .field("/pull_request")
+        .or_else(|| delivery.field("/issue"))
+}
+
+/// The issue or pull request has the label
+#[cfg(feature = "parse")]
+pub fn has_label(label: &str) -> impl Condition + Clone {
+    let label = Value::from(label);
+    move |delivery: &Delivery| {
+        issue(delivery)
+            .and_then(|issue| issue.pointer("/labels"))
+            .and_then(Value::as_array)
+            .is_some_and(|labels| labels.iter().any(|item| item.get("name") == Some(&label)))
+    }
+}
+
+/// The issue or pull request was opened by the user with the login
+#[cfg(feature = "parse")]
+pub fn author(login: &str) -> impl Condition + Clone {
+    let login = Value::from(login);
+    move |delivery: &Delivery| {
+        issue(delivery).and_then(|issue| issue.pointer("/user/login")) == Some(&login)
+    }
+}
+
+/// The title of the issue or pull request matches the regular expression
+#[cfg(feature = "regex-filter")]
+pub fn title_matches(regex: Regex) -> impl Condition + Clone {
+    move |delivery: &Delivery| {
+        issue(delivery)
+            .and_then(|issue| issue.pointer("/title"))
+            .and_then(Value::as_str)
+            .is_some_and(|title| regex.is_match(title))
+    }
+}
+
+#[cfg(feature = "parse")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Test matching fields of the payload
+    #[test]
+    fn conditions() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "pull_request".to_string());
+        let request_body =
+            r#"{"action": "opened", "number": 42, "pull_request": {"merged_at": null}}"#;
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(when_eq("/action", "opened").matches(&delivery));
+        assert!(when_eq("/number", 42).matches(&delivery));
+        assert!(!when_eq("/action", "closed").matches(&delivery));
+        assert!(when_ne("/action", "closed").matches(&delivery));
+        assert!(when_ne("/missing", "closed").matches(&delivery));
+        assert!(when_one_of("/action", &["opened", "reopened"]).matches(&delivery));
+        assert!(!when_one_of("/action", &["closed"]).matches(&delivery));
+        assert!(when_exists("/pull_request").matches(&delivery));
+        assert!(!when_exists("/pull_request/merged_at").matches(&delivery));
+    }
+
+    /// Test matching the runs of GitHub Actions and checks
+    #[test]
+    fn actions_conditions() {
+        let delivery = |event: &str, request_body: &str| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert("x-github-event".to_string(), event.to_string());
+            Delivery::new(headers, Some(request_body.to_string())).unwrap()
+        };
+        let run = delivery(
+            "workflow_run",
+            r#"{"action": "completed", "workflow_run": {"status": "completed", "conclusion": "failure"}}"#,
+        );
+        assert!(workflow_conclusion("failure").matches(&run));
+        assert!(!workflow_conclusion("success").matches(&run));
+        assert!(workflow_status("completed").matches(&run));
+        let job = delivery(
+            "workflow_job",
+            r#"{"action": "in_progress", "workflow_job": {"status": "in_progress", "conclusion": null}}"#,
+        );
+        assert!(workflow_status("in_progress").matches(&job));
+        assert!(!workflow_conclusion("failure").matches(&job));
+        let suite = delivery(
+            "check_suite",
+            r#"{"action": "completed", "check_suite": {"status": "completed", "conclusion": "timed_out"}}"#,
+        );
+        assert!(check_suite_status("completed").matches(&suite));
+        assert!(check_suite_conclusion("timed_out").matches(&suite));
+        assert!(!workflow_status("completed").matches(&suite));
+        let check = delivery(
+            "check_run",
+            r#"{"action": "completed", "check_run": {"conclusion": "action_required"}}"#,
+        );
+        assert!(check_run_conclusion("action_required").matches(&check));
+    }
+
+    /// Test matching the pipelines and jobs of GitLab CI
+    #[test]
+    fn gitlab_ci_conditions() {
+        let delivery = |event: &str, request_body: &str| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert("x-gitlab-event".to_string(), event.to_string());
+            Delivery::new(headers, Some(request_body.to_string())).unwrap()
+        };
+        let pipeline = delivery(
+            "Pipeline Hook",
+            r#"{"object_kind": "pipeline", "object_attributes": {"status": "failed", "stages": ["build", "deploy"]}}"#,
+        );
+        assert!(object_kind("pipeline").matches(&pipeline));
+        assert!(pipeline_status("failed").matches(&pipeline));
+        assert!(!pipeline_status("success").matches(&pipeline));
+        assert!(job_stage("deploy").matches(&pipeline));
+        assert!(!job_stage("test").matches(&pipeline));
+        assert!(!job_status("failed").matches(&pipeline));
+        let job = delivery(
+            "Job Hook",
+            r#"{"object_kind": "build", "build_stage": "deploy", "build_status": "failed", "build_name": "production"}"#,
+        );
+        assert!(object_kind("build").matches(&job));
+        assert!(job_status("failed").matches(&job));
+        assert!(job_stage("deploy").matches(&job));
+        assert!(!job_stage("build").matches(&job));
+        assert!(!pipeline_status("failed").matches(&job));
+    }
+
+    /// Test matching issues and pull requests
+    #[test]
+    fn issue_conditions() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "pull_request".to_string());
+        let request_body = r#"{"action": "labeled", "pull_request": {"title": "[WIP] Deploy", "user": {"login": "bors"}, "labels": [{"name": "needs-deploy"}]}}"#;
+        let delivery = Delivery::new(headers.clone(), Some(request_body.to_string())).unwrap();
+        assert!(has_label("needs-deploy").matches(&delivery));
+        assert!(!has_label("bug").matches(&delivery));
+        assert!(author("bors").matches(&delivery));
+        assert!(!author("octocat").matches(&delivery));
+        #[cfg(feature = "regex-filter")]
+        {
+            assert!(title_matches(Regex::new(r"^\[WIP\]").unwrap()).matches(&delivery));
+            assert!(!title_matches(Regex::new("^Release").unwrap()).matches(&delivery));
+        }
+        headers.insert("x-github-event".to_string(), "issues".to_string());
+        let request_body =
+            r#"{"action": "opened", "issue": {"user": {"login": "octocat"}, "labels": []}}"#;
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(author("octocat").matches(&delivery));
+        assert!(!has_label("needs-deploy").matches(&delivery));
+    }
+}
@@ -0,0 +1,305 @@
+//! Git synchronization
+//!
+//! `GitSyncHook` keeps local checkouts in sync with the branches pushed to a repository, and runs a command after
+//! each synchronization (e.g. to restart the service):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::command::CommandHook;
+//! use rifling::git::GitSyncHook;
+//! use rifling::Hook;
+//!
+//! let sync = GitSyncHook::new()
+//!     .branch("master", "/srv/app")
+//!     .branch("staging", "/srv/app-staging")
+//!     .post_sync(CommandHook::shell("make install").inherit_env(&["PATH"]));
+//! let hook = Hook::new("push", Some("secret".to_string()), sync);
+//! ```
+//!
+//! Pushes to other branches, of tags and deleting branches are ignored. Without any branch configured with
+//! `GitSyncHook::branch`, pushes to the default branch of the repository are synchronized to the directory set with
+//! `GitSyncHook::default_dir`.
+//!
+//! Missing checkouts are cloned from the URL of the repository in the payload (e.g. `repository.clone_url` of
+//! GitHub), or the one set with `GitSyncHook::url`. Existing checkouts are fetched and reset to the pushed branch,
+//! changes to tracked files are discarded. Directories may contain `{repository}` to keep the checkouts of many
+//! repositories (see `rifling::repository`). `git` is run with the environment of the server (for its credentials),
+//! without prompting for passwords, and synchronizations of a hook are run one at a time.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use super::command::CommandHook;
+use super::context::Context;
+use super::error::Error;
+use super::handler::Delivery;
+use super::hook::HookFunc;
+use super::repository::working_dir;
+
+/// Hook function synchronizing local checkouts with pushed branches
+#[derive(Clone, Debug)]
+pub struct GitSyncHook {
+    git: String,
+    url: Option<String>,
+    branches: Vec<(String, PathBuf)>,
+    default_dir: Option<PathBuf>,
+    post_sync: Option<CommandHook>,
+    lock: Arc<Mutex<()>>,
+}
+
+/// First string field of the payload at the JSON pointers
+fn field<'a>(delivery: &'a Delivery, pointers: &[&str]) -> Option<&'a str> {
+    pointers
+        .iter()
+        .find_map(|pointer| delivery.field(pointer).and_then(Value::as_str))
+}
+
+/// The main impl clause of `GitSyncHook`
+impl GitSyncHook {
+    /// Create a new hook, configure where branches are checked out with `branch` or `default_dir`
+    pub fn new() -> Self {
+        Self {
+            git: "git".to_string(),
+            url: None,
+            branches: Vec::new(),
+            default_dir: None,
+            post_sync: None,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Keep the branch checked out in the directory
+    pub fn branch(mut self, branch: &str, dir: impl Into<PathBuf>) -> Self {
+        self.branches.push((branch.to_string(), dir.into()));
+        self
+    }
+
+    /// Keep the default branch of the repository checked out in the directory, if no branch is set with `branch`
+    pub fn default_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.default_dir = Some(dir.into());
+        self
+    }
+
+    /// Clone from the URL instead of the one in the payload (e.g. to use SSH)
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Set the path of the `git` executable (`git` by default)
+    pub fn git(mut self, git: &str) -> Self {
+        self.git = git.to_string();
+        self
+    }
+
+    /// Run the command in the checkout after it's synchronized
+    pub fn post_sync(mut self, command: CommandHook) -> Self {
+        self.post_sync = Some(command);
+        self
+    }
+
+    /// Branch pushed and the directory it's checked out in, if it's synchronized
+    fn target(&self, delivery: &Delivery) -> Option<(String, PathBuf)> {
+        let branch = field(delivery, &["/ref"])?.strip_prefix("refs/heads/")?;
+        let deleted = delivery.field("/deleted").and_then(Value::as_bool) == Some(true)
+            || field(delivery, &["/after"]).is_some_and(|after| after.bytes().all(|b| b == b'0'));
+        if deleted || branch.starts_with('-') {
+            return None;
+        }
+        if !self.branches.is_empty() {
+            return self
+                .branches
+                .iter()
+                .find(|(name, _)| name == branch)
+                .map(|(name, dir)| (name.clone(), dir.clone()));
+        }
+        let default_branch = field(
+            delivery,
+            &["/repository/default_branch", "/project/default_branch"],
+        )?;
+        match &self.default_dir {
+            Some(dir) if branch == default_branch => Some((branch.to_string(), dir.clone())),
+            _ => None,
+        }
+    }
+
+    /// Run `git` with the arguments in the directory, returning its output
+    fn run_git(&self, dir: Option<&Path>, args: &[&str]) -> Result<String, Error> {
+        let mut command = Command::new(&self.git);
+        if let Some(dir) = dir {
+            command.current_dir(dir);
+        }
+        debug!("Running git {:?}", args);
+        let output = command
+            .args(args)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|error| Error::HookFailed(format!("Unable to run git: {}", error)))?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        if !output.status.success() {
+            return Err(Error::HookFailed(format!(
+                "git {} exited with {}: {}",
+                args[0],
+                output.status,
+                text.trim()
+            )));
+        }
+        Ok(text)
+    }
+
+    /// Clone or update the checkout of the branch in the directory, returning the output of `git`
+    fn sync(&self, delivery: &Delivery, branch: &str, dir: &Path) -> Result<String, Error> {
+        if !dir.join(".git").exists() {
+            let url = match &self.url {
+                Some(url) => url.as_str(),
+                None => field(
+                    delivery,
+                    &[
+                        "/repository/clone_url",
+                        "/repository/git_http_url",
+                        "/project/git_http_url",
+                    ],
+                )
+                .ok_or_else(|| Error::HookFailed("No URL to clone the repository".to_string()))?,
+            };
+            let dir = dir.to_string_lossy();
+            return self.run_git(
+                None,
+                &[
+                    "clone",
+                    "--branch",
+                    branch,
+                    "--single-branch",
+                    "--",
+                    url,
+                    &dir,
+                ],
+            );
+        }
+        let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch);
+        let mut output = self.run_git(Some(dir), &["fetch", "origin", &refspec])?;
+        let remote_branch = format!("origin/{}", branch);
+        output.push_str(&self.run_git(
+            Some(dir),
+            &["checkout", "--force", "-B", branch, &remote_branch],
+        )?);
+        Ok(output)
+    }
+}
+
+/// Implement `Default` to `GitSyncHook`
+impl Default for GitSyncHook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implement `HookFunc` to `GitSyncHook`
+impl HookFunc for GitSyncHook {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.run_with_context(&Context::new("push"), delivery)
+    }
+
+    fn run_with_context(&self, context: &Context, delivery: &Delivery) -> Result<(), Error> {
+        let (branch, dir) = match self.target(delivery) {
+            Some((branch, dir)) => (branch, working_dir(&dir, delivery)?),
+            None => {
+                debug!("Push not synchronized");
+                return Ok(());
+            }
+        };
+        let _lock = match self.lock.lock() {
+            Ok(lock) => lock,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        debug!("Synchronizing {} in {}", &branch, dir.display());
+        let mut output = self.sync(delivery, &branch, &dir)?;
+        let result = match &self.post_sync {
+            Some(command) => {
+                let post_sync = Context::new(context.pattern());
+                let result = command
+                    .clone()
+                    .current_dir(&dir)
+                    .run_with_context(&post_sync, delivery);
+                output.push_str(&post_sync.take_output().unwrap_or_default());
+                result
+            }
+            None => Ok(()),
+        };
+        context.record_output(output);
+        result
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+
+    /// Run git in the directory for the test
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=rifling",
+                "-c",
+                "user.email=rifling@example.com",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    fn push(branch: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        let request_body = format!(
+            r#"{{"ref": "refs/heads/{}", "after": "c0ffee", "repository": {{"default_branch": "master"}}}}"#,
+            branch
+        );
+        Delivery::new(headers, Some(request_body)).unwrap()
+    }
+
+    /// Test cloning and updating checkouts
+    #[test]
+    fn git_sync() {
+        let root = std::env::temp_dir().join(format!("rifling-git-sync-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let origin = root.join("origin");
+        fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "-q"]);
+        git(&origin, &["symbolic-ref", "HEAD", "refs/heads/master"]);
+        fs::write(origin.join("version"), "1").unwrap();
+        git(&origin, &["add", "version"]);
+        git(&origin, &["commit", "-q", "-m", "First"]);
+        let checkout = root.join("checkout");
+        let hook = GitSyncHook::new()
+            .url(&origin.to_string_lossy())
+            .default_dir(&checkout)
+            .post_sync(CommandHook::shell("cat version > synced"));
+        hook.run(&push("master")).unwrap();
+        assert_eq!(fs::read_to_string(checkout.join("synced")).unwrap(), "1");
+        fs::write(origin.join("version"), "2").unwrap();
+        git(&origin, &["commit", "-q", "-am", "Second"]);
+        let context = Context::new("push");
+        hook.run_with_context(&context, &push("master")).unwrap();
+        assert_eq!(fs::read_to_string(checkout.join("version")).unwrap(), "2");
+        assert_eq!(fs::read_to_string(checkout.join("synced")).unwrap(), "2");
+        assert!(context.take_output().is_some());
+        // Other branches are ignored
+        fs::write(origin.join("version"), "3").unwrap();
+        git(&origin, &["checkout", "-q", "-b", "feature"]);
+        git(&origin, &["commit", "-q", "-am", "Third"]);
+        hook.run(&push("feature")).unwrap();
+        assert_eq!(fs::read_to_string(checkout.join("version")).unwrap(), "2");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
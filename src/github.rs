@@ -0,0 +1,298 @@
+//! GitHub events
+//!
+//! Typed payloads of the organization and sponsorship events of GitHub, to be used with `Hook::typed` or
+//! `Delivery::parse_as`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::github::{MembershipEvent, SponsorshipEvent};
+//! use rifling::{Delivery, Hook};
+//!
+//! let membership = Hook::typed("membership", None, |event: MembershipEvent, _: &Delivery| {
+//!     if event.is_added() {
+//!         println!("{} joined {}", event.member.login, event.team.slug);
+//!     }
+//! });
+//! let sponsorship = Hook::typed("sponsorship", None, |event: SponsorshipEvent, _: &Delivery| {
+//!     println!("{} sponsors for {} cents", event.sponsor().login, event.monthly_price_in_cents());
+//! });
+//! ```
+//!
+//! Only the commonly used fields are included, the others can be read with `Delivery::field`. Actions are kept as
+//! sent (e.g. `added`), new actions of GitHub don't break the deserialization.
+
+use serde::Deserialize;
+
+/// User, bot or organization acting in or being the subject of the event
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Account {
+    pub login: String,
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub account_type: Option<String>, // `User`, `Bot` or `Organization`
+    pub html_url: Option<String>,
+}
+
+/// Organization the event happened in
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Organization {
+    pub login: String,
+    pub id: u64,
+    pub description: Option<String>,
+}
+
+/// Team of an organization, only `id` and `name` are set for deleted teams
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Team {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub slug: String,
+    pub description: Option<String>,
+    pub privacy: Option<String>,
+    pub permission: Option<String>,
+    pub parent: Option<Box<Team>>,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// Repository a team was given access to or removed from
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Repository {
+    pub id: u64,
+    pub name: String,
+    pub full_name: String,
+}
+
+/// Membership of a user in an organization
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Membership {
+    pub state: String, // `active` or `pending`
+    pub role: String,  // `admin`, `member` or `billing_manager`
+    pub user: Option<Account>,
+}
+
+/// Invitation to join an organization
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Invitation {
+    pub id: u64,
+    pub login: Option<String>,
+    pub email: Option<String>,
+    pub role: String,
+}
+
+/// Payload of `membership` events, a user was added to or removed from a team
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct MembershipEvent {
+    pub action: String, // `added` or `removed`
+    pub scope: String,  // `team`
+    pub member: Account,
+    pub team: Team,
+    pub organization: Organization,
+    pub sender: Account,
+}
+
+/// Payload of `organization` events, e.g. a member was invited, added or removed
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct OrganizationEvent {
+    pub action: String, // e.g. `member_invited`, `member_added`, `member_removed`, `renamed` or `deleted`
+    pub membership: Option<Membership>,
+    pub invitation: Option<Invitation>,
+    pub user: Option<Account>, // the invited user of `member_invited`
+    pub organization: Organization,
+    pub sender: Account,
+}
+
+/// Payload of `team` events, e.g. a team was created or given access to a repository
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TeamEvent {
+    pub action: String, // e.g. `created`, `deleted`, `edited` or `added_to_repository`
+    pub team: Team,
+    pub repository: Option<Repository>,
+    pub organization: Organization,
+    pub sender: Account,
+}
+
+/// Payload of `org_block` events, a user was blocked or unblocked by an organization
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct OrgBlockEvent {
+    pub action: String, // `blocked` or `unblocked`
+    pub blocked_user: Account,
+    pub organization: Organization,
+    pub sender: Account,
+}
+
+/// Tier of a sponsorship
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SponsorsTier {
+    pub name: String,
+    pub description: Option<String>,
+    pub monthly_price_in_cents: u64,
+    #[serde(default)]
+    pub is_one_time: bool,
+    #[serde(default)]
+    pub is_custom_amount: bool,
+}
+
+/// Sponsorship of a user or an organization
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Sponsorship {
+    pub created_at: String,
+    pub sponsorable: Account,
+    pub sponsor: Account,
+    pub privacy_level: String, // `public` or `private`
+    pub tier: SponsorsTier,
+}
+
+/// Previous value of a field changed by the event
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Change<T> {
+    pub from: T,
+}
+
+/// Fields of the sponsorship changed by `edited`, `tier_changed` and `pending_tier_change` events
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct SponsorshipChanges {
+    pub tier: Option<Change<SponsorsTier>>,
+    pub privacy_level: Option<Change<String>>,
+}
+
+/// Payload of `sponsorship` events
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SponsorshipEvent {
+    pub action: String, // e.g. `created`, `cancelled`, `tier_changed` or `pending_cancellation`
+    pub sponsorship: Sponsorship,
+    pub effective_date: Option<String>, // of pending changes
+    pub changes: Option<SponsorshipChanges>,
+    pub sender: Account,
+}
+
+/// The main impl clause of `MembershipEvent`
+impl MembershipEvent {
+    /// Test if the member was added to the team
+    pub fn is_added(&self) -> bool {
+        self.action == "added"
+    }
+}
+
+/// The main impl clause of `OrganizationEvent`
+impl OrganizationEvent {
+    /// User the membership or invitation of the event is about
+    pub fn member(&self) -> Option<&Account> {
+        self.membership
+            .as_ref()
+            .and_then(|membership| membership.user.as_ref())
+            .or(self.user.as_ref())
+    }
+}
+
+/// The main impl clause of `OrgBlockEvent`
+impl OrgBlockEvent {
+    /// Test if the user was blocked
+    pub fn is_blocked(&self) -> bool {
+        self.action == "blocked"
+    }
+}
+
+/// The main impl clause of `SponsorshipEvent`
+impl SponsorshipEvent {
+    /// Account sponsoring
+    pub fn sponsor(&self) -> &Account {
+        &self.sponsorship.sponsor
+    }
+
+    /// Account sponsored
+    pub fn sponsorable(&self) -> &Account {
+        &self.sponsorship.sponsorable
+    }
+
+    /// Monthly price of the tier in cents (the whole amount of one-time sponsorships)
+    pub fn monthly_price_in_cents(&self) -> u64 {
+        self.sponsorship.tier.monthly_price_in_cents
+    }
+
+    /// Tier before the change of `tier_changed` and `pending_tier_change` events
+    pub fn previous_tier(&self) -> Option<&SponsorsTier> {
+        self.changes
+            .as_ref()
+            .and_then(|changes| changes.tier.as_ref())
+            .map(|change| &change.from)
+    }
+
+    /// Test if the change (e.g. a cancellation) takes effect later, on `effective_date`
+    pub fn is_pending(&self) -> bool {
+        self.action.starts_with("pending_")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Delivery;
+    use std::collections::HashMap;
+
+    fn delivery(event: &str, request_body: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), event.to_string());
+        Delivery::new(headers, Some(request_body.to_string())).unwrap()
+    }
+
+    /// Test parsing events of organizations
+    #[test]
+    fn organization_events() {
+        let membership: MembershipEvent = delivery(
+            "membership",
+            r#"{"action": "added", "scope": "team", "member": {"login": "octocat", "id": 1, "type": "User"}, "team": {"id": 2, "name": "Ops", "slug": "ops", "permission": "pull"}, "organization": {"login": "rifling", "id": 3}, "sender": {"login": "RedL0tus", "id": 4}}"#,
+        )
+        .parse_as()
+        .unwrap();
+        assert!(membership.is_added());
+        assert_eq!(membership.member.account_type.as_deref(), Some("User"));
+        assert_eq!(membership.team.slug, "ops");
+        let organization: OrganizationEvent = delivery(
+            "organization",
+            r#"{"action": "member_added", "membership": {"state": "active", "role": "member", "user": {"login": "octocat", "id": 1}}, "organization": {"login": "rifling", "id": 3}, "sender": {"login": "RedL0tus", "id": 4}}"#,
+        )
+        .parse_as()
+        .unwrap();
+        assert_eq!(organization.member().unwrap().login, "octocat");
+        let team: TeamEvent = delivery(
+            "team",
+            r#"{"action": "deleted", "team": {"id": 2, "name": "Ops", "deleted": true}, "organization": {"login": "rifling", "id": 3}, "sender": {"login": "RedL0tus", "id": 4}}"#,
+        )
+        .parse_as()
+        .unwrap();
+        assert!(team.team.deleted);
+        let block: OrgBlockEvent = delivery(
+            "org_block",
+            r#"{"action": "blocked", "blocked_user": {"login": "spammer", "id": 5}, "organization": {"login": "rifling", "id": 3}, "sender": {"login": "RedL0tus", "id": 4}}"#,
+        )
+        .parse_as()
+        .unwrap();
+        assert!(block.is_blocked());
+        assert_eq!(block.blocked_user.login, "spammer");
+        let delivery = delivery("org_block", r#"{"organization": {"login": "rifling"}}"#);
+        assert_eq!(delivery.organization().unwrap(), "rifling");
+    }
+
+    /// Test parsing events of sponsorships
+    #[test]
+    fn sponsorship_events() {
+        let sponsorship: SponsorshipEvent = delivery(
+            "sponsorship",
+            r#"{"action": "pending_tier_change", "effective_date": "2019-12-30T00:00:00+00:00", "sponsorship": {"created_at": "2019-12-20T19:24:46+00:00", "sponsorable": {"login": "RedL0tus", "id": 4}, "sponsor": {"login": "octocat", "id": 1}, "privacy_level": "public", "tier": {"name": "$5 a month", "monthly_price_in_cents": 500}}, "changes": {"tier": {"from": {"name": "$10 a month", "monthly_price_in_cents": 1000}}}, "sender": {"login": "octocat", "id": 1}}"#,
+        )
+        .parse_as()
+        .unwrap();
+        assert!(sponsorship.is_pending());
+        assert_eq!(sponsorship.sponsor().login, "octocat");
+        assert_eq!(sponsorship.sponsorable().login, "RedL0tus");
+        assert_eq!(sponsorship.monthly_price_in_cents(), 500);
+        assert_eq!(
+            sponsorship.previous_tier().unwrap().monthly_price_in_cents,
+            1000
+        );
+        assert!(!sponsorship.sponsorship.tier.is_one_time);
+    }
+}
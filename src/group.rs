@@ -0,0 +1,87 @@
+//! Hook groups
+//!
+//! Hooks registered with `Constructor::group` belong to a named group, which can be disabled and enabled again at
+//! runtime with the handle returned by `Constructor::groups_handle` (e.g. to pause deployments during a freeze):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.group("deploy")
+//!     .register(Hook::new("push", None, |_: &Delivery| println!("Deploying")))
+//!     .register(Hook::new("release", None, |_: &Delivery| println!("Deploying")));
+//! let groups = cons.groups_handle();
+//! // Later, e.g. from the admin endpoint of the application
+//! groups.disable("deploy");
+//! assert!(!groups.is_enabled("deploy"));
+//! ```
+//!
+//! Hooks of disabled groups are not run, as if they were not registered. Groups are enabled by default, and the
+//! switches are shared by every `Handler` created from the constructor.
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use super::handler::Constructor;
+use super::hook::Hook;
+
+/// Group of hooks being registered, see `Constructor::group`
+pub struct Group<'a> {
+    constructor: &'a mut Constructor,
+    name: String,
+}
+
+/// Handle to the switches of the hook groups of a `Constructor` and its `Handler`s, cheap to clone
+#[derive(Clone, Debug, Default)]
+pub struct GroupsHandle {
+    disabled: Arc<RwLock<HashSet<String>>>,
+}
+
+/// The main impl clause of `Group`
+impl<'a> Group<'a> {
+    /// Create the group registering hooks to the constructor
+    pub(crate) fn new(constructor: &'a mut Constructor, name: &str) -> Self {
+        Self {
+            constructor,
+            name: name.to_string(),
+        }
+    }
+
+    /// Register a hook in the group
+    pub fn register(&mut self, mut hook: Hook) -> &mut Self {
+        hook.group = Some(self.name.clone());
+        self.constructor.register(hook);
+        self
+    }
+}
+
+/// The main impl clause of `GroupsHandle`
+impl GroupsHandle {
+    /// Run the hooks of the group again
+    pub fn enable(&self, name: &str) {
+        let mut disabled = match self.disabled.write() {
+            Ok(disabled) => disabled,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        disabled.remove(name);
+    }
+
+    /// Stop running the hooks of the group
+    pub fn disable(&self, name: &str) {
+        let mut disabled = match self.disabled.write() {
+            Ok(disabled) => disabled,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        disabled.insert(name.to_string());
+    }
+
+    /// Test if the hooks of the group are run
+    pub fn is_enabled(&self, name: &str) -> bool {
+        match self.disabled.read() {
+            Ok(disabled) => !disabled.contains(name),
+            Err(poisoned) => !poisoned.into_inner().contains(name),
+        }
+    }
+}
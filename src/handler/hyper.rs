@@ -12,17 +12,198 @@
 //!
 //! let _ = hyper::Server::bind(&"0.0.0.0:4567".parse().unwrap()).serve(Constructor::new());
 //! ```
+//!
+//! To make the address of the sender available (e.g. for rate limiting per IP address), serve
+//! `Constructor::with_remote_addr()` instead:
+//!
+//! ```
+//! extern crate rifling;
+//! extern crate hyper;
+//!
+//! use rifling::Constructor;
+//!
+//! let _ = hyper::Server::bind(&"0.0.0.0:4567".parse().unwrap()).serve(Constructor::new().with_remote_addr());
+//! ```
+//!
+//! Senders holding the body open (e.g. slow loris attacks) can be answered with `408 Request Timeout` by setting
+//! `Constructor::read_timeout` and `Constructor::idle_timeout`. The timeouts need the timer of the tokio runtime,
+//! which is available when serving with `hyper::rt::run`, as does holding the responses with
+//! `Constructor::min_response_time`.
 
 use futures::stream::Stream;
+use futures::sync::mpsc;
 use futures::{future, Future};
-use hyper::service::{NewService, Service};
-use hyper::{Body, Error, Request, Response, StatusCode};
+use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use hyper::server::conn::AddrStream;
+use hyper::service::{MakeService, NewService, Service};
+use hyper::{Body, Chunk, Error, Method, Request, Response, StatusCode};
+use tokio_timer::{timeout, Delay, Timeout};
 
-use std::collections::HashMap;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use super::Constructor;
-use super::Delivery;
+use super::super::context::ResponseStream;
+use super::super::error::Error as RiflingError;
+use super::super::outcome::Response as RiflingResponse;
+use super::super::router::{Router, RouterHandler};
+#[cfg(feature = "tls-server")]
+use super::super::tls::TlsStream;
 use super::Handler;
+use super::{Constructor, Headers, Settings};
+
+/// Failure to read the body of a request
+enum ReadError {
+    Hyper(Error),
+    /// Not received within the timeout
+    TimedOut(Duration),
+    /// Larger than the limit in bytes
+    TooLarge(usize),
+    /// The timer of the runtime is unavailable
+    Timer(tokio_timer::Error),
+}
+
+/// Convert the error of the body read with the idle timeout
+fn idle_error(error: timeout::Error<Error>, idle_timeout: Duration) -> ReadError {
+    if error.is_elapsed() {
+        ReadError::TimedOut(idle_timeout)
+    } else if error.is_timer() {
+        ReadError::Timer(error.into_timer().unwrap())
+    } else {
+        ReadError::Hyper(error.into_inner().unwrap())
+    }
+}
+
+/// Read the whole body within the read timeout, pausing no longer than the idle timeout
+fn read_body(
+    body: Body,
+    settings: &Settings,
+) -> Box<dyn Future<Item = Vec<u8>, Error = ReadError> + Send> {
+    let chunks: Box<dyn Stream<Item = Chunk, Error = ReadError> + Send> = match settings
+        .idle_timeout
+    {
+        Some(idle_timeout) => Box::new(
+            Timeout::new(body, idle_timeout).map_err(move |error| idle_error(error, idle_timeout)),
+        ),
+        None => Box::new(body.map_err(ReadError::Hyper)),
+    };
+    let max_body_size = settings.max_body_size;
+    let read: Box<dyn Future<Item = Vec<u8>, Error = ReadError> + Send> = Box::new(chunks.fold(
+        Vec::new(),
+        move |mut body, chunk| match max_body_size {
+            Some(limit) if body.len() + chunk.len() > limit => Err(ReadError::TooLarge(limit)),
+            _ => {
+                body.extend_from_slice(&chunk);
+                Ok(body)
+            }
+        },
+    ));
+    let read_timeout = settings.read_timeout;
+    match read_timeout {
+        Some(read_timeout) => Box::new(Timeout::new(read, read_timeout).map_err(move |error| {
+            if error.is_elapsed() {
+                ReadError::TimedOut(read_timeout)
+            } else if error.is_timer() {
+                ReadError::Timer(error.into_timer().unwrap())
+            } else {
+                error.into_inner().unwrap()
+            }
+        })),
+        None => read,
+    }
+}
+
+/// Body sending the fragments of the stream as hooks add them, one per line after the body
+fn streamed_body(body: String, stream: ResponseStream) -> Body {
+    let (sender, receiver) = mpsc::unbounded();
+    let _ = sender.unbounded_send(Chunk::from(body));
+    thread::spawn(move || {
+        for fragment in stream {
+            // The peer may be gone already
+            if sender
+                .unbounded_send(Chunk::from(format!("\n{}", fragment)))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    Body::wrap_stream(receiver.map_err(|()| io::Error::other("Response stream closed")))
+}
+
+/// Build the response to the request
+fn to_response(rifling_response: &RiflingResponse) -> Response<Body> {
+    let body = match &rifling_response.stream {
+        Some(stream) => streamed_body(rifling_response.body(), stream.clone()),
+        None => rifling_response.body().into(),
+    };
+    let mut response = Response::builder()
+        .status(rifling_response.status())
+        .body(body)
+        .unwrap();
+    if let Some(content_type) = rifling_response.content_type() {
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    }
+    if let Some(retry_after) = rifling_response.retry_after() {
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+    for (name, value) in &rifling_response.headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                response.headers_mut().append(name, value);
+            }
+            _ => debug!("Invalid response header: {}", name),
+        }
+    }
+    response
+}
+
+/// Future of the response to a request
+type ResponseFuture = Box<dyn Future<Item = Response<Body>, Error = Error> + Send + 'static>;
+
+/// Send the response once it's no longer held (see `Constructor::min_response_time`), waiting with the timer of the
+/// runtime instead of blocking its threads
+fn send(rifling_response: RiflingResponse) -> ResponseFuture {
+    match rifling_response.hold_until {
+        Some(hold_until) if hold_until > Instant::now() => {
+            Box::new(Delay::new(hold_until).then(move |result| {
+                if let Err(error) = result {
+                    error!("Unable to hold the response: {}", error);
+                }
+                Ok(to_response(&rifling_response))
+            }))
+        }
+        _ => Box::new(future::ok(to_response(&rifling_response))),
+    }
+}
+
+/// Wrapper of `Constructor` or `Router` passing the address of the peer to the handlers,
+/// see `Constructor::with_remote_addr`
+#[derive(Clone)]
+pub struct WithRemoteAddr<T>(T);
+
+/// Hyper-related impl clause of `Constructor`
+impl Constructor {
+    /// Serve the constructor with the address of the peer available to the handlers
+    pub fn with_remote_addr(self) -> WithRemoteAddr<Constructor> {
+        WithRemoteAddr(self)
+    }
+}
+
+/// Hyper-related impl clause of `Router`
+impl Router {
+    /// Serve the router with the address of the peer available to the handlers
+    pub fn with_remote_addr(self) -> WithRemoteAddr<Router> {
+        WithRemoteAddr(self)
+    }
+}
 
 /// Implement `NewService` trait to `Constructor`
 impl NewService for Constructor {
@@ -30,7 +211,7 @@ impl NewService for Constructor {
     type ResBody = Body;
     type Error = Error;
     type Service = Handler;
-    type Future = Box<Future<Item = Self::Service, Error = Self::InitError> + Send>;
+    type Future = Box<dyn Future<Item = Self::Service, Error = Self::InitError> + Send>;
     type InitError = Error;
 
     /// Create a new handler to handle the service
@@ -40,65 +221,348 @@ impl NewService for Constructor {
     }
 }
 
+/// Implement `NewService` trait to `Router`
+impl NewService for Router {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Error;
+    type Service = RouterHandler;
+    type Future = Box<dyn Future<Item = Self::Service, Error = Self::InitError> + Send>;
+    type InitError = Error;
+
+    /// Create new handlers for every mounted constructor
+    fn new_service(&self) -> Self::Future {
+        debug!("Creating new service");
+        Box::new(future::ok(RouterHandler::from(self)))
+    }
+}
+
+/// Implement `MakeService` trait to `WithRemoteAddr<Router>`
+impl<'a> MakeService<&'a AddrStream> for WithRemoteAddr<Router> {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Error;
+    type Service = RouterHandler;
+    type Future = Box<dyn Future<Item = Self::Service, Error = Self::MakeError> + Send>;
+    type MakeError = Error;
+
+    /// Create new handlers for the connection
+    fn make_service(&mut self, socket: &'a AddrStream) -> Self::Future {
+        debug!("Creating new service for {}", socket.remote_addr());
+        Box::new(future::ok(self.0.handler(Some(socket.remote_addr()))))
+    }
+}
+
+/// Implement `Service` trait to `RouterHandler`
+impl Service for RouterHandler {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Error;
+    type Future = ResponseFuture;
+
+    /// Dispatch the request to the handler mounted at its path
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        match self.route_mut(req.uri().path()) {
+            Some(handler) => handler.call(req),
+            None => {
+                debug!("No constructor mounted at '{}'", req.uri().path());
+                Box::new(future::ok(
+                    Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body("Not found".into())
+                        .unwrap(),
+                ))
+            }
+        }
+    }
+}
+
+/// Implement `MakeService` trait to `WithRemoteAddr<Constructor>`
+impl<'a> MakeService<&'a AddrStream> for WithRemoteAddr<Constructor> {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Error;
+    type Service = Handler;
+    type Future = Box<dyn Future<Item = Self::Service, Error = Self::MakeError> + Send>;
+    type MakeError = Error;
+
+    /// Create a new handler for the connection
+    fn make_service(&mut self, socket: &'a AddrStream) -> Self::Future {
+        debug!("Creating new service for {}", socket.remote_addr());
+        Box::new(future::ok(
+            Handler::from(&self.0).with_remote_addr(socket.remote_addr()),
+        ))
+    }
+}
+
+/// Implement `MakeService` trait to `WithRemoteAddr<Constructor>` for TLS connections, the identity of the client is
+/// available to the handlers too
+#[cfg(feature = "tls-server")]
+impl<'a> MakeService<&'a TlsStream> for WithRemoteAddr<Constructor> {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Error;
+    type Service = Handler;
+    type Future = Box<dyn Future<Item = Self::Service, Error = Self::MakeError> + Send>;
+    type MakeError = Error;
+
+    /// Create a new handler for the connection
+    fn make_service(&mut self, stream: &'a TlsStream) -> Self::Future {
+        let identity = stream.client_identity();
+        debug!(
+            "Creating new service for {} ({:?})",
+            stream.remote_addr(),
+            identity
+        );
+        let handler = Handler::from(&self.0).with_remote_addr(stream.remote_addr());
+        Box::new(future::ok(match identity {
+            Some(identity) => handler.with_client_identity(&identity),
+            None => handler,
+        }))
+    }
+}
+
 /// Implement `Service` struct from `Hyper` to `Handler`
 impl Service for Handler {
     type ReqBody = Body;
     type ResBody = Body;
     type Error = Error;
-    type Future = Box<Future<Item = Response<Body>, Error = Error> + Send + 'static>;
+    type Future = ResponseFuture;
 
     /// Handle the request
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
-        fn response(status_code: StatusCode, body: &'static str) -> Response<Body> {
-            Response::builder()
-                .status(status_code)
-                .body(body.into())
-                .unwrap()
+        if req.method() == Method::GET || req.method() == Method::HEAD {
+            if let Some(response) = self.landing() {
+                return send(response);
+            }
+        }
+        if let Some(response) = self.precheck(self.remote_addr, req.headers()) {
+            return send(response);
         }
-        let headers = req
-            .headers()
-            .clone()
-            .into_iter()
-            .map(|(name, content)| {
-                let key = if let Some(header_name) = name {
-                    header_name.as_str().to_string()
-                } else {
-                    "unknown".to_string().to_lowercase()
-                };
-                let value = if let Ok(header_value) = content.to_str() {
-                    header_value.to_string()
-                } else {
-                    "unknown".to_string()
-                };
-                (key, value)
+        let handler = self.clone();
+        let query = req.uri().query().map(String::from);
+        let (timestamp, start) = (SystemTime::now(), Instant::now());
+        let (parts, body) = req.into_parts();
+        // Kept in the map they're received in, converted when they're looked up
+        let headers = Headers::from(parts.headers);
+        let body = read_body(body, &self.settings);
+        Box::new(body.then(move |result| -> ResponseFuture {
+            match result {
+                Ok(body) => {
+                    let query = query.as_deref();
+                    send(handler.handle_from(handler.remote_addr, query, headers, &body))
+                }
+                Err(ReadError::TimedOut(timeout)) => {
+                    debug!("Request body not received within {:?}", timeout);
+                    let error = RiflingError::RequestTimeout(timeout);
+                    send(handler.refuse(handler.remote_addr, timestamp, start, error))
+                }
+                Err(ReadError::TooLarge(limit)) => {
+                    debug!("Request body larger than {} bytes", limit);
+                    let error = RiflingError::PayloadTooLarge(limit);
+                    send(handler.refuse(handler.remote_addr, timestamp, start, error))
+                }
+                Err(ReadError::Timer(error)) => {
+                    error!("Unable to time out the request: {}", error);
+                    Box::new(future::ok(
+                        Response::builder()
+                            .status(StatusCode::INTERNAL_SERVER_ERROR)
+                            .body("Timer unavailable".into())
+                            .unwrap(),
+                    ))
+                }
+                Err(ReadError::Hyper(error)) => Box::new(future::err(error)),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::context::Context;
+    use super::super::super::error;
+    use super::super::Delivery;
+    use super::*;
+    use crate::hook::Hook;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Test timing out requests holding the body open
+    #[test]
+    fn read_timeout() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_inner = errors.clone();
+        let mut cons = Constructor::new();
+        cons.idle_timeout(Duration::from_millis(100));
+        cons.read_timeout(Duration::from_secs(5));
+        cons.on_error(move |error: &error::Error| errors_inner.lock().unwrap().push(error.clone()));
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(cons);
+        let addr = server.local_addr();
+        thread::spawn(move || hyper::rt::run(server.map_err(|_| ())));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nX-GitHub-Event: push\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408"));
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![error::Error::RequestTimeout(Duration::from_millis(100))]
+        );
+    }
+
+    /// Test streaming the fragments added by hooks with chunked transfer encoding
+    #[test]
+    fn streamed_response() {
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::with_context("push", None, |context: &Context, _: &Delivery| {
+                context.respond("Building...");
+                context.respond("Done");
             })
-            .collect::<HashMap<String, String>>();
-        let mut delivery = match Delivery::new(headers, None) {
-            Ok(delivery_inner) => delivery_inner,
-            Err(err_msg) => return Box::new(future::ok(response(StatusCode::ACCEPTED, err_msg))),
+            .stream_response(),
+        );
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(cons);
+        let addr = server.local_addr();
+        thread::spawn(move || hyper::rt::run(server.map_err(|_| ())));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nX-GitHub-Event: push\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("transfer-encoding: chunked\r\n"));
+        assert!(
+            response.ends_with("\r\n\r\n2\r\nOK\r\nC\r\n\nBuilding...\r\n5\r\n\nDone\r\n0\r\n\r\n")
+        );
+    }
+
+    /// Test answering browsers with the landing page
+    #[test]
+    fn landing_page() {
+        let mut cons = Constructor::new();
+        cons.landing_page("deploy-bot");
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(cons);
+        let addr = server.local_addr();
+        thread::spawn(move || hyper::rt::run(server.map_err(|_| ())));
+        let request = |method: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "{} / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                method
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
         };
-        let executor = self.get_hooks(delivery.event.as_str());
-        if executor.is_empty() {
-            // No matched hook found
-            return Box::new(future::ok(response(
-                StatusCode::ACCEPTED,
-                "No matched hook configured",
-            )));
-        }
-        Box::new(
-            req.into_body()
-                .concat2()
-                .map(move |chunk| String::from_utf8(chunk.to_vec()).ok())
-                .and_then(move |request_body| {
-                    if request_body.is_some() {
-                        delivery.update_request_body(request_body);
-                        debug!("Received delivery: {:#?}", &delivery);
-                        executor.run(delivery);
-                        future::ok(response(StatusCode::OK, "OK"))
-                    } else {
-                        future::ok(response(StatusCode::ACCEPTED, "Invalid payload"))
-                    }
-                }),
+        let response = request("GET");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("content-type: application/json"));
+        assert!(response.contains(r#"{"service":"deploy-bot","status":"ok""#));
+        let response = request("HEAD");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(!response.contains("deploy-bot"));
+        assert!(request("POST").starts_with("HTTP/1.1 202"));
+    }
+
+    /// Test refusing requests with large bodies
+    #[test]
+    fn max_body_size() {
+        let mut cons = Constructor::new();
+        cons.max_body_size(4);
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(cons);
+        let addr = server.local_addr();
+        thread::spawn(move || hyper::rt::run(server.map_err(|_| ())));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nX-GitHub-Event: push\r\nContent-Length: 8\r\nConnection: close\r\n\r\n{{    }}"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"));
+    }
+
+    /// Test refusing deliveries with a wrong token before their body is sent
+    #[test]
+    fn token_precheck() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new(
+            "push",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(cons);
+        let addr = server.local_addr();
+        thread::spawn(move || hyper::rt::run(server.map_err(|_| ())));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nX-Gitlab-Event: Push\r\nX-Gitlab-Token: wrong\r\nContent-Length: 1048576\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        // The body is never sent, the response comes anyway
+        let mut status_line = [0; 12];
+        stream.read_exact(&mut status_line).unwrap();
+        assert_eq!(&status_line, b"HTTP/1.1 401");
+    }
+
+    /// Test holding responses with the timer, other requests being answered meanwhile
+    #[test]
+    fn min_response_time() {
+        let mut cons = Constructor::new();
+        cons.min_response_time(Duration::from_millis(500));
+        cons.landing_page("deploy-bot");
+        cons.register(Hook::new(
+            "push",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(cons);
+        let addr = server.local_addr();
+        thread::spawn(move || {
+            // A single thread, blocking it would delay the landing page too
+            let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+            runtime.block_on(server).unwrap();
+        });
+        let start = Instant::now();
+        let forged = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST / HTTP/1.1\r\nHost: localhost\r\nX-Gitlab-Event: Push\r\nX-Gitlab-Token: wrong\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        thread::sleep(Duration::from_millis(100));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
         )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(forged.join().unwrap().starts_with("HTTP/1.1 401"));
+        assert!(start.elapsed() >= Duration::from_millis(500));
     }
 }
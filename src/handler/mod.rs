@@ -6,18 +6,85 @@
 
 #[cfg(feature = "hyper-support")]
 mod hyper;
+#[cfg(feature = "sync-server")]
+mod sync;
+#[cfg(feature = "wasm-support")]
+mod wasm;
 
+#[cfg(feature = "hyper-support")]
+pub use self::hyper::WithRemoteAddr;
+
+#[cfg(feature = "regex-match")]
+use regex::Regex;
+#[cfg(feature = "typed")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "raw-payload")]
+use serde_json::value::RawValue;
 #[cfg(feature = "parse")]
 use serde_json::Value;
 #[cfg(feature = "content-type-urlencoded")]
 use url::form_urlencoded;
 
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::slice;
+use std::str::FromStr;
+#[cfg(feature = "http")]
+use std::sync::OnceLock;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::access_log::{self, AccessLogCallback, AccessLogEntry};
+#[cfg(feature = "parse")]
+use super::admission::{self, AdmissionResponse};
+use super::concurrency::{ConcurrencyLimiter, ConcurrencyPolicy, Permit};
+use super::context::{self, Context, ResponseStream};
+use super::error::Error;
+use super::group::{Group, GroupsHandle};
+use super::hook::{Authenticator, Hook};
+use super::landing;
+use super::maintenance::MaintenanceHandle;
+#[cfg(feature = "multipart")]
+use super::multipart::{self, Part};
+#[cfg(feature = "otel")]
+use super::otel;
+use super::outcome::{Outcome, Response};
+use super::overrides::OverridesHandle;
+use super::proxy::{self, Cidr, InvalidCidr};
+use super::ratelimit::{RateLimitScope, RateLimiter};
+use super::readiness::ReadinessCheck;
+use super::redact;
+use super::reload::ReloadHandle;
+use super::request_id;
+use super::retry::{self, Job, RetryQueue};
+use super::schedule::{self, Batched, PendingRuns, Schedule};
+use super::sink::Sink;
+use super::stats::{Stats, StatsHandle};
+use super::store::{DeliveryStore, HookRun};
+use super::transform::Transformer;
+use super::unauthenticated::{MissingControl, UnauthenticatedProvider};
+use super::validate::Validator;
+#[cfg(feature = "chrono")]
+use super::window::OutOfWindow;
 
-use super::hook::Hook;
+/// Registry of hooks, by the normalized event they are registered for
+pub type HookRegistry = HashMap<String, Vec<Hook>>;
 
-/// Registry of hooks
-pub type HookRegistry = HashMap<String, Hook>;
+/// Key of the fallback hook in the registry, see `Hook::fallback`
+const FALLBACK_KEY: &str = "<fallback>";
+
+/// Callback receiving errors occurred while handling deliveries
+pub type ErrorCallback = Arc<dyn Fn(&Error) + Send + Sync>;
+
+/// Callback receiving the response to every request
+pub type OutcomeCallback = Arc<dyn Fn(&Response) + Send + Sync>;
+
+/// Value of a response header, static or computed from the delivery
+type HeaderValueFn = Arc<dyn Fn(Option<&Delivery>) -> Option<String> + Send + Sync>;
 
 /// Find matched hooks from `HookRegistry`, accepting multiple keys.
 #[macro_export]
@@ -35,11 +102,9 @@ macro_rules! hooks_find_match {
 
 macro_rules! header_get_owned {
     ($headers:expr, $key:expr) => {
-        if let Some(header_value) = $headers.get($key) {
-            Some(header_value.to_owned())
-        } else {
-            None
-        }
+        $headers
+            .get($key)
+            .map(|header_value| header_value.to_owned())
     };
 }
 
@@ -48,14 +113,161 @@ macro_rules! header_get_owned {
 pub enum ContentType {
     JSON,
     URLENCODED,
+    MULTIPART,
 }
 
 /// Source of the delivery
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DeliveryType {
     GitHub,
     GitLab,
     DockerHub,
+    Forgejo,
+    Gitea,
+    Gitee,
+    Gerrit,
+    Jenkins,
+    CircleCI,
+    Grafana,
+    Discord,
+    Telegram,
+    Linear,
+    Intercom,
+    Artifactory,
+    Npm,
+    Kubernetes,
+    Form,
+    Unknown, // see `Constructor::accept_unknown`
+}
+
+/// Implement `FromStr` to `DeliveryType`, parsing the name of the provider ignoring case (e.g. `github`)
+impl FromStr for DeliveryType {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        let provider = match name.to_ascii_lowercase().as_str() {
+            "github" => DeliveryType::GitHub,
+            "gitlab" => DeliveryType::GitLab,
+            "dockerhub" => DeliveryType::DockerHub,
+            "forgejo" => DeliveryType::Forgejo,
+            "gitea" => DeliveryType::Gitea,
+            "gitee" => DeliveryType::Gitee,
+            "gerrit" => DeliveryType::Gerrit,
+            "jenkins" => DeliveryType::Jenkins,
+            "circleci" => DeliveryType::CircleCI,
+            "grafana" => DeliveryType::Grafana,
+            "discord" => DeliveryType::Discord,
+            "telegram" => DeliveryType::Telegram,
+            "linear" => DeliveryType::Linear,
+            "intercom" => DeliveryType::Intercom,
+            "artifactory" => DeliveryType::Artifactory,
+            "npm" => DeliveryType::Npm,
+            "kubernetes" => DeliveryType::Kubernetes,
+            "form" => DeliveryType::Form,
+            _ => return Err(Error::InvalidDelivery("Unknown provider")),
+        };
+        Ok(provider)
+    }
+}
+
+/// Provider assumed for a request it can't be determined from, and its event, see `Constructor::provider_hint`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ProviderHint {
+    provider: DeliveryType,
+    event: Option<String>,
+}
+
+/// How event names are normalized, in deliveries and when registering hooks
+#[derive(Clone)]
+pub enum EventNormalization {
+    /// Lower case, with whitespaces replaced by underscores (e.g. `Push Hook` becomes `push_hook`)
+    Normalize,
+    /// Keep event names as sent
+    Preserve,
+    /// Map event names with the function
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+/// How requests carrying the event headers of several providers (e.g. `X-GitHub-Event` and `X-Gitlab-Event`, sent by
+/// misconfigured proxies or test tools) are handled
+///
+/// Forgejo and Gitea send the headers of GitHub too, this is not ambiguous.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AmbiguityPolicy {
+    /// Pick the first provider in the order Forgejo, Gitea, GitHub, GitLab, Gitee, Linear and CircleCI
+    #[default]
+    FirstMatch,
+    /// Pick the first of the providers the request has the event header of, or the first match if none of them
+    Prefer(Vec<DeliveryType>),
+    /// Refuse the request as an invalid payload
+    Reject,
+}
+
+/// How deliveries with an empty body (e.g. system hooks of GitLab and some test pings) are handled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyBodyPolicy {
+    /// Run the hooks, the delivery has no payload
+    #[default]
+    Accept,
+    /// Refuse the request as an invalid payload, without running hooks
+    Reject,
+}
+
+/// Headers carrying the event, in order of detection
+/// Forgejo and Gitea send GitHub-style headers too, so they must be detected first
+const EVENT_HEADERS: [(&str, DeliveryType); 7] = [
+    ("x-forgejo-event", DeliveryType::Forgejo),
+    ("x-gitea-event", DeliveryType::Gitea),
+    ("x-github-event", DeliveryType::GitHub),
+    ("x-gitlab-event", DeliveryType::GitLab),
+    ("x-gitee-event", DeliveryType::Gitee),
+    ("linear-event", DeliveryType::Linear),
+    ("circleci-event-type", DeliveryType::CircleCI),
+];
+
+/// NewRelic IDs (`X-Newrelic-Id` header) known to be sent by Docker Hub, see `Constructor::docker_hub_id`
+const DOCKER_HUB_NEWRELIC_IDS: [&str; 1] = ["UQUFVFJUGwUJVlhaBgY="];
+
+/// Decoded value of the parameter of the query string (e.g. `provider=github&event=push`)
+pub(crate) fn query_param(query: &str, name: &str) -> Option<String> {
+    let value = query
+        .split('&')
+        .find_map(|pair| match pair.split_once('=') {
+            Some((key, value)) if key == name => Some(value),
+            _ => None,
+        })?;
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' if index + 2 < bytes.len()
+                && bytes[index + 1].is_ascii_hexdigit()
+                && bytes[index + 2].is_ascii_hexdigit() =>
+            {
+                // Both are ASCII hex digits
+                decoded.push(u8::from_str_radix(&value[index + 1..index + 3], 16).unwrap());
+                index += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Test if the providers whose event headers were sent (in the order of `EVENT_HEADERS`) are unrelated
+fn is_ambiguous(providers: &[DeliveryType]) -> bool {
+    let mut families: Vec<DeliveryType> = providers
+        .iter()
+        .map(|provider| match provider {
+            DeliveryType::Forgejo | DeliveryType::Gitea => DeliveryType::GitHub,
+            provider => *provider,
+        })
+        .collect();
+    families.dedup();
+    families.len() > 1
 }
 
 #[cfg(not(feature = "parse"))]
@@ -63,34 +275,507 @@ pub enum DeliveryType {
 #[derive(Debug, Clone)]
 pub enum Value {}
 
+/// The type of GitLab's system hook, the `event_name` field of the payload (e.g. `project_create`), or `object_kind`
+/// for those without it (e.g. `merge_request`)
+fn system_hook_event(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        payload
+            .get("event_name")
+            .or_else(|| payload.get("object_kind"))?
+            .as_str()
+            .map(String::from)
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// Headers of a request of the `http` crate, with lower cased names, values that aren't valid UTF-8 are `unknown`
+#[cfg(feature = "http")]
+pub(crate) fn http_headers(headers: &http::HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = value.to_str().unwrap_or("unknown");
+            (name.as_str().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// Headers of a request, read by their lower cased names without copying all of them
+/// You can implement this trait to your own struct
+pub trait HeaderAccessor {
+    /// Value of the header, if it was sent (and is valid UTF-8)
+    fn header(&self, name: &str) -> Option<&str>;
+
+    /// Keep the headers in the delivery, see `Headers`
+    fn into_headers(self) -> Headers
+    where
+        Self: Sized;
+}
+
+/// Headers of a delivery, with lower cased names
+///
+/// Headers received in an `http::HeaderMap` (e.g. by the server of hyper) are kept in it and converted one by one when
+/// they're looked up, they're all copied into a map only if `Delivery::headers` is called.
+#[derive(Clone)]
+pub struct Headers {
+    source: HeaderSource,
+    #[cfg(feature = "http")]
+    map: OnceLock<HashMap<String, String>>, // of the headers in an `http::HeaderMap`, once copied
+}
+
+/// Where the headers of a delivery are kept
+#[derive(Clone)]
+enum HeaderSource {
+    Map(HashMap<String, String>),
+    #[cfg(feature = "http")]
+    Http(http::HeaderMap),
+}
+
+/// The main impl clause of `Headers`
+impl Headers {
+    /// Value of the header by its lower cased name, values that aren't valid UTF-8 are `unknown`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match &self.source {
+            HeaderSource::Map(map) => map.get(name).map(String::as_str),
+            // The last value is kept when copied into a map
+            #[cfg(feature = "http")]
+            HeaderSource::Http(headers) => headers
+                .get_all(name)
+                .iter()
+                .next_back()
+                .map(|value| value.to_str().unwrap_or("unknown")),
+        }
+    }
+
+    /// Test if the header was sent
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Names and values of the headers, without copying them
+    pub(crate) fn iter(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_> {
+        match &self.source {
+            HeaderSource::Map(map) => Box::new(
+                map.iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+            ),
+            #[cfg(feature = "http")]
+            HeaderSource::Http(headers) => Box::new(
+                headers
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("unknown"))),
+            ),
+        }
+    }
+
+    /// All the headers in a map, copied the first time if they're kept in an `http::HeaderMap`
+    pub(crate) fn map(&self) -> &HashMap<String, String> {
+        match &self.source {
+            HeaderSource::Map(map) => map,
+            #[cfg(feature = "http")]
+            HeaderSource::Http(headers) => self.map.get_or_init(|| http_headers(headers)),
+        }
+    }
+}
+
+/// Implement `From<HashMap<String, String>>` to `Headers`, the names must be lower cased
+impl From<HashMap<String, String>> for Headers {
+    fn from(map: HashMap<String, String>) -> Self {
+        Self {
+            source: HeaderSource::Map(map),
+            #[cfg(feature = "http")]
+            map: OnceLock::new(),
+        }
+    }
+}
+
+/// Implement `From<http::HeaderMap>` to `Headers`, the headers are kept in the map
+#[cfg(feature = "http")]
+impl From<http::HeaderMap> for Headers {
+    fn from(headers: http::HeaderMap) -> Self {
+        Self {
+            source: HeaderSource::Http(headers),
+            map: OnceLock::new(),
+        }
+    }
+}
+
+/// Implement `HeaderAccessor` to `HashMap<String, String>`, the map is kept as is
+impl HeaderAccessor for HashMap<String, String> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.get(name).map(String::as_str)
+    }
+
+    fn into_headers(self) -> Headers {
+        Headers::from(self)
+    }
+}
+
+/// Implement `HeaderAccessor` to `&HashMap<String, String>`, the map is cloned
+impl HeaderAccessor for &HashMap<String, String> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.get(name).map(String::as_str)
+    }
+
+    fn into_headers(self) -> Headers {
+        Headers::from(self.clone())
+    }
+}
+
+/// Implement `HeaderAccessor` to `http::HeaderMap`, the headers are kept in the map
+#[cfg(feature = "http")]
+impl HeaderAccessor for http::HeaderMap {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.get(name)?.to_str().ok()
+    }
+
+    fn into_headers(self) -> Headers {
+        Headers::from(self)
+    }
+}
+
+/// Implement `HeaderAccessor` to `&http::HeaderMap`, the map is cloned (sharing the values) but its headers aren't
+/// converted until they're looked up
+#[cfg(feature = "http")]
+impl HeaderAccessor for &http::HeaderMap {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.get(name)?.to_str().ok()
+    }
+
+    fn into_headers(self) -> Headers {
+        Headers::from(self.clone())
+    }
+}
+
+/// The `type` field of the payload
+fn payload_type(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        payload.get("type")?.as_str().map(String::from)
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// The event of Grafana's alert, `grafana.alert.<state>` (e.g. `grafana.alert.firing` or `grafana.alert.ok`)
+///
+/// Both the `status` of unified alerting (`firing`/`resolved`) and the `state` of legacy alerting
+/// (`alerting`/`ok`/`no_data`/...) are mapped.
+fn grafana_event(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        let state = payload
+            .get("status")
+            .or_else(|| payload.get("state"))?
+            .as_str()?;
+        let state = match state {
+            "firing" | "alerting" => "firing",
+            "resolved" | "ok" => "ok",
+            state => state,
+        };
+        Some(format!("grafana.alert.{}", state))
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// The event of Discord's interaction, named after its `type` (e.g. `ping` or `application_command`)
+fn discord_event(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        let event = match payload.get("type")?.as_u64()? {
+            1 => "ping",
+            2 => "application_command",
+            3 => "message_component",
+            4 => "application_command_autocomplete",
+            5 => "modal_submit",
+            _ => "interaction",
+        };
+        Some(event.to_string())
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// The event of Telegram's `Update`, named after the type of the update (e.g. `message` or `callback_query`)
+fn telegram_event(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        payload
+            .as_object()?
+            .keys()
+            .find(|key| key.as_str() != "update_id")
+            .cloned()
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// The event of JFrog Artifactory, the `domain` and the `event_type` of the payload (e.g. `artifact.deployed`)
+fn artifactory_event(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        let domain = payload.get("domain")?.as_str()?;
+        let event_type = payload.get("event_type")?.as_str()?;
+        Some(format!("{}.{}", domain, event_type))
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// The event of npm hooks, the `event` field of the payload (e.g. `package:publish`)
+fn npm_event(request_body: Option<&str>) -> Option<String> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        payload.get("event")?.as_str().map(String::from)
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// Identify deliveries without identifying header by their payload, returns the event and the source
+///
+/// - Kubernetes: `AdmissionReview` requests, see `rifling::admission`.
+/// - Gerrit's webhooks plugin: `type` is the event, with `eventCreatedOn` (e.g. `patchset-created`).
+/// - Grafana: alerts with `orgId` (unified alerting) or `ruleId` (legacy alerting), see `grafana_event`.
+/// - Intercom: notifications (`"type": "notification_event"`), `topic` is the event (e.g. `conversation.user.created`).
+/// - Telegram: updates with `update_id`, see `telegram_event`.
+/// - Jenkins Notification Plugin: `build.phase` is mapped to `jenkins.build.<phase>` (e.g. `jenkins.build.completed`).
+/// - Docker Hub: pushes with `push_data` and `callback_url` are `docker_push`.
+/// - JFrog Artifactory: payloads with `domain` and `event_type`, see `artifactory_event`.
+fn payload_event(request_body: Option<&str>) -> Option<(String, DeliveryType)> {
+    #[cfg(feature = "parse")]
+    {
+        let payload: Value = serde_json::from_str(request_body?).ok()?;
+        if payload.get("kind").and_then(Value::as_str) == Some("AdmissionReview") {
+            let event = admission::event(&payload)?;
+            return Some((event, DeliveryType::Kubernetes));
+        }
+        if payload.get("eventCreatedOn").is_some() {
+            let event = payload.get("type")?.as_str()?;
+            return Some((event.to_string(), DeliveryType::Gerrit));
+        }
+        if payload.get("orgId").is_some() || payload.get("ruleId").is_some() {
+            let event = grafana_event(request_body)?;
+            return Some((event, DeliveryType::Grafana));
+        }
+        if payload.get("type").and_then(Value::as_str) == Some("notification_event") {
+            let event = payload.get("topic")?.as_str()?;
+            return Some((event.to_string(), DeliveryType::Intercom));
+        }
+        if payload.get("update_id").is_some() {
+            let event = telegram_event(request_body)?;
+            return Some((event, DeliveryType::Telegram));
+        }
+        if payload.get("push_data").is_some() && payload.get("callback_url").is_some() {
+            return Some(("docker_push".to_string(), DeliveryType::DockerHub));
+        }
+        if let Some(event) = artifactory_event(request_body) {
+            return Some((event, DeliveryType::Artifactory));
+        }
+        let phase = payload.pointer("/build/phase")?.as_str()?;
+        Some((format!("jenkins.build.{}", phase), DeliveryType::Jenkins))
+    }
+    #[cfg(not(feature = "parse"))]
+    {
+        let _ = request_body;
+        None
+    }
+}
+
+/// Identify plain form posts (e.g. from Trac), the `event` field is the event (`form` if missing)
+#[cfg_attr(not(feature = "multipart"), allow(unused_variables))]
+fn form_event(
+    content_type: &ContentType,
+    headers: &Headers,
+    request_body: Option<&str>,
+) -> Option<String> {
+    let request_body = request_body.filter(|request_body| !request_body.is_empty())?;
+    let event: Option<String> = match content_type {
+        #[cfg(feature = "content-type-urlencoded")]
+        ContentType::URLENCODED => Some(
+            form_urlencoded::parse(request_body.as_bytes())
+                .find(|(key, _)| key == "event")
+                .map(|(_, event)| event.into_owned()),
+        ),
+        #[cfg(feature = "multipart")]
+        ContentType::MULTIPART => {
+            let boundary = multipart::boundary(headers.get("content-type")?)?;
+            Some(
+                multipart::parse(request_body.as_bytes(), &boundary, 0)?
+                    .into_iter()
+                    .find(|part| part.name == "event" && part.filename.is_none())
+                    .map(|part| String::from_utf8_lossy(&part.data).into_owned()),
+            )
+        }
+        _ => None,
+    }?;
+    Some(event.unwrap_or_else(|| "form".to_string()))
+}
+
+/// Rule matching the `User-Agent` of deliveries, see `Constructor::allow_user_agent`
+#[derive(Clone, Debug)]
+enum UserAgentRule {
+    Prefix(String),
+    #[cfg(feature = "regex-match")]
+    Pattern(Regex),
+}
+
+/// The main impl clause of `UserAgentRule`
+impl UserAgentRule {
+    /// Test if the user agent matches the rule
+    fn matches(&self, user_agent: &str) -> bool {
+        match self {
+            UserAgentRule::Prefix(prefix) => user_agent.starts_with(prefix.as_str()),
+            #[cfg(feature = "regex-match")]
+            UserAgentRule::Pattern(regex) => regex.is_match(user_agent),
+        }
+    }
+}
+
+/// Settings shared by the `Constructor` and `Handler`s created from it
+#[derive(Clone, Default)]
+pub(crate) struct Settings {
+    allow_unverified: bool,
+    error_callback: Option<ErrorCallback>,
+    outcome_callback: Option<OutcomeCallback>,
+    rate_limiters: Vec<Arc<RateLimiter>>,
+    concurrency_policy: ConcurrencyPolicy,
+    concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    retry_queue: RetryQueue,
+    pending_runs: PendingRuns,
+    store: Option<Arc<dyn DeliveryStore>>,
+    sinks: Vec<Arc<dyn Sink>>,
+    max_delivery_age: Option<Duration>,
+    state: Option<Arc<dyn Any + Send + Sync>>,
+    #[cfg(feature = "parse")]
+    admission_review: bool,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    allowed_clients: Option<Vec<String>>,
+    enterprise_hosts: Vec<String>, // lower cased
+    strict_github: bool,
+    allowed_user_agents: Vec<UserAgentRule>,
+    denied_user_agents: Vec<UserAgentRule>,
+    trusted_proxies: Vec<Cidr>,
+    landing_page: Option<String>, // name of the service
+    request_id: bool,
+    min_response_time: Option<Duration>,
+    response_headers: Vec<(String, HeaderValueFn)>,
+    log_payloads: bool,
+    dry_run: bool,
+    raw_payload: bool,
+    validators: Vec<(String, Arc<dyn Validator>)>, // with events as registered
+    transformers: Vec<Arc<dyn Transformer>>,
+    stats: StatsHandle,
+    groups: GroupsHandle,
+    maintenance: MaintenanceHandle,
+    overrides: OverridesHandle,
+    reload: ReloadHandle,
+    access_log: Option<AccessLogCallback>,
+    event_normalization: EventNormalization,
+    ambiguity_policy: AmbiguityPolicy,
+    empty_body: EmptyBodyPolicy,
+    empty_body_providers: Vec<(DeliveryType, EmptyBodyPolicy)>,
+    provider_hint: Option<DeliveryType>,
+    provider_query: bool,
+    docker_hub_ids: Vec<String>,
+    accept_unknown: bool,
+    readiness_checks: Vec<(String, Arc<dyn ReadinessCheck>)>,
+    aliases: Vec<(String, String)>, // aliases and events as registered
+    #[cfg(feature = "multipart")]
+    multipart_file_limit: usize,
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    read_timeout: Option<Duration>,
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    idle_timeout: Option<Duration>,
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    max_body_size: Option<usize>,
+}
+
 /// Constructor of the server
 #[derive(Clone, Default)]
 pub struct Constructor {
     pub hooks: HookRegistry,
+    settings: Settings,
 }
 
 /// Information gathered from the received request
 /// Not sure what is included in the request, so all of the fields are wrapped in `Option<T>`
-#[derive(Debug, Clone)]
+///
+/// The fields are read with the accessors (e.g. `Delivery::event`). Deliveries are created with `Delivery::new` or, in
+/// tests, `DeliveryBuilder`.
+#[derive(Clone)]
 pub struct Delivery {
-    pub delivery_type: DeliveryType,
-    pub content_type: ContentType,
-    pub id: Option<String>,
-    pub event: String,
-    pub payload: Option<Value>,
-    pub unparsed_payload: Option<String>,
-    pub request_body: Option<String>, // for x-www-form-urlencoded authentication support
-    pub signature: Option<String>,
+    pub(crate) delivery_type: DeliveryType,
+    pub(crate) content_type: ContentType,
+    pub(crate) id: Option<String>,
+    pub(crate) event: String,
+    pub(crate) payload: Option<Value>,
+    pub(crate) unparsed_payload: Option<String>,
+    #[cfg(feature = "raw-payload")]
+    pub(crate) raw_payload: Option<Box<RawValue>>, // instead of `payload`, see `Constructor::raw_payload`
+    pub(crate) request_body: Option<String>, // for x-www-form-urlencoded authentication support
+    pub(crate) signature: Option<String>,
+    pub(crate) remote_addr: Option<IpAddr>, // of the sender, see `Constructor::trust_proxies`
+    pub(crate) scheme: Option<String>,      // of the request sent to trusted proxies
+    pub(crate) timestamp: Option<SystemTime>, // sent by the provider
+    pub(crate) received_at: SystemTime,
+    pub(crate) headers: Headers,
+    pub(crate) client_identity: Option<String>, // subject of the verified client certificate
+    pub(crate) enterprise_host: Option<String>, // of GitHub Enterprise Server
+    pub(crate) enterprise_version: Option<String>, // of GitHub Enterprise Server
+    pub(crate) request_id: Option<String>,      // see `Constructor::request_id`
+    pub(crate) query: Option<String>,           // of the request, without `?`
+    #[cfg(feature = "multipart")]
+    pub(crate) parts: Vec<Part>, // of multipart bodies
 }
 
 /// Executor of the hooks, passed into futures.
+/// It only receives deliveries authenticated by all of its hooks.
 pub struct Executor {
     matched_hooks: Vec<Hook>,
+    unverified: usize, // hooks accepting the delivery without verifying it
+    settings: Settings,
 }
 
 /// The main handler struct.
+#[derive(Clone)]
 pub struct Handler {
-    hooks: HookRegistry,
+    hooks: Arc<HookRegistry>,
+    settings: Settings,
+    remote_addr: Option<SocketAddr>,
+    client_identity: Option<String>,
+    query: Option<String>,
 }
 
 /// Main impl clause of the `Constructor`
@@ -103,149 +788,4942 @@ impl Constructor {
     }
 
     /// Register a hook to `Constructor`
+    ///
+    /// Hooks registered for the same event are all kept, each of them runs for the deliveries it matches (e.g. hooks
+    /// of the same event restricted to different providers with `Hook::providers`).
     pub fn register(&mut self, hook: Hook) {
-        self.hooks.insert(hook.event.to_string(), hook.clone());
+        let key = self.settings.event_normalization.key(&hook);
+        self.hooks.entry(key).or_default().push(hook);
     }
-}
 
-/// The main impl clause of `Delivery`
-impl Delivery {
-    /// Create a new Delivery
-    pub fn new(
-        headers: HashMap<String, String>,
-        request_body: Option<String>,
-    ) -> Result<Delivery, &'static str> {
-        debug!("Received headers: {:#?}", &headers);
-        // Identify delivery type
-        let (mut event, delivery_type) = if let Some(event_string) = headers.get("x-github-event") {
-            (event_string.to_owned(), DeliveryType::GitHub)
-        } else if let Some(event_string) = headers.get("x-gitlab-event") {
-            (event_string.to_owned(), DeliveryType::GitLab)
-        } else if let Some(newrelic_id) = headers.get("x-newrelic-id") {
-            // Determine source of delivery by NewRelic ID
-            if newrelic_id == &"UQUFVFJUGwUJVlhaBgY=".to_string() {
-                ("docker_push".to_string(), DeliveryType::DockerHub)
-            } else {
-                return Err("Could not determine delivery type");
-            }
-        } else {
-            return Err("Could not determine delivery type");
-        };
-        event.make_ascii_lowercase();
-        event = event.replace(" ", "_");
-        // Get content type
-        let content_type = if let Some(header_value) = headers.get("content-type") {
-            match header_value.to_lowercase().as_str() {
-                "application/json" => ContentType::JSON,
-                "application/x-www-form-urlencoded" => ContentType::URLENCODED,
-                _ => ContentType::JSON,
-            }
-        } else {
-            ContentType::JSON
-        };
-        // Get delivery ID: only available in requests from GitHub
-        let id = match delivery_type {
-            DeliveryType::GitHub => header_get_owned!(&headers, "x-github-delivery"),
-            _ => None,
-        };
-        let signature = match delivery_type {
-            DeliveryType::GitHub => header_get_owned!(&headers, "x-hub-signature"),
-            DeliveryType::GitLab => header_get_owned!(&headers, "x-gitlab-token"),
-            _ => None,
-        };
-        let mut delivery = Self {
-            delivery_type,
-            content_type,
-            id,
-            event,
-            payload: None,
-            unparsed_payload: None,
-            request_body: None,
-            signature,
-        };
-        if request_body.is_some() {
-            delivery.update_request_body(request_body);
+    /// Register the hooks of a provider sending no signatures, authenticated by its controls instead, see
+    /// `rifling::unauthenticated`
+    ///
+    /// Fails if no control is attached to the provider, none of its hooks are registered then.
+    pub fn register_unauthenticated(
+        &mut self,
+        provider: UnauthenticatedProvider,
+    ) -> Result<(), MissingControl> {
+        for hook in provider.into_hooks()? {
+            self.register(hook);
         }
-        Ok(delivery)
+        Ok(())
     }
 
-    /// Update request body of the delivery
-    pub fn update_request_body(&mut self, request_body: Option<String>) {
-        let payload: Option<String> = match self.content_type {
-            ContentType::JSON => request_body.clone(),
-            #[cfg(feature = "content-type-urlencoded")]
-            ContentType::URLENCODED => {
-                if let Some(request_body_string) = request_body.clone() {
-                    if let Some(payload_string) =
-                        form_urlencoded::parse(request_body_string.as_bytes())
-                            .into_owned()
-                            .collect::<HashMap<String, String>>()
-                            .get("payload")
-                    {
-                        Some(payload_string.clone())
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            }
-            #[cfg(not(feature = "content-type-urlencoded"))]
-            _ => None,
-        };
-        debug!("Payload body set to: {:?}", &payload);
-        #[cfg(feature = "parse")]
-        let parsed_payload = if let Some(payload_string) = &payload {
-            serde_json::from_str(payload_string.as_str()).ok()
-        } else {
-            None
-        };
-        #[cfg(not(feature = "parse"))]
-        let parsed_payload = None;
-        debug!("Parsed payload: {:#?}", &parsed_payload);
-        // Update delivery
-        self.request_body = request_body;
-        self.unparsed_payload = payload;
-        self.payload = parsed_payload;
+    /// Register hooks in the named group, which can be disabled at runtime with `Constructor::groups_handle`
+    pub fn group(&mut self, name: &str) -> Group<'_> {
+        Group::new(self, name)
     }
-}
 
-/// The main impl clause of `Executor`
-impl Executor {
-    /// Run the hooks
-    pub fn run(self, delivery: Delivery) {
-        for hook in self.matched_hooks {
-            debug!("Running hook for '{}' event", &hook.event);
-            hook.handle_delivery(&delivery);
+    /// Handle to enable and disable groups of hooks in `Handler`s created from this constructor
+    pub fn groups_handle(&self) -> GroupsHandle {
+        self.settings.groups.clone()
+    }
+
+    /// Register multiple hooks to `Constructor`, see also `hooks!`
+    pub fn register_all(&mut self, hooks: impl IntoIterator<Item = Hook>) {
+        for hook in hooks {
+            self.register(hook);
         }
     }
 
-    /// Test if there are no matched hook found
-    pub fn is_empty(&self) -> bool {
-        self.matched_hooks.len() == 0
+    /// Set the callback receiving errors occurred while handling deliveries
+    pub fn on_error(&mut self, callback: impl Fn(&Error) + Send + Sync + 'static) {
+        self.settings.error_callback = Some(Arc::new(callback));
     }
-}
 
-/// The main impl clause of Handler
-impl Handler {
-    fn get_hooks(&self, event: &str) -> Executor {
-        debug!("Finding matched hooks for '{}' event", &event);
-        let matched: Vec<Hook> = hooks_find_match!(self.hooks, event, "*");
-        debug!("{} matched hook(s) found", matched.len());
-        Executor {
-            matched_hooks: matched,
-        }
+    /// Set the callback receiving the response to every request, after the hooks are run
+    pub fn on_outcome(&mut self, callback: impl Fn(&Response) + Send + Sync + 'static) {
+        self.settings.outcome_callback = Some(Arc::new(callback));
     }
-}
 
-/// Implement `From<&Constructor>` trait for `Handler`
-/// As currently we don't have Generic Associate Types, I can only clone the registry.
-impl From<&Constructor> for Handler {
-    /// Create a handler object from constructor
-    fn from(constructor: &Constructor) -> Self {
-        debug!("Handler constructed");
-        Self {
-            hooks: constructor.hooks.clone(),
-        }
+    /// Set the callback receiving the access log entry of every request, see `rifling::access_log`
+    pub fn access_log(&mut self, callback: impl Fn(&AccessLogEntry) + Send + Sync + 'static) {
+        self.settings.access_log = Some(Arc::new(callback));
+    }
+
+    /// Write the access log entry of every request to the writer as a JSON line
+    pub fn access_log_writer(&mut self, writer: impl io::Write + Send + 'static) {
+        self.settings.access_log = Some(access_log::writer(writer));
+    }
+
+    /// Run hooks even if the signature can't be verified due to lack of cryptography support
+    ///
+    /// Deliveries that need to be verified cryptographically (e.g. from GitHub) are rejected by default
+    /// when neither `crypto-use-ring` nor `crypto-use-rustcrypto` is enabled.
+    pub fn allow_unverified(&mut self, allow: bool) {
+        self.settings.allow_unverified = allow;
+    }
+
+    /// Hold the responses to requests (except the landing page) until the duration has passed since they were
+    /// received, so that failing to authenticate can't be told from being handled by the time taken to respond
+    /// (disabled by default)
+    ///
+    /// Hooks run longer than the duration are not waited for any longer, pick a duration above their usual run time.
+    /// The server of hyper waits with the timer of the runtime, without blocking its threads, the server of
+    /// `sync-server` sleeps. Other servers have to wait until `Response::hold_until` themselves.
+    pub fn min_response_time(&mut self, duration: Duration) {
+        self.settings.min_response_time = Some(duration);
+    }
+
+    /// Limit the rate of deliveries with a token bucket of `burst` tokens, refilled completely in `period`
+    ///
+    /// Rate limiters are shared by all handlers created from this constructor (and its clones).
+    /// Multiple limiters with different scopes can be combined. Limits of the `Global` and `RemoteAddr` scopes are
+    /// checked before deliveries are verified, limits of the `Repository` scope once they're authenticated.
+    pub fn rate_limit(&mut self, scope: RateLimitScope, burst: u32, period: Duration) {
+        self.settings
+            .rate_limiters
+            .push(Arc::new(RateLimiter::new(scope, burst, period)));
+    }
+
+    /// Limit the number of hook executions running simultaneously
+    ///
+    /// Excess deliveries are handled according to `Constructor::concurrency_policy`.
+    pub fn max_concurrent_hooks(&mut self, max: usize) {
+        let policy = self.settings.concurrency_policy;
+        self.settings.concurrency_limiter = Some(Arc::new(ConcurrencyLimiter::new(max, policy)));
+    }
+
+    /// Reject deliveries sent longer than `max_age` ago (or that far in the future) to prevent replay attacks
+    ///
+    /// Only deliveries carrying a timestamp are checked: those from Gitee and Discord (in their headers), Gerrit and
+    /// Linear (in their payload, requires `parse`), and those built with `DeliveryBuilder::timestamp`. Deliveries
+    /// without one (e.g. from GitHub, GitLab or Docker Hub) are accepted.
+    pub fn max_delivery_age(&mut self, max_age: Duration) {
+        self.settings.max_delivery_age = Some(max_age);
+    }
+
+    /// Set the state shared by hooks created with `Hook::with_state`
+    pub fn with_state<S: Any + Send + Sync>(&mut self, state: S) {
+        self.settings.state = Some(Arc::new(state));
+    }
+
+    /// Serve Kubernetes admission reviews, see `rifling::admission`
+    #[cfg(feature = "parse")]
+    pub fn admission_review(&mut self, enable: bool) {
+        self.settings.admission_review = enable;
+    }
+
+    /// Authenticate every delivery with the authenticator before running hooks, in addition to the
+    /// verification of each hook
+    pub fn authenticator(&mut self, authenticator: impl Authenticator + 'static) {
+        self.settings.authenticator = Some(Arc::new(authenticator));
+    }
+
+    /// Only accept deliveries from clients whose verified certificate has one of the subjects
+    ///
+    /// The identity of the client is the common name of its certificate when served with `Builder::serve_tls`, or set by
+    /// the TLS layer in front of the handler with `Handler::with_client_identity`. Deliveries without it are rejected
+    /// too.
+    pub fn allowed_clients(&mut self, subjects: &[&str]) {
+        self.settings.allowed_clients =
+            Some(subjects.iter().map(|subject| subject.to_string()).collect());
+    }
+
+    /// Only accept deliveries of GitHub sent by the GitHub Enterprise Server instance with the host (e.g.
+    /// `ghe.corp.example`), can be called again to accept several instances
+    ///
+    /// Deliveries of GitHub without the `X-GitHub-Enterprise-Host` header (e.g. from github.com) are rejected too,
+    /// with `403 Forbidden`. Deliveries of other providers are not affected.
+    pub fn require_enterprise_host(&mut self, host: &str) {
+        self.settings
+            .enterprise_hosts
+            .push(host.to_ascii_lowercase());
+    }
+
+    /// Refuse deliveries of GitHub lacking any of the headers GitHub sends with `400 Bad Request`, disabled by
+    /// default
+    ///
+    /// They must have an `X-GitHub-Delivery` header, an `X-Hub-Signature-256` header with an HMAC-SHA256 signature
+    /// (the webhook must have a secret), a `User-Agent` starting with `GitHub-Hookshot/`, and be sent as
+    /// `application/json`. Useful to harden endpoints exposed to the internet, deliveries of other providers
+    /// (including Forgejo and Gitea) are not affected.
+    pub fn strict_github(&mut self, enable: bool) {
+        self.settings.strict_github = enable;
+    }
+
+    /// Only accept deliveries whose `User-Agent` starts with the prefix (e.g. `GitHub-Hookshot/` or `GitLab/`), can
+    /// be called again to accept several
+    ///
+    /// Other deliveries, including those without a `User-Agent`, are rejected with `403 Forbidden` before any
+    /// signature is verified. Anyone can send any `User-Agent`, this only cheaply turns away stray requests to
+    /// endpoints exposed to the internet.
+    pub fn allow_user_agent(&mut self, prefix: &str) {
+        self.settings
+            .allowed_user_agents
+            .push(UserAgentRule::Prefix(prefix.to_string()));
+    }
+
+    /// Only accept deliveries whose `User-Agent` matches the regular expression, like `allow_user_agent`
+    #[cfg(feature = "regex-match")]
+    pub fn allow_user_agent_matching(&mut self, regex: Regex) {
+        self.settings
+            .allowed_user_agents
+            .push(UserAgentRule::Pattern(regex));
+    }
+
+    /// Reject deliveries whose `User-Agent` starts with the prefix (e.g. `curl/`) with `403 Forbidden`, even if it's
+    /// allowed by `allow_user_agent`
+    pub fn deny_user_agent(&mut self, prefix: &str) {
+        self.settings
+            .denied_user_agents
+            .push(UserAgentRule::Prefix(prefix.to_string()));
+    }
+
+    /// Reject deliveries whose `User-Agent` matches the regular expression, like `deny_user_agent`
+    #[cfg(feature = "regex-match")]
+    pub fn deny_user_agent_matching(&mut self, regex: Regex) {
+        self.settings
+            .denied_user_agents
+            .push(UserAgentRule::Pattern(regex));
+    }
+
+    /// Trust the `Forwarded` and `X-Forwarded-For` headers of peers in the ranges (e.g. `10.0.0.0/8`), see
+    /// `rifling::proxy`
+    ///
+    /// The ranges are not changed if one of them is invalid.
+    pub fn trust_proxies(&mut self, cidrs: &[&str]) -> Result<(), InvalidCidr> {
+        self.settings.trusted_proxies = cidrs
+            .iter()
+            .map(|cidr| cidr.parse())
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Check the downstream target with the name when answering the landing page, which is answered with
+    /// `503 Service Unavailable` while it's unreachable, see `rifling::readiness`
+    pub fn readiness_check(&mut self, name: &str, check: impl ReadinessCheck + 'static) {
+        self.settings
+            .readiness_checks
+            .push((name.to_string(), Arc::new(check)));
+    }
+
+    /// Answer `GET` and `HEAD` requests with a landing page naming the service and the supported providers, to check
+    /// the webhook URL in a browser (e.g. `{"service":"deploy-bot","status":"ok",...}`), secrets are never included
+    ///
+    /// Disabled by default, such requests are handled like deliveries.
+    pub fn landing_page(&mut self, service: &str) {
+        self.settings.landing_page = Some(service.to_string());
+    }
+
+    /// Identify every request with the `X-Request-Id` header sent by proxies, or a generated ID, which is sent back in
+    /// the `X-Request-Id` header of the response, see `Delivery::request_id` (disabled by default)
+    pub fn request_id(&mut self, enable: bool) {
+        self.settings.request_id = enable;
+    }
+
+    /// Add the header to every response, e.g. `Server`
+    pub fn response_header(&mut self, name: &str, value: &str) {
+        let value = value.to_string();
+        self.settings
+            .response_headers
+            .push((name.to_string(), Arc::new(move |_| Some(value.clone()))));
+    }
+
+    /// Add the header computed from the delivery to responses to deliveries, unless the function returns `None`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, Delivery};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.response_header("Server", "rifling");
+    /// cons.response_header_with("X-Delivery-Id", |delivery: &Delivery| delivery.id().map(str::to_string));
+    /// ```
+    pub fn response_header_with(
+        &mut self,
+        name: &str,
+        value: impl Fn(&Delivery) -> Option<String> + Send + Sync + 'static,
+    ) {
+        self.settings.response_headers.push((
+            name.to_string(),
+            Arc::new(move |delivery: Option<&Delivery>| delivery.and_then(&value)),
+        ));
+    }
+
+    /// Skip the functions of matched hooks (unless overridden with `Hook::dry_run`), disabled by default
+    ///
+    /// Deliveries are still authenticated, validated, matched against the conditions of hooks, logged and recorded,
+    /// skipped hooks are logged and recorded to the delivery store as would have run (see `HookRun.dry_run`).
+    /// Deliveries whose hooks were all skipped are answered with `Outcome::DryRun`, except admission reviews which are
+    /// answered as if the skipped hooks admitted the object.
+    pub fn dry_run(&mut self, enable: bool) {
+        self.settings.dry_run = enable;
+    }
+
+    /// Log the body of deliveries (truncated) in debug logs, disabled by default
+    ///
+    /// Signatures, tokens and credentials are never logged.
+    pub fn log_payloads(&mut self, enable: bool) {
+        self.settings.log_payloads = enable;
+    }
+
+    /// Keep JSON payloads as validated raw JSON in `Delivery::raw_payload` instead of parsing them into
+    /// `Delivery::payload`, saving the allocations of the tree of values on large payloads (disabled by default)
+    ///
+    /// Conditions on fields of the payload (see `rifling::filter`) and admission reviews of Kubernetes need the
+    /// parsed payload, typed hooks deserialize the raw payload directly (see `Hook::typed`).
+    #[cfg(feature = "raw-payload")]
+    pub fn raw_payload(&mut self, enable: bool) {
+        self.settings.raw_payload = enable;
+    }
+
+    /// Set how event names are normalized (`EventNormalization::Normalize` by default), hooks registered before
+    /// are registered again with the normalized event
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, EventNormalization};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.event_normalization(EventNormalization::Preserve);
+    /// ```
+    pub fn event_normalization(&mut self, normalization: EventNormalization) {
+        self.settings
+            .reload
+            .set_normalization(normalization.clone());
+        self.settings.event_normalization = normalization;
+        let hooks: Vec<Hook> = self.hooks.drain().flat_map(|(_, hooks)| hooks).collect();
+        self.register_all(hooks);
+    }
+
+    /// Set how requests carrying the event headers of several providers are handled
+    /// (`AmbiguityPolicy::FirstMatch` by default)
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{AmbiguityPolicy, Constructor, DeliveryType};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.ambiguity_policy(AmbiguityPolicy::Prefer(vec![DeliveryType::GitLab]));
+    /// ```
+    pub fn ambiguity_policy(&mut self, policy: AmbiguityPolicy) {
+        self.settings.ambiguity_policy = policy;
+    }
+
+    /// Set how deliveries with an empty body are handled (`EmptyBodyPolicy::Accept` by default), unless set for
+    /// their provider with `empty_body_of`
+    pub fn empty_body(&mut self, policy: EmptyBodyPolicy) {
+        self.settings.empty_body = policy;
+    }
+
+    /// Set how deliveries of the provider with an empty body are handled
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, DeliveryType, EmptyBodyPolicy};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.empty_body(EmptyBodyPolicy::Reject);
+    /// // System hooks of GitLab may have no body
+    /// cons.empty_body_of(DeliveryType::GitLab, EmptyBodyPolicy::Accept);
+    /// ```
+    pub fn empty_body_of(&mut self, provider: DeliveryType, policy: EmptyBodyPolicy) {
+        self.settings
+            .empty_body_providers
+            .retain(|(delivery_type, _)| *delivery_type != provider);
+        self.settings.empty_body_providers.push((provider, policy));
+    }
+
+    /// Assume requests whose provider can't be determined (e.g. proxies stripped their headers) are sent by the
+    /// provider, instead of refusing them as invalid payloads
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, DeliveryType, Router};
+    ///
+    /// let mut gitlab = Constructor::new();
+    /// gitlab.provider_hint(DeliveryType::GitLab);
+    /// let router = Router::new().mount("/gitlab", gitlab);
+    /// ```
+    ///
+    /// Hints are per constructor, mount constructors with different hints on different paths with `Router`. The event
+    /// of such requests is taken from the `event` parameter of the query string if `Constructor::provider_query` is
+    /// enabled, or is `unknown` (hooks registered for `*` still match).
+    pub fn provider_hint(&mut self, provider: DeliveryType) {
+        self.settings.provider_hint = Some(provider);
+    }
+
+    /// Accept the deliveries of senders that can't be identified instead of rejecting them as invalid, to discover
+    /// and configure new senders from real traffic (disabled by default)
+    ///
+    /// Their provider is `DeliveryType::Unknown` and their event is `unknown`. Their headers are logged, they're kept
+    /// in the delivery store even if no hook matches, and only run the hook registered for the `unknown` event, not
+    /// those registered for `*` nor the fallback hook. They can't be authenticated, register that hook without secret.
+    pub fn accept_unknown(&mut self, enable: bool) {
+        self.settings.accept_unknown = enable;
+    }
+
+    /// Identify deliveries carrying the NewRelic ID (`X-Newrelic-Id` header) as sent by Docker Hub, besides the IDs
+    /// known to be used by it
+    ///
+    /// The IDs change with the infrastructure of Docker Hub, deliveries with an unknown one are identified by their
+    /// payload (`push_data` and `callback_url`) instead, which requires the `parse` feature.
+    pub fn docker_hub_id(&mut self, newrelic_id: &str) {
+        self.settings.docker_hub_ids.push(newrelic_id.to_string());
+    }
+
+    /// Take the provider of requests it can't be determined from (e.g. proxies stripped their headers) from the
+    /// `provider` parameter of their query string (e.g. `/hooks?provider=github&event=push`), and their event from
+    /// the `event` parameter, disabled by default
+    ///
+    /// It takes precedence over `Constructor::provider_hint`. Requests whose provider can be determined are not
+    /// affected, and deliveries are still authenticated as sent by the provider.
+    pub fn provider_query(&mut self, enable: bool) {
+        self.settings.provider_query = enable;
+    }
+
+    /// Make hooks and validators registered for the alias match deliveries of any of the events, e.g. to handle
+    /// pull requests from several providers with the same hook
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, Delivery, Hook};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.alias("pr", &["pull_request", "Merge Request Hook"]);
+    /// cons.register(Hook::new("pr", None, |delivery: &Delivery| {
+    ///     println!("Pull request from {:?}", delivery.delivery_type())
+    /// }));
+    /// ```
+    pub fn alias(&mut self, alias: &str, events: &[&str]) {
+        for event in events {
+            self.settings
+                .aliases
+                .push((alias.to_string(), event.to_string()));
+        }
+    }
+
+    /// Validate deliveries of the event (`*` for all events) with the validator before running hooks,
+    /// deliveries failing validation are rejected
+    pub fn validate(&mut self, event: &str, validator: impl Validator + 'static) {
+        self.settings
+            .validators
+            .push((event.to_string(), Arc::new(validator)));
+    }
+
+    /// Rewrite deliveries with the transformer before hooks are matched (e.g. rename events, see
+    /// `rifling::transform`), after the transformers added before
+    pub fn transform(&mut self, transformer: impl Transformer + 'static) {
+        self.settings.transformers.push(Arc::new(transformer));
+    }
+
+    /// Keep file parts of multipart bodies no larger than the limit in bytes, larger ones are dropped
+    /// (all of them by default)
+    #[cfg(feature = "multipart")]
+    pub fn multipart_file_limit(&mut self, limit: usize) {
+        self.settings.multipart_file_limit = limit;
+    }
+
+    /// Respond `408 Request Timeout` to requests whose body isn't received within the duration, with the hyper
+    /// adapter and the sync server
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    pub fn read_timeout(&mut self, timeout: Duration) {
+        self.settings.read_timeout = Some(timeout);
+    }
+
+    /// Respond `408 Request Timeout` to requests sending no part of their body for the duration, with the hyper
+    /// adapter only
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    pub fn idle_timeout(&mut self, timeout: Duration) {
+        self.settings.idle_timeout = Some(timeout);
+    }
+
+    /// Respond `413 Payload Too Large` to requests whose body is larger than the limit in bytes, with the hyper
+    /// adapter and the sync server
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    pub fn max_body_size(&mut self, limit: usize) {
+        self.settings.max_body_size = Some(limit);
+    }
+
+    /// Handle to the statistics of the deliveries handled by `Handler`s created from this constructor
+    pub fn stats_handle(&self) -> StatsHandle {
+        self.settings.stats.clone()
+    }
+
+    /// Handle to replace the hooks of `Handler`s created from this constructor at runtime
+    pub fn reload_handle(&self) -> ReloadHandle {
+        self.settings.reload.clone()
+    }
+
+    /// Handle to turn maintenance mode on and off in `Handler`s created from this constructor, deliveries are
+    /// authenticated and recorded to the store but no hook is run during maintenance
+    pub fn maintenance_handle(&self) -> MaintenanceHandle {
+        self.settings.maintenance.clone()
+    }
+
+    /// Set the status code responded during maintenance (503 by default)
+    pub fn maintenance_status(&mut self, status: u16) {
+        self.settings.maintenance.set_status(status);
+    }
+
+    /// Answer deliveries of the event with the status and body instead of running hooks, see `rifling::overrides`
+    pub fn override_response(&mut self, event: &str, status: u16, body: &str) {
+        self.settings.overrides.set(event, status, body);
+    }
+
+    /// Handle to change the response overrides of `Handler`s created from this constructor at runtime
+    pub fn overrides_handle(&self) -> OverridesHandle {
+        self.settings.overrides.clone()
+    }
+
+    /// Set the store recording deliveries and failures of hooks
+    pub fn store(&mut self, store: impl DeliveryStore + 'static) {
+        self.settings.store = Some(Arc::new(store));
+    }
+
+    /// Add a sink publishing every authenticated delivery which passed the checks, before hooks run (see `sink`)
+    pub fn sink(&mut self, sink: impl Sink + 'static) {
+        self.settings.sinks.push(Arc::new(sink));
+    }
+
+    /// Set what to do with deliveries exceeding the concurrency limit (rejected by default)
+    pub fn concurrency_policy(&mut self, policy: ConcurrencyPolicy) {
+        self.settings.concurrency_policy = policy;
+        if let Some(limiter) = &self.settings.concurrency_limiter {
+            let max = limiter.max();
+            self.max_concurrent_hooks(max);
+        }
+    }
+}
+
+/// The main impl clause of `Settings`
+impl Settings {
+    /// Pass the error to the error callback
+    fn report(&self, error: &Error) {
+        if let Some(callback) = &self.error_callback {
+            callback(error);
+        }
+    }
+
+    /// Record the delivery to the store
+    fn record(&self, delivery: &Delivery) {
+        if let Some(store) = &self.store {
+            store.record(delivery);
+        }
+    }
+
+    /// Publish the delivery with every sink
+    fn publish(&self, delivery: &Delivery) -> Result<(), Error> {
+        for sink in &self.sinks {
+            if let Err(error) = sink.publish(delivery) {
+                debug!("{}", error);
+                self.report(&error);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Provider assumed for the request with the query string if it can't be determined, see
+    /// `Constructor::provider_hint` and `Constructor::provider_query`
+    fn provider_hint(&self, query: Option<&str>) -> Option<ProviderHint> {
+        let query = query.filter(|_| self.provider_query);
+        let provider = match query.and_then(|query| query_param(query, "provider")) {
+            Some(name) => match name.parse() {
+                Ok(provider) => Some(provider),
+                Err(_) => {
+                    debug!("Unknown provider in the query string: {}", name);
+                    self.provider_hint
+                }
+            },
+            None => self.provider_hint,
+        };
+        match provider {
+            Some(provider) => Some(ProviderHint {
+                provider,
+                event: query.and_then(|query| query_param(query, "event")),
+            }),
+            None if self.accept_unknown => Some(ProviderHint {
+                provider: DeliveryType::Unknown,
+                event: None,
+            }),
+            None => None,
+        }
+    }
+
+    /// Run the hook function with the context of the attempt and a batch of deliveries (usually one), measuring its
+    /// duration
+    fn run(&self, hook: &Hook, deliveries: &[Delivery], attempt: u32) -> Result<(), Error> {
+        #[cfg(feature = "otel")]
+        let span = otel::start_hook(hook.event, hook.name.as_deref(), &deliveries[0]);
+        let context = Context::new(hook.event)
+            .with_name(hook.name.as_deref())
+            .with_run(attempt, deliveries[0].received_at)
+            .with_settings(self.state.as_deref(), &self.stats);
+        let start = Instant::now();
+        let result = hook.func.run_batch(&context, deliveries);
+        let duration = start.elapsed();
+        let name = hook.name.as_deref();
+        self.stats.record_hook(name, duration, result.is_ok());
+        access_log::record(hook.event, name, duration, result.is_ok());
+        if let Some(store) = &self.store {
+            let hook_run = HookRun {
+                event: hook.event,
+                name: hook.name.clone(),
+                ok: result.is_ok(),
+                error: result.as_ref().err().map(Error::to_string),
+                duration,
+                attempts: attempt,
+                output: context.take_output(),
+                dry_run: false,
+            };
+            for delivery in deliveries {
+                store.record_hook(delivery, &hook_run);
+            }
+        }
+        #[cfg(feature = "otel")]
+        otel::end_hook(span, &result);
+        result
+    }
+
+    /// Execute the hook now, or schedule it as configured with `Hook::delay`, `Hook::debounce` or `Hook::throttle`
+    fn start(&self, hook: &Hook, delivery: &Delivery) {
+        if self.is_dry_run(hook) {
+            return self.skip(hook, delivery);
+        }
+        #[cfg(feature = "chrono")]
+        {
+            if !self.within_window(hook, delivery) {
+                return;
+            }
+        }
+        let (delay, pending) = match hook.schedule {
+            None => return self.execute(hook, delivery, 1),
+            Some(Schedule::Delay(delay)) => (delay, None),
+            Some(Schedule::Debounce(period)) => {
+                let key = schedule::key(hook, delivery);
+                let id = self.pending_runs.replace(key.clone(), delivery.clone());
+                (period, Some((key, id)))
+            }
+            Some(Schedule::Throttle(period)) => {
+                let key = schedule::key(hook, delivery);
+                match self.pending_runs.update(key.clone(), delivery.clone()) {
+                    Some(id) => (period, Some((key, id))),
+                    None => {
+                        debug!("Run of {} already pending", hook.label());
+                        return;
+                    }
+                }
+            }
+            Some(Schedule::Batch { window, max }) => {
+                let key = schedule::batch_key(hook);
+                match self.pending_runs.push(key.clone(), delivery.clone(), max) {
+                    Batched::Started(id) => (window, Some((key, id))),
+                    Batched::Added => return,
+                    Batched::Full(deliveries) => return self.execute_batch(hook, &deliveries),
+                }
+            }
+        };
+        debug!("Running {} in {:?}", hook.label(), delay);
+        self.retry_queue.schedule(Job {
+            due: Instant::now() + delay,
+            attempt: 1,
+            hook: hook.clone(),
+            delivery: delivery.clone(),
+            settings: self.clone(),
+            pending,
+            queued: false,
+        });
+    }
+
+    /// Test if the function of the hook is skipped, see `Constructor::dry_run` and `Hook::dry_run`
+    fn is_dry_run(&self, hook: &Hook) -> bool {
+        hook.dry_run.unwrap_or(self.dry_run)
+    }
+
+    /// Skip the function of the hook, recording it would have run
+    fn skip(&self, hook: &Hook, delivery: &Delivery) {
+        info!("Dry run, {} would have run", hook.label());
+        if let Some(store) = &self.store {
+            let hook_run = HookRun {
+                event: hook.event,
+                name: hook.name.clone(),
+                ok: true,
+                error: None,
+                duration: Duration::from_secs(0),
+                attempts: 0,
+                output: None,
+                dry_run: true,
+            };
+            store.record_hook(delivery, &hook_run);
+        }
+    }
+
+    /// Test if the hook is active now, handling the delivery as set by `Hook::outside_window` if not
+    #[cfg(feature = "chrono")]
+    fn within_window(&self, hook: &Hook, delivery: &Delivery) -> bool {
+        let now = chrono::Utc::now();
+        let window = match &hook.active_window {
+            Some(window) if !window.contains(now) => window,
+            _ => return true,
+        };
+        match hook.out_of_window {
+            OutOfWindow::Drop => debug!("Dropped delivery outside the window of {}", hook.label()),
+            OutOfWindow::Queue => {
+                let delay = window.opens_in(now);
+                debug!(
+                    "Running {} when its window opens in {:?}",
+                    hook.label(),
+                    delay
+                );
+                self.retry_queue.schedule(Job {
+                    due: Instant::now() + delay,
+                    attempt: 1,
+                    hook: hook.clone(),
+                    delivery: delivery.clone(),
+                    settings: self.clone(),
+                    pending: None,
+                    queued: false,
+                });
+            }
+            OutOfWindow::DeadLetter => {
+                let error = hook.named_error(Error::OutsideWindow);
+                self.stats.record_dead_letter(hook.name.as_deref());
+                self.report(&error);
+                if let Some(store) = &self.store {
+                    store.record_failure(delivery, &error);
+                }
+            }
+        }
+        false
+    }
+
+    /// Acquire the permit to run hooks, if the concurrency is limited
+    fn acquire(&self) -> Result<Option<Permit<'_>>, Error> {
+        let limiter = match &self.concurrency_limiter {
+            Some(limiter) => limiter,
+            None => return Ok(None),
+        };
+        match limiter.acquire() {
+            Some(permit) => Ok(Some(permit)),
+            None => {
+                debug!("Too many running hooks");
+                self.report(&Error::Overloaded);
+                Err(Error::Overloaded)
+            }
+        }
+    }
+
+    /// Run the scheduled job, with the pending delivery or batch of coalesced hooks unless already run or superseded
+    ///
+    /// It waits for a permit if the concurrency is limited, whatever the policy. Hooks queued by
+    /// `ConcurrencyPolicy::Queue` are started then.
+    pub(crate) fn run_job(&self, job: Job) {
+        let _permit = self
+            .concurrency_limiter
+            .as_deref()
+            .map(|limiter| limiter.wait(job.queued));
+        if job.queued {
+            return self.start(&job.hook, &job.delivery);
+        }
+        let (key, id) = match &job.pending {
+            Some(pending) => pending,
+            None => return self.execute(&job.hook, &job.delivery, job.attempt),
+        };
+        if let Some(Schedule::Batch { .. }) = job.hook.schedule {
+            if let Some(deliveries) = self.pending_runs.take_batch(key, *id) {
+                self.execute_batch(&job.hook, &deliveries);
+            }
+        } else if let Some(delivery) = self.pending_runs.take(key, *id) {
+            self.execute(&job.hook, &delivery, job.attempt);
+        }
+    }
+
+    /// Run the hook function with a batch of deliveries, failed batches are not retried
+    fn execute_batch(&self, hook: &Hook, deliveries: &[Delivery]) {
+        debug!(
+            "Running {} with {} deliveries",
+            hook.label(),
+            deliveries.len()
+        );
+        if let Err(error) = self.run(hook, deliveries, 1) {
+            debug!("Failed to run {}: {}", hook.label(), &error);
+            let error = hook.named_error(error);
+            self.stats.record_dead_letter(hook.name.as_deref());
+            self.report(&error);
+            if let Some(store) = &self.store {
+                for delivery in deliveries {
+                    store.record_failure(delivery, &error);
+                }
+            }
+        }
+    }
+
+    /// Run the hook function, scheduling a retry if it fails and there are attempts left
+    pub(crate) fn execute(&self, hook: &Hook, delivery: &Delivery, attempt: u32) {
+        if attempt > 1 {
+            self.stats.record_retry_started(hook.name.as_deref());
+        }
+        let error = match self.run(hook, slice::from_ref(delivery), attempt) {
+            Ok(()) => {
+                self.report_status(hook, delivery, Ok(()));
+                return;
+            }
+            Err(error) => error,
+        };
+        debug!("Failed to run {}: {}", hook.label(), &error);
+        if attempt <= hook.retries {
+            let delay = retry::delay(hook.backoff, attempt + 1);
+            debug!("Retrying in {:?}", delay);
+            self.stats.record_retry_scheduled(hook.name.as_deref());
+            self.retry_queue.schedule(Job {
+                due: Instant::now() + delay,
+                attempt: attempt + 1,
+                hook: hook.clone(),
+                delivery: delivery.clone(),
+                settings: self.clone(),
+                pending: None,
+                queued: false,
+            });
+            return;
+        }
+        let error = if hook.retries > 0 {
+            Error::RetriesExhausted {
+                attempts: attempt,
+                error: match error {
+                    Error::HookFailed(message) => message,
+                    error => error.to_string(),
+                },
+            }
+        } else {
+            error
+        };
+        let error = hook.named_error(error);
+        self.stats.record_dead_letter(hook.name.as_deref());
+        self.report(&error);
+        if let Some(store) = &self.store {
+            store.record_failure(delivery, &error);
+        }
+        self.report_status(hook, delivery, Err(error));
+    }
+
+    /// Report the outcome of the hook as a commit status, in a separate thread
+    #[cfg(feature = "client")]
+    fn report_status(&self, hook: &Hook, delivery: &Delivery, result: Result<(), Error>) {
+        let status = match &hook.commit_status {
+            Some(status) => status.clone(),
+            None => return,
+        };
+        let settings = self.clone();
+        let event = hook.event;
+        let delivery = delivery.clone();
+        thread::spawn(move || {
+            if let Err(error) = status.post(event, &delivery, &result) {
+                settings.report(&error);
+            }
+        });
+    }
+
+    #[cfg(not(feature = "client"))]
+    fn report_status(&self, _hook: &Hook, _delivery: &Delivery, _result: Result<(), Error>) {}
+
+    /// Verify the signature of the delivery for the hook, returns whether it was actually verified (the hook has a
+    /// secret or an authenticator), or `None` if it fails
+    fn verify(&self, hook: &Hook, delivery: &Delivery) -> Option<bool> {
+        match hook.verify(delivery) {
+            Ok(()) => {
+                debug!("Valid payload found");
+                Some(hook.secret.is_some() || hook.authenticator.is_some())
+            }
+            Err(Error::CryptoUnavailable) if self.allow_unverified => {
+                warn!("Unable to verify payload, passing as configured...");
+                Some(false)
+            }
+            Err(error) => {
+                debug!("Invalid payload");
+                self.report(&error);
+                None
+            }
+        }
+    }
+
+    /// Check if the delivery was sent within the configured window
+    fn check_freshness(&self, delivery: &Delivery) -> Result<(), Error> {
+        let (max_age, timestamp) = match (self.max_delivery_age, delivery.timestamp) {
+            (Some(max_age), Some(timestamp)) => (max_age, timestamp),
+            _ => return Ok(()),
+        };
+        let age = match SystemTime::now().duration_since(timestamp) {
+            Ok(age) => age,
+            Err(error) => error.duration(), // Sent from the future
+        };
+        if age > max_age {
+            debug!("Delivery is {:?} old", age);
+            let error = Error::StaleDelivery(age);
+            self.report(&error);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Authenticate the delivery with the authenticator set by `Constructor::authenticator`
+    fn authenticate(&self, delivery: &Delivery) -> bool {
+        let authenticator = match &self.authenticator {
+            Some(authenticator) => authenticator,
+            None => return true,
+        };
+        match authenticator.verify(delivery) {
+            Ok(()) => true,
+            Err(error) => {
+                debug!("Rejected by the authenticator");
+                self.report(&error);
+                false
+            }
+        }
+    }
+
+    /// Check if the client is allowed by `Constructor::allowed_clients`
+    fn check_client(&self, delivery: &Delivery) -> Result<(), Error> {
+        let allowed_clients = match &self.allowed_clients {
+            Some(allowed_clients) => allowed_clients,
+            None => return Ok(()),
+        };
+        match &delivery.client_identity {
+            Some(identity) if allowed_clients.contains(identity) => Ok(()),
+            identity => {
+                debug!("Client {:?} is not allowed", identity);
+                let error = Error::UntrustedClient;
+                self.report(&error);
+                Err(error)
+            }
+        }
+    }
+
+    /// How deliveries of the provider with an empty body are handled, see `Constructor::empty_body_of`
+    fn empty_body_policy(&self, provider: DeliveryType) -> EmptyBodyPolicy {
+        self.empty_body_providers
+            .iter()
+            .find(|(delivery_type, _)| *delivery_type == provider)
+            .map_or(self.empty_body, |(_, policy)| *policy)
+    }
+
+    /// Check if the user agent is allowed by `Constructor::allow_user_agent` and `Constructor::deny_user_agent`
+    fn check_user_agent(&self, delivery: &Delivery) -> Result<(), Error> {
+        if self.allowed_user_agents.is_empty() && self.denied_user_agents.is_empty() {
+            return Ok(());
+        }
+        let user_agent = delivery.headers.get("user-agent");
+        let allowed = match user_agent {
+            Some(user_agent) => {
+                (self.allowed_user_agents.is_empty()
+                    || self
+                        .allowed_user_agents
+                        .iter()
+                        .any(|rule| rule.matches(user_agent)))
+                    && !self
+                        .denied_user_agents
+                        .iter()
+                        .any(|rule| rule.matches(user_agent))
+            }
+            None => self.allowed_user_agents.is_empty(),
+        };
+        if allowed {
+            return Ok(());
+        }
+        debug!("User agent {:?} is not allowed", user_agent);
+        let error = Error::UntrustedUserAgent(user_agent.unwrap_or_default().to_string());
+        self.report(&error);
+        Err(error)
+    }
+
+    /// Check if the delivery of GitHub was sent by a host required by `Constructor::require_enterprise_host`
+    fn check_enterprise_host(&self, delivery: &Delivery) -> Result<(), Error> {
+        if self.enterprise_hosts.is_empty() || delivery.delivery_type != DeliveryType::GitHub {
+            return Ok(());
+        }
+        let host = delivery
+            .enterprise_host
+            .as_deref()
+            .map(str::to_ascii_lowercase);
+        match host {
+            Some(host) if self.enterprise_hosts.contains(&host) => Ok(()),
+            host => {
+                debug!("GitHub Enterprise host {:?} is not allowed", host);
+                let error = Error::UntrustedHost(host.unwrap_or_default());
+                self.report(&error);
+                Err(error)
+            }
+        }
+    }
+
+    /// Check if the delivery of GitHub has all the headers GitHub sends, see `Constructor::strict_github`
+    fn check_strict_github(&self, delivery: &Delivery) -> Result<(), Error> {
+        if !self.strict_github || delivery.delivery_type != DeliveryType::GitHub {
+            return Ok(());
+        }
+        let header = |name: &str| delivery.headers.get(name);
+        let is_signature = |signature: &str| match signature.strip_prefix("sha256=") {
+            Some(digest) => digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()),
+            None => false,
+        };
+        let is_hookshot = |user_agent: &str| match user_agent.strip_prefix("GitHub-Hookshot/") {
+            Some(version) => !version.is_empty(),
+            None => false,
+        };
+        let is_json = |content_type: &str| {
+            content_type.split(';').next().is_some_and(|media_type| {
+                media_type.trim().eq_ignore_ascii_case("application/json")
+            })
+        };
+        let reason = if header("x-github-delivery").is_none_or(str::is_empty) {
+            "Missing X-GitHub-Delivery"
+        } else if !header("x-hub-signature-256").is_some_and(is_signature) {
+            "Missing or malformed X-Hub-Signature-256"
+        } else if !header("user-agent").is_some_and(is_hookshot) {
+            "User-Agent is not GitHub-Hookshot"
+        } else if !header("content-type").is_some_and(is_json) {
+            "Content-Type is not application/json"
+        } else {
+            return Ok(());
+        };
+        debug!("Delivery of GitHub refused by strict mode: {}", reason);
+        let error = Error::InvalidHeaders(reason);
+        self.report(&error);
+        Err(error)
+    }
+
+    /// Validate the delivery with the validators set by `Constructor::validate`
+    fn validate(&self, delivery: &Delivery) -> Result<(), Error> {
+        let events = self.events(delivery);
+        let validators = self
+            .validators
+            .iter()
+            .filter(|(event, _)| events.contains(&self.event_normalization.apply(event)));
+        for (_, validator) in validators {
+            if let Err(reason) = validator.validate(delivery) {
+                debug!("Delivery failed validation: {}", &reason);
+                let error = Error::ValidationFailed(reason);
+                self.report(&error);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the delivery with the transformers set by `Constructor::transform`, normalizing the events they set
+    fn transform(&self, delivery: &mut Delivery) {
+        for transformer in &self.transformers {
+            let event = delivery.event.clone();
+            transformer.transform(delivery);
+            if delivery.event != event {
+                delivery.event = self.event_normalization.apply(&delivery.event);
+            }
+        }
+    }
+
+    /// Parse the parts of multipart bodies from the raw body if it's not valid UTF-8, and drop file parts over
+    /// the limit
+    #[cfg(feature = "multipart")]
+    fn load_parts(&self, delivery: &mut Delivery, body: &[u8]) {
+        if delivery.request_body.as_ref().map(String::as_bytes) != Some(body) {
+            if let Some(boundary) = delivery
+                .headers
+                .get("content-type")
+                .and_then(multipart::boundary)
+            {
+                delivery.parts = multipart::parse(body, &boundary, usize::MAX).unwrap_or_default();
+            }
+        }
+        let limit = self.multipart_file_limit;
+        delivery
+            .parts
+            .retain(|part| part.filename.is_none() || part.data.len() <= limit);
+    }
+
+    /// Events hooks are looked up with for the delivery: its own, its aliases and the wildcard
+    fn events(&self, delivery: &Delivery) -> Vec<String> {
+        let normalization = &self.event_normalization;
+        let mut events = vec![delivery.event.clone()];
+        // Hooks registered for every system hook of GitLab match them too
+        if let (DeliveryType::GitLab, Some(header)) = (
+            delivery.delivery_type,
+            delivery.headers.get("x-gitlab-event"),
+        ) {
+            let event = normalization.apply(header);
+            if event != delivery.event {
+                events.push(event);
+            }
+        }
+        for (alias, event) in &self.aliases {
+            let alias = normalization.apply(alias);
+            if normalization.apply(event) == delivery.event && !events.contains(&alias) {
+                events.push(alias);
+            }
+        }
+        events.push("*".to_string());
+        events
+    }
+
+    /// Take tokens from the configured rate limiters of the scopes
+    fn throttle(&self, delivery: &Delivery, scopes: &[RateLimitScope]) -> Result<(), Error> {
+        let limiters = self.rate_limiters.iter();
+        for limiter in limiters.filter(|limiter| scopes.contains(&limiter.scope())) {
+            if let Err(retry_after) = limiter.check(delivery) {
+                debug!("Rate limit exceeded ({:?} scope)", limiter.scope());
+                let error = Error::RateLimited(retry_after);
+                self.report(&error);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Default normalization is `Normalize`
+impl Default for EventNormalization {
+    fn default() -> Self {
+        EventNormalization::Normalize
+    }
+}
+
+/// Implement `Debug` for `EventNormalization`, the function of `Custom` is omitted
+impl fmt::Debug for EventNormalization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventNormalization::Normalize => write!(f, "Normalize"),
+            EventNormalization::Preserve => write!(f, "Preserve"),
+            EventNormalization::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// The main impl clause of `EventNormalization`
+impl EventNormalization {
+    /// Normalize the event name, the wildcard `*` is never changed
+    pub fn apply(&self, event: &str) -> String {
+        if event == "*" {
+            return event.to_string();
+        }
+        match self {
+            EventNormalization::Normalize => event.to_ascii_lowercase().replace(' ', "_"),
+            EventNormalization::Preserve => event.to_string(),
+            EventNormalization::Custom(normalize) => normalize(event),
+        }
+    }
+
+    /// Key of the hook in the registry, the normalized event or a key apart from events for the fallback hook
+    pub(crate) fn key(&self, hook: &Hook) -> String {
+        if hook.fallback {
+            return FALLBACK_KEY.to_string();
+        }
+        self.apply(hook.event)
+    }
+
+    /// Registry of the hooks, by their keys
+    pub(crate) fn registry(&self, hooks: impl IntoIterator<Item = Hook>) -> HookRegistry {
+        let mut registry = HookRegistry::new();
+        for hook in hooks {
+            registry.entry(self.key(&hook)).or_default().push(hook);
+        }
+        registry
+    }
+}
+
+/// The main impl clause of `Delivery`
+impl Delivery {
+    /// Create a new Delivery from the headers (e.g. a `HashMap` or a `&http::HeaderMap`), the event name is
+    /// normalized with `EventNormalization::Normalize`
+    pub fn new(
+        headers: impl HeaderAccessor,
+        request_body: Option<String>,
+    ) -> Result<Delivery, &'static str> {
+        Self::with_normalization(
+            headers.into_headers(),
+            request_body,
+            &EventNormalization::Normalize,
+            &AmbiguityPolicy::FirstMatch,
+            &[],
+            false,
+            None,
+        )
+    }
+
+    /// Create a new Delivery from the parts and the body of a request of the `http` crate, e.g. received by a web
+    /// framework based on it, the event name is normalized with `EventNormalization::Normalize`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate http;
+    /// extern crate rifling;
+    ///
+    /// use rifling::Delivery;
+    ///
+    /// let request = http::Request::post("/hooks")
+    ///     .header("X-GitHub-Event", "push")
+    ///     .body(())
+    ///     .unwrap();
+    /// let (parts, _) = request.into_parts();
+    /// let delivery = Delivery::from_http_parts(&parts, br#"{"ref": "refs/heads/master"}"#).unwrap();
+    /// assert_eq!(delivery.event(), "push");
+    /// ```
+    ///
+    /// Fails if the body is not valid UTF-8, headers whose value isn't are kept as `unknown`.
+    #[cfg(feature = "http")]
+    pub fn from_http_parts(
+        parts: &http::request::Parts,
+        body: &[u8],
+    ) -> Result<Delivery, &'static str> {
+        let request_body = String::from_utf8(body.to_vec()).map_err(|_| "Invalid payload")?;
+        Self::new(&parts.headers, Some(request_body))
+    }
+
+    /// Create a new Delivery, normalizing the event name and picking the provider of ambiguous requests as
+    /// configured, the payload is kept raw if `raw_payload`, and the provider is taken from the hint if it can't be
+    /// determined
+    pub(crate) fn with_normalization(
+        headers: Headers,
+        request_body: Option<String>,
+        normalization: &EventNormalization,
+        ambiguity: &AmbiguityPolicy,
+        docker_hub_ids: &[String],
+        raw_payload: bool,
+        hint: Option<&ProviderHint>,
+    ) -> Result<Delivery, &'static str> {
+        debug!("Received headers: {:#?}", redact::Headers(&headers));
+        // Get content type, ignoring parameters (e.g. `charset`)
+        let content_type = match headers.get("content-type") {
+            Some(header_value) => match header_value.split(';').next().map(str::trim) {
+                Some(media_type)
+                    if media_type.eq_ignore_ascii_case("application/x-www-form-urlencoded") =>
+                {
+                    ContentType::URLENCODED
+                }
+                Some(media_type) if media_type.eq_ignore_ascii_case("multipart/form-data") => {
+                    ContentType::MULTIPART
+                }
+                _ => ContentType::JSON,
+            },
+            None => ContentType::JSON,
+        };
+        // Identify delivery type, by the headers carrying the event first
+        let sent: Vec<(&str, DeliveryType)> = EVENT_HEADERS
+            .iter()
+            .filter_map(|(name, provider)| Some((headers.get(name)?, *provider)))
+            .collect();
+        let providers: Vec<DeliveryType> = sent.iter().map(|(_, provider)| *provider).collect();
+        let picked = match ambiguity {
+            _ if !is_ambiguous(&providers) => sent.first(),
+            AmbiguityPolicy::FirstMatch => sent.first(),
+            AmbiguityPolicy::Prefer(preferred) => preferred
+                .iter()
+                .find_map(|preferred| sent.iter().find(|(_, provider)| provider == preferred))
+                .or_else(|| sent.first()),
+            AmbiguityPolicy::Reject => {
+                debug!("Event headers of {:?} found", &providers);
+                return Err("Ambiguous delivery type");
+            }
+        };
+        let (event, delivery_type) = if let Some((event_string, delivery_type)) = picked {
+            match delivery_type {
+                // The type in the payload takes precedence
+                DeliveryType::CircleCI => (
+                    payload_type(request_body.as_deref()).unwrap_or(event_string.to_string()),
+                    DeliveryType::CircleCI,
+                ),
+                DeliveryType::GitLab if event_string.eq_ignore_ascii_case("System Hook") => (
+                    system_hook_event(request_body.as_deref()).unwrap_or(event_string.to_string()),
+                    DeliveryType::GitLab,
+                ),
+                _ => (event_string.to_string(), *delivery_type),
+            }
+        } else if headers.contains_key("x-telegram-bot-api-secret-token") {
+            let event =
+                telegram_event(request_body.as_deref()).unwrap_or_else(|| "update".to_string());
+            (event, DeliveryType::Telegram)
+        } else if headers.contains_key("x-jfrog-event-auth") {
+            let event = artifactory_event(request_body.as_deref())
+                .unwrap_or_else(|| "artifactory".to_string());
+            (event, DeliveryType::Artifactory)
+        } else if headers.contains_key("x-npm-signature") {
+            let event = npm_event(request_body.as_deref()).unwrap_or_else(|| "npm".to_string());
+            (event, DeliveryType::Npm)
+        } else if headers.contains_key("x-signature-ed25519") {
+            let event =
+                discord_event(request_body.as_deref()).unwrap_or_else(|| "interaction".to_string());
+            (event, DeliveryType::Discord)
+        } else if headers
+            .get("user-agent")
+            .is_some_and(|user_agent| user_agent.starts_with("Grafana"))
+        {
+            let event = grafana_event(request_body.as_deref())
+                .unwrap_or_else(|| "grafana.alert".to_string());
+            (event, DeliveryType::Grafana)
+        } else if headers.get("x-newrelic-id").is_some_and(|newrelic_id| {
+            DOCKER_HUB_NEWRELIC_IDS.contains(&newrelic_id)
+                || docker_hub_ids.iter().any(|id| id == newrelic_id)
+        }) {
+            ("docker_push".to_string(), DeliveryType::DockerHub)
+        } else if let Some(event) = payload_event(request_body.as_deref()) {
+            event
+        } else if let Some(event) = form_event(&content_type, &headers, request_body.as_deref()) {
+            (event, DeliveryType::Form)
+        } else if let Some(hint) = hint {
+            debug!("Assuming the delivery is sent by {:?}", hint.provider);
+            let event = hint.event.as_deref().unwrap_or("unknown");
+            (event.to_string(), hint.provider)
+        } else {
+            return Err("Could not determine delivery type");
+        };
+        let event = normalization.apply(&event);
+        // Get delivery ID: not available in requests from GitLab, Docker Hub and others
+        let id = match delivery_type {
+            DeliveryType::GitHub => header_get_owned!(&headers, "x-github-delivery"),
+            DeliveryType::Forgejo => header_get_owned!(&headers, "x-forgejo-delivery")
+                .or_else(|| header_get_owned!(&headers, "x-gitea-delivery")),
+            DeliveryType::Gitea => header_get_owned!(&headers, "x-gitea-delivery"),
+            DeliveryType::CircleCI => header_get_owned!(&headers, "circleci-delivery-id"),
+            DeliveryType::Linear => header_get_owned!(&headers, "linear-delivery"),
+            _ => None,
+        };
+        let signature = match delivery_type {
+            DeliveryType::GitHub => header_get_owned!(&headers, "x-hub-signature-256")
+                .or_else(|| header_get_owned!(&headers, "x-hub-signature")),
+            DeliveryType::GitLab => header_get_owned!(&headers, "x-gitlab-token"),
+            DeliveryType::Gitee => header_get_owned!(&headers, "x-gitee-token"),
+            DeliveryType::Gerrit => header_get_owned!(&headers, "x-gerrit-signature"),
+            DeliveryType::CircleCI => header_get_owned!(&headers, "circleci-signature"),
+            // Basic authentication or bearer token, verified by the hook
+            DeliveryType::Grafana => header_get_owned!(&headers, "authorization"),
+            DeliveryType::Discord => header_get_owned!(&headers, "x-signature-ed25519"),
+            DeliveryType::Telegram => {
+                header_get_owned!(&headers, "x-telegram-bot-api-secret-token")
+            }
+            DeliveryType::Linear => header_get_owned!(&headers, "linear-signature"),
+            DeliveryType::Intercom => header_get_owned!(&headers, "x-hub-signature"),
+            DeliveryType::Artifactory => header_get_owned!(&headers, "x-jfrog-event-auth"),
+            DeliveryType::Npm => header_get_owned!(&headers, "x-npm-signature"),
+            // Token of Generic Webhook Trigger (or of form posts), as a header or a bearer token
+            DeliveryType::Jenkins | DeliveryType::Form => header_get_owned!(&headers, "token")
+                .or_else(|| {
+                    headers
+                        .get("authorization")
+                        .and_then(|value| value.strip_prefix("Bearer "))
+                        .map(String::from)
+                }),
+            DeliveryType::Kubernetes => headers
+                .get("authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(String::from),
+            DeliveryType::Forgejo => header_get_owned!(&headers, "x-forgejo-signature")
+                .or_else(|| header_get_owned!(&headers, "x-gitea-signature")),
+            DeliveryType::Gitea => header_get_owned!(&headers, "x-gitea-signature"),
+            _ => None,
+        };
+        // Get timestamp: milliseconds since UNIX epoch from Gitee, seconds from Discord,
+        // filled from the payload for Gerrit and Linear
+        let timestamp = match delivery_type {
+            DeliveryType::Gitee => headers
+                .get("x-gitee-timestamp")
+                .and_then(|timestamp| timestamp.trim().parse().ok())
+                .map(|millis| UNIX_EPOCH + Duration::from_millis(millis)),
+            DeliveryType::Discord => headers
+                .get("x-signature-timestamp")
+                .and_then(|timestamp| timestamp.trim().parse().ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            _ => None,
+        };
+        let mut delivery = Self {
+            delivery_type,
+            content_type,
+            id,
+            event,
+            payload: None,
+            unparsed_payload: None,
+            #[cfg(feature = "raw-payload")]
+            raw_payload: None,
+            request_body: None,
+            signature,
+            remote_addr: None,
+            scheme: None,
+            timestamp,
+            received_at: SystemTime::now(),
+            headers,
+            client_identity: None,
+            enterprise_host: None,
+            enterprise_version: None,
+            request_id: None,
+            query: None,
+            #[cfg(feature = "multipart")]
+            parts: Vec::new(),
+        };
+        if let DeliveryType::GitHub = delivery.delivery_type {
+            delivery.enterprise_host =
+                header_get_owned!(&delivery.headers, "x-github-enterprise-host");
+            delivery.enterprise_version =
+                header_get_owned!(&delivery.headers, "x-github-enterprise-version");
+        }
+        if request_body.is_some() {
+            delivery.set_request_body(request_body, raw_payload);
+        }
+        #[cfg(feature = "parse")]
+        {
+            if let DeliveryType::Gerrit = delivery.delivery_type {
+                delivery.timestamp = delivery
+                    .payload
+                    .as_ref()
+                    .and_then(|payload| payload.get("eventCreatedOn"))
+                    .and_then(Value::as_u64)
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+            }
+            if let DeliveryType::Kubernetes = delivery.delivery_type {
+                delivery.id = delivery
+                    .payload
+                    .as_ref()
+                    .and_then(|payload| payload.pointer("/request/uid"))
+                    .and_then(Value::as_str)
+                    .map(String::from);
+            }
+            if let DeliveryType::Linear = delivery.delivery_type {
+                delivery.timestamp = delivery
+                    .payload
+                    .as_ref()
+                    .and_then(|payload| payload.get("webhookTimestamp"))
+                    .and_then(Value::as_u64)
+                    .map(|millis| UNIX_EPOCH + Duration::from_millis(millis));
+            }
+        }
+        Ok(delivery)
+    }
+
+    /// Update request body of the delivery, the payload is parsed into `Delivery::payload`
+    pub fn update_request_body(&mut self, request_body: Option<String>) {
+        self.set_request_body(request_body, false);
+    }
+
+    /// Update request body of the delivery, keeping the payload raw if `raw_payload`
+    #[cfg_attr(not(feature = "raw-payload"), allow(unused_variables))]
+    fn set_request_body(&mut self, request_body: Option<String>, raw_payload: bool) {
+        let payload: Option<String> = match self.content_type {
+            ContentType::JSON => request_body.clone(),
+            #[cfg(feature = "content-type-urlencoded")]
+            ContentType::URLENCODED => request_body.as_ref().and_then(|request_body_string| {
+                form_urlencoded::parse(request_body_string.as_bytes())
+                    .into_owned()
+                    .find(|(key, _)| key == "payload")
+                    .map(|(_, payload_string)| payload_string)
+            }),
+            _ => None,
+        };
+        // Empty bodies have no payload
+        let payload = payload.filter(|payload| !payload.is_empty());
+        #[cfg(feature = "multipart")]
+        {
+            let boundary = self
+                .headers
+                .get("content-type")
+                .and_then(multipart::boundary);
+            self.parts = match (&self.content_type, boundary, &request_body) {
+                (ContentType::MULTIPART, Some(boundary), Some(request_body)) => {
+                    multipart::parse(request_body.as_bytes(), &boundary, usize::MAX)
+                        .unwrap_or_default()
+                }
+                _ => Vec::new(),
+            };
+        }
+        #[cfg(feature = "raw-payload")]
+        {
+            self.raw_payload = match &payload {
+                Some(payload_string) if raw_payload => serde_json::from_str(payload_string).ok(),
+                _ => None,
+            };
+        }
+        #[cfg(feature = "parse")]
+        let parsed_payload = match &payload {
+            #[cfg(feature = "raw-payload")]
+            Some(_) if raw_payload => None,
+            Some(payload_string) => serde_json::from_str(payload_string.as_str()).ok(),
+            None => None,
+        };
+        #[cfg(not(feature = "parse"))]
+        let parsed_payload = None;
+        // Update delivery
+        self.request_body = request_body;
+        self.unparsed_payload = payload;
+        self.payload = parsed_payload;
+    }
+
+    /// Field of the payload at the JSON pointer (RFC 6901, e.g. `/pull_request/head/ref`), requires parsed payload
+    #[cfg_attr(not(feature = "parse"), allow(unused_variables))]
+    pub fn field(&self, pointer: &str) -> Option<&Value> {
+        #[cfg(feature = "parse")]
+        {
+            self.payload.as_ref()?.pointer(pointer)
+        }
+        #[cfg(not(feature = "parse"))]
+        None
+    }
+
+    /// Deserialize the payload into the type, fails with `Error::ValidationFailed` if the payload is missing or
+    /// doesn't fit, see `Hook::typed`
+    #[cfg(feature = "typed")]
+    pub fn parse_as<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        #[cfg(feature = "raw-payload")]
+        {
+            if let Some(raw_payload) = &self.raw_payload {
+                return serde_json::from_str(raw_payload.get())
+                    .map_err(|error| Error::ValidationFailed(error.to_string()));
+            }
+        }
+        let payload = self
+            .payload
+            .as_ref()
+            .ok_or_else(|| Error::ValidationFailed("Missing payload".to_string()))?;
+        T::deserialize(payload).map_err(|error| Error::ValidationFailed(error.to_string()))
+    }
+
+    /// Fields of the `application/x-www-form-urlencoded` body, or text fields of the `multipart/form-data` body
+    /// (requires `multipart`), `None` for other content types
+    pub fn form_fields(&self) -> Option<HashMap<String, String>> {
+        match self.content_type {
+            #[cfg(feature = "content-type-urlencoded")]
+            ContentType::URLENCODED => {
+                let request_body = self.request_body.as_ref()?;
+                Some(
+                    form_urlencoded::parse(request_body.as_bytes())
+                        .into_owned()
+                        .collect(),
+                )
+            }
+            #[cfg(feature = "multipart")]
+            ContentType::MULTIPART => Some(
+                self.parts
+                    .iter()
+                    .filter(|part| part.filename.is_none())
+                    .map(|part| {
+                        let value = String::from_utf8_lossy(&part.data).into_owned();
+                        (part.name.clone(), value)
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Full name of the repository the delivery is about (e.g. `RedL0tus/rifling`), requires parsed payload
+    pub fn repository(&self) -> Option<String> {
+        #[cfg(feature = "parse")]
+        {
+            let payload = self.payload.as_ref()?;
+            payload
+                .pointer("/repository/full_name")
+                .or_else(|| payload.pointer("/project/path_with_namespace"))
+                .and_then(Value::as_str)
+                .map(String::from)
+        }
+        #[cfg(not(feature = "parse"))]
+        None
+    }
+
+    /// Login of the organization the delivery is about (e.g. `rifling`, the organization of the repository for
+    /// events of repositories), requires parsed payload
+    pub fn organization(&self) -> Option<String> {
+        #[cfg(feature = "parse")]
+        {
+            self.payload
+                .as_ref()?
+                .pointer("/organization/login")
+                .and_then(Value::as_str)
+                .map(String::from)
+        }
+        #[cfg(not(feature = "parse"))]
+        None
+    }
+
+    /// SHA of the commit the delivery is about (e.g. the head of a push or a pull request), requires parsed payload
+    pub fn commit_sha(&self) -> Option<String> {
+        #[cfg(feature = "parse")]
+        {
+            let payload = self.payload.as_ref()?;
+            [
+                "/pull_request/head/sha",
+                "/check_suite/head_sha",
+                "/check_run/head_sha",
+                "/checkout_sha",
+                "/after",
+                "/sha",
+            ]
+            .iter()
+            .filter_map(|pointer| payload.pointer(pointer).and_then(Value::as_str))
+            .find(|sha| !sha.is_empty() && sha.chars().any(|c| c != '0'))
+            .map(String::from)
+        }
+        #[cfg(not(feature = "parse"))]
+        None
+    }
+}
+
+/// Implement `Debug` to `Delivery`, secrets are redacted and bodies are elided
+impl fmt::Debug for Delivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Delivery")
+            .field("delivery_type", &self.delivery_type)
+            .field("content_type", &self.content_type)
+            .field("id", &self.id)
+            .field("event", &self.event)
+            .field(
+                "request_body",
+                &self.request_body.as_deref().map(redact::Length),
+            )
+            .field(
+                "signature",
+                &self.signature.as_ref().map(|_| redact::Secret),
+            )
+            .field("remote_addr", &self.remote_addr)
+            .field("scheme", &self.scheme)
+            .field("timestamp", &self.timestamp)
+            .field("received_at", &self.received_at)
+            .field("headers", &redact::Headers(&self.headers))
+            .field("client_identity", &self.client_identity)
+            .field("enterprise_host", &self.enterprise_host)
+            .field("enterprise_version", &self.enterprise_version)
+            .field("request_id", &self.request_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Accessors impl clause of `Delivery`
+impl Delivery {
+    /// Source of the delivery
+    pub fn delivery_type(&self) -> DeliveryType {
+        self.delivery_type
+    }
+
+    /// Type of the content of the request
+    pub fn content_type(&self) -> &ContentType {
+        &self.content_type
+    }
+
+    /// ID of the delivery, sent by some providers (e.g. GitHub)
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Event of the delivery, as normalized
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    /// Replace the event of the delivery, e.g. in transformers
+    pub fn set_event(&mut self, event: &str) {
+        self.event = event.to_string();
+    }
+
+    /// Parsed payload, requires the `parse` feature
+    pub fn payload(&self) -> Option<&Value> {
+        self.payload.as_ref()
+    }
+
+    /// Parsed payload to rewrite, e.g. in transformers
+    pub fn payload_mut(&mut self) -> Option<&mut Value> {
+        self.payload.as_mut()
+    }
+
+    /// Payload as received, extracted from the form of URL encoded bodies
+    pub fn unparsed_payload(&self) -> Option<&str> {
+        self.unparsed_payload.as_deref()
+    }
+
+    /// Payload kept raw, see `Constructor::raw_payload`
+    #[cfg(feature = "raw-payload")]
+    pub fn raw_payload(&self) -> Option<&RawValue> {
+        self.raw_payload.as_deref()
+    }
+
+    /// Body of the request
+    pub fn request_body(&self) -> Option<&str> {
+        self.request_body.as_deref()
+    }
+
+    /// Signature or token sent by the provider
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+
+    /// Address of the sender, see `Constructor::trust_proxies`
+    pub fn remote_addr(&self) -> Option<IpAddr> {
+        self.remote_addr
+    }
+
+    /// Scheme of the request sent to trusted proxies
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    /// Time the delivery was sent at, according to the provider
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+
+    /// Time the delivery was received at
+    pub fn received_at(&self) -> SystemTime {
+        self.received_at
+    }
+
+    /// Headers of the request, with lower cased names
+    pub fn headers(&self) -> &HashMap<String, String> {
+        self.headers.map()
+    }
+
+    /// Value of the header, by its lower cased name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Subject of the verified client certificate
+    pub fn client_identity(&self) -> Option<&str> {
+        self.client_identity.as_deref()
+    }
+
+    /// Host of GitHub Enterprise Server
+    pub fn enterprise_host(&self) -> Option<&str> {
+        self.enterprise_host.as_deref()
+    }
+
+    /// Version of GitHub Enterprise Server
+    pub fn enterprise_version(&self) -> Option<&str> {
+        self.enterprise_version.as_deref()
+    }
+
+    /// ID of the request, see `Constructor::request_id`
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Query string of the request, without `?`
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Parts of multipart bodies
+    #[cfg(feature = "multipart")]
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+}
+
+/// Builder of deliveries, e.g. to test hooks without sending requests
+///
+/// Example:
+///
+/// ```
+/// extern crate rifling;
+///
+/// use rifling::{DeliveryBuilder, DeliveryType};
+///
+/// let delivery = DeliveryBuilder::new(DeliveryType::GitHub, "push")
+///     .header("X-GitHub-Delivery", "72d3162e-cc78-11e3-81ab-4c9367dc0958")
+///     .body(r#"{"ref": "refs/heads/master"}"#)
+///     .build();
+/// assert_eq!(delivery.event(), "push");
+/// assert_eq!(delivery.header("x-github-delivery"), Some("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+/// ```
+///
+/// The event is kept as is, and the delivery is not identified from the headers: the ID and the signature have to
+/// be set explicitly.
+#[derive(Clone, Debug)]
+pub struct DeliveryBuilder {
+    delivery_type: DeliveryType,
+    event: String,
+    id: Option<String>,
+    signature: Option<String>,
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    remote_addr: Option<IpAddr>,
+    timestamp: Option<SystemTime>,
+    query: Option<String>,
+}
+
+/// The main impl clause of `DeliveryBuilder`
+impl DeliveryBuilder {
+    /// Create a builder of deliveries of the event from the provider, without headers nor body
+    pub fn new(delivery_type: DeliveryType, event: &str) -> Self {
+        Self {
+            delivery_type,
+            event: event.to_string(),
+            id: None,
+            signature: None,
+            headers: HashMap::new(),
+            body: None,
+            remote_addr: None,
+            timestamp: None,
+            query: None,
+        }
+    }
+
+    /// Set the ID of the delivery
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Set the signature or token sent by the provider
+    pub fn signature(mut self, signature: &str) -> Self {
+        self.signature = Some(signature.to_string());
+        self
+    }
+
+    /// Add the header, its name is lower cased
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .insert(name.to_ascii_lowercase(), value.to_string());
+        self
+    }
+
+    /// Set the body of the request, parsed as the payload
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    /// Set the address of the sender
+    pub fn remote_addr(mut self, remote_addr: IpAddr) -> Self {
+        self.remote_addr = Some(remote_addr);
+        self
+    }
+
+    /// Set the time the delivery was sent at
+    pub fn timestamp(mut self, timestamp: SystemTime) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Set the query string of the request, without `?`
+    pub fn query(mut self, query: &str) -> Self {
+        self.query = Some(query.to_string());
+        self
+    }
+
+    /// Build the delivery
+    pub fn build(self) -> Delivery {
+        let mut delivery = Delivery {
+            delivery_type: self.delivery_type,
+            content_type: ContentType::JSON,
+            id: self.id,
+            event: self.event,
+            payload: None,
+            unparsed_payload: None,
+            #[cfg(feature = "raw-payload")]
+            raw_payload: None,
+            request_body: None,
+            signature: self.signature,
+            remote_addr: self.remote_addr,
+            scheme: None,
+            timestamp: self.timestamp,
+            received_at: SystemTime::now(),
+            headers: Headers::from(self.headers),
+            client_identity: None,
+            enterprise_host: None,
+            enterprise_version: None,
+            request_id: None,
+            query: self.query,
+            #[cfg(feature = "multipart")]
+            parts: Vec::new(),
+        };
+        delivery.update_request_body(self.body);
+        delivery
+    }
+}
+
+/// Hooks started by `Executor::run_or_queue`
+enum Started {
+    /// Run with the delivery, the number of hooks
+    Run(usize),
+    /// Queued to run in the background, see `ConcurrencyPolicy::Queue`
+    Queued(usize),
+}
+
+/// The main impl clause of `Executor`
+impl Executor {
+    /// Run the hooks, returns the number of hooks run,
+    /// or `Error::Overloaded` if they can't be run due to the concurrency limit
+    pub fn run(self, delivery: Delivery) -> Result<usize, Error> {
+        let permit = self.acquire()?;
+        let left = self.start(&delivery, permit);
+        self.reject(left, &delivery);
+        Ok(self.matched_hooks.len() - left.len())
+    }
+
+    /// Run the hooks in a separate thread, streaming the fragments they add to the response, returns the number of
+    /// hooks run, or `Error::Overloaded` if they can't be run due to the concurrency limit
+    fn stream(self, delivery: Delivery) -> Result<(usize, ResponseStream), Error> {
+        let (sender, stream) = ResponseStream::new();
+        let (started, start) = mpsc::channel();
+        thread::spawn(move || {
+            let permit = match self.acquire() {
+                Ok(permit) => permit,
+                Err(error) => {
+                    let _ = started.send(Err(error));
+                    return;
+                }
+            };
+            let _ = started.send(Ok(self.matched_hooks.len()));
+            context::begin_streaming(sender);
+            let left = self.start(&delivery, permit);
+            self.reject(left, &delivery);
+            context::finish();
+        });
+        let hooks = start.recv().unwrap_or(Err(Error::Overloaded))?;
+        Ok((hooks, stream))
+    }
+
+    /// Run the hooks, or queue them to run in the background if the concurrency limit is reached and the policy is
+    /// `ConcurrencyPolicy::Queue`, fails with `Error::Overloaded` if they can't be queued either
+    ///
+    /// Hooks left once no permit is available (e.g. taken by other deliveries meanwhile) are queued as well, or
+    /// rejected with `Error::Overloaded` if they can't be queued.
+    fn run_or_queue(self, delivery: Delivery) -> Result<Started, Error> {
+        let hooks = self.matched_hooks.len();
+        let permit = match &self.settings.concurrency_limiter {
+            Some(limiter) => match limiter.acquire() {
+                Some(permit) => Some(permit),
+                None if self.queue(&self.matched_hooks, &delivery) => {
+                    return Ok(Started::Queued(hooks))
+                }
+                None => {
+                    debug!("Too many running hooks");
+                    self.settings.report(&Error::Overloaded);
+                    return Err(Error::Overloaded);
+                }
+            },
+            None => None,
+        };
+        let left = self.start(&delivery, permit);
+        if !self.queue(left, &delivery) {
+            self.reject(left, &delivery);
+        }
+        Ok(Started::Run(hooks - left.len()))
+    }
+
+    /// Queue the hooks to run in the background once running hooks finish, fails if the policy is not
+    /// `ConcurrencyPolicy::Queue` or the queue is full
+    fn queue(&self, hooks: &[Hook], delivery: &Delivery) -> bool {
+        let limiter = match &self.settings.concurrency_limiter {
+            Some(limiter) => limiter,
+            None => return false,
+        };
+        if hooks.is_empty() || !limiter.reserve(hooks.len()) {
+            return false;
+        }
+        debug!("Queued {} hook(s) until running hooks finish", hooks.len());
+        for hook in hooks {
+            self.settings.retry_queue.schedule(Job {
+                due: Instant::now(),
+                attempt: 1,
+                hook: hook.clone(),
+                delivery: delivery.clone(),
+                settings: self.settings.clone(),
+                pending: None,
+                queued: true,
+            });
+        }
+        true
+    }
+
+    /// Give up on the hooks left without a permit, failing with `Error::Overloaded`
+    fn reject(&self, hooks: &[Hook], delivery: &Delivery) {
+        for hook in hooks {
+            debug!("Too many running hooks to run {}", hook.label());
+            let error = hook.named_error(Error::Overloaded);
+            self.settings.report(&error);
+            if let Some(store) = &self.settings.store {
+                store.record_failure(delivery, &error);
+            }
+        }
+    }
+
+    /// Decide whether the object in the admission review of Kubernetes is admitted, all of the hooks have to admit
+    /// it
+    #[cfg(feature = "parse")]
+    fn admit(self, delivery: &Delivery) -> Outcome {
+        let mut permit = match self.acquire() {
+            Ok(permit) => permit,
+            Err(error) => return Outcome::Rejected(error),
+        };
+        let mut response = AdmissionResponse::allow(delivery);
+        for hook in &self.matched_hooks {
+            if self.settings.is_dry_run(hook) {
+                self.settings.skip(hook, delivery);
+                continue;
+            }
+            // Every hook holds a permit of its own
+            let _permit = match permit.take() {
+                Some(permit) => Some(permit),
+                None => match self.acquire() {
+                    Ok(permit) => permit,
+                    Err(error) => return Outcome::Rejected(error),
+                },
+            };
+            if let Err(error) = self.settings.run(hook, slice::from_ref(delivery), 1) {
+                debug!("Denied by {}: {}", hook.label(), &error);
+                response.deny(match error {
+                    Error::HookFailed(message) => message,
+                    error => error.to_string(),
+                });
+                break;
+            }
+        }
+        Outcome::Admission(response)
+    }
+
+    /// Acquire the permit to run the hooks, if the concurrency is limited
+    fn acquire(&self) -> Result<Option<Permit<'_>>, Error> {
+        self.settings.acquire()
+    }
+
+    /// Start running the hooks one after another, each holding a permit of its own (starting with the given one) if
+    /// the concurrency is limited, returns the hooks left once no permit is available
+    fn start<'a>(&'a self, delivery: &Delivery, mut permit: Option<Permit<'a>>) -> &'a [Hook] {
+        for (index, hook) in self.matched_hooks.iter().enumerate() {
+            let _permit = match (permit.take(), &self.settings.concurrency_limiter) {
+                (Some(permit), _) => Some(permit),
+                (None, Some(limiter)) => match limiter.acquire() {
+                    Some(permit) => Some(permit),
+                    None => return &self.matched_hooks[index..],
+                },
+                (None, None) => None,
+            };
+            debug!("Running {}", hook.label());
+            self.settings.start(hook, delivery);
+        }
+        &[]
+    }
+
+    /// Test if the functions of all the hooks are skipped, see `Constructor::dry_run`
+    fn dry_run(&self) -> bool {
+        self.matched_hooks
+            .iter()
+            .all(|hook| self.settings.is_dry_run(hook))
+    }
+
+    /// Test if any of the hooks streams the response, see `Hook::stream_response`
+    fn streams(&self) -> bool {
+        self.matched_hooks.iter().any(|hook| hook.stream_response)
+    }
+
+    /// Validate the delivery with the functions of the hooks (e.g. typed hooks deserializing the payload)
+    fn validate(&self, delivery: &Delivery) -> Result<(), Error> {
+        for hook in &self.matched_hooks {
+            if let Err(error) = hook.func.validate(delivery) {
+                debug!("Delivery failed validation of {}: {}", hook.label(), &error);
+                let error = hook.named_error(error);
+                self.settings.report(&error);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Test if there are no matched hook found
+    pub fn is_empty(&self) -> bool {
+        self.matched_hooks.len() == 0
+    }
+}
+
+/// The main impl clause of Handler
+impl Handler {
+    /// Set the address of the peer sending requests to this handler
+    pub fn with_remote_addr(mut self, remote_addr: SocketAddr) -> Self {
+        self.remote_addr = Some(remote_addr);
+        self
+    }
+
+    /// Set the identity of the client verified by the TLS layer (e.g. the subject of its certificate), see
+    /// `Constructor::allowed_clients`
+    pub fn with_client_identity(mut self, identity: &str) -> Self {
+        self.client_identity = Some(identity.to_string());
+        self
+    }
+
+    /// Set the query string of the requests passed to `Handler::handle` (e.g. `provider=github`), see
+    /// `Constructor::provider_query`
+    pub fn with_query(mut self, query: &str) -> Self {
+        self.query = Some(query.to_string());
+        self
+    }
+
+    /// Statistics of the deliveries handled by this handler and the others created from the same constructor
+    pub fn stats(&self) -> Stats {
+        self.settings.stats.snapshot()
+    }
+
+    /// Handle a request given by its headers and body, for frameworks without built-in support
+    ///
+    /// Header names are case insensitive. Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, Delivery, Handler, Hook};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+    /// let handler = Handler::from(&cons);
+    ///
+    /// let response = handler.handle(vec![("X-GitHub-Event", "push")], b"{}");
+    /// assert_eq!(response.status(), 200);
+    /// ```
+    pub fn handle<K: AsRef<str>, V: AsRef<str>>(
+        &self,
+        headers: impl IntoIterator<Item = (K, V)>,
+        body: impl AsRef<[u8]>,
+    ) -> Response {
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| (name.as_ref().to_lowercase(), value.as_ref().to_string()))
+            .collect::<HashMap<String, String>>();
+        self.handle_from(
+            self.remote_addr,
+            self.query.as_deref(),
+            Headers::from(headers),
+            body.as_ref(),
+        )
+    }
+
+    /// Handle a request sent from the address with the query string, header names must be lower cased
+    fn handle_from(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        query: Option<&str>,
+        headers: Headers,
+        body: &[u8],
+    ) -> Response {
+        let (timestamp, start) = (SystemTime::now(), Instant::now());
+        if self.settings.access_log.is_some() {
+            access_log::begin();
+        }
+        context::begin();
+        let (outcome, delivery) = self.process(remote_addr, query, headers, body);
+        self.respond(remote_addr, timestamp, start, outcome, delivery)
+    }
+
+    /// Answer a `GET` or `HEAD` request with the landing page, if enabled with `Constructor::landing_page`
+    pub fn landing(&self) -> Option<Response> {
+        self.landing_from(self.remote_addr)
+    }
+
+    /// Run the readiness checks, returns the names of the unreachable downstream targets (see
+    /// `Constructor::readiness_check`)
+    pub fn unreachable_targets(&self) -> Vec<String> {
+        self.settings
+            .readiness_checks
+            .iter()
+            .filter_map(|(name, check)| match check.check() {
+                Ok(()) => None,
+                Err(error) => {
+                    warn!("Downstream target '{}' is unreachable: {}", name, error);
+                    Some(name.clone())
+                }
+            })
+            .collect()
+    }
+
+    /// Answer a `GET` or `HEAD` request sent from the address with the landing page, if enabled
+    fn landing_from(&self, remote_addr: Option<SocketAddr>) -> Option<Response> {
+        let service = self.settings.landing_page.as_ref()?;
+        let (timestamp, start) = (SystemTime::now(), Instant::now());
+        if self.settings.access_log.is_some() {
+            access_log::begin();
+        }
+        let unreachable = self.unreachable_targets();
+        let outcome = if unreachable.is_empty() {
+            Outcome::Landing(landing::page(service, &unreachable))
+        } else {
+            Outcome::Unready(landing::page(service, &unreachable))
+        };
+        Some(self.respond(remote_addr, timestamp, start, outcome, None))
+    }
+
+    /// Respond to a request from the address whose body couldn't be read (e.g. too slow or too large), it started at
+    /// the given time
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    fn refuse(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        timestamp: SystemTime,
+        start: Instant,
+        error: Error,
+    ) -> Response {
+        self.settings.report(&error);
+        self.respond(
+            remote_addr,
+            timestamp,
+            start,
+            Outcome::Rejected(error),
+            None,
+        )
+    }
+
+    /// Log and count the outcome of a request received at the given time, and build the response
+    fn respond(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        timestamp: SystemTime,
+        start: Instant,
+        outcome: Outcome,
+        delivery: Option<Delivery>,
+    ) -> Response {
+        debug!("Outcome: {:?}", &outcome);
+        let hold_until = match outcome {
+            Outcome::Landing(_) | Outcome::Unready(_) => None,
+            _ => self
+                .settings
+                .min_response_time
+                .map(|min_response_time| start + min_response_time),
+        };
+        let request_id = match &delivery {
+            Some(delivery) => delivery.request_id.clone(),
+            None if self.settings.request_id => Some(request_id::generate()),
+            None => None,
+        };
+        if let Some(callback) = &self.settings.access_log {
+            callback(&AccessLogEntry {
+                timestamp,
+                remote_addr: delivery
+                    .as_ref()
+                    .and_then(|delivery| delivery.remote_addr)
+                    .or_else(|| remote_addr.map(|addr| addr.ip())),
+                provider: delivery
+                    .as_ref()
+                    .map(|delivery| format!("{:?}", delivery.delivery_type)),
+                event: delivery.as_ref().map(|delivery| delivery.event.clone()),
+                delivery_id: delivery.as_ref().and_then(|delivery| delivery.id.clone()),
+                request_id: request_id.clone(),
+                status: outcome.status(),
+                duration: start.elapsed(),
+                hooks: access_log::finish(),
+            });
+        }
+        if let Some(delivery) = &delivery {
+            self.settings.stats.record_delivery(delivery);
+        }
+        if let Outcome::AuthFailed = outcome {
+            self.settings.stats.record_auth_failure();
+        }
+        let mut headers = Vec::new();
+        if let Some(request_id) = request_id {
+            headers.push(("X-Request-Id".to_string(), request_id));
+        }
+        for (name, value) in &self.settings.response_headers {
+            if let Some(value) = value(delivery.as_ref()) {
+                headers.push((name.clone(), value));
+            }
+        }
+        let response = Response {
+            outcome,
+            delivery,
+            fragments: context::finish(),
+            headers,
+            stream: context::take_stream(),
+            hold_until,
+        };
+        if let Some(callback) = &self.settings.outcome_callback {
+            callback(&response);
+        }
+        response
+    }
+
+    /// Run the request through the pipeline
+    fn process(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        query: Option<&str>,
+        headers: Headers,
+        body: &[u8],
+    ) -> (Outcome, Option<Delivery>) {
+        let request_body = match String::from_utf8(body.to_vec()) {
+            Ok(request_body) => request_body,
+            // Files in multipart bodies may be binary, they're parsed from the raw body later
+            #[cfg(feature = "multipart")]
+            Err(_) if multipart::is_multipart(&headers) => {
+                String::from_utf8_lossy(body).into_owned()
+            }
+            Err(_) => {
+                let error = Error::InvalidDelivery("Invalid payload");
+                return (Outcome::InvalidPayload(error), None);
+            }
+        };
+        // The body is needed to identify some deliveries (e.g. from Gerrit and Jenkins)
+        let normalization = &self.settings.event_normalization;
+        let mut delivery = match Delivery::with_normalization(
+            headers,
+            Some(request_body),
+            normalization,
+            &self.settings.ambiguity_policy,
+            &self.settings.docker_hub_ids,
+            self.settings.raw_payload,
+            self.settings.provider_hint(query).as_ref(),
+        ) {
+            Ok(delivery_inner) => delivery_inner,
+            Err(err_msg) => {
+                return (
+                    Outcome::InvalidPayload(Error::InvalidDelivery(err_msg)),
+                    None,
+                )
+            }
+        };
+        self.identify(&mut delivery, remote_addr);
+        delivery.query = query.map(String::from);
+        if body.is_empty()
+            && self.settings.empty_body_policy(delivery.delivery_type) == EmptyBodyPolicy::Reject
+        {
+            debug!("Empty body of {:?} refused", delivery.delivery_type);
+            let error = Error::InvalidDelivery("Empty body");
+            return (Outcome::InvalidPayload(error), Some(delivery));
+        }
+        #[cfg(feature = "multipart")]
+        self.settings.load_parts(&mut delivery, body);
+        self.settings.transform(&mut delivery);
+        #[cfg(feature = "otel")]
+        let context = otel::start_delivery(&delivery);
+        #[cfg(feature = "otel")]
+        let guard = context.clone().attach();
+        let outcome = self.dispatch(&delivery);
+        #[cfg(feature = "otel")]
+        {
+            drop(guard);
+            otel::end_delivery(&context, &outcome);
+        }
+        (outcome, Some(delivery))
+    }
+
+    /// Fill in who sent the delivery from the address: the address of the client (see
+    /// `Constructor::trust_proxies`), its identity and the ID of the request
+    fn identify(&self, delivery: &mut Delivery, remote_addr: Option<SocketAddr>) {
+        if let Some(addr) = remote_addr {
+            let (client, scheme) =
+                proxy::resolve(addr.ip(), &delivery.headers, &self.settings.trusted_proxies);
+            delivery.remote_addr = Some(client);
+            delivery.scheme = scheme;
+        }
+        delivery.client_identity = self.client_identity.clone();
+        if self.settings.request_id {
+            delivery.request_id = Some(request_id::from_headers(&delivery.headers));
+        }
+    }
+
+    /// Refuse the request from the address before its body is read if it fails to authenticate by its headers
+    /// (lower cased) alone, which is possible for providers authenticating with a token (GitLab and Gitee)
+    ///
+    /// Returns `None` if the body is needed to decide, e.g. hooks have custom authenticators or deliveries are
+    /// transformed.
+    #[cfg(any(feature = "hyper-support", feature = "sync-server"))]
+    fn precheck(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        headers: impl HeaderAccessor,
+    ) -> Option<Response> {
+        let settings = &self.settings;
+        if settings.allowed_clients.is_some()
+            || settings.authenticator.is_some()
+            || !settings.transformers.is_empty()
+        {
+            return None;
+        }
+        // Only copy the headers of deliveries which may be authenticated by their headers alone
+        if headers.header("x-gitlab-event").is_none() && headers.header("x-gitee-event").is_none() {
+            return None;
+        }
+        let mut delivery = Delivery::with_normalization(
+            headers.into_headers(),
+            None,
+            &settings.event_normalization,
+            &settings.ambiguity_policy,
+            &settings.docker_hub_ids,
+            false,
+            None,
+        )
+        .ok()?;
+        if !matches!(
+            delivery.delivery_type,
+            DeliveryType::GitLab | DeliveryType::Gitee
+        ) {
+            return None;
+        }
+        let mut hooks = self.registered_hooks(&delivery, false);
+        if hooks.is_empty() {
+            hooks = self.registered_hooks(&delivery, true);
+        }
+        let mut errors = Vec::new();
+        for hook in &hooks {
+            if hook.authenticator.is_some() {
+                return None;
+            }
+            match hook.verify(&delivery) {
+                Err(Error::CryptoUnavailable) | Ok(()) => return None,
+                Err(error) => errors.push(error),
+            }
+        }
+        if errors.is_empty() {
+            return None;
+        }
+        debug!("Delivery failed to authenticate before reading its body");
+        errors.iter().for_each(|error| settings.report(error));
+        let (timestamp, start) = (SystemTime::now(), Instant::now());
+        if settings.access_log.is_some() {
+            access_log::begin();
+        }
+        self.identify(&mut delivery, remote_addr);
+        Some(self.respond(
+            remote_addr,
+            timestamp,
+            start,
+            Outcome::AuthFailed,
+            Some(delivery),
+        ))
+    }
+
+    /// Run the delivery through the checks and the matched hooks
+    ///
+    /// Deliveries are rate limited globally and by address before anything else, so that floods are turned away
+    /// before their signatures are verified. Limits by repository apply to authenticated deliveries only.
+    fn dispatch(&self, delivery: &Delivery) -> Outcome {
+        let scopes = [RateLimitScope::Global, RateLimitScope::RemoteAddr];
+        if let Err(error) = self
+            .settings
+            .throttle(delivery, &scopes)
+            .and_then(|()| self.settings.check_user_agent(delivery))
+            .and_then(|()| self.settings.check_client(delivery))
+            .and_then(|()| self.settings.check_strict_github(delivery))
+            .and_then(|()| self.settings.check_enterprise_host(delivery))
+        {
+            return Outcome::Rejected(error);
+        }
+        if let (DeliveryType::Discord, "ping") = (&delivery.delivery_type, delivery.event.as_str())
+        {
+            return self.pong(delivery);
+        }
+        #[cfg(feature = "parse")]
+        {
+            if delivery.delivery_type == DeliveryType::Kubernetes && !self.settings.admission_review
+            {
+                let error = Error::InvalidDelivery("Admission review is not enabled");
+                return Outcome::InvalidPayload(error);
+            }
+        }
+        if let DeliveryType::Unknown = delivery.delivery_type {
+            info!(
+                "Accepted delivery of an unknown sender with headers: {:?}",
+                redact::Headers(&delivery.headers)
+            );
+        }
+        let executor = match self.get_hooks(delivery) {
+            Ok(executor) => executor,
+            Err(Outcome::NoMatch) if delivery.delivery_type == DeliveryType::Unknown => {
+                // Kept to configure the sender later
+                self.settings.record(delivery);
+                return Outcome::NoMatch;
+            }
+            // Objects without matched hook are admitted
+            Err(Outcome::NoMatch) if delivery.delivery_type == DeliveryType::Kubernetes => {
+                Executor {
+                    matched_hooks: Vec::new(),
+                    unverified: 0,
+                    settings: self.settings.clone(),
+                }
+            }
+            Err(outcome) => return outcome,
+        };
+        debug!("Received delivery: {:#?}", delivery);
+        if self.settings.log_payloads {
+            if let Some(request_body) = &delivery.request_body {
+                debug!("Request body: {}", redact::Truncated(request_body));
+            }
+        }
+        self.settings.record(delivery);
+        if let Err(error) = self
+            .settings
+            .throttle(delivery, &[RateLimitScope::Repository])
+            .and_then(|()| self.settings.check_freshness(delivery))
+            .and_then(|()| self.settings.validate(delivery))
+            .and_then(|()| executor.validate(delivery))
+            .and_then(|()| self.settings.publish(delivery))
+        {
+            return Outcome::Rejected(error);
+        }
+        let maintenance = &self.settings.maintenance;
+        if maintenance.is_enabled() {
+            debug!("Skipped hooks during maintenance");
+            return Outcome::Maintenance {
+                status: maintenance.status(),
+            };
+        }
+        let normalization = &self.settings.event_normalization;
+        let events = self.settings.events(delivery);
+        if let Some(response) = self
+            .settings
+            .overrides
+            .find(&events, |event| normalization.apply(event))
+        {
+            debug!("Response to '{}' event overridden", &delivery.event);
+            return Outcome::Overridden(response);
+        }
+        #[cfg(feature = "parse")]
+        {
+            if let DeliveryType::Kubernetes = delivery.delivery_type {
+                return executor.admit(delivery);
+            }
+        }
+        let unverified = executor.unverified;
+        let dry_run = executor.dry_run();
+        let result = if executor.streams() {
+            executor.stream(delivery.clone()).map(|(hooks, stream)| {
+                context::attach_stream(stream);
+                Started::Run(hooks)
+            })
+        } else {
+            executor.run_or_queue(delivery.clone())
+        };
+        match result {
+            Ok(Started::Run(hooks)) if dry_run => Outcome::DryRun { hooks },
+            Ok(Started::Run(hooks)) => Outcome::Handled { hooks, unverified },
+            Ok(Started::Queued(hooks)) => Outcome::Queued { hooks },
+            Err(error) => Outcome::Rejected(error),
+        }
+    }
+
+    /// Answer the PING of Discord, it has to be authenticated by any of the registered hooks
+    fn pong(&self, delivery: &Delivery) -> Outcome {
+        let registry = self.registry();
+        if registry.is_empty() {
+            return Outcome::NoMatch;
+        }
+        let mut hooks = registry
+            .values()
+            .flatten()
+            .filter(|hook| hook.accepts(DeliveryType::Discord));
+        let authenticated = hooks.any(|hook| match hook.verify(delivery) {
+            Ok(()) => true,
+            Err(Error::CryptoUnavailable) => self.settings.allow_unverified,
+            Err(_) => false,
+        });
+        if authenticated {
+            Outcome::Pong
+        } else {
+            Outcome::AuthFailed
+        }
+    }
+
+    /// Registered hooks, or the hooks set with `ReloadHandle::reload`
+    fn registry(&self) -> Arc<HookRegistry> {
+        self.settings
+            .reload
+            .registry()
+            .unwrap_or_else(|| self.hooks.clone())
+    }
+
+    /// Authenticate the delivery with the hooks registered for its event, then keep the hooks whose conditions hold,
+    /// along with whether they actually verified it
+    ///
+    /// This is the only place signatures are verified, before any condition on the payload is evaluated. If no hook
+    /// is left, fails with `Outcome::NoMatch`, or `Outcome::AuthFailed` if the delivery failed to authenticate.
+    /// The fallback hook is tried only if no other hook matched.
+    fn match_hooks(&self, delivery: &Delivery) -> Result<Vec<(Hook, bool)>, Outcome> {
+        let mut authenticated = false;
+        for fallback in [false, true] {
+            let hooks = self.registered_hooks(delivery, fallback);
+            if hooks.is_empty() {
+                continue;
+            }
+            if !authenticated && !self.settings.authenticate(delivery) {
+                return Err(Outcome::AuthFailed);
+            }
+            let hooks: Vec<(Hook, bool)> = hooks
+                .into_iter()
+                .filter_map(|hook| {
+                    let verified = self.settings.verify(&hook, delivery)?;
+                    Some((hook, verified))
+                })
+                .collect();
+            if hooks.is_empty() {
+                return Err(Outcome::AuthFailed);
+            }
+            authenticated = true;
+            let matched: Vec<(Hook, bool)> = hooks
+                .into_iter()
+                .filter(|(hook, _)| hook.matches(delivery))
+                .collect();
+            if !matched.is_empty() {
+                return Ok(matched);
+            }
+        }
+        Err(Outcome::NoMatch)
+    }
+
+    /// Enabled hooks registered for the event of the delivery (or its aliases) accepting its provider, or the
+    /// enabled fallback hook
+    fn registered_hooks(&self, delivery: &Delivery, fallback: bool) -> Vec<Hook> {
+        let registry = self.registry();
+        let accepted = |hook: &&Hook| hook.accepts(delivery.delivery_type) && self.is_enabled(hook);
+        // Deliveries of unknown senders only match the hook registered for them
+        if let DeliveryType::Unknown = delivery.delivery_type {
+            return registry
+                .get(&delivery.event)
+                .into_iter()
+                .flatten()
+                .filter(|hook| !fallback && !hook.has_pattern() && !hook.fallback)
+                .filter(accepted)
+                .cloned()
+                .collect();
+        }
+        if fallback {
+            return registry
+                .get(FALLBACK_KEY)
+                .into_iter()
+                .flatten()
+                .filter(|hook| hook.fallback)
+                .filter(accepted)
+                .cloned()
+                .collect();
+        }
+        let events = self.settings.events(delivery);
+        let hooks = events
+            .iter()
+            .filter_map(|event| registry.get(event))
+            .flatten()
+            .filter(|hook| !hook.has_pattern() && !hook.fallback);
+        #[cfg(feature = "regex-match")]
+        let hooks = hooks.chain(
+            registry
+                .values()
+                .flatten()
+                .filter(|hook| !hook.fallback && hook.matches_event(&events)),
+        );
+        hooks.filter(accepted).cloned().collect()
+    }
+
+    /// Test if the group of the hook, if any, is enabled
+    fn is_enabled(&self, hook: &Hook) -> bool {
+        match &hook.group {
+            Some(group) if !self.settings.groups.is_enabled(group) => {
+                debug!("Skipped hook of disabled group '{}'", group);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn get_hooks(&self, delivery: &Delivery) -> Result<Executor, Outcome> {
+        debug!("Finding matched hooks for '{}' event", &delivery.event);
+        let matched = self.match_hooks(delivery)?;
+        debug!("{} matched hook(s) found", matched.len());
+        Ok(Executor {
+            unverified: matched.iter().filter(|(_, verified)| !verified).count(),
+            matched_hooks: matched.into_iter().map(|(hook, _)| hook).collect(),
+            settings: self.settings.clone(),
+        })
+    }
+}
+
+/// Implement `From<&Constructor>` trait for `Handler`
+/// As currently we don't have Generic Associate Types, I can only clone the registry.
+impl From<&Constructor> for Handler {
+    /// Create a handler object from constructor
+    fn from(constructor: &Constructor) -> Self {
+        debug!("Handler constructed");
+        Self {
+            hooks: Arc::new(constructor.hooks.clone()),
+            settings: constructor.settings.clone(),
+            remote_addr: None,
+            client_identity: None,
+            query: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "parse")]
+    use crate::filter;
+    use crate::overrides::ResponseOverride;
+    use crate::store::MemoryStore;
+    #[cfg(feature = "parse")]
+    use crate::transform::RenameEvent;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+
+    /// Test retrying a failing hook until the retries are exhausted
+    #[test]
+    fn retries_exhausted() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut cons = Constructor::new();
+        {
+            let errors = errors.clone();
+            cons.on_error(move |error: &Error| errors.lock().unwrap().push(error.clone()));
+        }
+        let hook = {
+            let runs = runs.clone();
+            Hook::new("push", None, move |_: &Delivery| -> Result<(), &str> {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Err("Nope")
+            })
+        }
+        .retries(2)
+        .backoff(Duration::from_millis(1));
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        let delivery = Delivery::new(headers, None).unwrap();
+        Handler::from(&cons).settings.execute(&hook, &delivery, 1);
+        let started = Instant::now();
+        while errors.lock().unwrap().is_empty() && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![Error::RetriesExhausted {
+                attempts: 3,
+                error: "Nope".to_string()
+            }]
+        );
+    }
+
+    /// Test waiting for a permit before running hooks in the background
+    #[test]
+    fn background_runs_limited() {
+        let mut cons = Constructor::new();
+        cons.max_concurrent_hooks(1);
+        let limiter = cons.settings.concurrency_limiter.clone().unwrap();
+        let running = Arc::new(Mutex::new(Vec::new()));
+        {
+            let (limiter, running) = (limiter.clone(), running.clone());
+            cons.register(
+                Hook::new("push", None, move |_: &Delivery| {
+                    running.lock().unwrap().push(limiter.running())
+                })
+                .delay(Duration::from_millis(10)),
+            );
+        }
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(response.status(), 200);
+        let permit = limiter.acquire().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(running.lock().unwrap().is_empty());
+        drop(permit);
+        let started = Instant::now();
+        while running.lock().unwrap().is_empty() && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*running.lock().unwrap(), vec![1]);
+    }
+
+    /// Test queueing deliveries exceeding the concurrency limit to run in the background
+    #[test]
+    fn queued_deliveries() {
+        let mut cons = Constructor::new();
+        cons.max_concurrent_hooks(1);
+        cons.concurrency_policy(ConcurrencyPolicy::Queue(1));
+        let limiter = cons.settings.concurrency_limiter.clone().unwrap();
+        let runs = Arc::new(AtomicUsize::new(0));
+        {
+            let runs = runs.clone();
+            cons.register(Hook::new("push", None, move |_: &Delivery| {
+                runs.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        let handler = Handler::from(&cons);
+        let request = || handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        let permit = limiter.acquire().unwrap();
+        let response = request();
+        assert_eq!(response.outcome, Outcome::Queued { hooks: 1 });
+        assert_eq!(response.status(), 202);
+        assert_eq!(limiter.waiting(), 1);
+        // Queue is full
+        assert_eq!(request().outcome, Outcome::Rejected(Error::Overloaded));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        drop(permit);
+        let started = Instant::now();
+        while runs.load(Ordering::SeqCst) == 0 && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(limiter.waiting(), 0);
+    }
+
+    /// Test running scheduled hooks alongside slow ones
+    #[test]
+    fn background_runs_parallel() {
+        let (release, released) = mpsc::channel::<()>();
+        let released = Arc::new(Mutex::new(released));
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::new("push", None, move |_: &Delivery| {
+                let _ = released.lock().unwrap().recv();
+            })
+            .delay(Duration::from_millis(1)),
+        );
+        cons.register(
+            Hook::new("issues", None, move |_: &Delivery| {
+                sender.lock().unwrap().send(()).unwrap();
+            })
+            .delay(Duration::from_millis(50)),
+        );
+        let handler = Handler::from(&cons);
+        for event in &["push", "issues"] {
+            handler.handle(vec![("X-GitHub-Event", *event)], b"{}");
+        }
+        // The slow hook is still running
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(()));
+        release.send(()).unwrap();
+    }
+
+    /// Test holding a permit per hook run, for deliveries matching several hooks
+    #[test]
+    fn permits_per_hook() {
+        let mut cons = Constructor::new();
+        cons.max_concurrent_hooks(1);
+        cons.concurrency_policy(ConcurrencyPolicy::Queue(2));
+        let limiter = cons.settings.concurrency_limiter.clone().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        for event in &["push", "*"] {
+            let (limiter, sender) = (limiter.clone(), Arc::new(Mutex::new(sender.clone())));
+            cons.register(Hook::new(event, None, move |_: &Delivery| {
+                let run = (limiter.running(), limiter.waiting());
+                sender.lock().unwrap().send(run).unwrap();
+            }));
+        }
+        let handler = Handler::from(&cons);
+        let request = || handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            request().outcome,
+            Outcome::Handled {
+                hooks: 2,
+                unverified: 2
+            }
+        );
+        assert_eq!(receiver.recv_timeout(timeout), Ok((1, 0)));
+        assert_eq!(receiver.recv_timeout(timeout), Ok((1, 0)));
+        let permit = limiter.acquire().unwrap();
+        assert_eq!(request().outcome, Outcome::Queued { hooks: 2 });
+        assert_eq!(limiter.waiting(), 2);
+        // Queue is full
+        assert_eq!(request().outcome, Outcome::Rejected(Error::Overloaded));
+        drop(permit);
+        let mut runs = vec![
+            receiver.recv_timeout(timeout).unwrap(),
+            receiver.recv_timeout(timeout).unwrap(),
+        ];
+        runs.sort();
+        assert_eq!(runs, [(1, 0), (1, 1)]);
+    }
+
+    /// Test tracking the health of named hooks through retries
+    #[test]
+    fn hook_health() {
+        let cons = Constructor::new();
+        let stats = cons.stats_handle();
+        let hook = Hook::new("push", None, |_: &Delivery| Err("Nope"))
+            .named("deploy")
+            .retries(1)
+            .backoff(Duration::from_millis(1));
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        let delivery = Delivery::new(headers, None).unwrap();
+        assert_eq!(stats.hook_health("deploy"), None);
+        Handler::from(&cons).settings.execute(&hook, &delivery, 1);
+        let started = Instant::now();
+        while stats.hook_health("deploy").unwrap().dead_lettered == 0
+            && started.elapsed() < Duration::from_secs(5)
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let health = stats.hook_health("deploy").unwrap();
+        assert_eq!(health.dead_lettered, 1);
+        assert_eq!(health.consecutive_failures, 2);
+        assert_eq!(health.pending_retries, 0);
+        assert_eq!(health.last_success, None);
+        let hook = Hook::new("push", None, |_: &Delivery| ()).named("deploy");
+        Handler::from(&cons).settings.execute(&hook, &delivery, 1);
+        let health = stats.hook_health("deploy").unwrap();
+        assert_eq!(health.consecutive_failures, 0);
+        assert!(health.last_success.is_some());
+        assert_eq!(stats.snapshot().per_hook_health["deploy"], health);
+    }
+
+    /// Test rejecting stale deliveries
+    #[test]
+    fn stale_delivery() {
+        let mut cons = Constructor::new();
+        cons.max_delivery_age(Duration::from_secs(300));
+        let settings = Handler::from(&cons).settings;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        let mut delivery = Delivery::new(headers, None).unwrap();
+        assert!(settings.check_freshness(&delivery).is_ok());
+        delivery.timestamp = Some(SystemTime::now() - Duration::from_secs(60));
+        assert!(settings.check_freshness(&delivery).is_ok());
+        delivery.timestamp = Some(SystemTime::now() - Duration::from_secs(600));
+        assert!(settings.check_freshness(&delivery).is_err());
+        delivery.timestamp = Some(SystemTime::now() + Duration::from_secs(600));
+        assert!(settings.check_freshness(&delivery).is_err());
+    }
+
+    /// Test passing the state to hooks
+    #[test]
+    fn hook_with_state() {
+        let hook = Hook::with_state("push", None, |pushes: &AtomicUsize, _: &Delivery| {
+            pushes.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        let delivery = Delivery::new(headers, None).unwrap();
+        let mut cons = Constructor::new();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        {
+            let errors = errors.clone();
+            cons.on_error(move |error: &Error| errors.lock().unwrap().push(error.clone()));
+        }
+        Handler::from(&cons).settings.execute(&hook, &delivery, 1);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![Error::StateUnavailable("core::sync::atomic::AtomicUsize")]
+        );
+        cons.with_state(AtomicUsize::new(0));
+        let settings = Handler::from(&cons).settings;
+        settings.execute(&hook, &delivery, 1);
+        let state = settings.state.unwrap();
+        let pushes = state.downcast_ref::<AtomicUsize>().unwrap();
+        assert_eq!(pushes.load(Ordering::SeqCst), 1);
+    }
+
+    /// Test streaming the fragments added by hooks to the response
+    #[test]
+    fn streamed_response() {
+        let (proceed, gate) = mpsc::channel::<()>();
+        let gate = Arc::new(Mutex::new(gate));
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::with_context("push", None, move |context: &Context, _: &Delivery| {
+                context.respond("Building...");
+                gate.lock().unwrap().recv().unwrap();
+                context.respond("Done");
+            })
+            .stream_response(),
+        );
+        cons.register(Hook::with_context(
+            "*",
+            None,
+            |context: &Context, _: &Delivery| context.respond("Queued"),
+        ));
+        let handler = Handler::from(&cons);
+        // The response is returned before the hooks are run
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "OK");
+        proceed.send(()).unwrap();
+        let mut fragments = response.stream.unwrap().collect::<Vec<_>>();
+        fragments.sort();
+        assert_eq!(fragments, vec!["Building...", "Done", "Queued"]);
+        let response = handler.handle(vec![("X-GitHub-Event", "issues")], "{}");
+        assert!(response.stream.is_none());
+    }
+
+    /// Test passing the context to hooks
+    #[test]
+    fn hook_with_context() {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let attempts_inner = attempts.clone();
+        let mut cons = Constructor::new();
+        cons.with_state(AtomicUsize::new(42));
+        cons.register(Hook::with_context(
+            "*",
+            None,
+            move |context: &Context, delivery: &Delivery| {
+                attempts_inner.lock().unwrap().push(context.attempt());
+                assert_eq!(context.pattern(), "*");
+                assert_eq!(context.received_at(), delivery.received_at);
+                let state = context.state::<AtomicUsize>().unwrap();
+                assert_eq!(state.load(Ordering::SeqCst), 42);
+                context.count("builds", 2);
+                context.respond("Build queued");
+            },
+        ));
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        assert_eq!(response.body(), "OK\nBuild queued");
+        assert_eq!(handler.stats().metrics.get("builds"), Some(&2));
+        let hook = &cons.hooks["*"][0];
+        let delivery = response.delivery.unwrap();
+        handler.settings.execute(hook, &delivery, 3);
+        assert_eq!(*attempts.lock().unwrap(), vec![1, 3]);
+        // Metrics are counted outside of requests too, fragments are dropped
+        assert_eq!(handler.stats().metrics.get("builds"), Some(&4));
+        assert!(context::finish().is_empty());
+    }
+
+    /// Test matching events with regular expressions
+    #[cfg(feature = "regex-match")]
+    #[test]
+    fn regex_hooks() {
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::new("issue-events", None, |_: &Delivery| ())
+                .matching(regex::Regex::new("^(issues|issue_comment)$").unwrap()),
+        );
+        cons.register(Hook::new("issues", None, |_: &Delivery| ()));
+        let handler = Handler::from(&cons);
+        let request = |event: &str| {
+            handler
+                .handle(vec![("X-GitHub-Event", event)], "{}")
+                .outcome
+        };
+        assert_eq!(
+            request("issues"),
+            Outcome::Handled {
+                hooks: 2,
+                unverified: 2
+            }
+        );
+        assert_eq!(
+            request("issue_comment"),
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(request("issue-events"), Outcome::NoMatch);
+        assert_eq!(request("push"), Outcome::NoMatch);
+    }
+
+    /// Test running the fallback hook for unmatched deliveries only
+    #[test]
+    fn fallback_hook() {
+        let unmatched = Arc::new(Mutex::new(Vec::new()));
+        let unmatched_inner = unmatched.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.register(
+            Hook::with_context("*", None, move |context: &Context, delivery: &Delivery| {
+                unmatched_inner.lock().unwrap().push(delivery.event.clone());
+                context.respond("Unexpected event");
+            })
+            .fallback(),
+        );
+        let request = |cons: &Constructor, event: &str| {
+            Handler::from(cons).handle(vec![("X-GitHub-Event", event)], "{}")
+        };
+        assert_eq!(
+            request(&cons, "push").outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let response = request(&cons, "issues");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(response.body(), "OK\nUnexpected event");
+        assert_eq!(*unmatched.lock().unwrap(), vec!["issues".to_string()]);
+        // Hooks for every event match before the fallback hook
+        cons.register(Hook::new("*", None, |_: &Delivery| ()));
+        assert_eq!(
+            request(&cons, "issues").outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(unmatched.lock().unwrap().len(), 1);
+    }
+
+    /// Test publishing authenticated deliveries with sinks
+    #[test]
+    fn sinks() {
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let published_inner = published.clone();
+        let mut cons = Constructor::new();
+        cons.sink(move |delivery: &Delivery| -> Result<(), Error> {
+            published_inner.lock().unwrap().push(delivery.event.clone());
+            Ok(())
+        });
+        cons.register(Hook::new(
+            "push",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        let handler = Handler::from(&cons);
+        let request = |token: &str| {
+            handler.handle(
+                vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", token)],
+                "{}",
+            )
+        };
+        assert_eq!(request("wrong").outcome, Outcome::AuthFailed);
+        assert_eq!(request("secret").status(), 200);
+        assert_eq!(*published.lock().unwrap(), vec!["push".to_string()]);
+        cons.sink(|_: &Delivery| -> Result<(), Error> { Err(Error::Sink("down".to_string())) });
+        let handler = Handler::from(&cons);
+        let response = handler.handle(
+            vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", "secret")],
+            "{}",
+        );
+        assert_eq!(response.status(), 503);
+        assert_eq!(published.lock().unwrap().len(), 2);
+    }
+
+    /// Test holding responses for the minimum response time, without blocking the handler
+    #[test]
+    fn min_response_time() {
+        let mut cons = Constructor::new();
+        cons.min_response_time(Duration::from_secs(60));
+        cons.landing_page("deploy-bot");
+        cons.register(Hook::new(
+            "push",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        let handler = Handler::from(&cons);
+        let start = Instant::now();
+        let held = [
+            handler.handle(
+                vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", "wrong")],
+                "{}",
+            ),
+            handler.handle(
+                vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", "secret")],
+                "{}",
+            ),
+            handler.handle(vec![("Content-Type", "text/plain")], ""),
+        ];
+        assert!(start.elapsed() < Duration::from_secs(60));
+        for response in held.iter() {
+            assert!(response.hold_until.unwrap() >= start + Duration::from_secs(60));
+            assert!(response.hold() > Duration::from_secs(59));
+        }
+        assert_eq!(held[2].status(), 202);
+        let landing = handler.landing().unwrap();
+        assert_eq!(landing.hold_until, None);
+        assert_eq!(landing.hold(), Duration::from_secs(0));
+    }
+
+    /// Test authenticating deliveries before evaluating conditions on their payload
+    #[test]
+    fn authentication_first() {
+        let conditions = Arc::new(AtomicUsize::new(0));
+        let conditions_inner = conditions.clone();
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::new("push", Some("secret".to_string()), |_: &Delivery| ()).when(
+                move |_: &Delivery| {
+                    conditions_inner.fetch_add(1, Ordering::SeqCst);
+                    false
+                },
+            ),
+        );
+        cons.register(Hook::new("issue", None, |_: &Delivery| ()));
+        let handler = Handler::from(&cons);
+        let request = |event: &str, token: &str| {
+            handler
+                .handle(
+                    vec![("X-Gitlab-Event", event), ("X-Gitlab-Token", token)],
+                    "{}",
+                )
+                .outcome
+        };
+        assert_eq!(request("Push", "wrong"), Outcome::AuthFailed);
+        assert_eq!(conditions.load(Ordering::SeqCst), 0);
+        assert_eq!(request("Push", "secret"), Outcome::NoMatch);
+        assert_eq!(conditions.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            request("Issue", "wrong"),
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+    }
+
+    /// Test running hooks with payloads deserialized into their own types
+    #[cfg(feature = "typed")]
+    #[test]
+    fn typed_hooks() {
+        #[derive(serde::Deserialize)]
+        struct Push {
+            after: String,
+        }
+
+        let pushed = Arc::new(Mutex::new(Vec::new()));
+        let pushed_inner = pushed.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::typed(
+            "push",
+            None,
+            move |push: Push, _: &Delivery| {
+                pushed_inner.lock().unwrap().push(push.after);
+            },
+        ));
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], r#"{"after": "c0ffee"}"#);
+        assert_eq!(
+            response.delivery.unwrap().parse_as::<Push>().unwrap().after,
+            "c0ffee"
+        );
+        assert_eq!(*pushed.lock().unwrap(), vec!["c0ffee".to_string()]);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], r#"{"after": 42}"#);
+        assert_eq!(response.status(), 422);
+        assert_eq!(pushed.lock().unwrap().len(), 1);
+    }
+
+    /// Test keeping payloads as raw JSON
+    #[cfg(feature = "raw-payload")]
+    #[test]
+    fn raw_payload() {
+        let mut cons = Constructor::new();
+        cons.raw_payload(true);
+        cons.register(Hook::new("push", None, |delivery: &Delivery| {
+            assert!(delivery.payload.is_none());
+        }));
+        let handler = Handler::from(&cons);
+        let request_body = r#"{"after": "c0ffee", "commits": []}"#;
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], request_body);
+        assert_eq!(response.status(), 200);
+        let mut delivery = response.delivery.unwrap();
+        assert_eq!(delivery.raw_payload.as_ref().unwrap().get(), request_body);
+        #[cfg(feature = "typed")]
+        {
+            #[derive(serde::Deserialize)]
+            struct Push {
+                after: String,
+            }
+            assert_eq!(delivery.parse_as::<Push>().unwrap().after, "c0ffee");
+        }
+        delivery.update_request_body(Some(request_body.to_string()));
+        assert!(delivery.raw_payload.is_none());
+        assert!(delivery.field("/after").is_some());
+    }
+
+    /// Test requests carrying the event headers of several providers
+    #[test]
+    fn ambiguous_providers() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let request = |cons: &Constructor, headers: Vec<(&str, &str)>| {
+            let response = Handler::from(cons).handle(headers, "{}");
+            match response.delivery {
+                Some(delivery) => Ok(delivery.delivery_type),
+                None => Err(response.outcome),
+            }
+        };
+        let github_and_gitlab = vec![("X-GitHub-Event", "push"), ("X-Gitlab-Event", "Push")];
+        let forgejo = vec![
+            ("X-Forgejo-Event", "push"),
+            ("X-Gitea-Event", "push"),
+            ("X-GitHub-Event", "push"),
+        ];
+        let gitea_and_gitlab = vec![("X-Gitea-Event", "push"), ("X-Gitlab-Event", "Push")];
+        assert_eq!(
+            request(&cons, github_and_gitlab.clone()),
+            Ok(DeliveryType::GitHub)
+        );
+        cons.ambiguity_policy(AmbiguityPolicy::Prefer(vec![
+            DeliveryType::Gitee,
+            DeliveryType::GitLab,
+        ]));
+        assert_eq!(
+            request(&cons, github_and_gitlab.clone()),
+            Ok(DeliveryType::GitLab)
+        );
+        assert_eq!(request(&cons, forgejo.clone()), Ok(DeliveryType::Forgejo));
+        cons.ambiguity_policy(AmbiguityPolicy::Prefer(vec![DeliveryType::Linear]));
+        assert_eq!(
+            request(&cons, github_and_gitlab.clone()),
+            Ok(DeliveryType::GitHub)
+        );
+        cons.ambiguity_policy(AmbiguityPolicy::Reject);
+        let rejected = Err(Outcome::InvalidPayload(Error::InvalidDelivery(
+            "Ambiguous delivery type",
+        )));
+        assert_eq!(request(&cons, github_and_gitlab), rejected);
+        assert_eq!(request(&cons, gitea_and_gitlab), rejected);
+        assert_eq!(request(&cons, forgejo), Ok(DeliveryType::Forgejo));
+        assert_eq!(
+            request(&cons, vec![("X-Gitlab-Event", "Push")]),
+            Ok(DeliveryType::GitLab)
+        );
+    }
+
+    /// Test matching system hooks of GitLab by the type in their payload
+    #[cfg(feature = "parse")]
+    #[test]
+    fn gitlab_system_hooks() {
+        let runs: Arc<Mutex<Vec<&str>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut cons = Constructor::new();
+        for event in &["project_create", "merge_request", "system_hook"] {
+            let runs = runs.clone();
+            cons.register(Hook::new(event, None, move |_: &Delivery| {
+                runs.lock().unwrap().push(event)
+            }));
+        }
+        let handler = Handler::from(&cons);
+        let request = |request_body: &str| {
+            runs.lock().unwrap().clear();
+            let headers = vec![("X-Gitlab-Event", "System Hook")];
+            let delivery = handler.handle(headers, request_body).delivery.unwrap();
+            let mut runs = runs.lock().unwrap().clone();
+            runs.sort();
+            (delivery.event, runs)
+        };
+        assert_eq!(
+            request(r#"{"event_name": "project_create", "name": "rifling"}"#),
+            (
+                "project_create".to_string(),
+                vec!["project_create", "system_hook"]
+            )
+        );
+        assert_eq!(
+            request(r#"{"object_kind": "merge_request"}"#),
+            (
+                "merge_request".to_string(),
+                vec!["merge_request", "system_hook"]
+            )
+        );
+        assert_eq!(
+            request(r#"{"event_name": "user_add_to_team"}"#),
+            ("user_add_to_team".to_string(), vec!["system_hook"])
+        );
+        assert_eq!(
+            request("{}"),
+            ("system_hook".to_string(), vec!["system_hook"])
+        );
+    }
+
+    /// Test handling deliveries with an empty body
+    #[test]
+    fn empty_bodies() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("*", None, move |_: &Delivery| {
+            runs_inner.fetch_add(1, Ordering::SeqCst);
+        }));
+        let system_hook = vec![
+            ("X-Gitlab-Event", "System Hook"),
+            ("Content-Type", "application/json"),
+        ];
+        let ping = vec![
+            ("X-GitHub-Event", "ping"),
+            ("Content-Type", "application/json"),
+        ];
+        let response = Handler::from(&cons).handle(system_hook.clone(), "");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert!(response.delivery.unwrap().unparsed_payload.is_none());
+        cons.empty_body(EmptyBodyPolicy::Reject);
+        cons.empty_body_of(DeliveryType::GitLab, EmptyBodyPolicy::Accept);
+        let handler = Handler::from(&cons);
+        assert_eq!(handler.handle(system_hook, "").status(), 200);
+        let response = handler.handle(ping.clone(), "");
+        assert_eq!(
+            response.outcome,
+            Outcome::InvalidPayload(Error::InvalidDelivery("Empty body"))
+        );
+        assert_eq!(response.delivery.unwrap().event, "ping");
+        assert_eq!(handler.handle(ping, "{}").status(), 200);
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    /// Test identifying deliveries from Docker Hub by their NewRelic ID or their payload
+    #[test]
+    fn docker_hub() {
+        let mut cons = Constructor::new();
+        let identify = |cons: &Constructor, newrelic_id: &str, body: &str| {
+            let response = Handler::from(cons).handle(vec![("X-Newrelic-Id", newrelic_id)], body);
+            response
+                .delivery
+                .map(|delivery| (delivery.delivery_type, delivery.event))
+        };
+        let docker_push = Some((DeliveryType::DockerHub, "docker_push".to_string()));
+        assert_eq!(identify(&cons, "UQUFVFJUGwUJVlhaBgY=", "{}"), docker_push);
+        assert_eq!(identify(&cons, "VQYGUlNbGwIFUlNU", "{}"), None);
+        #[cfg(feature = "parse")]
+        assert_eq!(
+            identify(
+                &cons,
+                "VQYGUlNbGwIFUlNU",
+                r#"{"push_data": {"tag": "latest"}, "callback_url": "https://registry.hub.docker.com/"}"#
+            ),
+            docker_push
+        );
+        cons.docker_hub_id("VQYGUlNbGwIFUlNU");
+        assert_eq!(identify(&cons, "VQYGUlNbGwIFUlNU", "{}"), docker_push);
+    }
+
+    /// Test accepting and recording the deliveries of unknown senders
+    #[test]
+    fn unknown_senders() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let store = MemoryStore::new(8);
+        let mut cons = Constructor::new();
+        cons.store(store.clone());
+        cons.register(Hook::new("*", None, |_: &Delivery| {
+            Err("Wildcard hook run")
+        }));
+        let headers = vec![("User-Agent", "Acme-Hookshot/1.0")];
+        let response = Handler::from(&cons).handle(headers.clone(), "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::InvalidPayload(Error::InvalidDelivery("Could not determine delivery type"))
+        );
+        cons.accept_unknown(true);
+        let response = Handler::from(&cons).handle(headers.clone(), "{}");
+        assert_eq!(response.outcome, Outcome::NoMatch);
+        let records = store.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].delivery.delivery_type, DeliveryType::Unknown);
+        assert_eq!(
+            records[0].delivery.header("user-agent"),
+            Some("Acme-Hookshot/1.0")
+        );
+        cons.register(Hook::new("unknown", None, move |_: &Delivery| {
+            runs_inner.fetch_add(1, Ordering::SeqCst);
+        }));
+        let response = Handler::from(&cons).handle(headers, "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(store.records().len(), 2);
+    }
+
+    /// Test assuming the provider of requests it can't be determined from
+    #[test]
+    fn provider_hints() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push_hook", None, |_: &Delivery| ()));
+        let request = |cons: &Constructor, query: &str, headers: Vec<(&str, &str)>| {
+            let response = Handler::from(cons).with_query(query).handle(headers, "{}");
+            match response.delivery {
+                Some(delivery) => Ok((delivery.delivery_type, delivery.event, response.outcome)),
+                None => Err(response.outcome),
+            }
+        };
+        let token = vec![("X-Gitlab-Token", "secret")];
+        let undetermined = Err(Outcome::InvalidPayload(Error::InvalidDelivery(
+            "Could not determine delivery type",
+        )));
+        assert_eq!(request(&cons, "", token.clone()), undetermined);
+        cons.provider_hint(DeliveryType::GitLab);
+        assert_eq!(
+            request(&cons, "provider=github&event=push", token.clone()),
+            Ok((
+                DeliveryType::GitLab,
+                "unknown".to_string(),
+                Outcome::NoMatch
+            ))
+        );
+        assert_eq!(
+            request(&cons, "", vec![("X-GitHub-Event", "push")]),
+            Ok((DeliveryType::GitHub, "push".to_string(), Outcome::NoMatch))
+        );
+        cons.provider_query(true);
+        let handled = Outcome::Handled {
+            hooks: 1,
+            unverified: 1,
+        };
+        assert_eq!(
+            request(&cons, "event=Push+Hook", token.clone()),
+            Ok((
+                DeliveryType::GitLab,
+                "push_hook".to_string(),
+                handled.clone()
+            ))
+        );
+        assert_eq!(
+            request(&cons, "provider=GitHub&event=push%20hook", token.clone()),
+            Ok((DeliveryType::GitHub, "push_hook".to_string(), handled))
+        );
+        assert_eq!(
+            request(&cons, "provider=svn", token).map(|(provider, _, _)| provider),
+            Ok(DeliveryType::GitLab)
+        );
+        assert_eq!("Gitea".parse::<DeliveryType>(), Ok(DeliveryType::Gitea));
+    }
+
+    /// Test restricting hooks to providers
+    #[test]
+    fn hook_providers() {
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::new("push", None, |_: &Delivery| ()).providers(&[DeliveryType::GitHub]),
+        );
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(response.outcome, Outcome::NoMatch);
+    }
+
+    /// Test registering hooks of the same event for different providers
+    #[test]
+    fn hook_providers_same_event() {
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        let mut cons = Constructor::new();
+        for provider in [DeliveryType::GitHub, DeliveryType::Gitea] {
+            let runs = runs.clone();
+            cons.register(
+                Hook::new("push", None, move |_: &Delivery| {
+                    runs.lock().unwrap().push(provider)
+                })
+                .providers(&[provider]),
+            );
+        }
+        assert_eq!(cons.hooks["push"].len(), 2);
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(*runs.lock().unwrap(), vec![DeliveryType::GitHub]);
+        handler.handle(vec![("X-Gitea-Event", "push")], "{}");
+        assert_eq!(
+            *runs.lock().unwrap(),
+            vec![DeliveryType::GitHub, DeliveryType::Gitea]
+        );
+        handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(runs.lock().unwrap().len(), 2);
+    }
+
+    /// Test adding headers and request IDs to responses
+    #[test]
+    fn response_headers() {
+        let mut cons = Constructor::new();
+        cons.request_id(true);
+        cons.response_header("Server", "rifling");
+        cons.response_header_with("X-Event", |delivery: &Delivery| {
+            Some(delivery.event.clone())
+        });
+        let handler = Handler::from(&cons);
+        let response = handler.handle(
+            vec![("X-GitHub-Event", "push"), ("X-Request-Id", "req-42")],
+            "{}",
+        );
+        assert_eq!(response.delivery.unwrap().request_id.unwrap(), "req-42");
+        assert_eq!(
+            response.headers,
+            vec![
+                ("X-Request-Id".to_string(), "req-42".to_string()),
+                ("Server".to_string(), "rifling".to_string()),
+                ("X-Event".to_string(), "push".to_string()),
+            ]
+        );
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        let (name, request_id) = &response.headers[0];
+        assert_eq!(name, "X-Request-Id");
+        assert_eq!(request_id.len(), 32);
+        assert_eq!(
+            response.delivery.unwrap().request_id.as_ref(),
+            Some(request_id)
+        );
+    }
+
+    /// Test reporting failures of named hooks
+    #[test]
+    fn named_hooks() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_inner = errors.clone();
+        let mut cons = Constructor::new();
+        cons.on_error(move |error: &Error| errors_inner.lock().unwrap().push(error.clone()));
+        cons.register(
+            Hook::new("*", None, |_: &Delivery| Err("Deployment failed")).named("deploy-prod"),
+        );
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let error = Error::Hook {
+            name: "deploy-prod".to_string(),
+            error: Box::new(Error::HookFailed("Deployment failed".to_string())),
+        };
+        assert_eq!(
+            error.to_string(),
+            "deploy-prod: Hook failed: Deployment failed"
+        );
+        assert_eq!(*errors.lock().unwrap(), vec![error]);
+        let stats = handler.stats();
+        assert_eq!(stats.per_hook.get("deploy-prod"), Some(&1));
+        assert_eq!(stats.per_hook_failures.get("deploy-prod"), Some(&1));
+    }
+
+    /// Test counting several named hooks registered for `*`
+    #[test]
+    fn named_wildcard_hooks() {
+        let mut cons = Constructor::new();
+        for index in 0..10 {
+            let hook = Hook::new("*", None, move |_: &Delivery| match index {
+                3 => Err("Audit failed"),
+                _ => Ok(()),
+            });
+            cons.register(hook.named(&format!("audit-{}", index)));
+        }
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "push")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 10,
+                unverified: 10
+            }
+        );
+        let stats = handler.stats();
+        for index in 0..10 {
+            let name = format!("audit-{}", index);
+            assert_eq!(stats.per_hook.get(&name), Some(&1));
+        }
+        assert_eq!(stats.per_hook_failures.len(), 1);
+        assert_eq!(stats.per_hook_failures.get("audit-3"), Some(&1));
+    }
+
+    /// Test authenticating every delivery with the authenticator of the constructor
+    #[test]
+    fn constructor_authenticator() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.authenticator(
+            |delivery: &Delivery| match delivery.headers.get("x-api-key") {
+                Some("secret") => Ok(()),
+                _ => Err(Error::InvalidSignature),
+            },
+        );
+        let handler = Handler::from(&cons);
+        let response = handler.handle(
+            vec![("X-Gitlab-Event", "Push"), ("X-Api-Key", "secret")],
+            "{}",
+        );
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(response.outcome, Outcome::AuthFailed);
+    }
+
+    /// Test rejecting clients with unknown identity
+    #[test]
+    fn allowed_clients() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.allowed_clients(&["CN=deploy.example.com"]);
+        let request = |handler: Handler| handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        let response = request(Handler::from(&cons).with_client_identity("CN=deploy.example.com"));
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(
+            response.delivery.unwrap().client_identity,
+            Some("CN=deploy.example.com".to_string())
+        );
+        let response = request(Handler::from(&cons).with_client_identity("CN=evil.example.com"));
+        assert_eq!(response.status(), 403);
+        assert_eq!(request(Handler::from(&cons)).status(), 403);
+    }
+
+    /// Test rejecting deliveries from unexpected GitHub Enterprise Server instances
+    #[test]
+    fn enterprise_hosts() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.require_enterprise_host("ghe.corp.example");
+        cons.require_enterprise_host("GHE.Other.example");
+        let handler = Handler::from(&cons);
+        let request = |host: Option<&str>| {
+            let mut headers = vec![("X-GitHub-Event", "push")];
+            if let Some(host) = host {
+                headers.push(("X-GitHub-Enterprise-Host", host));
+                headers.push(("X-GitHub-Enterprise-Version", "3.14.2"));
+            }
+            handler.handle(headers, "{}")
+        };
+        let response = request(Some("ghe.corp.example"));
+        assert_eq!(response.status(), 200);
+        let delivery = response.delivery.unwrap();
+        assert_eq!(delivery.enterprise_host.unwrap(), "ghe.corp.example");
+        assert_eq!(delivery.enterprise_version.unwrap(), "3.14.2");
+        assert_eq!(request(Some("ghe.other.example")).status(), 200);
+        let response = request(Some("ghe.evil.example"));
+        assert_eq!(
+            response.outcome,
+            Outcome::Rejected(Error::UntrustedHost("ghe.evil.example".to_string()))
+        );
+        assert_eq!(response.status(), 403);
+        assert_eq!(request(None).status(), 403);
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(response.status(), 200);
+    }
+
+    /// Test refusing deliveries of GitHub lacking the headers GitHub sends
+    #[test]
+    fn strict_github() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.strict_github(true);
+        let handler = Handler::from(&cons);
+        let signature = format!("sha256={}", "0a".repeat(32));
+        let headers = [
+            ("x-github-event", "push"),
+            ("x-github-delivery", "72d3162e-cc78-11e3-81ab-4c9367dc0958"),
+            ("x-hub-signature-256", signature.as_str()),
+            ("user-agent", "GitHub-Hookshot/044aadd"),
+            ("content-type", "application/json; charset=utf-8"),
+        ];
+        let request = |name: &str, value: Option<&str>| {
+            let headers = headers.iter().filter_map(|(header, header_value)| {
+                match (*header == name, value) {
+                    (false, _) => Some((*header, *header_value)),
+                    (true, Some(value)) => Some((*header, value)),
+                    (true, None) => None,
+                }
+            });
+            handler.handle(headers, "{}").outcome
+        };
+        assert_eq!(request("", None).status(), 200);
+        let refused = |reason| Outcome::Rejected(Error::InvalidHeaders(reason));
+        assert_eq!(
+            request("x-github-delivery", None),
+            refused("Missing X-GitHub-Delivery")
+        );
+        assert_eq!(
+            request("x-hub-signature-256", Some("sha256=0a")),
+            refused("Missing or malformed X-Hub-Signature-256")
+        );
+        assert_eq!(
+            request("user-agent", Some("curl/8.5.0")),
+            refused("User-Agent is not GitHub-Hookshot")
+        );
+        let response = request("content-type", Some("application/x-www-form-urlencoded"));
+        assert_eq!(response, refused("Content-Type is not application/json"));
+        assert_eq!(response.status(), 400);
+        let response = handler.handle(vec![("X-Gitea-Event", "push")], "{}");
+        assert_eq!(response.status(), 200);
+    }
+
+    /// Test rejecting deliveries by their user agent
+    #[test]
+    fn user_agents() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("*", None, |_: &Delivery| ()));
+        let request = |cons: &Constructor, user_agent: Option<&str>| {
+            let mut headers = vec![("X-GitHub-Event", "push")];
+            if let Some(user_agent) = user_agent {
+                headers.push(("User-Agent", user_agent));
+            }
+            Handler::from(cons).handle(headers, "{}")
+        };
+        assert_eq!(request(&cons, None).status(), 200);
+        cons.allow_user_agent("GitHub-Hookshot/");
+        cons.allow_user_agent("GitLab/");
+        assert_eq!(
+            request(&cons, Some("GitHub-Hookshot/044aadd")).status(),
+            200
+        );
+        assert_eq!(request(&cons, Some("GitLab/17.0.0")).status(), 200);
+        let response = request(&cons, Some("curl/8.5.0"));
+        assert_eq!(
+            response.outcome,
+            Outcome::Rejected(Error::UntrustedUserAgent("curl/8.5.0".to_string()))
+        );
+        assert_eq!(response.status(), 403);
+        assert_eq!(request(&cons, None).status(), 403);
+        cons.deny_user_agent("GitLab/16.");
+        assert_eq!(request(&cons, Some("GitLab/16.11.2")).status(), 403);
+        assert_eq!(request(&cons, Some("GitLab/17.0.0")).status(), 200);
+        #[cfg(feature = "regex-match")]
+        {
+            cons.allow_user_agent_matching(Regex::new(r"^Go-http-client/\d").unwrap());
+            assert_eq!(request(&cons, Some("Go-http-client/2.0")).status(), 200);
+            cons.deny_user_agent_matching(Regex::new("(?i)bot").unwrap());
+            assert_eq!(request(&cons, Some("GitHub-Hookshot/Bot")).status(), 403);
+        }
+    }
+
+    /// Test resolving the address of the sender behind trusted proxies
+    #[test]
+    fn trusted_proxies() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        assert!(cons.trust_proxies(&["10.0.0.0/8", "proxy"]).is_err());
+        cons.trust_proxies(&["10.0.0.0/8"]).unwrap();
+        let headers = vec![
+            ("X-Gitlab-Event", "Push"),
+            ("X-Forwarded-For", "192.0.2.1, 10.0.0.2"),
+            ("X-Forwarded-Proto", "https"),
+        ];
+        let request = |peer: &str| {
+            let handler = Handler::from(&cons).with_remote_addr(peer.parse().unwrap());
+            handler.handle(headers.clone(), "{}").delivery.unwrap()
+        };
+        let delivery = request("10.0.0.1:4567");
+        assert_eq!(delivery.remote_addr, Some("192.0.2.1".parse().unwrap()));
+        assert_eq!(delivery.scheme, Some("https".to_string()));
+        let delivery = request("198.51.100.1:4567");
+        assert_eq!(delivery.remote_addr, Some("198.51.100.1".parse().unwrap()));
+        assert_eq!(delivery.scheme, None);
+    }
+
+    /// Test skipping hooks whose conditions don't hold
+    #[cfg(feature = "parse")]
+    #[test]
+    fn hook_conditions() {
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::new("pull_request", None, |_: &Delivery| ())
+                .when(filter::when_eq("/action", "opened")),
+        );
+        let handler = Handler::from(&cons);
+        let request = |body: &str| handler.handle(vec![("X-GitHub-Event", "pull_request")], body);
+        let response = request(r#"{"action": "opened"}"#);
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(
+            response.delivery.unwrap().field("/action"),
+            Some(&Value::from("opened"))
+        );
+        assert_eq!(request(r#"{"action": "closed"}"#).outcome, Outcome::NoMatch);
+    }
+
+    /// Test counting deliveries across handlers created from the same constructor
+    #[test]
+    fn stats() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new(
+            "push",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        let stats = cons.stats_handle();
+        let handler = Handler::from(&cons);
+        let response = handler.handle(
+            vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", "secret")],
+            "{}",
+        );
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 0
+            }
+        );
+        let response = Handler::from(&cons).handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(response.outcome, Outcome::AuthFailed);
+        handler.handle(vec![("X-GitHub-Event", "issues")], "{}");
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.deliveries, 3);
+        assert_eq!(snapshot.per_event.get("push"), Some(&2));
+        assert_eq!(snapshot.per_provider.get("GitLab"), Some(&2));
+        assert_eq!(snapshot.per_provider.get("GitHub"), Some(&1));
+        assert_eq!(snapshot.auth_failures, 1);
+        assert_eq!(snapshot.hook_runs, 1);
+        assert!(snapshot.average_hook_latency.is_some());
+        assert_eq!(handler.stats().deliveries, 3);
+    }
+
+    /// Test counting deliveries per repository and by the size of their payload
+    #[cfg(feature = "parse")]
+    #[test]
+    fn load_stats() {
+        let cons = Constructor::new();
+        let handler = Handler::from(&cons);
+        let push = |repository: &str, padding: usize| {
+            let request_body = format!(
+                r#"{{"repository": {{"full_name": "{}"}}, "padding": "{}"}}"#,
+                repository,
+                " ".repeat(padding)
+            );
+            handler.handle(vec![("X-GitHub-Event", "push")], request_body);
+        };
+        push("RedL0tus/rifling", 0);
+        push("RedL0tus/rifling", 2048);
+        push("octocat/hello-world", 0);
+        handler.handle(vec![("X-GitHub-Event", "ping")], "");
+        let stats = handler.stats();
+        assert_eq!(stats.top_repositories(1), vec![("RedL0tus/rifling", 2)]);
+        assert_eq!(stats.top_repositories(5).len(), 2);
+        assert_eq!(stats.top_events(1), vec![("push", 3)]);
+        assert_eq!(stats.payload_sizes, vec![3, 1, 0, 0, 0, 0]);
+        assert!(stats.payload_bytes > 2048);
+    }
+
+    /// Test emitting access log entries with the timings of hooks
+    #[test]
+    fn access_log() {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let entries_inner = entries.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.access_log(move |entry: &AccessLogEntry| {
+            entries_inner.lock().unwrap().push(entry.clone())
+        });
+        let handler = Handler::from(&cons);
+        handler.handle(
+            vec![
+                ("X-GitHub-Event", "push"),
+                ("X-GitHub-Delivery", "72d3162e"),
+            ],
+            "{}",
+        );
+        handler.handle(vec![("X-Unknown-Event", "push")], "{}");
+        let entries = entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].provider, Some("GitHub".to_string()));
+        assert_eq!(entries[0].delivery_id, Some("72d3162e".to_string()));
+        assert_eq!(entries[0].status, 200);
+        assert_eq!(entries[0].hooks.len(), 1);
+        assert!(entries[0].hooks[0].ok);
+        assert_eq!(entries[1].event, None);
+        assert!(entries[1].hooks.is_empty());
+    }
+
+    /// Test normalizing event names of deliveries and hooks with the configured policy
+    #[test]
+    fn event_normalization() {
+        let request = |cons: &Constructor| {
+            Handler::from(cons)
+                .handle(vec![("X-Gitlab-Event", "Push Hook")], "{}")
+                .outcome
+        };
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("Push Hook", None, |_: &Delivery| ()));
+        assert_eq!(
+            request(&cons),
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        cons.event_normalization(EventNormalization::Preserve);
+        assert_eq!(
+            request(&cons),
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert!(cons.hooks.contains_key("Push Hook"));
+        let mut cons = Constructor::new();
+        cons.event_normalization(EventNormalization::Preserve);
+        cons.register(Hook::new("push_hook", None, |_: &Delivery| ()));
+        assert_eq!(request(&cons), Outcome::NoMatch);
+        cons.event_normalization(EventNormalization::Custom(Arc::new(|event: &str| {
+            event.trim_end_matches(" Hook").to_lowercase()
+        })));
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        assert_eq!(
+            request(&cons),
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+    }
+
+    /// Test handling plain form posts
+    #[cfg(feature = "content-type-urlencoded")]
+    #[test]
+    fn form_posts() {
+        let fields = Arc::new(Mutex::new(None));
+        let fields_inner = fields.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::new(
+            "ticket_changed",
+            Some("secret".to_string()),
+            move |delivery: &Delivery| *fields_inner.lock().unwrap() = delivery.form_fields(),
+        ));
+        let handler = Handler::from(&cons);
+        let content_type = (
+            "Content-Type",
+            "application/x-www-form-urlencoded; charset=utf-8",
+        );
+        let body = "event=ticket_changed&ticket=42&summary=Fix+the+build";
+        let response = handler.handle(vec![content_type, ("Token", "secret")], body);
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 0
+            }
+        );
+        let fields = fields.lock().unwrap().clone().unwrap();
+        assert_eq!(fields.get("ticket"), Some(&"42".to_string()));
+        assert_eq!(fields.get("summary"), Some(&"Fix the build".to_string()));
+        let response = handler.handle(vec![content_type], body);
+        assert_eq!(response.outcome, Outcome::AuthFailed);
+        let response = handler.handle(vec![content_type], "ticket=42");
+        assert_eq!(response.delivery.unwrap().event, "form");
+    }
+
+    /// Test handling multipart bodies with binary files
+    #[cfg(feature = "multipart")]
+    #[test]
+    fn multipart_posts() {
+        let parts = Arc::new(Mutex::new(Vec::new()));
+        let parts_inner = parts.clone();
+        let mut cons = Constructor::new();
+        cons.multipart_file_limit(4);
+        cons.register(Hook::new("inbound", None, move |delivery: &Delivery| {
+            *parts_inner.lock().unwrap() = delivery.parts.clone();
+            assert_eq!(
+                delivery.form_fields().unwrap().get("event"),
+                Some(&"inbound".to_string())
+            );
+        }));
+        let handler = Handler::from(&cons);
+        let content_type = ("Content-Type", "multipart/form-data; boundary=rifling");
+        let mut body =
+            b"--rifling\r\nContent-Disposition: form-data; name=\"event\"\r\n\r\ninbound\r\n"
+                .to_vec();
+        body.extend_from_slice(
+            b"--rifling\r\nContent-Disposition: form-data; name=\"a\"; filename=\"a.bin\"\r\n\r\n",
+        );
+        body.extend_from_slice(&[0xff, 0xfe, 0x00]);
+        body.extend_from_slice(b"\r\n--rifling\r\nContent-Disposition: form-data; name=\"b\"; filename=\"b.bin\"\r\n\r\n");
+        body.extend_from_slice(&[0xff; 8]);
+        body.extend_from_slice(b"\r\n--rifling--\r\n");
+        let response = handler.handle(vec![content_type], &body);
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let parts = parts.lock().unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].data, vec![0xff, 0xfe, 0x00]);
+    }
+
+    /// Test coalescing deliveries of debounced and throttled hooks
+    #[test]
+    fn scheduled_hooks() {
+        let (sender, receiver) = mpsc::channel();
+        let debounced = Arc::new(Mutex::new(sender.clone()));
+        let throttled = Arc::new(Mutex::new(sender));
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::new("push", None, move |delivery: &Delivery| {
+                let run = ("push", delivery.id.clone());
+                debounced.lock().unwrap().send(run).unwrap();
+            })
+            .debounce(Duration::from_millis(100)),
+        );
+        cons.register(
+            Hook::new("issues", None, move |delivery: &Delivery| {
+                let run = ("issues", delivery.id.clone());
+                throttled.lock().unwrap().send(run).unwrap();
+            })
+            .throttle(Duration::from_millis(100)),
+        );
+        let handler = Handler::from(&cons);
+        for id in &["1", "2", "3"] {
+            for event in &["push", "issues"] {
+                let headers = vec![("X-GitHub-Event", *event), ("X-GitHub-Delivery", *id)];
+                let response = handler.handle(headers, b"{}");
+                assert_eq!(
+                    response.outcome,
+                    Outcome::Handled {
+                        hooks: 1,
+                        unverified: 1
+                    }
+                );
+            }
+        }
+        assert!(receiver.try_recv().is_err());
+        let timeout = Duration::from_secs(5);
+        let mut runs = vec![
+            receiver.recv_timeout(timeout).unwrap(),
+            receiver.recv_timeout(timeout).unwrap(),
+        ];
+        runs.sort();
+        let id = Some("3".to_string());
+        assert_eq!(runs, [("issues", id.clone()), ("push", id)]);
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    /// Test coalescing deliveries per hook, for hooks sharing an event
+    #[test]
+    fn scheduled_hooks_sharing_event() {
+        let (sender, receiver) = mpsc::channel();
+        let mut cons = Constructor::new();
+        for name in &["first", "second"] {
+            let sender = Arc::new(Mutex::new(sender.clone()));
+            cons.register(
+                Hook::new("push", None, move |delivery: &Delivery| {
+                    let run = (*name, delivery.id.clone());
+                    sender.lock().unwrap().send(run).unwrap();
+                })
+                .debounce(Duration::from_millis(50)),
+            );
+        }
+        let handler = Handler::from(&cons);
+        for id in &["1", "2"] {
+            let headers = vec![("X-GitHub-Event", "push"), ("X-GitHub-Delivery", *id)];
+            assert_eq!(
+                handler.handle(headers, b"{}").outcome,
+                Outcome::Handled {
+                    hooks: 2,
+                    unverified: 2
+                }
+            );
+        }
+        let timeout = Duration::from_secs(5);
+        let mut runs = vec![
+            receiver.recv_timeout(timeout).unwrap(),
+            receiver.recv_timeout(timeout).unwrap(),
+        ];
+        runs.sort();
+        let id = Some("2".to_string());
+        assert_eq!(runs, [("first", id.clone()), ("second", id)]);
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    /// Test running hooks with batches of deliveries
+    #[test]
+    fn batched_hooks() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::batch("push", None, move |deliveries: &[Delivery]| {
+                let ids = deliveries.iter().map(|delivery| delivery.id.clone());
+                sender
+                    .lock()
+                    .unwrap()
+                    .send(ids.collect::<Vec<_>>())
+                    .unwrap();
+            })
+            .batch_window(Duration::from_millis(100), 2),
+        );
+        let handler = Handler::from(&cons);
+        for id in &["1", "2", "3"] {
+            let headers = vec![("X-GitHub-Event", "push"), ("X-GitHub-Delivery", *id)];
+            assert_eq!(
+                handler.handle(headers, b"{}").outcome,
+                Outcome::Handled {
+                    hooks: 1,
+                    unverified: 1
+                }
+            );
+        }
+        let id = |id: &str| Some(id.to_string());
+        // Full batches are run immediately
+        assert_eq!(receiver.try_recv(), Ok(vec![id("1"), id("2")]));
+        assert!(receiver.try_recv().is_err());
+        let batch = receiver.recv_timeout(Duration::from_secs(5));
+        assert_eq!(batch, Ok(vec![id("3")]));
+    }
+
+    /// Test batching deliveries per hook, for hooks sharing an event
+    #[test]
+    fn batched_hooks_sharing_event() {
+        let (sender, receiver) = mpsc::channel();
+        let mut cons = Constructor::new();
+        for name in &["first", "second"] {
+            let sender = Arc::new(Mutex::new(sender.clone()));
+            cons.register(
+                Hook::batch("push", None, move |deliveries: &[Delivery]| {
+                    let ids = deliveries.iter().map(|delivery| delivery.id.clone());
+                    let run = (*name, ids.collect::<Vec<_>>());
+                    sender.lock().unwrap().send(run).unwrap();
+                })
+                .batch_window(Duration::from_millis(50), 10),
+            );
+        }
+        let handler = Handler::from(&cons);
+        for id in &["1", "2"] {
+            let headers = vec![("X-GitHub-Event", "push"), ("X-GitHub-Delivery", *id)];
+            assert_eq!(
+                handler.handle(headers, b"{}").outcome,
+                Outcome::Handled {
+                    hooks: 2,
+                    unverified: 2
+                }
+            );
+        }
+        let timeout = Duration::from_secs(5);
+        let mut runs = vec![
+            receiver.recv_timeout(timeout).unwrap(),
+            receiver.recv_timeout(timeout).unwrap(),
+        ];
+        runs.sort();
+        let ids = vec![Some("1".to_string()), Some("2".to_string())];
+        assert_eq!(runs, [("first", ids.clone()), ("second", ids)]);
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    /// Test rate limiting deliveries globally before verifying them
+    #[test]
+    fn rate_limited_before_verification() {
+        let mut cons = Constructor::new();
+        cons.rate_limit(RateLimitScope::Global, 1, Duration::from_secs(60));
+        cons.register(Hook::new(
+            "push_hook",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        let handler = Handler::from(&cons);
+        let headers = vec![("X-Gitlab-Event", "Push Hook"), ("X-Gitlab-Token", "nope")];
+        assert_eq!(
+            handler.handle(headers.clone(), "{}").outcome,
+            Outcome::AuthFailed
+        );
+        let response = handler.handle(headers, "{}");
+        assert!(matches!(
+            response.outcome,
+            Outcome::Rejected(Error::RateLimited(_))
+        ));
+        assert_eq!(response.status(), 429);
+    }
+
+    /// Test skipping hooks during maintenance
+    #[test]
+    fn maintenance() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let store = MemoryStore::new(8);
+        let mut cons = Constructor::new();
+        cons.store(store.clone());
+        cons.register(Hook::new(
+            "push_hook",
+            Some("secret".to_string()),
+            move |_: &Delivery| {
+                runs_inner.fetch_add(1, Ordering::SeqCst);
+            },
+        ));
+        let maintenance = cons.maintenance_handle();
+        let handler = Handler::from(&cons);
+        let request = |token: &str| {
+            handler.handle(
+                vec![("X-Gitlab-Event", "Push Hook"), ("X-Gitlab-Token", token)],
+                b"{}",
+            )
+        };
+        maintenance.enable();
+        let response = request("secret");
+        assert_eq!(response.outcome, Outcome::Maintenance { status: 503 });
+        assert_eq!(response.status(), 503);
+        assert_eq!(request("wrong").outcome, Outcome::AuthFailed);
+        cons.maintenance_status(202);
+        assert_eq!(request("secret").status(), 202);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        // Deliveries failing to authenticate are not recorded
+        assert_eq!(store.records().len(), 2);
+        maintenance.disable();
+        assert_eq!(
+            request("secret").outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 0
+            }
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let records = store.records();
+        let hooks = &records.last().unwrap().hooks;
+        assert_eq!(hooks.len(), 1);
+        assert!(hooks[0].ok && hooks[0].attempts == 1);
+        assert!(records[0].hooks.is_empty());
+    }
+
+    /// Test overriding the responses to events
+    #[test]
+    fn response_overrides() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("*", None, move |_: &Delivery| {
+            runs_inner.fetch_add(1, Ordering::SeqCst);
+        }));
+        cons.alias("deploy", &["deployment", "Deployment Hook"]);
+        cons.override_response("Deployment Hook", 202, "Frozen");
+        let overrides = cons.overrides_handle();
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-Gitlab-Event", "Deployment Hook")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Overridden(ResponseOverride {
+                status: 202,
+                body: "Frozen".to_string()
+            })
+        );
+        assert_eq!(response.body(), "Frozen");
+        assert_eq!(
+            handler
+                .handle(vec![("X-GitHub-Event", "deployment")], "{}")
+                .status(),
+            200
+        );
+        overrides.replace(vec![("deploy", 503, "Deploy freeze")]);
+        let response = handler.handle(vec![("X-GitHub-Event", "deployment")], "{}");
+        assert_eq!(
+            (response.status(), response.body()),
+            (503, "Deploy freeze".to_string())
+        );
+        assert_eq!(
+            handler
+                .handle(vec![("X-GitHub-Event", "push")], "{}")
+                .status(),
+            200
+        );
+        overrides.remove("deploy");
+        let response = handler.handle(vec![("X-GitHub-Event", "deployment")], "{}");
+        assert_eq!(response.status(), 200);
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    /// Test rewriting deliveries before matching hooks
+    #[cfg(feature = "parse")]
+    #[test]
+    fn transformers() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let mut cons = Constructor::new();
+        cons.transform(
+            RenameEvent::new("pull_request", "PR Updated")
+                .when(filter::when_eq("/action", "synchronize")),
+        );
+        cons.transform(|delivery: &mut Delivery| {
+            if let Some(Value::Object(payload)) = delivery.payload.as_mut() {
+                payload.insert("transformed".to_string(), Value::Bool(true));
+            }
+        });
+        cons.register(Hook::new("pr_updated", None, move |delivery: &Delivery| {
+            assert_eq!(delivery.field("/transformed"), Some(&Value::Bool(true)));
+            runs_inner.fetch_add(1, Ordering::SeqCst);
+        }));
+        let handler = Handler::from(&cons);
+        let headers = vec![("X-GitHub-Event", "pull_request")];
+        let response = handler.handle(headers.clone(), r#"{"action": "synchronize"}"#);
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let response = handler.handle(headers, r#"{"action": "opened"}"#);
+        assert_eq!(response.outcome, Outcome::NoMatch);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    /// Test handling deliveries outside the active window of hooks
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn active_windows() {
+        use crate::window::OutOfWindow;
+        use chrono::{Datelike, Utc};
+        let today = Utc::now().weekday();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut cons = Constructor::new();
+        let errors_inner = errors.clone();
+        cons.on_error(move |error: &Error| errors_inner.lock().unwrap().push(error.clone()));
+        for (event, days, policy) in [
+            ("push", today..=today, OutOfWindow::DeadLetter),
+            ("release", today.succ()..=today.succ(), OutOfWindow::Drop),
+            (
+                "deployment",
+                today.succ()..=today.succ(),
+                OutOfWindow::DeadLetter,
+            ),
+        ] {
+            let runs = runs.clone();
+            let hook = Hook::new(event, None, move |_: &Delivery| {
+                runs.fetch_add(1, Ordering::SeqCst);
+            })
+            .active_between("00:00".."00:00", days, Utc)
+            .unwrap()
+            .outside_window(policy);
+            cons.register(hook);
+        }
+        let handler = Handler::from(&cons);
+        for event in &["push", "release", "deployment"] {
+            let response = handler.handle(vec![("X-GitHub-Event", *event)], "{}");
+            assert_eq!(response.status(), 200);
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(*errors.lock().unwrap(), vec![Error::OutsideWindow]);
+    }
+
+    /// Test skipping the functions of hooks in dry runs
+    #[test]
+    fn dry_run() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let store = MemoryStore::new(10);
+        let mut cons = Constructor::new();
+        cons.store(store.clone());
+        cons.dry_run(true);
+        for (event, name, dry_run) in [("push", "build", None), ("release", "publish", Some(false))]
+        {
+            let runs = runs.clone();
+            let mut hook = Hook::new(event, None, move |_: &Delivery| {
+                runs.fetch_add(1, Ordering::SeqCst);
+            })
+            .named(name);
+            if let Some(dry_run) = dry_run {
+                hook = hook.dry_run(dry_run);
+            }
+            cons.register(hook);
+        }
+        let handler = Handler::from(&cons);
+        let response = handler.handle(
+            vec![("X-GitHub-Event", "push"), ("X-GitHub-Delivery", "1")],
+            "{}",
+        );
+        assert_eq!(response.outcome, Outcome::DryRun { hooks: 1 });
+        assert_eq!(response.status(), 200);
+        let hook_run = store.status("1").unwrap().hook("build").cloned().unwrap();
+        assert!(hook_run.dry_run && hook_run.ok);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        let response = handler.handle(vec![("X-GitHub-Event", "release")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    /// Test replacing the hooks at runtime
+    #[test]
+    fn reload() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let reload = cons.reload_handle();
+        let handler = Handler::from(&cons);
+        let request = |event: &str| handler.handle(vec![("X-Gitlab-Event", event)], b"{}");
+        assert_eq!(request("Push Hook").outcome, Outcome::NoMatch);
+        reload.reload(vec![Hook::new("Push Hook", None, |_: &Delivery| ())]);
+        assert_eq!(
+            request("Push Hook").outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        cons.event_normalization(EventNormalization::Preserve);
+        assert_eq!(
+            Handler::from(&cons)
+                .handle(vec![("X-Gitlab-Event", "Push Hook")], b"{}")
+                .outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+    }
+
+    /// Test rebuilding the hooks on `SIGHUP`
+    #[cfg(all(unix, feature = "reload-signal"))]
+    #[test]
+    fn reload_on_sighup() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let rebuilds = Arc::new(AtomicUsize::new(0));
+        let rebuilds_inner = rebuilds.clone();
+        cons.reload_handle()
+            .reload_on_sighup(
+                move || match rebuilds_inner.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err("unreadable configuration"),
+                    _ => Ok(vec![Hook::new("issues", None, |_: &Delivery| ())]),
+                },
+            )
+            .unwrap();
+        let handler = Handler::from(&cons);
+        let request = |event: &str| handler.handle(vec![("X-GitHub-Event", event)], b"{}");
+        let wait_rebuilds = |count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while rebuilds.load(Ordering::SeqCst) < count && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            // Let the hooks be replaced after they're rebuilt
+            thread::sleep(Duration::from_millis(50));
+        };
+        // The hooks are kept when they can't be rebuilt
+        signal_hook::low_level::raise(signal_hook::consts::SIGHUP).unwrap();
+        wait_rebuilds(1);
+        assert_ne!(request("push").outcome, Outcome::NoMatch);
+        signal_hook::low_level::raise(signal_hook::consts::SIGHUP).unwrap();
+        wait_rebuilds(2);
+        assert_eq!(request("push").outcome, Outcome::NoMatch);
+        assert_ne!(request("issues").outcome, Outcome::NoMatch);
+    }
+
+    /// Test disabling and enabling groups of hooks
+    #[test]
+    fn groups() {
+        let mut cons = Constructor::new();
+        cons.group("deploy")
+            .register(Hook::new("push", None, |_: &Delivery| ()))
+            .register(Hook::new("release", None, |_: &Delivery| ()));
+        cons.register(Hook::new("issues", None, |_: &Delivery| ()));
+        let groups = cons.groups_handle();
+        let handler = Handler::from(&cons);
+        let request = |event: &str| handler.handle(vec![("X-GitHub-Event", event)], b"{}");
+        groups.disable("deploy");
+        assert_eq!(request("push").outcome, Outcome::NoMatch);
+        assert_eq!(request("release").outcome, Outcome::NoMatch);
+        assert_eq!(
+            request("issues").outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        groups.enable("deploy");
+        assert_eq!(
+            request("push").outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+    }
+
+    /// Test matching hooks registered for aliases of events
+    #[test]
+    fn event_aliases() {
+        let mut cons = Constructor::new();
+        cons.alias("pr", &["pull_request", "Merge Request Hook"]);
+        cons.register(Hook::new("pr", None, |_: &Delivery| ()));
+        cons.register(Hook::new("pull_request", None, |_: &Delivery| ()));
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-GitHub-Event", "pull_request")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 2,
+                unverified: 2
+            }
+        );
+        let response = handler.handle(vec![("X-Gitlab-Event", "Merge Request Hook")], "{}");
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push Hook")], "{}");
+        assert_eq!(response.outcome, Outcome::NoMatch);
+    }
+
+    /// Test rejecting deliveries failing validation
+    #[test]
+    fn validation() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_inner = errors.clone();
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        cons.on_error(move |error: &Error| errors_inner.lock().unwrap().push(error.clone()));
+        cons.validate("*", |delivery: &Delivery| match &delivery.request_body {
+            Some(body) if body.contains("ref") => Ok(()),
+            _ => Err("Missing ref".to_string()),
+        });
+        let handler = Handler::from(&cons);
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push")], r#"{"ref": "master"}"#);
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        let response = handler.handle(vec![("X-Gitlab-Event", "Push")], "{}");
+        assert_eq!(response.status(), 422);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![Error::ValidationFailed("Missing ref".to_string())]
+        );
+    }
+
+    /// Test redacting secrets when formatting deliveries
+    #[test]
+    fn delivery_debug() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        headers.insert("x-gitlab-token".to_string(), "secret".to_string());
+        let delivery =
+            Delivery::new(headers, Some(r#"{"password": "hunter2"}"#.to_string())).unwrap();
+        let formatted = format!("{:?}", delivery);
+        assert!(formatted.contains("push_hook"));
+        assert!(!formatted.contains("secret"));
+        assert!(!formatted.contains("hunter2"));
+    }
+
+    /// Test the outcome passed to the callback
+    #[test]
+    fn outcome_callback() {
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let mut cons = Constructor::new();
+        cons.register(Hook::new(
+            "push",
+            Some("secret".to_string()),
+            |_: &Delivery| (),
+        ));
+        {
+            let outcomes = outcomes.clone();
+            cons.on_outcome(move |response: &Response| {
+                outcomes.lock().unwrap().push(response.outcome.clone())
+            });
+        }
+        let handler = Handler::from(&cons);
+        handler.handle(
+            vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", "secret")],
+            "{}",
+        );
+        handler.handle(
+            vec![("X-Gitlab-Event", "Push"), ("X-Gitlab-Token", "wrong")],
+            "{}",
+        );
+        handler.handle(vec![("X-Gitlab-Event", "Issue")], "{}");
+        handler.handle(vec![("X-Unknown-Event", "push")], "{}");
+        assert_eq!(
+            *outcomes.lock().unwrap(),
+            vec![
+                Outcome::Handled {
+                    hooks: 1,
+                    unverified: 0
+                },
+                Outcome::AuthFailed,
+                Outcome::NoMatch,
+                Outcome::InvalidPayload(Error::InvalidDelivery(
+                    "Could not determine delivery type"
+                )),
+            ]
+        );
+    }
+
+    /// Test answering the PING of Discord and running hooks for authenticated interactions
+    #[cfg(all(feature = "crypto-use-ring", feature = "parse"))]
+    #[test]
+    fn discord_interactions() {
+        let public_key = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        let mut cons = Constructor::new();
+        cons.register(Hook::new(
+            "application_command",
+            Some(public_key.to_string()),
+            |_: &Delivery| (),
+        ));
+        let handler = Handler::from(&cons);
+        let request = |signature: &str, body: &str| {
+            handler.handle(
+                vec![
+                    ("X-Signature-Ed25519", signature),
+                    ("X-Signature-Timestamp", "1576754827"),
+                ],
+                body,
+            )
+        };
+        let ping = r#"{"type": 1}"#;
+        let response = request("70cf88739ddbcaac1ffbbf4505fe4d01b5c7671c8524d8bc3fc6cf6932df6d16a88aac0e0c21b2a1c2155ada8ddd2e52cf9eb9b6e7fe0da00774ecd97169e705", ping);
+        assert_eq!(response.outcome, Outcome::Pong);
+        assert_eq!(response.body(), r#"{"type":1}"#);
+        let response = request("134ab16513cff9a8bafdd801d1e581276e8f6584804747e7d5411a1cb99c739919cc84f925f468e0828207c54b41b791083a1ea53bfec407e1fa022a13374a08", ping);
+        assert_eq!(response.outcome, Outcome::AuthFailed);
+        // The timestamp is verified as sent, even if it parses to the same time
+        let response = handler.handle(
+            vec![
+                ("X-Signature-Ed25519", "a14eec35bbd5ded9470c7fbe939e508d5953075541ed5ba5aac7a661d7848819ce525197c89d47b47133b410565d6f790f2a3d1bbbfbbe726d4af45a8f18380e"),
+                ("X-Signature-Timestamp", "01576754827"),
+            ],
+            ping,
+        );
+        assert_eq!(response.outcome, Outcome::Pong);
+        let response = request("a14eec35bbd5ded9470c7fbe939e508d5953075541ed5ba5aac7a661d7848819ce525197c89d47b47133b410565d6f790f2a3d1bbbfbbe726d4af45a8f18380e", ping);
+        assert_eq!(response.outcome, Outcome::AuthFailed);
+        let command = r#"{"type": 2, "data": {"name": "deploy"}}"#;
+        let response = request("134ab16513cff9a8bafdd801d1e581276e8f6584804747e7d5411a1cb99c739919cc84f925f468e0828207c54b41b791083a1ea53bfec407e1fa022a13374a08", command);
+        assert_eq!(
+            response.outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 0
+            }
+        );
+    }
+
+    /// Test answering the admission reviews of Kubernetes
+    #[cfg(feature = "parse")]
+    #[test]
+    fn admission_review() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new(
+            "admission.pod.create",
+            None,
+            |delivery: &Delivery| match delivery
+                .payload
+                .as_ref()
+                .and_then(|payload| payload.pointer("/request/namespace"))
+                .and_then(Value::as_str)
+            {
+                Some("kube-system") => Err("Forbidden namespace"),
+                _ => Ok(()),
+            },
+        ));
+        let review = |namespace: &str| {
+            format!(
+                r#"{{"apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "request": {{"uid": "705ab4f5", "kind": {{"group": "", "version": "v1", "kind": "Pod"}}, "operation": "CREATE", "namespace": "{}"}}}}"#,
+                namespace
+            )
+        };
+        let response = Handler::from(&cons).handle(Vec::<(&str, &str)>::new(), review("default"));
+        assert_eq!(
+            response.outcome,
+            Outcome::InvalidPayload(Error::InvalidDelivery("Admission review is not enabled"))
+        );
+        cons.admission_review(true);
+        let handler = Handler::from(&cons);
+        let response = handler.handle(Vec::<(&str, &str)>::new(), review("default"));
+        assert_eq!(response.delivery.unwrap().event, "admission.pod.create");
+        let reply: Value = serde_json::from_str(&response.outcome.body()).unwrap();
+        assert_eq!(reply["response"]["uid"], "705ab4f5");
+        assert_eq!(reply["response"]["allowed"], true);
+        let response = handler.handle(Vec::<(&str, &str)>::new(), review("kube-system"));
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), Some("application/json"));
+        let reply: Value = serde_json::from_str(&response.body()).unwrap();
+        assert_eq!(reply["response"]["allowed"], false);
+        assert_eq!(
+            reply["response"]["status"]["message"],
+            "Forbidden namespace"
+        );
+    }
+
+    /// Test admission reviews going through the checks of other deliveries
+    #[cfg(feature = "parse")]
+    #[test]
+    fn admission_review_checks() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let mut cons = Constructor::new();
+        cons.admission_review(true);
+        cons.register(Hook::new(
+            "admission.pod.create",
+            None,
+            move |_: &Delivery| {
+                runs_inner.fetch_add(1, Ordering::SeqCst);
+            },
+        ));
+        cons.validate("*", |delivery: &Delivery| match &delivery.request_body {
+            Some(body) if body.contains("namespace") => Ok(()),
+            _ => Err("Missing namespace".to_string()),
+        });
+        let review = r#"{"apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "request": {"uid": "705ab4f5", "kind": {"group": "", "version": "v1", "kind": "Pod"}, "operation": "CREATE"}}"#;
+        let response = Handler::from(&cons).handle(Vec::<(&str, &str)>::new(), review);
+        assert_eq!(
+            response.outcome,
+            Outcome::Rejected(Error::ValidationFailed("Missing namespace".to_string()))
+        );
+        let review = review.replace("CREATE\"", "CREATE\", \"namespace\": \"default\"");
+        cons.override_response("admission.pod.create", 202, "Frozen");
+        let response = Handler::from(&cons).handle(Vec::<(&str, &str)>::new(), review);
+        assert_eq!(response.status(), 202);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+    }
+
+    /// Test skipping the hooks of admission reviews during maintenance
+    #[cfg(feature = "parse")]
+    #[test]
+    fn admission_review_maintenance() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let mut cons = Constructor::new();
+        cons.admission_review(true);
+        cons.register(Hook::new(
+            "admission.pod.create",
+            None,
+            move |_: &Delivery| {
+                runs_inner.fetch_add(1, Ordering::SeqCst);
+            },
+        ));
+        let maintenance = cons.maintenance_handle();
+        maintenance.enable();
+        let handler = Handler::from(&cons);
+        let review = r#"{"apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "request": {"uid": "705ab4f5", "kind": {"group": "", "version": "v1", "kind": "Pod"}, "operation": "CREATE"}}"#;
+        let response = handler.handle(Vec::<(&str, &str)>::new(), review);
+        assert_eq!(response.outcome, Outcome::Maintenance { status: 503 });
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        maintenance.disable();
+        let response = handler.handle(Vec::<(&str, &str)>::new(), review);
+        assert!(matches!(response.outcome, Outcome::Admission(_)));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    /// Test skipping the hooks of admission reviews in dry runs
+    #[cfg(feature = "parse")]
+    #[test]
+    fn admission_review_dry_run() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_inner = runs.clone();
+        let store = MemoryStore::new(8);
+        let mut cons = Constructor::new();
+        cons.admission_review(true);
+        cons.dry_run(true);
+        cons.store(store.clone());
+        cons.register(Hook::new(
+            "admission.pod.create",
+            None,
+            move |_: &Delivery| {
+                runs_inner.fetch_add(1, Ordering::SeqCst);
+                Err("Forbidden")
+            },
+        ));
+        let review = r#"{"apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "request": {"uid": "705ab4f5", "kind": {"group": "", "version": "v1", "kind": "Pod"}, "operation": "CREATE"}}"#;
+        let response = Handler::from(&cons).handle(Vec::<(&str, &str)>::new(), review);
+        let reply: Value = serde_json::from_str(&response.body()).unwrap();
+        assert_eq!(reply["response"]["allowed"], true);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        assert!(store.records()[0].hooks[0].dry_run);
+    }
+
+    /// Test reading and materializing headers of the `http` crate
+    #[cfg(feature = "http")]
+    #[test]
+    fn header_accessors() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("X-Gitlab-Event", "Push Hook".parse().unwrap());
+        headers.insert("x-custom", http::HeaderValue::from_bytes(b"\xff").unwrap());
+        let accessor = &headers;
+        assert_eq!(accessor.header("x-gitlab-event"), Some("Push Hook"));
+        assert_eq!(accessor.header("x-custom"), None);
+        assert_eq!(accessor.header("x-github-event"), None);
+        let delivery = Delivery::new(&headers, None).unwrap();
+        assert_eq!(delivery.delivery_type, DeliveryType::GitLab);
+        assert_eq!(delivery.event, "push_hook");
+        assert_eq!(delivery.header("x-custom"), Some("unknown"));
+        assert_eq!(delivery.headers()["x-custom"], "unknown");
+        // Owned maps are kept as they are, until the headers are looked up
+        let delivery = Delivery::new(headers, None).unwrap();
+        assert_eq!(delivery.event, "push_hook");
+        assert_eq!(delivery.header("X-Gitlab-Event"), Some("Push Hook"));
+        assert_eq!(delivery.header("x-custom"), Some("unknown"));
+        assert_eq!(delivery.headers().len(), 2);
+        assert_eq!(delivery.headers()["x-gitlab-event"], "Push Hook");
+    }
+
+    /// Test building deliveries and reading them through the accessors
+    #[test]
+    fn delivery_builder() {
+        let delivery = DeliveryBuilder::new(DeliveryType::GitLab, "push_hook")
+            .signature("secret")
+            .header("X-Gitlab-Event", "Push Hook")
+            .body(r#"{"ref": "refs/heads/master"}"#)
+            .remote_addr("10.0.0.1".parse().unwrap())
+            .query("token=secret")
+            .build();
+        assert_eq!(delivery.delivery_type(), DeliveryType::GitLab);
+        assert_eq!(delivery.event(), "push_hook");
+        assert_eq!(delivery.id(), None);
+        assert_eq!(delivery.header("x-gitlab-event"), Some("Push Hook"));
+        assert_eq!(
+            delivery.request_body(),
+            Some(r#"{"ref": "refs/heads/master"}"#)
+        );
+        assert_eq!(delivery.remote_addr(), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(delivery.query(), Some("token=secret"));
+        #[cfg(feature = "parse")]
+        assert_eq!(
+            delivery.field("/ref"),
+            Some(&Value::String("refs/heads/master".to_string()))
+        );
+        let hook = Hook::new("push_hook", Some("secret".to_string()), |_: &Delivery| ());
+        assert!(hook.auth(&delivery));
+    }
+
+    /// Test marking the landing page unhealthy while downstream targets are unreachable
+    #[test]
+    fn readiness_checks() {
+        let reachable = Arc::new(Mutex::new(true));
+        let state = reachable.clone();
+        let mut cons = Constructor::new();
+        cons.landing_page("deploy-bot");
+        cons.readiness_check("redis", move || {
+            if *state.lock().unwrap() {
+                Ok(())
+            } else {
+                Err("connection refused")
+            }
+        });
+        let handler = Handler::from(&cons);
+        let response = handler.landing().unwrap();
+        assert!(matches!(response.outcome, Outcome::Landing(_)));
+        assert_eq!(response.outcome.status(), 200);
+        *reachable.lock().unwrap() = false;
+        assert_eq!(handler.unreachable_targets(), vec!["redis".to_string()]);
+        let response = handler.landing().unwrap();
+        assert_eq!(response.outcome.status(), 503);
+        assert!(response
+            .outcome
+            .body()
+            .contains(r#""status":"unavailable","unreachable":["redis"]"#));
     }
 }
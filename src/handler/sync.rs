@@ -0,0 +1,310 @@
+//! Blocking server without async runtime
+//!
+//! With the `sync-server` feature, a `Constructor` can be served by a pool of threads using
+//! [tiny_http](https://crates.io/crates/tiny_http):
+//!
+//! ```no_run
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+//! cons.serve_sync("0.0.0.0:4567", 4).unwrap();
+//! ```
+//!
+//! The address of the peer is always available to the handlers.
+//!
+//! `Constructor::read_timeout` is checked between reads of the body only, a sender pausing while sending the body
+//! still occupies a thread until it resumes or disconnects.
+
+use tiny_http::{HTTPVersion, Header, Method, Request, Response, Server, StatusCode};
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use super::super::context::ResponseStream;
+use super::super::error::Error;
+use super::super::outcome::Response as RiflingResponse;
+#[cfg(all(unix, feature = "socket-activation"))]
+use super::super::systemd;
+use super::{Constructor, Handler};
+
+/// Blocking server impl clause of `Constructor`
+impl Constructor {
+    /// Listen on the address and handle requests with `threads` threads, blocks forever
+    pub fn serve_sync(&self, addr: impl ToSocketAddrs, threads: usize) -> io::Result<()> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        self.serve_on(server, threads);
+        Ok(())
+    }
+
+    /// Serve the listening socket passed by systemd, or listen on the address if there's none, and notify systemd
+    /// that the service is ready, see `rifling::systemd`
+    #[cfg(all(unix, feature = "socket-activation"))]
+    pub fn serve_activated(&self, addr: impl ToSocketAddrs, threads: usize) -> io::Result<()> {
+        let server = match systemd::listener()? {
+            Some(listener) => Server::from_listener(listener, None),
+            None => Server::http(addr),
+        }
+        .map_err(io::Error::other)?;
+        systemd::notify_ready()?;
+        self.serve_on(server, threads);
+        Ok(())
+    }
+
+    /// Handle requests received by the server
+    fn serve_on(&self, server: Server, threads: usize) {
+        let server = Arc::new(server);
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let server = server.clone();
+                let handler = Handler::from(self);
+                thread::spawn(move || {
+                    for request in server.incoming_requests() {
+                        handler.handle_sync(request);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Blocking server impl clause of `Handler`
+impl Handler {
+    /// Handle the request and send the response
+    fn handle_sync(&self, mut request: Request) {
+        let headers = request
+            .headers()
+            .iter()
+            .map(|header| {
+                (
+                    header.field.as_str().as_str().to_lowercase(),
+                    header.value.as_str().to_string(),
+                )
+            })
+            .collect::<HashMap<String, String>>();
+        let remote_addr = request.remote_addr().cloned().or(self.remote_addr);
+        if let Method::Get | Method::Head = request.method() {
+            if let Some(response) = self.landing_from(remote_addr) {
+                return respond(request, to_response(&response));
+            }
+        }
+        if let Some(response) = self.precheck(remote_addr, &headers) {
+            thread::sleep(response.hold());
+            return respond(request, to_response(&response));
+        }
+        let url = request.url().to_string();
+        let query = url.split_once('?').map(|(_, query)| query);
+        let (timestamp, start) = (SystemTime::now(), Instant::now());
+        let mut body = Vec::new();
+        let mut buffer = [0; 8192];
+        let response = loop {
+            match request.as_reader().read(&mut buffer) {
+                Ok(0) => break self.handle_from(remote_addr, query, headers.into(), &body),
+                Ok(length) => body.extend_from_slice(&buffer[..length]),
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    let response = Response::from_string("Invalid payload").with_status_code(202);
+                    return respond(request, response);
+                }
+            }
+            if let Some(limit) = self.settings.max_body_size {
+                if body.len() > limit {
+                    debug!("Request body larger than {} bytes", limit);
+                    let error = Error::PayloadTooLarge(limit);
+                    break self.refuse(remote_addr, timestamp, start, error);
+                }
+            }
+            if let Some(timeout) = self.settings.read_timeout {
+                if start.elapsed() > timeout {
+                    debug!("Request body not received within {:?}", timeout);
+                    let error = Error::RequestTimeout(timeout);
+                    break self.refuse(remote_addr, timestamp, start, error);
+                }
+            }
+        };
+        // Only the thread of this request is blocked, see `Constructor::min_response_time`
+        thread::sleep(response.hold());
+        match &response.stream {
+            Some(stream) if *request.http_version() >= HTTPVersion(1, 1) => {
+                if let Err(error) = stream_response(request, &response, stream.clone()) {
+                    debug!("Failed to send response: {}", error);
+                }
+            }
+            Some(stream) => {
+                // Chunked transfer encoding requires HTTP/1.1, send the response once the hooks are run
+                let mut response = response.clone();
+                response.fragments.extend(stream.clone());
+                respond(request, to_response(&response))
+            }
+            None => respond(request, to_response(&response)),
+        }
+    }
+}
+
+/// Send the response, followed by the fragments of the stream as hooks add them, one per line
+fn stream_response(
+    request: Request,
+    rifling_response: &RiflingResponse,
+    stream: ResponseStream,
+) -> io::Result<()> {
+    let status = rifling_response.status();
+    let mut writer = request.into_writer();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=UTF-8\r\nTransfer-Encoding: chunked\r\n",
+        status,
+        StatusCode(status).default_reason_phrase()
+    )?;
+    for (name, value) in &rifling_response.headers {
+        match Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            Ok(header) => write!(writer, "{}\r\n", header)?,
+            Err(()) => debug!("Invalid response header: {}", name),
+        }
+    }
+    writer.write_all(b"\r\n")?;
+    let mut send = |chunk: &str| {
+        write!(writer, "{:x}\r\n{}\r\n", chunk.len(), chunk)?;
+        writer.flush()
+    };
+    send(&rifling_response.body())?;
+    for fragment in stream {
+        send(&format!("\n{}", fragment))?;
+    }
+    writer.write_all(b"0\r\n\r\n")?;
+    writer.flush()
+}
+
+/// Build the response to the request
+fn to_response(rifling_response: &RiflingResponse) -> Response<io::Cursor<Vec<u8>>> {
+    let mut response =
+        Response::from_string(rifling_response.body()).with_status_code(rifling_response.status());
+    if let Some(content_type) = rifling_response.content_type() {
+        if let Ok(header) = Header::from_bytes("Content-Type", content_type) {
+            response.add_header(header);
+        }
+    }
+    if let Some(retry_after) = rifling_response.retry_after() {
+        if let Ok(header) = Header::from_bytes("Retry-After", retry_after.to_string()) {
+            response.add_header(header);
+        }
+    }
+    for (name, value) in &rifling_response.headers {
+        match Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            Ok(header) => response.add_header(header),
+            Err(()) => debug!("Invalid response header: {}", name),
+        }
+    }
+    response
+}
+
+/// Send the response, the peer may be gone already
+fn respond(request: Request, response: Response<io::Cursor<Vec<u8>>>) {
+    if let Err(error) = request.respond(response) {
+        debug!("Failed to send response: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Delivery;
+    use super::*;
+    use crate::context::Context;
+    use crate::hook::Hook;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    /// Test serving a constructor
+    #[test]
+    fn serve_sync() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        thread::spawn(move || cons.serve_on(server, 2));
+        let request = |event: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST / HTTP/1.1\r\nHost: localhost\r\nX-GitHub-Event: {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                event
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        assert!(request("push").starts_with("HTTP/1.1 200"));
+        assert!(request("issues").starts_with("HTTP/1.1 202"));
+    }
+
+    /// Test streaming the fragments added by hooks with chunked transfer encoding
+    #[test]
+    fn streamed_response() {
+        let mut cons = Constructor::new();
+        cons.register(
+            Hook::with_context("push", None, |context: &Context, _: &Delivery| {
+                context.respond("Building...");
+                context.respond("Done");
+            })
+            .stream_response(),
+        );
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        thread::spawn(move || cons.serve_on(server, 1));
+        let request = |version: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST / HTTP/{}\r\nHost: localhost\r\nX-GitHub-Event: push\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                version
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = request("1.1");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(
+            response.ends_with("\r\n\r\n2\r\nOK\r\nc\r\n\nBuilding...\r\n5\r\n\nDone\r\n0\r\n\r\n")
+        );
+        let response = request("1.0");
+        assert!(response.ends_with("\r\n\r\nOK\nBuilding...\nDone"));
+    }
+
+    /// Test timing out requests sending their body slowly
+    #[test]
+    fn read_timeout() {
+        let mut cons = Constructor::new();
+        cons.read_timeout(Duration::from_millis(50));
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        thread::spawn(move || cons.serve_on(server, 1));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // Bodies up to 1 KiB are read by tiny_http before passing the request
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nX-GitHub-Event: push\r\nContent-Length: 2048\r\nConnection: close\r\n\r\n{{{}",
+            " ".repeat(1023)
+        )
+        .unwrap();
+        stream.flush().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        write!(stream, "{}}}", " ".repeat(1023)).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408"));
+    }
+}
@@ -0,0 +1,71 @@
+//! Adapter for WebAssembly runtimes such as Cloudflare Workers
+//!
+//! With the `wasm-support` feature (and without the default features), the core of rifling compiles for
+//! `wasm32-unknown-unknown`, payloads are authenticated with the libraries from RustCrypto team.
+//!
+//! The runtime's request is passed to `Handler::handle_request` as a map of headers and the body:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery, Handler, Hook};
+//!
+//! use std::collections::HashMap;
+//!
+//! let mut cons = Constructor::new();
+//! cons.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+//! let handler = Handler::from(&cons);
+//!
+//! // Taken from the request received by the worker
+//! let mut headers = HashMap::new();
+//! headers.insert("X-GitHub-Event".to_string(), "push".to_string());
+//! let body = "{}".to_string();
+//!
+//! let response = handler.handle_request(headers, body);
+//! assert_eq!(response.status(), 200);
+//! ```
+//!
+//! There are no threads in `wasm32-unknown-unknown`, so retries (`Hook::retries`) can't be scheduled and responses
+//! can't be streamed (`Hook::stream_response`), and neither the system clock: rate limits and
+//! `Constructor::max_delivery_age` must not be configured.
+
+use std::collections::HashMap;
+
+use super::super::outcome::Response;
+use super::Handler;
+
+/// WebAssembly-related impl clause of `Handler`
+impl Handler {
+    /// Handle a request given by its headers and body, see `Handler::handle`
+    pub fn handle_request(&self, headers: HashMap<String, String>, body: String) -> Response {
+        self.handle(headers, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::outcome::Outcome;
+    use super::super::{Constructor, Delivery};
+    use super::*;
+    use crate::hook::Hook;
+
+    /// Test handling requests with and without matched hooks
+    #[test]
+    fn handle_request() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let handler = Handler::from(&cons);
+        let request = |event: &str| {
+            let mut headers = HashMap::new();
+            headers.insert("X-Gitlab-Event".to_string(), event.to_string());
+            handler.handle_request(headers, "{}".to_string())
+        };
+        assert_eq!(request("Push").status(), 200);
+        let response = request("Tag Push Hook");
+        assert_eq!(response.outcome, Outcome::NoMatch);
+        assert_eq!(response.delivery.unwrap().event, "tag_push_hook");
+        let response = handler.handle_request(HashMap::new(), String::new());
+        assert_eq!(response.status(), 202);
+        assert!(response.delivery.is_none());
+    }
+}
@@ -15,29 +15,42 @@
 //! let hook = Hook::new("push", None, |_: &Delivery| println!("Pushed!"));
 //! ```
 //!
-//! The last parameter is a trait object of the trait `HookFunc`, it's currently implemented to `Fn(&Delivery)`.
-//! `Delivery` contains the information of the request received.
+//! The last parameter is a trait object of the trait `HookFunc`, it's currently implemented to `Fn(&Delivery)`
+//! returning either `()` or `Result<(), E>` where `E` implements `Display`.
+//! `Delivery` contains the information of the request received. Hooks created with `Hook::with_context` receive the
+//! `Context` of the run too.
 //!
 //! To use the hook, you need to register it to the `Constructor`.
 
-#[cfg(any(feature = "crypto-use-rustcrypto", feature = "crypto-use-ring"))]
-use hex::FromHex;
-#[cfg(feature = "crypto-use-rustcrypto")]
-use hmac::{Hmac, Mac};
-#[cfg(feature = "crypto-use-ring")]
-use ring::digest;
-#[cfg(feature = "crypto-use-ring")]
-use ring::hmac;
-#[cfg(feature = "crypto-use-rustcrypto")]
-use sha1::Sha1;
+#[cfg(feature = "chrono")]
+use chrono::{TimeZone, Weekday};
+#[cfg(feature = "regex-match")]
+use regex::Regex;
+#[cfg(feature = "typed")]
+use serde::de::DeserializeOwned;
 
+use std::any::{self, Any};
+use std::fmt::Display;
+use std::marker::PhantomData;
+#[cfg(feature = "chrono")]
+use std::ops::{Range, RangeInclusive};
+use std::slice;
 use std::sync::Arc;
+use std::time::Duration;
 
+use super::context::Context;
+use super::crypto;
+use super::crypto::{Algorithm, SignatureEncoding};
+use super::error::Error;
+use super::filter::Condition;
 use super::handler::Delivery;
 use super::handler::DeliveryType;
-
-#[cfg(feature = "crypto-use-rustcrypto")]
-type HmacSha1 = Hmac<Sha1>;
+use super::schedule::Schedule;
+#[cfg(feature = "client")]
+use super::status::CommitStatus;
+use super::verify;
+#[cfg(feature = "chrono")]
+use super::window::{ActiveWindow, OutOfWindow};
 
 /// Unwrap `Option<T>` or return false
 #[macro_export]
@@ -50,10 +63,130 @@ macro_rules! unwrap_or_false {
     };
 }
 
+/// Build hooks sharing the same secret
+///
+/// Example:
+///
+/// ```
+/// #[macro_use]
+/// extern crate rifling;
+///
+/// use rifling::{Constructor, Delivery};
+///
+/// # fn main() {
+/// let mut cons = Constructor::new();
+/// cons.register_all(hooks! {
+///     secret: Some(String::from("secret")),
+///     "push" => |_: &Delivery| println!("Pushed!"),
+///     "issues" => |delivery: &Delivery| println!("Issue event: {:?}", delivery.id()),
+/// });
+/// # }
+/// ```
+///
+/// Without `secret: ...,` the hooks are created without secret.
+#[macro_export]
+macro_rules! hooks {
+    (secret: $secret:expr, $($event:expr => $func:expr),* $(,)?) => {{
+        let secret: Option<String> = $secret;
+        let hooks: Vec<$crate::Hook> = vec![
+            $($crate::Hook::new($event, secret.clone(), $func)),*
+        ];
+        hooks
+    }};
+    ($($event:expr => $func:expr),* $(,)?) => {
+        $crate::hooks!(secret: None, $($event => $func),*)
+    };
+}
+
 /// The part of the hook that will be executed after validating the payload
 /// You can implement this trait to your own struct
 pub trait HookFunc: Sync + Send {
-    fn run(&self, delivery: &Delivery);
+    fn run(&self, delivery: &Delivery) -> Result<(), Error>;
+
+    /// Run with the state set by `Constructor::with_state`, which is ignored by default
+    fn run_with_state(
+        &self,
+        _state: Option<&(dyn Any + Send + Sync)>,
+        delivery: &Delivery,
+    ) -> Result<(), Error> {
+        self.run(delivery)
+    }
+
+    /// Run with the context of the run, only the state is used by default
+    fn run_with_context(&self, context: &Context, delivery: &Delivery) -> Result<(), Error> {
+        self.run_with_state(context.any_state(), delivery)
+    }
+
+    /// Run with a batch of deliveries, see `Hook::batch`, they're run one by one by default
+    fn run_batch(&self, context: &Context, deliveries: &[Delivery]) -> Result<(), Error> {
+        deliveries
+            .iter()
+            .try_for_each(|delivery| self.run_with_context(context, delivery))
+    }
+
+    /// Validate the delivery before any hook is run, deliveries failing validation are rejected with
+    /// `422 Unprocessable Entity`, they're all valid by default
+    fn validate(&self, _delivery: &Delivery) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Hook function with access to the state shared by all hooks, set by `Constructor::with_state`
+/// You can implement this trait to your own struct
+pub trait HookFuncWithState<S>: Sync + Send {
+    fn run(&self, state: &S, delivery: &Delivery) -> Result<(), Error>;
+}
+
+/// Hook function receiving the context of the run, see `Hook::with_context`
+/// You can implement this trait to your own struct
+pub trait HookFuncWithContext: Sync + Send {
+    fn run(&self, context: &Context, delivery: &Delivery) -> Result<(), Error>;
+}
+
+/// Hook function receiving the payload deserialized into its own type, see `Hook::typed`
+/// You can implement this trait to your own struct
+#[cfg(feature = "typed")]
+pub trait HookFuncWithPayload<T>: Sync + Send {
+    fn run(&self, payload: T, delivery: &Delivery) -> Result<(), Error>;
+}
+
+/// Hook function receiving the deliveries gathered by `Hook::batch` at once
+/// You can implement this trait to your own struct
+pub trait BatchHookFunc: Sync + Send {
+    fn run(&self, deliveries: &[Delivery]) -> Result<(), Error>;
+}
+
+/// `HookFunc` wrapping a `BatchHookFunc`
+struct BatchedHookFunc<F>(F);
+
+/// `HookFunc` wrapping a `HookFuncWithContext`, run with a new context for the event when run without one
+struct ContextHookFunc<F> {
+    func: F,
+    pattern: &'static str,
+}
+
+/// `HookFunc` wrapping a `HookFuncWithPayload`
+#[cfg(feature = "typed")]
+struct TypedHookFunc<T, F> {
+    func: F,
+    payload_type: PhantomData<fn(T)>,
+}
+
+/// `HookFunc` wrapping a `HookFuncWithState`
+struct StatefulHookFunc<S, F> {
+    func: F,
+    state_type: PhantomData<fn(&S)>,
+}
+
+/// Custom authentication of deliveries, replacing the built-in verification of the hook
+/// You can implement this trait to your own struct
+pub trait Authenticator: Sync + Send {
+    fn verify(&self, delivery: &Delivery) -> Result<(), Error>;
+}
+
+/// Return value of functions used as `HookFunc`
+pub trait HookResult {
+    fn into_result(self) -> Result<(), Error>;
 }
 
 /// The actual hook, contains the event it's going to listen, the secret to authenticate the payload, and the function to execute.
@@ -61,20 +194,178 @@ pub trait HookFunc: Sync + Send {
 pub struct Hook {
     pub event: &'static str,
     pub secret: Option<String>,
-    pub func: Arc<HookFunc>, // To allow the registration of multiple hooks, it has to be a trait object.
+    pub algorithms: Vec<Algorithm>,
+    pub signature_encoding: SignatureEncoding,
+    pub signature_prefix: Option<String>,
+    pub retries: u32,
+    pub backoff: Duration,
+    pub schedule: Option<Schedule>,
+    #[cfg(feature = "chrono")]
+    pub active_window: Option<ActiveWindow>, // set by `Hook::active_between`
+    #[cfg(feature = "chrono")]
+    pub out_of_window: OutOfWindow, // set by `Hook::outside_window`
+    #[cfg(feature = "client")]
+    pub commit_status: Option<Arc<CommitStatus>>,
+    pub authenticator: Option<Arc<dyn Authenticator>>,
+    pub conditions: Vec<Arc<dyn Condition>>,
+    pub group: Option<String>, // set by `Constructor::group`
+    pub name: Option<String>,  // set by `Hook::named`
+    #[cfg(feature = "regex-match")]
+    pub pattern: Option<Regex>, // set by `Hook::matching`
+    pub fallback: bool,        // set by `Hook::fallback`
+    pub providers: Vec<DeliveryType>, // set by `Hook::providers`, empty for every provider
+    pub stream_response: bool, // set by `Hook::stream_response`
+    pub dry_run: Option<bool>, // set by `Hook::dry_run`, `None` to follow `Constructor::dry_run`
+    pub unsigned_docker_hub: bool, // set by `Hook::accept_unsigned_docker_hub`
+    pub func: Arc<dyn HookFunc>, // To allow the registration of multiple hooks, it has to be a trait object.
+}
+
+/// Implement `HookResult` to `()`, hooks returning nothing never fail
+impl HookResult for () {
+    fn into_result(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Implement `HookResult` to `Result<(), E>`, the error is converted into `Error::HookFailed`
+impl<E: Display> HookResult for Result<(), E> {
+    fn into_result(self) -> Result<(), Error> {
+        self.map_err(|error| Error::HookFailed(error.to_string()))
+    }
 }
 
 /// Implement `HookFunc` to `Fn(&Delivery)`.
-impl<F> HookFunc for F
+impl<F, R> HookFunc for F
 where
-    F: Fn(&Delivery) + Clone + Sync + Send + 'static,
+    F: Fn(&Delivery) -> R + Clone + Sync + Send + 'static,
+    R: HookResult,
 {
     /// Run the function
-    fn run(&self, delivery: &Delivery) {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self(delivery).into_result()
+    }
+}
+
+/// Implement `Authenticator` to `Fn(&Delivery) -> Result<(), Error>`.
+impl<F> Authenticator for F
+where
+    F: Fn(&Delivery) -> Result<(), Error> + Sync + Send + 'static,
+{
+    /// Run the function
+    fn verify(&self, delivery: &Delivery) -> Result<(), Error> {
         self(delivery)
     }
 }
 
+/// Implement `HookFuncWithState<S>` to `Fn(&S, &Delivery)`.
+impl<S, F, R> HookFuncWithState<S> for F
+where
+    F: Fn(&S, &Delivery) -> R + Sync + Send + 'static,
+    R: HookResult,
+{
+    /// Run the function
+    fn run(&self, state: &S, delivery: &Delivery) -> Result<(), Error> {
+        self(state, delivery).into_result()
+    }
+}
+
+/// Implement `HookFunc` to `StatefulHookFunc`, it fails if the state is not set or has a different type
+impl<S, F> HookFunc for StatefulHookFunc<S, F>
+where
+    S: Any + Send + Sync,
+    F: HookFuncWithState<S>,
+{
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.run_with_state(None, delivery)
+    }
+
+    fn run_with_state(
+        &self,
+        state: Option<&(dyn Any + Send + Sync)>,
+        delivery: &Delivery,
+    ) -> Result<(), Error> {
+        match state.and_then(|state| state.downcast_ref::<S>()) {
+            Some(state) => self.func.run(state, delivery),
+            None => Err(Error::StateUnavailable(any::type_name::<S>())),
+        }
+    }
+}
+
+/// Implement `HookFuncWithPayload<T>` to `Fn(T, &Delivery)`.
+#[cfg(feature = "typed")]
+impl<T, F, R> HookFuncWithPayload<T> for F
+where
+    F: Fn(T, &Delivery) -> R + Sync + Send + 'static,
+    R: HookResult,
+{
+    /// Run the function
+    fn run(&self, payload: T, delivery: &Delivery) -> Result<(), Error> {
+        self(payload, delivery).into_result()
+    }
+}
+
+/// Implement `HookFunc` to `TypedHookFunc`, deliveries whose payload doesn't deserialize are rejected
+#[cfg(feature = "typed")]
+impl<T, F> HookFunc for TypedHookFunc<T, F>
+where
+    T: DeserializeOwned,
+    F: HookFuncWithPayload<T>,
+{
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.func.run(delivery.parse_as()?, delivery)
+    }
+
+    fn validate(&self, delivery: &Delivery) -> Result<(), Error> {
+        delivery.parse_as::<T>().map(|_| ())
+    }
+}
+
+/// Implement `HookFuncWithContext` to `Fn(&Context, &Delivery)`.
+impl<F, R> HookFuncWithContext for F
+where
+    F: Fn(&Context, &Delivery) -> R + Sync + Send + 'static,
+    R: HookResult,
+{
+    /// Run the function
+    fn run(&self, context: &Context, delivery: &Delivery) -> Result<(), Error> {
+        self(context, delivery).into_result()
+    }
+}
+
+/// Implement `HookFunc` to `ContextHookFunc`
+impl<F: HookFuncWithContext> HookFunc for ContextHookFunc<F> {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.func.run(&Context::new(self.pattern), delivery)
+    }
+
+    fn run_with_context(&self, context: &Context, delivery: &Delivery) -> Result<(), Error> {
+        self.func.run(context, delivery)
+    }
+}
+
+/// Implement `BatchHookFunc` to `Fn(&[Delivery]) -> R`
+impl<F, R> BatchHookFunc for F
+where
+    F: Fn(&[Delivery]) -> R + Sync + Send + 'static,
+    R: HookResult,
+{
+    /// Run the function
+    fn run(&self, deliveries: &[Delivery]) -> Result<(), Error> {
+        self(deliveries).into_result()
+    }
+}
+
+/// Implement `HookFunc` to `BatchedHookFunc`, single deliveries are run as batches of one
+impl<F: BatchHookFunc> HookFunc for BatchedHookFunc<F> {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.0.run(slice::from_ref(delivery))
+    }
+
+    fn run_batch(&self, _context: &Context, deliveries: &[Delivery]) -> Result<(), Error> {
+        self.0.run(deliveries)
+    }
+}
+
 /// Main impl clause of `Hook`()
 impl Hook {
     /// Create a new hook
@@ -92,95 +383,634 @@ impl Hook {
         Self {
             event,
             secret,
+            algorithms: Algorithm::ALL.to_vec(),
+            signature_encoding: SignatureEncoding::Hex,
+            signature_prefix: None,
+            retries: 0,
+            backoff: Duration::from_secs(30),
+            schedule: None,
+            #[cfg(feature = "chrono")]
+            active_window: None,
+            #[cfg(feature = "chrono")]
+            out_of_window: OutOfWindow::Drop,
+            #[cfg(feature = "client")]
+            commit_status: None,
+            authenticator: None,
+            conditions: Vec::new(),
+            group: None,
+            name: None,
+            #[cfg(feature = "regex-match")]
+            pattern: None,
+            fallback: false,
+            providers: Vec::new(),
+            stream_response: false,
+            dry_run: None,
+            unsigned_docker_hub: false,
             func: Arc::new(func),
         }
     }
 
-    #[cfg(feature = "crypto-use-ring")]
-    /// Authenticate the payload from GitHub using `ring`
-    pub fn auth_github(&self, delivery: &Delivery) -> bool {
-        let secret = unwrap_or_false!(&self.secret);
-        let signature = unwrap_or_false!(&delivery.signature);
-        debug!("Received signature: {}", signature);
-        let request_body = unwrap_or_false!(&delivery.request_body);
-        debug!("Request body: {}", &request_body);
-        let signature_hex = signature[5..signature.len()].as_bytes();
-        if let Ok(signature_bytes) = Vec::from_hex(signature_hex) {
-            let secret_bytes = secret.as_bytes();
-            let request_body_bytes = request_body.as_bytes();
-            let key = hmac::SigningKey::new(&digest::SHA1, &secret_bytes);
-            debug!("Validating payload with given secret");
-            return hmac::verify_with_own_key(&key, &request_body_bytes, &signature_bytes).is_ok();
+    /// Create a new hook with access to the state set by `Constructor::with_state`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, Delivery, Hook};
+    ///
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// struct AppState {
+    ///     pushes: AtomicUsize,
+    /// }
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.with_state(AppState { pushes: AtomicUsize::new(0) });
+    /// cons.register(Hook::with_state("push", None, |state: &AppState, _: &Delivery| {
+    ///     state.pushes.fetch_add(1, Ordering::SeqCst);
+    /// }));
+    /// ```
+    pub fn with_state<S: Any + Send + Sync>(
+        event: &'static str,
+        secret: Option<String>,
+        func: impl HookFuncWithState<S> + 'static,
+    ) -> Self {
+        Self::new(
+            event,
+            secret,
+            StatefulHookFunc {
+                func,
+                state_type: PhantomData,
+            },
+        )
+    }
+
+    /// Create a new hook receiving the payload deserialized into the type, requires `typed`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    /// extern crate serde;
+    ///
+    /// use rifling::{Delivery, Hook};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Push {
+    ///     after: String,
+    /// }
+    ///
+    /// let hook = Hook::typed("push", None, |push: Push, _: &Delivery| println!("Pushed {}", push.after));
+    /// ```
+    ///
+    /// Deliveries whose payload doesn't deserialize into the type are rejected with `422 Unprocessable Entity`
+    /// before any hook is run.
+    #[cfg(feature = "typed")]
+    pub fn typed<T: DeserializeOwned + 'static>(
+        event: &'static str,
+        secret: Option<String>,
+        func: impl HookFuncWithPayload<T> + 'static,
+    ) -> Self {
+        Self::new(
+            event,
+            secret,
+            TypedHookFunc {
+                func,
+                payload_type: PhantomData,
+            },
+        )
+    }
+
+    /// Create a new hook receiving the `Context` of the run along with the delivery, see `rifling::context`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Context, Delivery, Hook};
+    ///
+    /// let hook = Hook::with_context("push", None, |context: &Context, _: &Delivery| {
+    ///     println!("Attempt {} for '{}'", context.attempt(), context.pattern());
+    /// })
+    /// .retries(3);
+    /// ```
+    pub fn with_context(
+        event: &'static str,
+        secret: Option<String>,
+        func: impl HookFuncWithContext + 'static,
+    ) -> Self {
+        Self::new(
+            event,
+            secret,
+            ContextHookFunc {
+                func,
+                pattern: event,
+            },
+        )
+    }
+
+    /// Create a new hook receiving the deliveries gathered for the window (10 seconds by default) at once, or as
+    /// soon as the maximum number of deliveries (100 by default) is reached, see `Hook::batch_window`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Delivery, Hook};
+    ///
+    /// use std::time::Duration;
+    ///
+    /// let hook = Hook::batch("push", None, |deliveries: &[Delivery]| {
+    ///     println!("Building once for {} pushes", deliveries.len());
+    /// })
+    /// .batch_window(Duration::from_secs(30), 50);
+    /// ```
+    ///
+    /// Failed batches are not retried, the error is recorded in the store for every delivery of the batch.
+    pub fn batch(
+        event: &'static str,
+        secret: Option<String>,
+        func: impl BatchHookFunc + 'static,
+    ) -> Self {
+        let mut hook = Self::new(event, secret, BatchedHookFunc(func));
+        hook.schedule = Some(Schedule::Batch {
+            window: Duration::from_secs(10),
+            max: 100,
+        });
+        hook
+    }
+
+    /// Name the hook (e.g. `deploy-prod`), the name is used in logs, statistics, the access log and errors
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Delivery, Hook};
+    ///
+    /// let hook = Hook::new("*", None, |_: &Delivery| println!("Deploying...")).named("deploy-prod");
+    /// assert_eq!(hook.label(), "hook 'deploy-prod'");
+    /// ```
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Describe the hook in logs, by its name or by its event
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("hook '{}'", name),
+            None => format!("hook for '{}' event", self.event),
         }
-        debug!("Invalid signature");
-        return false;
-    }
-
-    #[cfg(feature = "crypto-use-rustcrypto")]
-    /// Authenticate the payload from GitHub using crates provided by RustCrypto team
-    pub fn auth_github(&self, delivery: &Delivery) -> bool {
-        let secret = unwrap_or_false!(&self.secret);
-        let signature = unwrap_or_false!(&delivery.signature);
-        debug!("Received signature: {}", &signature);
-        let request_body = unwrap_or_false!(&delivery.request_body);
-        debug!("Request body: {}", &request_body);
-        let signature_hex = signature[5..signature.len()].as_bytes();
-        if let Ok(signature_bytes) = Vec::from_hex(signature_hex) {
-            let secret_bytes = secret.as_bytes();
-            let request_body_bytes = request_body.as_bytes();
-            let mut mac = unwrap_or_false!(HmacSha1::new_varkey(secret_bytes).ok());
-            mac.input(request_body_bytes);
-            debug!("Validating payload with given secret");
-            return mac.verify(&signature_bytes).is_ok();
+    }
+
+    /// Identify the hook among those sharing its event, clones of a hook (e.g. in scheduled jobs) share its identity
+    pub(crate) fn identity(&self) -> usize {
+        Arc::as_ptr(&self.func) as *const () as usize
+    }
+
+    /// Attach the name of the hook to the error, if named
+    pub(crate) fn named_error(&self, error: Error) -> Error {
+        match &self.name {
+            Some(name) => Error::Hook {
+                name: name.clone(),
+                error: Box::new(error),
+            },
+            None => error,
         }
-        debug!("Invalid signature");
-        return false;
-    }
-
-    #[cfg(all(
-        not(feature = "crypto-use-rustcrypto"),
-        not(feature = "crypto-use-ring")
-    ))]
-    /// With no cryptography library enabled, we are unable to authenticate payload.
-    fn auth_github(&self, _delivery: &Delivery) -> bool {
-        warn!(
-            "Unable to authenticate GitHub payload due to lack of cryptography support, passing..."
-        );
-        true
     }
 
-    /// Authenticate payload from GitLab, it does not require any cryptography algorithm
-    fn auth_gitlab(&self, delivery: &Delivery) -> bool {
-        let secret = unwrap_or_false!(&self.secret);
-        let signature = unwrap_or_false!(&delivery.signature);
-        debug!("Received token: {}", &signature);
-        if signature == secret {
-            true
+    /// Set the HMAC algorithms accepted when authenticating signed payloads (all of them by default)
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Hook, Delivery};
+    /// use rifling::crypto::Algorithm;
+    ///
+    /// let hook = Hook::new("push", None, |_: &Delivery| println!("Pushed!"))
+    ///     .with_algorithms(&[Algorithm::Sha256, Algorithm::Sha512]);
+    /// ```
+    pub fn with_algorithms(mut self, algorithms: &[Algorithm]) -> Self {
+        self.algorithms = algorithms.to_vec();
+        self
+    }
+
+    /// Set the encoding of the digest in the signature (hex by default)
+    pub fn with_signature_encoding(mut self, encoding: SignatureEncoding) -> Self {
+        self.signature_encoding = encoding;
+        self
+    }
+
+    /// Set a custom prefix of the signature (e.g. `v1=`), which replaces the `<algorithm>=` prefix.
+    /// Signature will be checked against every accepted algorithm.
+    pub fn with_signature_prefix(mut self, prefix: &str) -> Self {
+        self.signature_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Authenticate deliveries with the authenticator instead of the built-in verification, the secret is ignored
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Delivery, Error, Hook};
+    ///
+    /// let hook = Hook::new("push", None, |_: &Delivery| println!("Pushed!"))
+    ///     .with_authenticator(|delivery: &Delivery| match delivery.signature() {
+    ///         Some(signature) if signature.ends_with("rifling") => Ok(()),
+    ///         Some(_) => Err(Error::InvalidSignature),
+    ///         None => Err(Error::MissingSignature),
+    ///     });
+    /// ```
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Only run the hook for deliveries satisfying the condition, see `rifling::filter`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Delivery, Hook};
+    ///
+    /// let hook = Hook::new("push", None, |_: &Delivery| println!("Pushed from the dashboard!"))
+    ///     .when(|delivery: &Delivery| delivery.headers().contains_key("x-dashboard-user"));
+    /// ```
+    pub fn when(mut self, condition: impl Condition + 'static) -> Self {
+        self.conditions.push(Arc::new(condition));
+        self
+    }
+
+    /// Match events with the regular expression instead of the event of the hook, which is only used to register it
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate regex;
+    /// extern crate rifling;
+    ///
+    /// use regex::Regex;
+    /// use rifling::{Constructor, Delivery, Hook};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.register(
+    ///     Hook::new("issue-events", None, |delivery: &Delivery| println!("{}", delivery.event()))
+    ///         .matching(Regex::new("^(issues|issue_comment)$").unwrap()),
+    /// );
+    /// ```
+    ///
+    /// Events are matched after normalization (see `Constructor::event_normalization`), aliases are matched too.
+    #[cfg(feature = "regex-match")]
+    pub fn matching(mut self, pattern: Regex) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Run the hook only for deliveries no other hook matched, whatever their event, instead of the event of the hook
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, Delivery, Hook};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+    /// cons.register(
+    ///     Hook::new("*", None, |delivery: &Delivery| println!("Unexpected event: {}", delivery.event())).fallback(),
+    /// );
+    /// ```
+    ///
+    /// Unlike hooks registered for `*`, it doesn't run when other hooks matched, including hooks for `*`. Only one
+    /// fallback hook can be registered, it may respond with `Context::respond` (see `Hook::with_context`).
+    pub fn fallback(mut self) -> Self {
+        self.fallback = true;
+        self
+    }
+
+    /// Run the hook only for deliveries from the providers, instead of every provider sending the event
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Delivery, DeliveryType, Hook};
+    ///
+    /// let hook = Hook::new("push", None, |_: &Delivery| println!("Pushed to GitHub!"))
+    ///     .providers(&[DeliveryType::GitHub]);
+    /// ```
+    ///
+    /// Deliveries from other providers are handled as if the hook was not registered.
+    pub fn providers(mut self, providers: &[DeliveryType]) -> Self {
+        self.providers = providers.to_vec();
+        self
+    }
+
+    /// Stream the fragments the hook adds with `Context::respond` to the response as it adds them, instead of
+    /// sending the response once it's run
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Context, Delivery, Hook};
+    ///
+    /// let hook = Hook::with_context("deploy", None, |context: &Context, _: &Delivery| {
+    ///     context.respond("Building...");
+    ///     context.respond("Deploying...");
+    ///     context.respond("Done");
+    /// })
+    /// .stream_response();
+    /// ```
+    ///
+    /// Useful for long hooks triggered by hand, e.g. with `curl -N`. The status and headers are sent before the hooks
+    /// matching the delivery are run (in a separate thread), so they don't tell whether the hooks succeeded. Servers
+    /// without threads (WebAssembly runtimes) can't stream responses.
+    pub fn stream_response(mut self) -> Self {
+        self.stream_response = true;
+        self
+    }
+
+    /// Skip the hook function (or run it, with `false`) whatever `Constructor::dry_run` is set to, e.g. to try a new
+    /// hook against production traffic
+    pub fn dry_run(mut self, enable: bool) -> Self {
+        self.dry_run = Some(enable);
+        self
+    }
+
+    /// Accept deliveries from Docker Hub even though the hook has a secret, Docker Hub doesn't sign its deliveries so
+    /// they're rejected with `Error::MissingSignature` otherwise
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Delivery, Hook};
+    ///
+    /// let hook = Hook::new("push", Some("secret".to_string()), |_: &Delivery| println!("Image pushed"))
+    ///     .accept_unsigned_docker_hub();
+    /// ```
+    ///
+    /// Anyone able to reach the hook can trigger it with a Docker Hub shaped body, restrict the clients (e.g. with
+    /// `Constructor::allowed_clients`) or use a secret URL instead.
+    pub fn accept_unsigned_docker_hub(mut self) -> Self {
+        self.unsigned_docker_hub = true;
+        self
+    }
+
+    /// Test if the hook accepts deliveries from the provider, see `Hook::providers`
+    pub(crate) fn accepts(&self, provider: DeliveryType) -> bool {
+        self.providers.is_empty() || self.providers.contains(&provider)
+    }
+
+    /// Test if the hook matches events with a regular expression, see `Hook::matching`
+    pub(crate) fn has_pattern(&self) -> bool {
+        #[cfg(feature = "regex-match")]
+        return self.pattern.is_some();
+        #[cfg(not(feature = "regex-match"))]
+        false
+    }
+
+    /// Test if the regular expression of the hook matches one of the events
+    #[cfg(feature = "regex-match")]
+    pub(crate) fn matches_event(&self, events: &[String]) -> bool {
+        match &self.pattern {
+            Some(pattern) => events
+                .iter()
+                .any(|event| event != "*" && pattern.is_match(event)),
+            None => false,
+        }
+    }
+
+    /// Test if the delivery satisfies all of the conditions of the hook
+    pub fn matches(&self, delivery: &Delivery) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(delivery))
+    }
+
+    /// Set how many times the hook is retried when it fails (0 by default)
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set the delay before the first retry (30 seconds by default), doubled after every attempt
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Run the hook the duration after the delivery, see `rifling::schedule`
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.schedule = Some(Schedule::Delay(delay));
+        self
+    }
+
+    /// Run the hook once no other delivery of the same repository is received for the duration, with the last one
+    pub fn debounce(mut self, period: Duration) -> Self {
+        self.schedule = Some(Schedule::Debounce(period));
+        self
+    }
+
+    /// Run the hook at most once per duration and repository, with the last delivery received meanwhile
+    pub fn throttle(mut self, period: Duration) -> Self {
+        self.schedule = Some(Schedule::Throttle(period));
+        self
+    }
+
+    /// Gather deliveries for the window, or until there are `max` of them, and run the hook with them at once
+    pub fn batch_window(mut self, window: Duration, max: usize) -> Self {
+        self.schedule = Some(Schedule::Batch { window, max });
+        self
+    }
+
+    /// Only run the hook between the times (`HH:MM`) on the days, in the time zone, see `rifling::window`
+    ///
+    /// Fails with `Error::InvalidWindow` if the times are not `HH:MM`.
+    #[cfg(feature = "chrono")]
+    pub fn active_between<Tz>(
+        mut self,
+        hours: Range<&str>,
+        days: RangeInclusive<Weekday>,
+        tz: Tz,
+    ) -> Result<Self, Error>
+    where
+        Tz: TimeZone + Send + Sync + 'static,
+    {
+        self.active_window = Some(ActiveWindow::try_new(hours, days, tz)?);
+        Ok(self)
+    }
+
+    /// Set what happens to deliveries received outside the active window (`OutOfWindow::Drop` by default)
+    #[cfg(feature = "chrono")]
+    pub fn outside_window(mut self, policy: OutOfWindow) -> Self {
+        self.out_of_window = policy;
+        self
+    }
+
+    /// Report the outcome of the hook as a status of the commit in the delivery, see `rifling::status`
+    #[cfg(feature = "client")]
+    pub fn report_status(mut self, status: CommitStatus) -> Self {
+        self.commit_status = Some(Arc::new(status));
+        self
+    }
+
+    /// Verify the HMAC signature of the payload (from GitHub, Forgejo, Gitea, Gerrit, Linear, Intercom or npm)
+    fn verify_hmac(&self, secret: &str, delivery: &Delivery) -> Result<(), Error> {
+        if !crypto::is_available() {
+            warn!("Unable to authenticate signed payload due to lack of cryptography support");
+            return Err(Error::CryptoUnavailable);
+        }
+        let signature = delivery.signature.as_ref().ok_or(Error::MissingSignature)?;
+        let request_body = delivery
+            .request_body
+            .as_ref()
+            .ok_or(Error::InvalidSignature)?;
+        let (algorithm, digest) = if let Some(prefix) = &self.signature_prefix {
+            if !signature.starts_with(prefix.as_str()) {
+                debug!("Signature does not start with '{}'", prefix);
+                return Err(Error::InvalidSignature);
+            }
+            (None, &signature[prefix.len()..])
         } else {
-            debug!("Invalid token");
-            false
+            crypto::split_signature(signature)
+        };
+        let candidates: Vec<Algorithm> = match algorithm {
+            Some(algorithm) if self.algorithms.contains(&algorithm) => vec![algorithm],
+            Some(algorithm) => {
+                debug!("Algorithm '{}' is not accepted", algorithm.name());
+                return Err(Error::InvalidSignature);
+            }
+            None => self.algorithms.clone(),
+        };
+        verify::hmac(
+            &candidates,
+            self.signature_encoding,
+            secret,
+            request_body.as_bytes(),
+            digest,
+        )
+    }
+
+    /// Verify the token of the payload (from GitLab, Jenkins, Telegram, Artifactory or Kubernetes), it does not require any
+    /// cryptography algorithm
+    fn verify_token(&self, secret: &str, delivery: &Delivery) -> Result<(), Error> {
+        let token = delivery.signature.as_ref().ok_or(Error::MissingSignature)?;
+        verify::token(secret, token)
+    }
+
+    /// Verify the signature of the payload from CircleCI, see `verify::circleci`
+    fn verify_circleci(&self, secret: &str, delivery: &Delivery) -> Result<(), Error> {
+        if !crypto::is_available() {
+            warn!("Unable to authenticate signed payload due to lack of cryptography support");
+            return Err(Error::CryptoUnavailable);
         }
+        let signature = delivery.signature.as_ref().ok_or(Error::MissingSignature)?;
+        let request_body = delivery
+            .request_body
+            .as_ref()
+            .ok_or(Error::InvalidSignature)?;
+        verify::circleci(secret, request_body.as_bytes(), signature)
     }
 
-    /// Authenticate payload
-    pub fn auth(&self, delivery: &Delivery) -> bool {
-        if self.secret.is_some() {
+    /// Verify the Ed25519 signature of the timestamp and the body from Discord, the secret is the hex encoded
+    /// public key of the application
+    fn verify_discord(&self, secret: &str, delivery: &Delivery) -> Result<(), Error> {
+        if !crypto::is_ed25519_available() {
+            warn!("Unable to authenticate signed payload due to lack of Ed25519 support");
+            return Err(Error::CryptoUnavailable);
+        }
+        let signature = delivery.signature.as_ref().ok_or(Error::MissingSignature)?;
+        // The timestamp is signed as sent, not as parsed into `Delivery::timestamp`
+        let timestamp = delivery
+            .header("x-signature-timestamp")
+            .ok_or(Error::MissingSignature)?;
+        let request_body = delivery
+            .request_body
+            .as_ref()
+            .ok_or(Error::InvalidSignature)?;
+        verify::discord(secret, timestamp, request_body.as_bytes(), signature)
+    }
+
+    /// Verify the `Authorization` header of the payload from Grafana, see `verify::grafana`
+    fn verify_grafana(&self, secret: &str, delivery: &Delivery) -> Result<(), Error> {
+        let authorization = delivery.signature.as_ref().ok_or(Error::MissingSignature)?;
+        verify::grafana(secret, authorization)
+    }
+
+    /// Verify the token of the payload from Gitee, see `verify::gitee`
+    fn verify_gitee(&self, secret: &str, delivery: &Delivery) -> Result<(), Error> {
+        let token = delivery.signature.as_ref().ok_or(Error::MissingSignature)?;
+        verify::gitee(secret, token, delivery.timestamp)
+    }
+
+    /// Verify payload, returns the reason if it's rejected
+    pub fn verify(&self, delivery: &Delivery) -> Result<(), Error> {
+        if let Some(authenticator) = &self.authenticator {
+            return authenticator.verify(delivery);
+        }
+        if let Some(secret) = &self.secret {
             match delivery.delivery_type {
-                DeliveryType::GitHub => self.auth_github(delivery),
-                DeliveryType::GitLab => self.auth_gitlab(delivery),
-                _ => true, // Not supported (e.g. Docker Hub, it sucks)
+                DeliveryType::GitHub
+                | DeliveryType::Forgejo
+                | DeliveryType::Gitea
+                | DeliveryType::Gerrit
+                | DeliveryType::Linear
+                | DeliveryType::Intercom
+                | DeliveryType::Npm => self.verify_hmac(secret, delivery),
+                DeliveryType::GitLab
+                | DeliveryType::Jenkins
+                | DeliveryType::Form
+                | DeliveryType::Telegram
+                | DeliveryType::Artifactory
+                | DeliveryType::Kubernetes => self.verify_token(secret, delivery),
+                DeliveryType::Gitee => self.verify_gitee(secret, delivery),
+                DeliveryType::CircleCI => self.verify_circleci(secret, delivery),
+                DeliveryType::Grafana => self.verify_grafana(secret, delivery),
+                DeliveryType::Discord => self.verify_discord(secret, delivery),
+                DeliveryType::DockerHub if self.unsigned_docker_hub => {
+                    debug!("Docker Hub doesn't sign deliveries, passing...");
+                    Ok(())
+                }
+                // Nothing to verify the secret against, fail closed
+                DeliveryType::DockerHub | DeliveryType::Unknown => Err(Error::MissingSignature),
             }
         } else {
             debug!("No secret given, passing...");
-            true
+            Ok(())
         }
     }
 
+    /// Authenticate payload
+    pub fn auth(&self, delivery: &Delivery) -> bool {
+        self.verify(delivery).is_ok()
+    }
+
     /// Handle the request
     pub fn handle_delivery(self, delivery: &Delivery) {
         if self.auth(delivery) {
             debug!("Valid payload found");
-            self.func.run(delivery);
+            if let Err(error) = self.func.run(delivery) {
+                debug!("Hook failed: {}", error);
+            }
             return;
         }
         debug!("Invalid payload");
@@ -190,80 +1020,254 @@ impl Hook {
 #[cfg(any(feature = "crypto-use-rustcrypto", feature = "crypto-use-ring"))]
 #[cfg(test)]
 mod tests {
-    #[cfg(feature = "crypto-use-rustcrypto")]
-    use super::HmacSha1;
     use super::*;
-    use hex::ToHex;
-    #[cfg(feature = "crypto-use-ring")]
-    use ring::digest;
-    #[cfg(feature = "crypto-use-ring")]
-    use ring::hmac;
     use std::collections::HashMap;
 
-    /// Test GitHub payload authentication with `ring`: Valid signature
-    #[cfg(feature = "crypto-use-ring")]
-    #[test]
-    fn payload_authentication_github_ring() {
-        let secret = String::from("secret");
-        let hook = Hook::new("*", Some(secret.clone()), |_: &Delivery| {});
-        let payload = String::from(r#"{"zen": "Bazinga!"}"#);
-        let request_body = payload.clone();
-        let secret_bytes = secret.as_bytes();
-        let request_bytes = request_body.as_bytes();
-        let key = hmac::SigningKey::new(&digest::SHA1, &secret_bytes);
-        let mut signature = String::new();
-        hmac::sign(&key, &request_bytes)
-            .as_ref()
-            .write_hex(&mut signature)
-            .unwrap();
-        let signature_field = String::from(format!("sha1={}", signature));
+    /// Build a GitHub delivery with given signature header
+    fn github_delivery(signature_header: &str, signature: &str, request_body: &str) -> Delivery {
         let mut headers: HashMap<String, String> = HashMap::new();
         headers.insert("x-github-event".to_string(), "push".to_string());
-        headers.insert("x-hub-signature".to_string(), signature_field);
-        let delivery = Delivery::new(headers, Some(request_body));
-        assert!(hook.auth(&delivery.unwrap()));
+        headers.insert(signature_header.to_string(), signature.to_string());
+        Delivery::new(headers, Some(request_body.to_string())).unwrap()
     }
 
-    /// Test GitHub payload authentication with crates from RustCrypto team: Valid signature
-    #[cfg(feature = "crypto-use-rustcrypto")]
+    /// Test GitHub payload authentication: Valid signature
     #[test]
-    fn payload_authentication_github_rustcrypto() {
-        let secret = String::from("secret");
-        let hook = Hook::new("*", Some(secret.clone()), |_: &Delivery| {});
-        let payload = String::from(r#"{"zen": "Bazinga!"}"#);
-        let request_body = payload.clone();
-        let secret_bytes = secret.as_bytes();
-        let request_bytes = request_body.as_bytes();
-        let mut mac = HmacSha1::new_varkey(&secret_bytes).expect("Invalid key");
-        mac.input(&request_bytes);
-        let mut signature = String::new();
-        mac.result()
-            .code()
-            .as_ref()
-            .write_hex(&mut signature)
-            .expect("Invalid signature");
-        let signature_field = String::from(format!("sha1={}", signature));
-        let mut headers: HashMap<String, String> = HashMap::new();
-        headers.insert("x-github-event".to_string(), "push".to_string());
-        headers.insert("x-hub-signature".to_string(), signature_field);
-        let delivery = Delivery::new(headers, Some(request_body));
-        assert!(hook.auth(&delivery.unwrap()));
-        //assert!(true);
+    fn payload_authentication_github() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let delivery = github_delivery(
+            "x-hub-signature",
+            "sha1=ec760ee6d10bf638089f078b5a0c23f6575821e7",
+            r#"{"zen": "Bazinga!"}"#,
+        );
+        assert!(hook.auth(&delivery));
     }
 
     /// Test GitHub payload authentication: Invalid signature
     #[test]
     fn payload_authentication_github_fail() {
-        let secret = String::from("secret");
-        let hook = Hook::new("*", Some(secret.clone()), |_: &Delivery| {});
-        let payload = String::from(r#"{"zen": "Another test!"}"#);
-        let request_body = payload.clone();
-        let signature_field = String::from("sha1=ec760ee6d10bf638089f078b5a0c23f6575821e7");
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let delivery = github_delivery(
+            "x-hub-signature",
+            "sha1=ec760ee6d10bf638089f078b5a0c23f6575821e7",
+            r#"{"zen": "Another test!"}"#,
+        );
+        assert!(!hook.auth(&delivery));
+    }
+
+    /// Test GitHub payload authentication: Valid SHA-256 signature
+    #[test]
+    fn payload_authentication_github_sha256() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let delivery = github_delivery(
+            "x-hub-signature-256",
+            "sha256=f7197d1a3352f7d76992f8cb4f3c45a3f267dc6414cbb8b447393ca8d476a90d",
+            r#"{"zen": "Bazinga!"}"#,
+        );
+        assert!(hook.auth(&delivery));
+        let hook = hook.with_algorithms(&[Algorithm::Sha512]);
+        assert!(!hook.auth(&delivery));
+    }
+
+    /// Test payload authentication: Custom prefix and base64 encoded SHA-512 signature
+    #[test]
+    fn payload_authentication_custom_prefix_base64() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {})
+            .with_signature_prefix("v1:")
+            .with_signature_encoding(SignatureEncoding::Base64);
+        let signature = "v1:3gGJJTJ0qfc8jT4BnrLmqOO943g5J8tDfQeoYAvU8JgJhSD473Sh2aG5a4PB3PTnlFOu4+rUL0StDtCqJjO8ew==";
+        let delivery = github_delivery("x-hub-signature", signature, r#"{"zen": "Bazinga!"}"#);
+        assert!(hook.auth(&delivery));
+        let delivery =
+            github_delivery("x-hub-signature", &signature[3..], r#"{"zen": "Bazinga!"}"#);
+        assert!(!hook.auth(&delivery));
+    }
+
+    /// Test Forgejo payload authentication, Forgejo also sends GitHub-style headers
+    #[test]
+    fn payload_authentication_forgejo() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
         let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-forgejo-event".to_string(), "push".to_string());
         headers.insert("x-github-event".to_string(), "push".to_string());
-        headers.insert("x-hub-signature".to_string(), signature_field);
-        let delivery = Delivery::new(headers, Some(request_body));
-        assert_eq!(hook.auth(&delivery.unwrap()), false);
+        headers.insert(
+            "x-forgejo-signature".to_string(),
+            "f7197d1a3352f7d76992f8cb4f3c45a3f267dc6414cbb8b447393ca8d476a90d".to_string(),
+        );
+        headers.insert("x-hub-signature".to_string(), "sha1=0000".to_string());
+        let delivery = Delivery::new(headers, Some(r#"{"zen": "Bazinga!"}"#.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Forgejo));
+        assert!(hook.auth(&delivery));
+        let delivery = github_delivery(
+            "x-gitea-signature",
+            "f7197d1a3352f7d76992f8cb4f3c45a3f267dc6414cbb8b447393ca8d476a90d",
+            r#"{"zen": "Bazinga!"}"#,
+        );
+        assert!(!hook.auth(&delivery));
+    }
+
+    /// Test Gerrit payload authentication, the event is taken from the payload
+    #[test]
+    fn payload_authentication_gerrit() {
+        let hook = Hook::new(
+            "patchset-created",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let request_body = r#"{"type": "patchset-created", "eventCreatedOn": 1576754827}"#;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(
+            "x-gerrit-signature".to_string(),
+            "sha256=7f8e6d3691fcdca927109f64a97d23f5bbeedafac25b3492bdb3c79bc86770fc".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Gerrit));
+        assert_eq!(delivery.event, "patchset-created");
+        assert!(delivery.timestamp.is_some());
+        assert!(hook.auth(&delivery));
+        let delivery = Delivery::new(HashMap::new(), Some(request_body.to_string())).unwrap();
+        assert_eq!(hook.verify(&delivery), Err(Error::MissingSignature));
+        assert!(Delivery::new(HashMap::new(), Some(r#"{"type": "push"}"#.to_string())).is_err());
+    }
+
+    /// Test Linear payload authentication, the signature has no prefix
+    #[test]
+    fn payload_authentication_linear() {
+        let hook = Hook::new("issue", Some(String::from("secret")), |_: &Delivery| {});
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("linear-event".to_string(), "Issue".to_string());
+        headers.insert(
+            "linear-delivery".to_string(),
+            "234d1a4e-b617-4388-90fe-adc3633d6b72".to_string(),
+        );
+        headers.insert(
+            "linear-signature".to_string(),
+            "f7197d1a3352f7d76992f8cb4f3c45a3f267dc6414cbb8b447393ca8d476a90d".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some(r#"{"zen": "Bazinga!"}"#.to_string())).unwrap();
+        assert_eq!(delivery.event, "issue");
+        assert!(delivery.id.is_some());
+        assert!(hook.auth(&delivery));
+    }
+
+    /// Test Intercom payload authentication, the event is the topic of the notification
+    #[cfg(feature = "parse")]
+    #[test]
+    fn payload_authentication_intercom() {
+        let hook = Hook::new(
+            "conversation.user.created",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let request_body =
+            r#"{"type": "notification_event", "topic": "conversation.user.created"}"#;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(
+            "x-hub-signature".to_string(),
+            "sha1=f5e76f38b87d265f7a80c82a447729544213eb80".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Intercom));
+        assert_eq!(delivery.event, "conversation.user.created");
+        assert!(hook.auth(&delivery));
+    }
+
+    /// Test JFrog Artifactory payload authentication, the event is the domain and the type of the event
+    #[cfg(feature = "parse")]
+    #[test]
+    fn payload_authentication_artifactory() {
+        let hook = Hook::new(
+            "artifact.deployed",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let request_body =
+            r#"{"domain": "artifact", "event_type": "deployed", "data": {"repo_key": "libs"}}"#;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-jfrog-event-auth".to_string(), "secret".to_string());
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Artifactory));
+        assert_eq!(delivery.event, "artifact.deployed");
+        assert!(hook.auth(&delivery));
+        let unsigned = Delivery::new(HashMap::new(), Some(request_body.to_string())).unwrap();
+        assert!(matches!(unsigned.delivery_type, DeliveryType::Artifactory));
+        assert!(!hook.auth(&unsigned));
+    }
+
+    /// Test npm payload authentication, the event is the `event` field of the payload
+    #[cfg(feature = "parse")]
+    #[test]
+    fn payload_authentication_npm() {
+        let hook = Hook::new(
+            "package:publish",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let request_body = r#"{"event": "package:publish", "name": "rifling", "type": "package"}"#;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(
+            "x-npm-signature".to_string(),
+            "sha256=f97176629e2375253096ca98e6e6922d5354ec1bb84ccfeb8e25d17db22f5fe3".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Npm));
+        assert_eq!(delivery.event, "package:publish");
+        assert!(hook.auth(&delivery));
+    }
+
+    /// Test CircleCI payload authentication with multiple signatures
+    #[test]
+    fn payload_authentication_circleci() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let circleci_delivery = |signature: &str| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert(
+                "circleci-event-type".to_string(),
+                "workflow-completed".to_string(),
+            );
+            headers.insert("circleci-signature".to_string(), signature.to_string());
+            Delivery::new(
+                headers,
+                Some(r#"{"type": "workflow-completed", "id": "3888f21b"}"#.to_string()),
+            )
+            .unwrap()
+        };
+        let delivery = circleci_delivery(
+            "v1=5984e39d5bba6275577bc7af35aa263a72146f6a251be474f74a9941fa49edde",
+        );
+        assert_eq!(delivery.event, "workflow-completed");
+        assert!(hook.auth(&delivery));
+        assert!(hook.auth(&circleci_delivery(
+            "v0=abcdef,v1=5984e39d5bba6275577bc7af35aa263a72146f6a251be474f74a9941fa49edde"
+        )));
+        assert!(!hook.auth(&circleci_delivery(
+            "5984e39d5bba6275577bc7af35aa263a72146f6a251be474f74a9941fa49edde"
+        )));
+    }
+
+    /// Test Gitee payload authentication: Password and signed secret
+    #[test]
+    fn payload_authentication_gitee() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let gitee_delivery = |token: &str| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert("x-gitee-event".to_string(), "Push Hook".to_string());
+            headers.insert("x-gitee-token".to_string(), token.to_string());
+            headers.insert("x-gitee-timestamp".to_string(), "1576754827988".to_string());
+            Delivery::new(headers, Some("{}".to_string())).unwrap()
+        };
+        let delivery = gitee_delivery("secret");
+        assert_eq!(delivery.event, "push_hook");
+        assert!(hook.auth(&delivery));
+        assert!(hook.auth(&gitee_delivery(
+            "q5xwWkTPMOtVd1ewbiw/WGv39pOUUdzf5IBLzdJVQBk="
+        )));
+        assert!(hook.auth(&gitee_delivery(
+            "q5xwWkTPMOtVd1ewbiw%2FWGv39pOUUdzf5IBLzdJVQBk%3D"
+        )));
+        assert!(!hook.auth(&gitee_delivery(
+            "Q5xwWkTPMOtVd1ewbiw/WGv39pOUUdzf5IBLzdJVQBk="
+        )));
     }
 }
 
@@ -293,6 +1297,168 @@ mod tests_gitlab {
         headers.insert("x-gitlab-event".to_string(), "push".to_string());
         headers.insert("x-gitlab-token".to_string(), secret);
         let delivery = Delivery::new(headers, None);
-        assert_eq!(hook.auth(&delivery.unwrap()), false);
+        assert!(!hook.auth(&delivery.unwrap()));
+    }
+
+    /// Test payload authentication with a custom authenticator, which overrides the secret
+    #[test]
+    fn payload_authentication_authenticator() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {})
+            .with_authenticator(|delivery: &Delivery| match delivery.signature.as_deref() {
+                Some("SECRET") => Ok(()),
+                _ => Err(Error::InvalidSignature),
+            });
+        let gitlab_delivery = |token: &str| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert("x-gitlab-event".to_string(), "push".to_string());
+            headers.insert("x-gitlab-token".to_string(), token.to_string());
+            Delivery::new(headers, None).unwrap()
+        };
+        assert!(hook.auth(&gitlab_delivery("SECRET")));
+        assert_eq!(
+            hook.verify(&gitlab_delivery("secret")),
+            Err(Error::InvalidSignature)
+        );
+    }
+
+    /// Test Jenkins payload authentication with the token of Generic Webhook Trigger
+    #[cfg(feature = "parse")]
+    #[test]
+    fn payload_authentication_jenkins() {
+        let hook = Hook::new(
+            "jenkins.build.completed",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let request_body =
+            r#"{"name": "deploy", "build": {"phase": "COMPLETED", "status": "SUCCESS"}}"#;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("authorization".to_string(), "Bearer secret".to_string());
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert_eq!(delivery.event, "jenkins.build.completed");
+        assert!(hook.auth(&delivery));
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("token".to_string(), "AnotherSecret".to_string());
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(!hook.auth(&delivery));
+    }
+
+    /// Test Telegram payload authentication with the secret token, the event is the type of the update
+    #[cfg(feature = "parse")]
+    #[test]
+    fn payload_authentication_telegram() {
+        let hook = Hook::new(
+            "callback_query",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let request_body =
+            r#"{"update_id": 10000, "callback_query": {"id": "4382", "data": "deploy"}}"#;
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(
+            "x-telegram-bot-api-secret-token".to_string(),
+            "secret".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Telegram));
+        assert_eq!(delivery.event, "callback_query");
+        assert!(hook.auth(&delivery));
+        let delivery = Delivery::new(HashMap::new(), Some(request_body.to_string())).unwrap();
+        assert_eq!(delivery.event, "callback_query");
+        assert_eq!(hook.verify(&delivery), Err(Error::MissingSignature));
+    }
+
+    /// Test Grafana payload authentication with bearer token and basic authentication
+    #[cfg(feature = "parse")]
+    #[test]
+    fn payload_authentication_grafana() {
+        let hook = Hook::new(
+            "grafana.alert.firing",
+            Some(String::from("secret")),
+            |_: &Delivery| {},
+        );
+        let grafana_delivery = |authorization: &str| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert("user-agent".to_string(), "Grafana".to_string());
+            headers.insert("authorization".to_string(), authorization.to_string());
+            let request_body = r#"{"title": "[FIRING:1] HighLoad", "status": "firing"}"#;
+            Delivery::new(headers, Some(request_body.to_string())).unwrap()
+        };
+        let delivery = grafana_delivery("Bearer secret");
+        assert_eq!(delivery.event, "grafana.alert.firing");
+        assert!(hook.auth(&delivery));
+        // admin:secret
+        assert!(hook.auth(&grafana_delivery("Basic YWRtaW46c2VjcmV0")));
+        // admin:AnotherSecret
+        assert!(!hook.auth(&grafana_delivery("Basic YWRtaW46QW5vdGhlclNlY3JldA==")));
+        assert!(!hook.auth(&grafana_delivery("secret")));
+        let request_body = r#"{"ruleId": 1, "title": "[OK] HighLoad", "state": "ok"}"#;
+        let delivery = Delivery::new(HashMap::new(), Some(request_body.to_string())).unwrap();
+        assert!(matches!(delivery.delivery_type, DeliveryType::Grafana));
+        assert_eq!(delivery.event, "grafana.alert.ok");
+    }
+
+    /// Test Docker Hub payload authentication: Rejected by hooks with a secret unless they accept it unsigned
+    #[test]
+    fn payload_authentication_docker_hub() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(
+            "x-newrelic-id".to_string(),
+            "UQUFVFJUGwUJVlhaBgY=".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some("{}".to_string())).unwrap();
+        assert_eq!(delivery.delivery_type, DeliveryType::DockerHub);
+        assert_eq!(hook.verify(&delivery), Err(Error::MissingSignature));
+        #[cfg(feature = "parse")]
+        {
+            let request_body = r#"{"push_data": {"tag": "latest"}, "callback_url": "https://registry.hub.docker.com/"}"#;
+            let delivery = Delivery::new(HashMap::new(), Some(request_body.to_string())).unwrap();
+            assert_eq!(delivery.delivery_type, DeliveryType::DockerHub);
+            assert_eq!(hook.verify(&delivery), Err(Error::MissingSignature));
+        }
+        let hook = hook.accept_unsigned_docker_hub();
+        assert_eq!(hook.verify(&delivery), Ok(()));
+    }
+
+    /// Test payload authentication of unknown senders: Rejected by hooks with a secret
+    #[test]
+    fn payload_authentication_unknown() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "push".to_string());
+        headers.insert("x-gitlab-token".to_string(), "secret".to_string());
+        let mut delivery = Delivery::new(headers, None).unwrap();
+        delivery.delivery_type = DeliveryType::Unknown;
+        assert_eq!(hook.verify(&delivery), Err(Error::MissingSignature));
+        let hook = Hook::new("*", None, |_: &Delivery| {});
+        assert_eq!(hook.verify(&delivery), Ok(()));
+    }
+}
+
+#[cfg(all(
+    not(feature = "crypto-use-rustcrypto"),
+    not(feature = "crypto-use-ring")
+))]
+#[cfg(test)]
+mod tests_no_crypto {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Test GitHub payload authentication without cryptography support: Rejected
+    #[test]
+    fn payload_authentication_github_unverifiable() {
+        let hook = Hook::new("*", Some(String::from("secret")), |_: &Delivery| {});
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        headers.insert(
+            "x-hub-signature".to_string(),
+            "sha1=ec760ee6d10bf638089f078b5a0c23f6575821e7".to_string(),
+        );
+        let delivery = Delivery::new(headers, Some(r#"{"zen": "Bazinga!"}"#.to_string()));
+        assert_eq!(
+            hook.verify(&delivery.unwrap()),
+            Err(Error::CryptoUnavailable)
+        );
     }
 }
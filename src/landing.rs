@@ -0,0 +1,80 @@
+//! Landing page
+//!
+//! Webhook URLs get opened in browsers when checking the setup. With `Constructor::landing_page`, `GET` and `HEAD`
+//! requests are answered with a small JSON document instead of being handled as deliveries:
+//!
+//! ```text
+//! {"service":"deploy-bot","status":"ok","method":"POST","providers":["GitHub","GitLab",...]}
+//! ```
+//!
+//! Neither the hooks nor their secrets are listed. The status is `unavailable` while downstream targets are
+//! unreachable, see `rifling::readiness`.
+
+use super::access_log;
+
+/// Providers whose deliveries are understood
+const PROVIDERS: &[&str] = &[
+    "GitHub",
+    "GitLab",
+    "DockerHub",
+    "Forgejo",
+    "Gitea",
+    "Gitee",
+    "Gerrit",
+    "Jenkins",
+    "CircleCI",
+    "Grafana",
+    "Discord",
+    "Telegram",
+    "Linear",
+    "Intercom",
+    "Artifactory",
+    "npm",
+    "Kubernetes",
+];
+
+/// Body of the landing page of the service, with the unreachable downstream targets
+pub(crate) fn page(service: &str, unreachable: &[String]) -> String {
+    let mut json = String::from(r#"{"service":"#);
+    access_log::write_string(&mut json, service);
+    if unreachable.is_empty() {
+        json.push_str(r#","status":"ok""#);
+    } else {
+        json.push_str(r#","status":"unavailable","unreachable":["#);
+        for (index, target) in unreachable.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            access_log::write_string(&mut json, target);
+        }
+        json.push(']');
+    }
+    json.push_str(r#","method":"POST","providers":["#);
+    for (index, provider) in PROVIDERS.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        access_log::write_string(&mut json, provider);
+    }
+    json.push_str("]}");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test building the landing page
+    #[test]
+    fn landing_page() {
+        let page = page("deploy \"bot\"", &[]);
+        assert!(page.starts_with(
+            r#"{"service":"deploy \"bot\"","status":"ok","method":"POST","providers":["GitHub","#
+        ));
+        assert!(page.ends_with(r#""Kubernetes"]}"#));
+        let targets = ["redis".to_string(), "registry".to_string()];
+        assert!(super::page("bot", &targets).starts_with(
+            r#"{"service":"bot","status":"unavailable","unreachable":["redis","registry"],"method":"POST","#
+        ));
+    }
+}
@@ -30,39 +30,157 @@
 //!  - Error handling.
 //!  - Support other web frameworks (such as Tide).
 
-#[cfg(any(feature = "crypto-use-ring", feature = "crypto-use-rustcrypto"))]
+extern crate base64;
 extern crate hex;
 #[cfg(feature = "logging")]
 #[macro_use]
 extern crate log;
+#[cfg(feature = "chrono")]
+extern crate chrono;
 #[cfg(feature = "hyper-support")]
 extern crate futures;
+#[cfg(feature = "template-handlebars")]
+extern crate handlebars;
 #[cfg(feature = "crypto-use-rustcrypto")]
 extern crate hmac;
+#[cfg(feature = "http")]
+extern crate http;
 #[cfg(feature = "hyper-support")]
 extern crate hyper;
+#[cfg(feature = "client")]
+extern crate hyper_rustls;
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub extern crate inventory;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
+#[cfg(feature = "sink-redis")]
+extern crate redis;
 #[cfg(feature = "crypto-use-ring")]
 extern crate ring;
+#[cfg(feature = "tls-server")]
+extern crate rustls;
 #[cfg(feature = "parse")]
 extern crate serde_json;
 #[cfg(feature = "crypto-use-rustcrypto")]
 extern crate sha1;
+#[cfg(feature = "crypto-use-rustcrypto")]
+extern crate sha2;
+#[cfg(all(unix, feature = "reload-signal"))]
+extern crate signal_hook;
+#[cfg(feature = "sync-server")]
+extern crate tiny_http;
+#[cfg(any(feature = "client", feature = "tls-server"))]
+extern crate tokio;
+#[cfg(feature = "hyper-support")]
+extern crate tokio_timer;
+#[cfg(feature = "crypto-use-ring")]
+extern crate untrusted;
 #[cfg(feature = "content-type-urlencoded")]
 extern crate url;
 
 #[doc(hidden)]
 #[macro_use]
 mod macros;
+pub mod access_log;
+#[cfg(feature = "parse")]
+pub mod admission;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "macros")]
+pub mod collect;
+#[cfg(feature = "command")]
+pub mod command;
+pub mod concurrency;
+pub mod context;
+pub mod crypto;
+pub mod error;
+pub mod filter;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "typed")]
+pub mod github;
+pub mod group;
 pub mod handler;
 pub mod hook;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+mod landing;
+pub mod maintenance;
+#[cfg(feature = "multipart")]
+pub mod multipart;
+#[cfg(any(
+    feature = "notify-slack",
+    feature = "notify-matrix",
+    feature = "notify-smtp"
+))]
+pub mod notify;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod outcome;
+pub mod overrides;
+pub mod pipeline;
+pub mod proxy;
+pub mod ratelimit;
+pub mod readiness;
+mod redact;
+pub mod reload;
+#[cfg(feature = "command")]
+pub mod repository;
+mod request_id;
+pub mod retry;
+pub mod router;
+pub mod schedule;
+#[cfg(feature = "hyper-support")]
+pub mod server;
+pub mod sink;
+pub mod stats;
+#[cfg(feature = "client")]
+pub mod status;
+pub mod store;
+#[cfg(all(unix, feature = "socket-activation"))]
+pub mod systemd;
+#[cfg(feature = "parse")]
+pub mod template;
+#[cfg(feature = "tls-server")]
+pub mod tls;
+pub mod transform;
+pub mod unauthenticated;
+pub mod validate;
+pub mod verify;
+#[cfg(feature = "chrono")]
+pub mod window;
 
+pub use context::Context;
+pub use context::ResponseStream;
+pub use error::Error;
+pub use handler::AmbiguityPolicy;
 pub use handler::Constructor;
 pub use handler::ContentType;
 pub use handler::Delivery;
+pub use handler::DeliveryBuilder;
 pub use handler::DeliveryType;
+pub use handler::EmptyBodyPolicy;
+pub use handler::EventNormalization;
 pub use handler::Handler;
+pub use handler::HeaderAccessor;
+pub use handler::Headers;
+pub use hook::Authenticator;
+pub use hook::BatchHookFunc;
 pub use hook::Hook;
 pub use hook::HookFunc;
+pub use hook::HookFuncWithContext;
+#[cfg(feature = "typed")]
+pub use hook::HookFuncWithPayload;
+pub use hook::HookFuncWithState;
+pub use hook::HookResult;
+pub use outcome::Outcome;
+pub use outcome::Response;
+#[cfg(feature = "macros")]
+pub use rifling_macros::hook;
+pub use router::Router;
+#[cfg(feature = "hyper-support")]
+pub use server::serve_multi;
 
 #[cfg(test)]
 mod tests {
@@ -2,38 +2,54 @@
 #[doc(hidden)]
 #[macro_export]
 macro_rules! debug {
-    ($($element:expr), *) => {
+    ($($element:expr), *) => {{
         #[cfg(feature = "logging-print")]
         println!($($element, )*);
-    };
+        #[cfg(not(feature = "logging-print"))]
+        {
+            $(let _ = &$element;)*
+        }
+    }};
 }
 
 #[cfg(not(feature = "logging"))]
 #[doc(hidden)]
 #[macro_export]
 macro_rules! info {
-    ($($element:expr), *) => {
+    ($($element:expr), *) => {{
         #[cfg(feature = "logging-print")]
         println!($($element, )*);
-    };
+        #[cfg(not(feature = "logging-print"))]
+        {
+            $(let _ = &$element;)*
+        }
+    }};
 }
 
 #[cfg(not(feature = "logging"))]
 #[doc(hidden)]
 #[macro_export]
 macro_rules! warn {
-    ($($element:expr), *) => {
+    ($($element:expr), *) => {{
         #[cfg(feature = "logging-print")]
         println!($($element, )*);
-    };
+        #[cfg(not(feature = "logging-print"))]
+        {
+            $(let _ = &$element;)*
+        }
+    }};
 }
 
 #[cfg(not(feature = "logging"))]
 #[doc(hidden)]
 #[macro_export]
 macro_rules! error {
-    ($($element:expr), *) => {
+    ($($element:expr), *) => {{
         #[cfg(feature = "logging-print")]
         println!($($element, )*);
-    };
+        #[cfg(not(feature = "logging-print"))]
+        {
+            $(let _ = &$element;)*
+        }
+    }};
 }
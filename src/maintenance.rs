@@ -0,0 +1,76 @@
+//! Maintenance mode
+//!
+//! While maintenance mode is on, deliveries are still authenticated and recorded to the store set with
+//! `Constructor::store`, but no hook is run and they're answered with `503 Service Unavailable` (or the status set
+//! with `Constructor::maintenance_status`). The recorded deliveries can be replayed once the maintenance is over.
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//!
+//! let cons = Constructor::new();
+//! let maintenance = cons.maintenance_handle();
+//! maintenance.enable();
+//! // Migrate the database, then
+//! maintenance.disable();
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Arc;
+
+/// Status code responded during maintenance by default
+const DEFAULT_STATUS: u16 = 503;
+
+/// State of the maintenance mode
+#[derive(Debug)]
+struct State {
+    enabled: AtomicBool,
+    status: AtomicU16,
+}
+
+/// Handle to the maintenance mode of a `Constructor` and its `Handler`s, cheap to clone
+#[derive(Clone, Debug)]
+pub struct MaintenanceHandle {
+    state: Arc<State>,
+}
+
+/// Maintenance mode is off by default
+impl Default for MaintenanceHandle {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(State {
+                enabled: AtomicBool::new(false),
+                status: AtomicU16::new(DEFAULT_STATUS),
+            }),
+        }
+    }
+}
+
+/// The main impl clause of `MaintenanceHandle`
+impl MaintenanceHandle {
+    /// Turn maintenance mode on, hooks are skipped
+    pub fn enable(&self) {
+        self.state.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Turn maintenance mode off, hooks are run again
+    pub fn disable(&self) {
+        self.state.enabled.store(false, Ordering::SeqCst);
+    }
+
+    /// Test if maintenance mode is on
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Status code responded during maintenance
+    pub fn status(&self) -> u16 {
+        self.state.status.load(Ordering::Relaxed)
+    }
+
+    /// Set the status code responded during maintenance
+    pub(crate) fn set_status(&self, status: u16) {
+        self.state.status.store(status, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,158 @@
+//! Multipart bodies
+//!
+//! With the `multipart` feature, `multipart/form-data` bodies (e.g. inbound mails routed by Mailgun) are parsed into
+//! `Delivery::parts`. Text fields are also available with `Delivery::form_fields`, and such deliveries are identified
+//! as form posts (`DeliveryType::Form`) unless a provider is recognized.
+//!
+//! File parts are dropped unless they're no larger than the limit set with `Constructor::multipart_file_limit`
+//! (0 by default):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.multipart_file_limit(64 * 1024);
+//! cons.register(Hook::new("form", None, |delivery: &Delivery| {
+//!     for part in delivery.parts().iter().filter(|part| part.filename.is_some()) {
+//!         println!("Attachment {:?} of {} bytes", part.filename, part.data.len());
+//!     }
+//! }));
+//! ```
+
+use super::handler::Headers;
+
+/// Part of a multipart body
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    /// Name of the field
+    pub name: String,
+    /// Name of the file, for file parts
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Boundary in the value of the `Content-Type` header, if it's `multipart/form-data`
+pub(crate) fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';').map(str::trim);
+    if !params.next()?.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// Test if the request has a `multipart/form-data` body, header names must be lower cased
+pub(crate) fn is_multipart(headers: &Headers) -> bool {
+    headers.get("content-type").and_then(boundary).is_some()
+}
+
+/// Find the needle in the haystack, starting from the position
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() {
+        return None;
+    }
+    haystack[from..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+/// Parse the headers of a part, returns the name, the file name and the content type
+fn parse_headers(headers: &str) -> Option<(String, Option<String>, Option<String>)> {
+    let mut disposition = None;
+    let mut content_type = None;
+    for line in headers.split("\r\n") {
+        let (name, value) = match line.split_once(':') {
+            Some(header) => header,
+            None => continue,
+        };
+        if name.trim().eq_ignore_ascii_case("content-disposition") {
+            disposition = Some(value.trim());
+        } else if name.trim().eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.trim().to_string());
+        }
+    }
+    let mut name = None;
+    let mut filename = None;
+    for param in disposition?.split(';').skip(1) {
+        if let Some((key, value)) = param.split_once('=') {
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim() {
+                "name" => name = Some(value),
+                "filename" => filename = Some(value),
+                _ => (),
+            }
+        }
+    }
+    Some((name?, filename, content_type))
+}
+
+/// Parse the body into parts, file parts larger than `file_limit` bytes are dropped
+pub(crate) fn parse(body: &[u8], boundary: &str, file_limit: usize) -> Option<Vec<Part>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut position = find(body, &delimiter, 0)? + delimiter.len();
+    loop {
+        if body[position..].starts_with(b"--") {
+            return Some(parts); // Closing delimiter
+        }
+        let headers_start = position + 2; // Line break after the delimiter
+        let headers_end = find(body, b"\r\n\r\n", headers_start)?;
+        let data_start = headers_end + 4;
+        let data_end = find(body, &[b"\r\n", &delimiter[..]].concat(), data_start)?;
+        let headers = String::from_utf8_lossy(&body[headers_start..headers_end]);
+        if let Some((name, filename, content_type)) = parse_headers(&headers) {
+            let data = &body[data_start..data_end];
+            if filename.is_none() || data.len() <= file_limit {
+                parts.push(Part {
+                    name,
+                    filename,
+                    content_type,
+                    data: data.to_vec(),
+                });
+            } else {
+                debug!("Dropped file part '{}' of {} bytes", name, data.len());
+            }
+        }
+        position = data_end + 2 + delimiter.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test parsing multipart bodies
+    #[test]
+    fn multipart() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"----rifling\""),
+            Some("----rifling".to_string())
+        );
+        assert_eq!(boundary("application/json"), None);
+        let body = "------rifling\r\n\
+                    Content-Disposition: form-data; name=\"subject\"\r\n\r\n\
+                    Build failed\r\n\
+                    ------rifling\r\n\
+                    Content-Disposition: form-data; name=\"attachment-1\"; filename=\"log.txt\"\r\n\
+                    Content-Type: text/plain\r\n\r\n\
+                    error: aborting\r\n\
+                    ------rifling--\r\n";
+        let parts = parse(body.as_bytes(), "----rifling", 1024).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "subject");
+        assert_eq!(parts[0].data, b"Build failed");
+        assert_eq!(parts[1].filename, Some("log.txt".to_string()));
+        assert_eq!(parts[1].content_type, Some("text/plain".to_string()));
+        assert_eq!(parts[1].data, b"error: aborting");
+        let parts = parse(body.as_bytes(), "----rifling", 0).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert!(parse(b"not multipart", "----rifling", 0).is_none());
+    }
+}
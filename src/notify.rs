@@ -0,0 +1,447 @@
+//! Notifications
+//!
+//! Hook functions telling a channel when something happens, without writing any code: `SlackNotifyHook` posts to a
+//! Slack incoming webhook (`notify-slack`), `MatrixNotifyHook` sends to a Matrix room (`notify-matrix`), and
+//! `SmtpNotifyHook` sends emails through an SMTP relay (`notify-smtp`):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::notify::SmtpNotifyHook;
+//! use rifling::Hook;
+//!
+//! let notify = SmtpNotifyHook::new("127.0.0.1:25", "rifling@example.com", &["ops@example.com"])
+//!     .subject("{{repository.full_name}}: push to {{ref}}")
+//!     .body("{{sender.login}} pushed {{after}} to {{ref}}");
+//! let hook = Hook::new("push", None, notify);
+//! ```
+//!
+//! Messages are templates whose placeholders are replaced with fields of the payload, given as paths with dots
+//! (e.g. `{{repository.full_name}}`, `{{commits.0.message}}`), see `rifling::template`.
+//!
+//! Notifications which can't be sent fail the hook, so they're retried as configured with `Hook::retry`.
+
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+use futures::{Future, Stream};
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+use hyper::client::connect::HttpConnector;
+#[cfg(feature = "notify-matrix")]
+use hyper::header::AUTHORIZATION;
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+use hyper::header::{CONTENT_TYPE, USER_AGENT};
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+use hyper::{Body, Client, Method, Request};
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+use hyper_rustls::HttpsConnector;
+#[cfg(feature = "notify-matrix")]
+use serde_json::json;
+#[cfg(feature = "notify-slack")]
+use serde_json::Map;
+#[cfg(feature = "notify-slack")]
+use serde_json::Value;
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+use tokio::runtime::current_thread::Runtime;
+
+#[cfg(feature = "notify-smtp")]
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(feature = "notify-smtp")]
+use std::net::TcpStream;
+#[cfg(feature = "notify-smtp")]
+use std::time::Duration;
+
+use super::error::Error;
+use super::handler::Delivery;
+use super::hook::HookFunc;
+use super::template::Template;
+
+/// Timeout of connections to SMTP relays
+#[cfg(feature = "notify-smtp")]
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Send the request, blocking until the server answers
+#[cfg(any(feature = "notify-slack", feature = "notify-matrix"))]
+fn send(request: Request<Body>) -> Result<(), Error> {
+    debug!("Sending notification to {}", request.uri());
+    let client: Client<HttpsConnector<HttpConnector>> =
+        Client::builder().build(HttpsConnector::new(1));
+    let response = client.request(request).and_then(|response| {
+        let status = response.status();
+        response
+            .into_body()
+            .concat2()
+            .map(move |body| (status, body))
+    });
+    let mut runtime = Runtime::new().map_err(|error| Error::HookFailed(error.to_string()))?;
+    let (status, body) = runtime
+        .block_on(response)
+        .map_err(|error| Error::HookFailed(error.to_string()))?;
+    if !status.is_success() {
+        return Err(Error::HookFailed(format!(
+            "{} {}",
+            status,
+            String::from_utf8_lossy(&body)
+        )));
+    }
+    Ok(())
+}
+
+/// Hook function posting messages to a Slack incoming webhook
+#[cfg(feature = "notify-slack")]
+#[derive(Clone, Debug)]
+pub struct SlackNotifyHook {
+    webhook_url: String,
+    template: Template,
+    username: Option<String>,
+    icon_emoji: Option<String>,
+}
+
+/// The main impl clause of `SlackNotifyHook`
+#[cfg(feature = "notify-slack")]
+impl SlackNotifyHook {
+    /// Create a new hook posting the message rendered from the template to the incoming webhook URL
+    pub fn new(webhook_url: &str, template: &str) -> Self {
+        Self {
+            webhook_url: webhook_url.to_string(),
+            template: Template::new(template),
+            username: None,
+            icon_emoji: None,
+        }
+    }
+
+    /// Set the name messages are posted as, if the webhook allows it
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    /// Set the emoji used as icon (e.g. `:rocket:`), if the webhook allows it
+    pub fn icon_emoji(mut self, icon_emoji: &str) -> Self {
+        self.icon_emoji = Some(icon_emoji.to_string());
+        self
+    }
+
+    /// Build the request posting the message for the delivery
+    fn request(&self, delivery: &Delivery) -> Result<Request<Body>, Error> {
+        let mut body = Map::new();
+        body.insert("text".to_string(), self.template.render(delivery)?.into());
+        if let Some(username) = &self.username {
+            body.insert("username".to_string(), username.as_str().into());
+        }
+        if let Some(icon_emoji) = &self.icon_emoji {
+            body.insert("icon_emoji".to_string(), icon_emoji.as_str().into());
+        }
+        Request::builder()
+            .method(Method::POST)
+            .uri(self.webhook_url.as_str())
+            .header(USER_AGENT, "rifling")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(Value::Object(body).to_string()))
+            .map_err(|error| Error::HookFailed(error.to_string()))
+    }
+}
+
+/// Implement `HookFunc` to `SlackNotifyHook`
+#[cfg(feature = "notify-slack")]
+impl HookFunc for SlackNotifyHook {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        send(self.request(delivery)?)
+    }
+}
+
+/// Hook function sending messages to a Matrix room, as notices
+#[cfg(feature = "notify-matrix")]
+#[derive(Clone, Debug)]
+pub struct MatrixNotifyHook {
+    homeserver: String,
+    room_id: String,
+    access_token: String,
+    template: Template,
+}
+
+/// Percent-encode the segment of a path
+#[cfg(feature = "notify-matrix")]
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The main impl clause of `MatrixNotifyHook`
+#[cfg(feature = "notify-matrix")]
+impl MatrixNotifyHook {
+    /// Create a new hook sending the message rendered from the template to the room (e.g. `!abc:example.com`) of the
+    /// homeserver (e.g. `https://matrix.example.com`), as the user of the access token
+    pub fn new(homeserver: &str, room_id: &str, access_token: &str, template: &str) -> Self {
+        Self {
+            homeserver: homeserver.trim_end_matches('/').to_string(),
+            room_id: room_id.to_string(),
+            access_token: access_token.to_string(),
+            template: Template::new(template),
+        }
+    }
+
+    /// Build the request sending the message for the delivery
+    fn request(&self, delivery: &Delivery) -> Result<Request<Body>, Error> {
+        let body = json!({
+            "msgtype": "m.notice",
+            "body": self.template.render(delivery)?,
+        });
+        Request::builder()
+            .method(Method::PUT)
+            .uri(format!(
+                "{}/_matrix/client/v3/rooms/{}/send/m.room.message/rifling-{}",
+                &self.homeserver,
+                encode_segment(&self.room_id),
+                super::request_id::generate()
+            ))
+            .header(AUTHORIZATION, format!("Bearer {}", &self.access_token))
+            .header(USER_AGENT, "rifling")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .map_err(|error| Error::HookFailed(error.to_string()))
+    }
+}
+
+/// Implement `HookFunc` to `MatrixNotifyHook`
+#[cfg(feature = "notify-matrix")]
+impl HookFunc for MatrixNotifyHook {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        send(self.request(delivery)?)
+    }
+}
+
+/// Hook function sending emails through an SMTP relay (e.g. a local Postfix), without TLS nor authentication
+#[cfg(feature = "notify-smtp")]
+#[derive(Clone, Debug)]
+pub struct SmtpNotifyHook {
+    relay: String,
+    from: String,
+    to: Vec<String>,
+    subject: Template,
+    body: Template,
+}
+
+/// Read the reply of the SMTP server, failing if its code is not one of the expected ones
+#[cfg(feature = "notify-smtp")]
+fn smtp_reply(stream: &mut BufReader<TcpStream>, expected: &[u16]) -> io::Result<()> {
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // Lines of multiline replies but the last one have a dash after the code
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        let code = line.get(..3).and_then(|code| code.parse().ok());
+        return match code {
+            Some(code) if expected.contains(&code) => Ok(()),
+            _ => Err(io::Error::other(line.trim_end().to_string())),
+        };
+    }
+}
+
+/// Send the SMTP command and read its reply
+#[cfg(feature = "notify-smtp")]
+fn smtp_command(
+    stream: &mut BufReader<TcpStream>,
+    command: &str,
+    expected: &[u16],
+) -> io::Result<()> {
+    stream.get_mut().write_all(command.as_bytes())?;
+    stream.get_mut().write_all(b"\r\n")?;
+    smtp_reply(stream, expected)
+}
+
+/// Encode the value of a header, with RFC 2047 if not ASCII, with line breaks removed
+#[cfg(feature = "notify-smtp")]
+fn encode_header(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.is_ascii() {
+        value
+    } else {
+        format!("=?utf-8?b?{}?=", base64::encode(&value))
+    }
+}
+
+/// The main impl clause of `SmtpNotifyHook`
+#[cfg(feature = "notify-smtp")]
+impl SmtpNotifyHook {
+    /// Create a new hook sending emails from the address to the recipients, through the relay at the address
+    /// (e.g. `127.0.0.1:25`)
+    pub fn new(relay: &str, from: &str, to: &[&str]) -> Self {
+        Self {
+            relay: relay.to_string(),
+            from: from.to_string(),
+            to: to.iter().map(|to| to.to_string()).collect(),
+            subject: Template::new("Notification from rifling"),
+            body: Template::new(""),
+        }
+    }
+
+    /// Set the template of the subject
+    pub fn subject(mut self, template: &str) -> Self {
+        self.subject = Template::new(template);
+        self
+    }
+
+    /// Set the template of the body
+    pub fn body(mut self, template: &str) -> Self {
+        self.body = Template::new(template);
+        self
+    }
+
+    /// Format the message for the delivery, with lines ending with CRLF and dot-stuffed, ending with a line with a
+    /// single dot
+    fn message(&self, delivery: &Delivery) -> Result<String, Error> {
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            &self.from,
+            self.to.join(", "),
+            encode_header(&self.subject.render(delivery)?)
+        );
+        for line in self.body.render(delivery)?.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push_str(".\r\n");
+        Ok(message)
+    }
+
+    /// Send the message through the relay
+    fn send(&self, message: &str) -> io::Result<()> {
+        let stream = TcpStream::connect(&self.relay)?;
+        stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let mut stream = BufReader::new(stream);
+        smtp_reply(&mut stream, &[220])?;
+        smtp_command(&mut stream, "EHLO rifling", &[250])?;
+        smtp_command(&mut stream, &format!("MAIL FROM:<{}>", &self.from), &[250])?;
+        for to in &self.to {
+            smtp_command(&mut stream, &format!("RCPT TO:<{}>", to), &[250, 251])?;
+        }
+        smtp_command(&mut stream, "DATA", &[354])?;
+        stream.get_mut().write_all(message.as_bytes())?;
+        smtp_reply(&mut stream, &[250])?;
+        smtp_command(&mut stream, "QUIT", &[221])
+    }
+}
+
+/// Implement `HookFunc` to `SmtpNotifyHook`
+#[cfg(feature = "notify-smtp")]
+impl HookFunc for SmtpNotifyHook {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        let message = self.message(delivery)?;
+        self.send(&message)
+            .map_err(|error| Error::HookFailed(format!("{}: {}", &self.relay, error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    #[cfg(feature = "notify-smtp")]
+    use std::net::TcpListener;
+    #[cfg(feature = "notify-smtp")]
+    use std::thread;
+
+    fn delivery() -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        let request_body = r#"{"ref": "refs/heads/master", "forced": false, "repository": {"full_name": "RedL0tus/rifling"}, "sender": {"login": "octocat"}, "commits": [{"message": "Fix it"}]}"#;
+        Delivery::new(headers, Some(request_body.to_string())).unwrap()
+    }
+
+    /// Test building the requests of Slack
+    #[cfg(feature = "notify-slack")]
+    #[test]
+    fn slack_request() {
+        let slack =
+            SlackNotifyHook::new("https://hooks.slack.com/services/T/B/X", "Push to {{ref}}")
+                .username("rifling");
+        let request = slack.request(&delivery()).unwrap();
+        assert_eq!(request.uri(), "https://hooks.slack.com/services/T/B/X");
+        let body = request.into_body().concat2().wait().unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["text"], "Push to refs/heads/master");
+        assert_eq!(body["username"], "rifling");
+    }
+
+    /// Test building the requests of Matrix
+    #[cfg(feature = "notify-matrix")]
+    #[test]
+    fn matrix_request() {
+        let matrix = MatrixNotifyHook::new(
+            "https://matrix.example.com/",
+            "!room:example.com",
+            "token",
+            "{{sender.login}} pushed",
+        );
+        let request = matrix.request(&delivery()).unwrap();
+        assert!(request.uri().to_string().starts_with(
+            "https://matrix.example.com/_matrix/client/v3/rooms/%21room%3Aexample.com/send/m.room.message/rifling-"
+        ));
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer token");
+        let body = request.into_body().concat2().wait().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["body"], "octocat pushed");
+    }
+
+    /// Test sending emails through a fake relay
+    #[cfg(feature = "notify-smtp")]
+    #[test]
+    fn smtp_notify() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let relay = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            stream.write_all(b"220 relay ESMTP\r\n").unwrap();
+            let mut received = String::new();
+            let mut data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    return received;
+                }
+                received.push_str(&line);
+                let reply: &[u8] = match line.as_str() {
+                    "EHLO rifling\r\n" => b"250-relay\r\n250 8BITMIME\r\n",
+                    "DATA\r\n" => {
+                        data = true;
+                        b"354 Go ahead\r\n"
+                    }
+                    ".\r\n" if data => {
+                        data = false;
+                        b"250 Queued\r\n"
+                    }
+                    "QUIT\r\n" => b"221 Bye\r\n",
+                    _ if data => b"",
+                    _ => b"250 OK\r\n",
+                };
+                stream.write_all(reply).unwrap();
+            }
+        });
+        let hook = SmtpNotifyHook::new(&relay, "rifling@example.com", &["ops@example.com"])
+            .subject("Push to {{repository.full_name}} ✓")
+            .body("By {{sender.login}}\n.hidden");
+        hook.run(&delivery()).unwrap();
+        let received = server.join().unwrap();
+        assert!(received.starts_with(
+            "EHLO rifling\r\nMAIL FROM:<rifling@example.com>\r\nRCPT TO:<ops@example.com>\r\nDATA\r\nFrom: rifling@example.com\r\n"
+        ));
+        assert!(received.contains("Subject: =?utf-8?b?"));
+        assert!(received.ends_with("\r\n\r\nBy octocat\r\n..hidden\r\n.\r\nQUIT\r\n"));
+    }
+}
@@ -0,0 +1,176 @@
+//! OpenTelemetry tracing
+//!
+//! With the `otel` feature, every delivery is traced with a span named `webhook <event>`, and each hook run with
+//! a child span named `hook <event>`. When the request carries a W3C `traceparent` header (and optionally
+//! `tracestate`), the span of the delivery continues the trace of the sender or the upstream proxy.
+//!
+//! Spans are created with the global tracer `rifling`, install a tracer provider with
+//! `opentelemetry::global::set_tracer_provider` to export them. Hooks run after a retry delay are children of the
+//! span from `traceparent` instead, as the span of the delivery has already ended.
+
+use opentelemetry::global::{self, BoxedSpan};
+use opentelemetry::trace::{
+    Span, SpanContext, SpanId, SpanKind, Status, TraceContextExt, TraceFlags, TraceId, TraceState,
+    Tracer,
+};
+use opentelemetry::{Context, KeyValue};
+
+use super::error::Error;
+use super::handler::Delivery;
+use super::outcome::Outcome;
+
+/// Name of the tracer
+const TRACER_NAME: &str = "rifling";
+
+/// Parse the `traceparent` header, `00-<trace id>-<parent id>-<flags>`
+fn parse_traceparent(traceparent: &str, tracestate: Option<&str>) -> Option<SpanContext> {
+    let mut parts = traceparent.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let flags = parts.next()?;
+    if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+    if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+        return None;
+    }
+    let trace_id = TraceId::from_hex(trace_id).ok()?;
+    let span_id = SpanId::from_hex(span_id).ok()?;
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    let trace_state: TraceState = tracestate
+        .and_then(|tracestate| tracestate.parse().ok())
+        .unwrap_or_default();
+    let span_context = SpanContext::new(
+        trace_id,
+        span_id,
+        TraceFlags::new(flags) & TraceFlags::SAMPLED,
+        true,
+        trace_state,
+    );
+    if span_context.is_valid() {
+        Some(span_context)
+    } else {
+        None
+    }
+}
+
+/// Context of the sender, from the `traceparent` header of the delivery
+fn remote_context(delivery: &Delivery) -> Context {
+    let headers = &delivery.headers;
+    let traceparent = match headers.get("traceparent") {
+        Some(traceparent) => traceparent,
+        None => return Context::new(),
+    };
+    let tracestate = headers.get("tracestate");
+    match parse_traceparent(traceparent, tracestate) {
+        Some(span_context) => Context::new().with_remote_span_context(span_context),
+        None => {
+            debug!("Invalid traceparent header: {}", traceparent);
+            Context::new()
+        }
+    }
+}
+
+/// Start the span of the delivery, the returned context should be attached while handling the delivery
+pub(crate) fn start_delivery(delivery: &Delivery) -> Context {
+    let tracer = global::tracer(TRACER_NAME);
+    let mut attributes = vec![
+        KeyValue::new("rifling.provider", format!("{:?}", delivery.delivery_type)),
+        KeyValue::new("rifling.event", delivery.event.clone()),
+    ];
+    if let Some(id) = &delivery.id {
+        attributes.push(KeyValue::new("rifling.delivery_id", id.clone()));
+    }
+    let span = tracer
+        .span_builder(format!("webhook {}", delivery.event))
+        .with_kind(SpanKind::Server)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &remote_context(delivery));
+    Context::new().with_span(span)
+}
+
+/// End the span of the delivery with the outcome
+pub(crate) fn end_delivery(context: &Context, outcome: &Outcome) {
+    let span = context.span();
+    let status = outcome.status();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        i64::from(status),
+    ));
+    if status >= 400 {
+        span.set_status(Status::error(outcome.body()));
+    }
+    span.end();
+}
+
+/// Start the span of the hook run, as a child of the span of the delivery if it's still active
+pub(crate) fn start_hook(event: &str, name: Option<&str>, delivery: &Delivery) -> BoxedSpan {
+    let tracer = global::tracer(TRACER_NAME);
+    let current = Context::current();
+    let parent = if current.has_active_span() {
+        current
+    } else {
+        remote_context(delivery)
+    };
+    let mut attributes = vec![KeyValue::new("rifling.event", delivery.event.clone())];
+    if let Some(name) = name {
+        attributes.push(KeyValue::new("rifling.hook", name.to_string()));
+    }
+    tracer
+        .span_builder(format!("hook {}", name.unwrap_or(event)))
+        .with_kind(SpanKind::Internal)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent)
+}
+
+/// End the span of the hook run with its result
+pub(crate) fn end_hook(mut span: BoxedSpan, result: &Result<(), Error>) {
+    if let Err(error) = result {
+        span.set_status(Status::error(error.to_string()));
+    }
+    span.end();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test parsing `traceparent` headers
+    #[test]
+    fn traceparent() {
+        let span_context = parse_traceparent(
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            Some("congo=t61rcWkgMzE"),
+        )
+        .unwrap();
+        assert_eq!(
+            span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        assert_eq!(
+            span_context.span_id(),
+            SpanId::from_hex("b7ad6b7169203331").unwrap()
+        );
+        assert!(span_context.is_sampled());
+        assert!(span_context.is_remote());
+        assert_eq!(span_context.trace_state().get("congo"), Some("t61rcWkgMzE"));
+        assert!(parse_traceparent(
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00",
+            None
+        )
+        .is_some_and(|span_context| !span_context.is_sampled()));
+        // All zero trace ID
+        assert!(parse_traceparent(
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            None
+        )
+        .is_none());
+        assert!(parse_traceparent("00-0af7651916cd43dd-b7ad6b7169203331-01", None).is_none());
+        assert!(parse_traceparent(
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            None
+        )
+        .is_none());
+    }
+}
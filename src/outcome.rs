@@ -0,0 +1,214 @@
+//! Outcome
+//!
+//! `Outcome` describes what happened to a request passed to `Handler::handle`, it is returned in a
+//! `Response` along with the delivery, and passed to the callback registered with `Constructor::on_outcome`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Outcome, Response};
+//!
+//! let mut cons = Constructor::new();
+//! cons.on_outcome(|response: &Response| {
+//!     if let Outcome::AuthFailed = response.outcome {
+//!         eprintln!("Unauthenticated delivery: {:?}", response.delivery);
+//!     }
+//! });
+//! ```
+//!
+//! The status code and body sent back to the sender are derived from the outcome.
+
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "parse")]
+use super::admission::AdmissionResponse;
+use super::context::ResponseStream;
+use super::error::{self, Error};
+use super::handler::Delivery;
+use super::overrides::ResponseOverride;
+
+/// What happened to a request
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// The delivery was authenticated and passed to the given number of hooks, `unverified` of them accepted it
+    /// without verifying it (no secret, or `Constructor::allow_unverified`)
+    Handled { hooks: usize, unverified: usize },
+    /// The delivery was authenticated and matched the given number of hooks, which are queued until running hooks
+    /// finish (see `ConcurrencyPolicy::Queue`)
+    Queued { hooks: usize },
+    /// The delivery was authenticated and matched the given number of hooks, which were all skipped by a dry run
+    /// (see `Constructor::dry_run`)
+    DryRun { hooks: usize },
+    /// The PING of Discord was authenticated and answered with a PONG, no hook is run
+    Pong,
+    /// A `GET` or `HEAD` request was answered with the landing page, see `Constructor::landing_page`
+    Landing(String),
+    /// A `GET` or `HEAD` request was answered with the landing page naming the unreachable downstream targets, see
+    /// `Constructor::readiness_check`
+    Unready(String),
+    /// The admission review of Kubernetes was decided by the hooks
+    #[cfg(feature = "parse")]
+    Admission(AdmissionResponse),
+    /// No hook is registered for the event
+    NoMatch,
+    /// Hooks are registered for the event, but the delivery failed to authenticate with all of them
+    AuthFailed,
+    /// The request is not a delivery rifling understands
+    InvalidPayload(Error),
+    /// The delivery was authenticated and recorded, but no hook is run during maintenance, responded with the
+    /// given status
+    Maintenance { status: u16 },
+    /// The delivery was authenticated and recorded, but its event has a response override, no hook is run
+    Overridden(ResponseOverride),
+    /// The delivery was refused before running any hook (untrusted client, rate limited, stale,
+    /// invalid or overloaded), or its body was too slow or too large
+    Rejected(Error),
+}
+
+/// Response to a request, returned by `Handler::handle`
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub outcome: Outcome,
+    /// The delivery, if the request is recognized as one
+    pub delivery: Option<Delivery>,
+    /// Fragments added by hooks with `Context::respond`
+    pub fragments: Vec<String>,
+    /// Additional headers, see `Constructor::response_header` and `Constructor::request_id`
+    pub headers: Vec<(String, String)>,
+    /// Fragments added by hooks streaming the response, to send after the body, see `Hook::stream_response`
+    pub stream: Option<ResponseStream>,
+    /// Time before which the response must not be sent, see `Constructor::min_response_time`
+    pub hold_until: Option<Instant>,
+}
+
+/// The main impl clause of `Outcome`
+impl Outcome {
+    /// HTTP status code to respond with
+    pub fn status(&self) -> u16 {
+        match self {
+            Outcome::Handled { .. } | Outcome::DryRun { .. } => 200,
+            Outcome::Pong | Outcome::Landing(_) => 200,
+            Outcome::Unready(_) => 503,
+            #[cfg(feature = "parse")]
+            Outcome::Admission(_) => 200,
+            Outcome::NoMatch | Outcome::Queued { .. } => 202,
+            Outcome::AuthFailed => 401,
+            Outcome::InvalidPayload(_) => 202,
+            Outcome::Rejected(Error::RateLimited(_)) => 429,
+            Outcome::Rejected(Error::StaleDelivery(_)) => 400,
+            Outcome::Rejected(Error::UntrustedClient) => 403,
+            Outcome::Rejected(Error::UntrustedHost(_)) => 403,
+            Outcome::Rejected(Error::UntrustedUserAgent(_)) => 403,
+            Outcome::Rejected(Error::InvalidHeaders(_)) => 400,
+            Outcome::Rejected(Error::ValidationFailed(_)) => 422,
+            Outcome::Rejected(Error::RequestTimeout(_)) => 408,
+            Outcome::Rejected(Error::PayloadTooLarge(_)) => 413,
+            Outcome::Maintenance { status } => *status,
+            Outcome::Overridden(response) => response.status,
+            Outcome::Rejected(_) => 503,
+        }
+    }
+
+    /// Body to respond with
+    pub fn body(&self) -> String {
+        match self {
+            Outcome::Handled { .. } => "OK".to_string(),
+            Outcome::DryRun { hooks } => format!("Dry run, {} hook(s) would have run", hooks),
+            Outcome::Queued { hooks } => format!("Queued, {} hook(s) will run", hooks),
+            Outcome::Pong => r#"{"type":1}"#.to_string(),
+            Outcome::Landing(page) | Outcome::Unready(page) => page.clone(),
+            #[cfg(feature = "parse")]
+            Outcome::Admission(response) => response.to_review().to_string(),
+            Outcome::NoMatch => "No matched hook configured".to_string(),
+            Outcome::AuthFailed => "Authentication failed".to_string(),
+            Outcome::Maintenance { .. } => "Under maintenance".to_string(),
+            Outcome::Overridden(response) => response.body.clone(),
+            Outcome::InvalidPayload(error) | Outcome::Rejected(error) => error.to_string(),
+        }
+    }
+
+    /// Value of the `Content-Type` header, set when the body is not plain text
+    pub fn content_type(&self) -> Option<&'static str> {
+        match self {
+            Outcome::Pong | Outcome::Landing(_) | Outcome::Unready(_) => Some("application/json"),
+            #[cfg(feature = "parse")]
+            Outcome::Admission(_) => Some("application/json"),
+            _ => None,
+        }
+    }
+
+    /// Value of the `Retry-After` header in seconds, set when rate limited
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Outcome::Rejected(Error::RateLimited(retry_after)) => {
+                Some(error::retry_after_secs(*retry_after))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The main impl clause of `Response`
+impl Response {
+    /// Shorthand for `self.outcome.status()`
+    pub fn status(&self) -> u16 {
+        self.outcome.status()
+    }
+
+    /// Body to respond with, the body of the outcome followed by the fragments added by hooks, one per line
+    pub fn body(&self) -> String {
+        let mut body = self.outcome.body();
+        if let Outcome::Handled { .. } = self.outcome {
+            for fragment in &self.fragments {
+                body.push('\n');
+                body.push_str(fragment);
+            }
+        }
+        body
+    }
+
+    /// Shorthand for `self.outcome.content_type()`
+    pub fn content_type(&self) -> Option<&'static str> {
+        self.outcome.content_type()
+    }
+
+    /// Shorthand for `self.outcome.retry_after()`
+    pub fn retry_after(&self) -> Option<u64> {
+        self.outcome.retry_after()
+    }
+
+    /// Time left before the response can be sent, zero if it's not held
+    pub fn hold(&self) -> Duration {
+        self.hold_until
+            .map(|hold_until| hold_until.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test mapping outcomes to responses
+    #[test]
+    fn outcome_response() {
+        assert_eq!(
+            Outcome::Handled {
+                hooks: 2,
+                unverified: 0
+            }
+            .status(),
+            200
+        );
+        assert_eq!(Outcome::AuthFailed.status(), 401);
+        assert_eq!(Outcome::Pong.body(), r#"{"type":1}"#);
+        assert_eq!(Outcome::Pong.content_type(), Some("application/json"));
+        let rate_limited = Outcome::Rejected(Error::RateLimited(Duration::from_millis(1500)));
+        assert_eq!(rate_limited.status(), 429);
+        assert_eq!(rate_limited.retry_after(), Some(2));
+        let stale = Outcome::Rejected(Error::StaleDelivery(Duration::from_secs(600)));
+        assert_eq!(stale.status(), 400);
+        assert_eq!(stale.body(), "Delivery is 600 second(s) old");
+        assert_eq!(Outcome::Rejected(Error::Overloaded).status(), 503);
+    }
+}
@@ -0,0 +1,103 @@
+//! Response overrides
+//!
+//! Deliveries of events with an override (set with `Constructor::override_response`) are answered with its status and body instead of running hooks, e.g. to
+//! reply `503 Service Unavailable` to `deployment` events during a deploy freeze. Like during maintenance, they're
+//! still authenticated, checked and recorded to the store first.
+//!
+//! Overrides can be set on the `Constructor`, or changed at runtime with the handle returned by
+//! `Constructor::overrides_handle`, e.g. from the configuration of the application:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//!
+//! let mut cons = Constructor::new();
+//! let overrides = cons.overrides_handle();
+//! // Read from the configuration file of the application
+//! overrides.replace(vec![("deployment", 503, "Deploy freeze")]);
+//! // After the freeze
+//! overrides.remove("deployment");
+//! ```
+//!
+//! Events are normalized like those of hooks and matched against the event of deliveries and its aliases (see
+//! `Constructor::alias`), the override of `*` applies to every event without one.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+
+/// Status and body responded to deliveries of an event
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResponseOverride {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Handle to the response overrides of a `Constructor` and its `Handler`s, cheap to clone
+#[derive(Clone, Debug, Default)]
+pub struct OverridesHandle {
+    overrides: Arc<RwLock<HashMap<String, ResponseOverride>>>, // by events as set
+}
+
+/// The main impl clause of `OverridesHandle`
+impl OverridesHandle {
+    /// Answer deliveries of the event with the status and body instead of running hooks
+    pub fn set(&self, event: &str, status: u16, body: &str) {
+        let response = ResponseOverride {
+            status,
+            body: body.to_string(),
+        };
+        self.write().insert(event.to_string(), response);
+    }
+
+    /// Run hooks for deliveries of the event again
+    pub fn remove(&self, event: &str) {
+        self.write().remove(event);
+    }
+
+    /// Replace all the overrides at once, e.g. after re-reading the configuration
+    pub fn replace<'a>(&self, overrides: impl IntoIterator<Item = (&'a str, u16, &'a str)>) {
+        let overrides = overrides
+            .into_iter()
+            .map(|(event, status, body)| {
+                let response = ResponseOverride {
+                    status,
+                    body: body.to_string(),
+                };
+                (event.to_string(), response)
+            })
+            .collect();
+        *self.write() = overrides;
+    }
+
+    /// Override of the first event having one, with events normalized by the function
+    pub(crate) fn find(
+        &self,
+        events: &[String],
+        normalize: impl Fn(&str) -> String,
+    ) -> Option<ResponseOverride> {
+        let overrides = match self.overrides.read() {
+            Ok(overrides) => overrides,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if overrides.is_empty() {
+            return None;
+        }
+        let normalized: HashMap<String, &ResponseOverride> = overrides
+            .iter()
+            .map(|(event, response)| (normalize(event), response))
+            .collect();
+        events
+            .iter()
+            .find_map(|event| normalized.get(event))
+            .map(|response| (*response).clone())
+    }
+
+    /// Lock the overrides to change them
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, ResponseOverride>> {
+        match self.overrides.write() {
+            Ok(overrides) => overrides,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
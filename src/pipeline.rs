@@ -0,0 +1,224 @@
+//! Hook pipelines
+//!
+//! `Pipeline` runs named stages one after another for each delivery, passing them the delivery and the data written
+//! by earlier stages, e.g. to resolve a deployment target, deploy it and tell a channel about it:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::pipeline::{Pipeline, PipelineData};
+//! use rifling::{Delivery, Hook};
+//!
+//! let pipeline = Pipeline::new("release")
+//!     .stage("resolve", |data: &mut PipelineData, delivery: &Delivery| {
+//!         let target = match delivery.field("/ref").and_then(|value| value.as_str()) {
+//!             Some("refs/heads/main") => "production",
+//!             _ => "staging",
+//!         };
+//!         data.insert("target", target);
+//!     })
+//!     .stage("deploy", |data: &mut PipelineData, _: &Delivery| {
+//!         let target = data.require("target")?;
+//!         println!("Deploying to {}", target);
+//!         data.insert("version", "v1.0.0");
+//!         Ok::<(), rifling::Error>(())
+//!     })
+//!     .stage("notify", |data: &mut PipelineData, _: &Delivery| {
+//!         println!("Deployed {:?} to {:?}", data.get("version"), data.get("target"));
+//!     });
+//! let hook = Hook::new("push", Some("secret".to_string()), pipeline);
+//! ```
+//!
+//! Each delivery (and each retry of it) starts with empty data. The first failing stage aborts the pipeline, the
+//! stages after it are not run and the hook fails with `Error::HookFailed` naming the pipeline and the stage.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::error::Error;
+use super::handler::Delivery;
+use super::hook::{HookFunc, HookResult};
+
+/// Stage of a pipeline, receiving the data written by earlier stages
+/// You can implement this trait to your own struct
+pub trait Stage: Sync + Send {
+    fn run(&self, data: &mut PipelineData, delivery: &Delivery) -> Result<(), Error>;
+}
+
+/// Data passed from stage to stage of a pipeline run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PipelineData {
+    values: HashMap<String, String>,
+}
+
+/// Named stages run one after another for each delivery
+#[derive(Clone)]
+pub struct Pipeline {
+    name: String,
+    stages: Vec<(String, Arc<dyn Stage>)>,
+}
+
+/// The main impl clause of `PipelineData`
+impl PipelineData {
+    /// Create new, empty data
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of the key for the next stages, replacing the previous one
+    pub fn insert(&mut self, key: &str, value: impl Into<String>) {
+        self.values.insert(key.to_string(), value.into());
+    }
+
+    /// Value of the key, if an earlier stage set it
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Value of the key, failing the stage if no earlier stage set it
+    pub fn require(&self, key: &str) -> Result<&str, Error> {
+        self.get(key)
+            .ok_or_else(|| Error::HookFailed(format!("Missing '{}' in the pipeline data", key)))
+    }
+
+    /// Remove the key, returning its value
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    /// Keys and values set by the stages
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// The main impl clause of `Pipeline`
+impl Pipeline {
+    /// Create a new pipeline without stages
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Run the stage after the ones added before
+    pub fn stage(mut self, name: &str, stage: impl Stage + 'static) -> Self {
+        self.stages.push((name.to_string(), Arc::new(stage)));
+        self
+    }
+
+    /// Name of the pipeline
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the stages for the delivery, stopping at the first failing one, and return the data they wrote
+    pub fn run_stages(&self, delivery: &Delivery) -> Result<PipelineData, Error> {
+        let mut data = PipelineData::new();
+        for (stage, func) in self.stages.iter() {
+            debug!("Running stage '{}' of pipeline '{}'", stage, &self.name);
+            if let Err(error) = func.run(&mut data, delivery) {
+                error!(
+                    "Stage '{}' of pipeline '{}' failed, aborting: {}",
+                    stage, &self.name, &error
+                );
+                return Err(Error::HookFailed(format!(
+                    "Stage '{}' of pipeline '{}' failed: {}",
+                    stage, &self.name, error
+                )));
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Implement `Debug` to `Pipeline`, stages are listed by name
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stages: Vec<&str> = self.stages.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("Pipeline")
+            .field("name", &self.name)
+            .field("stages", &stages)
+            .finish()
+    }
+}
+
+/// Implement `Stage` to `Fn(&mut PipelineData, &Delivery)`.
+impl<F, R> Stage for F
+where
+    F: Fn(&mut PipelineData, &Delivery) -> R + Sync + Send + 'static,
+    R: HookResult,
+{
+    /// Run the function
+    fn run(&self, data: &mut PipelineData, delivery: &Delivery) -> Result<(), Error> {
+        self(data, delivery).into_result()
+    }
+}
+
+/// Implement `HookFunc` to `Pipeline`
+impl HookFunc for Pipeline {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.run_stages(delivery).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn delivery() -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        Delivery::new(headers, Some(r#"{"ref": "refs/heads/main"}"#.to_string())).unwrap()
+    }
+
+    /// Test passing data from stage to stage
+    #[test]
+    fn stages() {
+        let pipeline = Pipeline::new("release")
+            .stage("resolve", |data: &mut PipelineData, _: &Delivery| {
+                data.insert("target", "production");
+            })
+            .stage("deploy", |data: &mut PipelineData, _: &Delivery| {
+                let version = format!("{}-v1", data.require("target")?);
+                data.insert("version", version);
+                Ok::<(), Error>(())
+            });
+        let data = pipeline.run_stages(&delivery()).unwrap();
+        assert_eq!(data.get("version"), Some("production-v1"));
+        assert_eq!(data.iter().count(), 2);
+        assert!(HookFunc::run(&pipeline, &delivery()).is_ok());
+    }
+
+    /// Test aborting pipelines at the first failing stage
+    #[test]
+    fn failing_stage() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let pipeline = Pipeline::new("release")
+            .stage("resolve", |_: &mut PipelineData, _: &Delivery| {
+                Err("no target")
+            })
+            .stage("deploy", move |_: &mut PipelineData, _: &Delivery| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        match HookFunc::run(&pipeline, &delivery()) {
+            Err(Error::HookFailed(message)) => {
+                assert!(message.starts_with("Stage 'resolve' of pipeline 'release' failed"))
+            }
+            _ => panic!("Pipeline didn't fail"),
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        let missing = Pipeline::new("release")
+            .stage("deploy", |data: &mut PipelineData, _: &Delivery| {
+                data.require("target").map(|_| ())
+            });
+        assert!(missing.run_stages(&delivery()).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Trusted proxies
+//!
+//! Behind reverse proxies, the address of the peer is the address of the proxy, and the address of the sender is in
+//! the `Forwarded` (RFC 7239) or `X-Forwarded-For` header. These headers are only used when the peer is one of the
+//! proxies trusted with `Constructor::trust_proxies`, as anyone can send them:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//!
+//! let mut cons = Constructor::new();
+//! cons.trust_proxies(&["127.0.0.1/32", "10.0.0.0/8", "::1/128"]).unwrap();
+//! ```
+//!
+//! The address of the sender is the nearest address in the header which is not a trusted proxy. It's available as
+//! `Delivery::remote_addr`, used by rate limits per IP address and in the access log. The scheme of the original
+//! request (`proto=` or `X-Forwarded-Proto`) is available as `Delivery::scheme`.
+
+use std::error;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use super::handler::Headers;
+
+/// Range of IP addresses, e.g. `10.0.0.0/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+/// Error parsing a `Cidr`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidCidr(pub String);
+
+/// Implement `Display` for `InvalidCidr`
+impl fmt::Display for InvalidCidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid CIDR: {}", self.0)
+    }
+}
+
+impl error::Error for InvalidCidr {}
+
+/// Parse `<address>/<prefix length>`, a single address without prefix length is accepted too
+impl FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(cidr: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCidr(cidr.to_string());
+        let (addr, prefix) = match cidr.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (cidr.trim(), None),
+        };
+        let addr = addr.parse::<IpAddr>().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// The main impl clause of `Cidr`
+impl Cidr {
+    /// Test if the address is in the range
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let (range, addr, bits) = match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(range), IpAddr::V4(addr)) => (
+                u128::from(u32::from(range)),
+                u128::from(u32::from(addr)),
+                32,
+            ),
+            (IpAddr::V6(range), IpAddr::V6(addr)) => (u128::from(range), u128::from(addr), 128),
+            _ => return false,
+        };
+        let shift = bits - u32::from(self.prefix);
+        shift >= bits || range >> shift == addr >> shift
+    }
+}
+
+/// Address in a node of `Forwarded` or `X-Forwarded-For` (e.g. `"[2001:db8::1]:4711"`), unless obfuscated
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| node.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+}
+
+/// Values of the parameter in the elements of the `Forwarded` header
+fn forwarded_params<'a>(forwarded: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    forwarded.split(',').filter_map(move |element| {
+        element
+            .split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    })
+}
+
+/// Address of the sender and scheme of the request sent to the peer, header names must be lower cased
+pub(crate) fn resolve(
+    peer: IpAddr,
+    headers: &Headers,
+    trusted: &[Cidr],
+) -> (IpAddr, Option<String>) {
+    let is_trusted = |addr: &IpAddr| trusted.iter().any(|cidr| cidr.contains(addr));
+    if !is_trusted(&peer) {
+        return (peer, None);
+    }
+    let (nodes, scheme): (Vec<&str>, Option<&str>) = match headers.get("forwarded") {
+        Some(forwarded) => (
+            forwarded_params(forwarded, "for").collect(),
+            forwarded_params(forwarded, "proto").next(),
+        ),
+        None => (
+            headers
+                .get("x-forwarded-for")
+                .map(|nodes| nodes.split(',').collect())
+                .unwrap_or_default(),
+            headers.get("x-forwarded-proto"),
+        ),
+    };
+    let mut client = peer;
+    for node in nodes.iter().rev() {
+        match parse_node(node) {
+            Some(addr) => {
+                client = addr;
+                if !is_trusted(&addr) {
+                    break;
+                }
+            }
+            None => break, // Obfuscated or unknown, the nearest known address is used
+        }
+    }
+    let scheme = scheme.map(|scheme| scheme.trim_matches('"').to_lowercase());
+    (client, scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Test matching addresses with ranges
+    #[test]
+    fn cidr() {
+        let range: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(range.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(range.contains(&"::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!range.contains(&"11.0.0.1".parse().unwrap()));
+        let range: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(range.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!range.contains(&"10.1.2.3".parse().unwrap()));
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(&"192.0.2.1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("localhost".parse::<Cidr>().is_err());
+    }
+
+    /// Test resolving the address of the sender
+    #[test]
+    fn resolve_forwarded() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let headers = |name: &str, value: &str| {
+            let mut headers = HashMap::new();
+            headers.insert(name.to_string(), value.to_string());
+            Headers::from(headers)
+        };
+        // Only the nearest untrusted address counts, the first ones may be forged
+        let forwarded = headers("x-forwarded-for", "203.0.113.9, 192.0.2.1, 10.0.0.2");
+        assert_eq!(
+            resolve(proxy, &forwarded, &trusted),
+            ("192.0.2.1".parse().unwrap(), None)
+        );
+        let peer = "192.0.2.7".parse().unwrap();
+        assert_eq!(resolve(peer, &forwarded, &trusted), (peer, None));
+        let forwarded = headers(
+            "forwarded",
+            "for=\"[2001:db8::17]:4711\";proto=HTTPS, for=10.0.0.2",
+        );
+        assert_eq!(
+            resolve(proxy, &forwarded, &trusted),
+            ("2001:db8::17".parse().unwrap(), Some("https".to_string()))
+        );
+        let forwarded = headers("forwarded", "for=_hidden, for=10.0.0.2");
+        assert_eq!(
+            resolve(proxy, &forwarded, &trusted),
+            ("10.0.0.2".parse().unwrap(), None)
+        );
+    }
+}
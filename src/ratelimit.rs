@@ -0,0 +1,161 @@
+//! Rate limiting
+//!
+//! Token bucket rate limiters, configured on the `Constructor` with `Constructor::rate_limit`.
+//!
+//! Every limiter has a scope deciding which deliveries share a bucket:
+//!
+//!  - `Global`: all deliveries.
+//!  - `RemoteAddr`: deliveries from the same source IP address.
+//!  - `Repository`: deliveries concerning the same repository (requires the `parse` feature).
+//!
+//! Example:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//! use rifling::ratelimit::RateLimitScope;
+//!
+//! use std::time::Duration;
+//!
+//! let mut cons = Constructor::new();
+//! // Bursts of 10 deliveries from every IP address, refilled at 1 delivery per 6 seconds.
+//! cons.rate_limit(RateLimitScope::RemoteAddr, 10, Duration::from_secs(60));
+//! ```
+//!
+//! Exceeding deliveries are answered with `429 Too Many Requests` and a `Retry-After` header.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::handler::Delivery;
+
+/// Number of buckets kept before full buckets are pruned
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Which deliveries share a bucket
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitScope {
+    Global,
+    RemoteAddr,
+    Repository,
+}
+
+/// State of a single bucket
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket rate limiter
+#[derive(Debug)]
+pub struct RateLimiter {
+    scope: RateLimitScope,
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+/// The main impl clause of `RateLimitScope`
+impl RateLimitScope {
+    /// Key of the bucket for the delivery, `None` if the delivery is not limited in this scope
+    pub fn key(self, delivery: &Delivery) -> Option<String> {
+        match self {
+            RateLimitScope::Global => Some(String::new()),
+            RateLimitScope::RemoteAddr => delivery.remote_addr.map(|addr| addr.to_string()),
+            RateLimitScope::Repository => delivery.repository(),
+        }
+    }
+}
+
+/// The main impl clause of `Bucket`
+impl Bucket {
+    /// Refill the bucket according to the time passed
+    fn refill(&mut self, now: Instant, capacity: f64, refill_per_sec: f64) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * refill_per_sec).min(capacity);
+        self.updated = now;
+    }
+}
+
+/// The main impl clause of `RateLimiter`
+impl RateLimiter {
+    /// Create a new rate limiter allowing bursts of `burst` deliveries, refilled completely in `period`
+    pub fn new(scope: RateLimitScope, burst: u32, period: Duration) -> Self {
+        let capacity = f64::from(burst.max(1));
+        let period = period.as_secs_f64();
+        Self {
+            scope,
+            capacity,
+            refill_per_sec: if period > 0.0 {
+                capacity / period
+            } else {
+                f64::INFINITY
+            },
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Scope of the limiter
+    pub fn scope(&self) -> RateLimitScope {
+        self.scope
+    }
+
+    /// Take a token for the delivery, returns how long to wait if the bucket is empty
+    pub fn check(&self, delivery: &Delivery) -> Result<(), Duration> {
+        match self.scope.key(delivery) {
+            Some(key) => self.acquire(&key),
+            None => Ok(()),
+        }
+    }
+
+    /// Take a token from the bucket, returns how long to wait if the bucket is empty
+    pub fn acquire(&self, key: &str) -> Result<(), Duration> {
+        self.acquire_at(key, Instant::now())
+    }
+
+    fn acquire_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if !buckets.contains_key(key) && buckets.len() >= PRUNE_THRESHOLD {
+            let (capacity, refill_per_sec) = (self.capacity, self.refill_per_sec);
+            buckets.retain(|_, bucket| {
+                bucket.refill(now, capacity, refill_per_sec);
+                bucket.tokens < capacity
+            });
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.refill(now, self.capacity, self.refill_per_sec);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.refill_per_sec;
+            Err(Duration::from_millis((wait * 1000.0).ceil() as u64))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test exhausting and refilling a bucket
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::new(RateLimitScope::Global, 2, Duration::from_secs(10));
+        let now = Instant::now();
+        assert!(limiter.acquire_at("", now).is_ok());
+        assert!(limiter.acquire_at("", now).is_ok());
+        assert_eq!(limiter.acquire_at("", now), Err(Duration::from_secs(5)));
+        assert!(limiter.acquire_at("another", now).is_ok());
+        assert!(limiter.acquire_at("", now + Duration::from_secs(5)).is_ok());
+    }
+}
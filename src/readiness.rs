@@ -0,0 +1,116 @@
+//! Readiness of downstream targets
+//!
+//! Hooks forwarding deliveries to other services (e.g. through sinks) can't do their job while these services are
+//! down, deliveries only pile up in the retry queue. Readiness checks added with `Constructor::readiness_check` mark
+//! the landing page (see `Constructor::landing_page`) unhealthy while a target is unreachable, so load balancers
+//! probing it (e.g. mounted at `/healthz` with `Router`) stop routing webhooks to the instance:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use std::time::Duration;
+//!
+//! use rifling::readiness::TcpCheck;
+//! use rifling::{Constructor, Router};
+//!
+//! let mut health = Constructor::new();
+//! health.landing_page("deploy-bot");
+//! health.readiness_check("redis", TcpCheck::new("127.0.0.1:6379", Duration::from_secs(1)));
+//! health.readiness_check("registry", || Ok::<(), String>(()));
+//! let router = Router::new().mount("/healthz", health);
+//! ```
+//!
+//! The landing page is then answered with `503 Service Unavailable`, naming the unreachable targets:
+//!
+//! ```text
+//! {"service":"deploy-bot","status":"unavailable","unreachable":["redis"],"method":"POST","providers":[...]}
+//! ```
+//!
+//! Checks are run for each request to the landing page, `Handler::unreachable_targets` runs them for health endpoints
+//! served otherwise. Deliveries are still handled while targets are unreachable.
+
+use std::fmt;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::error::Error;
+use super::hook::HookResult;
+
+/// Check of a downstream target, failing while it's unreachable
+/// You can implement this trait to your own struct
+pub trait ReadinessCheck: Send + Sync {
+    fn check(&self) -> Result<(), Error>;
+}
+
+/// Check connecting to the address over TCP
+#[derive(Clone, Debug)]
+pub struct TcpCheck {
+    addr: String,
+    timeout: Duration,
+}
+
+/// The main impl clause of `TcpCheck`
+impl TcpCheck {
+    /// Check connecting to the address (e.g. `redis.internal:6379`), giving up after the timeout
+    pub fn new(addr: &str, timeout: Duration) -> Self {
+        Self {
+            addr: addr.to_string(),
+            timeout,
+        }
+    }
+}
+
+/// Implement `ReadinessCheck` to `TcpCheck`, any of the addresses the host resolves to has to accept the connection
+impl ReadinessCheck for TcpCheck {
+    fn check(&self) -> Result<(), Error> {
+        let unreachable = |reason: &dyn fmt::Display| {
+            Error::Unreachable(format!("Unable to connect to {}: {}", &self.addr, reason))
+        };
+        let addrs = self
+            .addr
+            .to_socket_addrs()
+            .map_err(|error| unreachable(&error))?;
+        let mut last_error = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(_) => return Ok(()),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(match last_error {
+            Some(error) => unreachable(&error),
+            None => unreachable(&"no address"),
+        })
+    }
+}
+
+/// Implement `ReadinessCheck` to `Fn()`, the check fails if the function returns an error (see `HookResult`)
+impl<F, R> ReadinessCheck for F
+where
+    F: Fn() -> R + Send + Sync,
+    R: HookResult,
+{
+    /// Run the function
+    fn check(&self) -> Result<(), Error> {
+        self().into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Test checking targets over TCP
+    #[test]
+    fn tcp_check() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let check = TcpCheck::new(&addr, Duration::from_secs(1));
+        assert_eq!(check.check(), Ok(()));
+        drop(listener);
+        assert!(matches!(check.check(), Err(Error::Unreachable(_))));
+        let invalid = TcpCheck::new("not an address", Duration::from_secs(1));
+        assert!(matches!(invalid.check(), Err(Error::Unreachable(_))));
+    }
+}
@@ -0,0 +1,105 @@
+//! Redaction of secrets in logs
+//!
+//! Signatures, tokens and credentials are never logged, neither as headers nor as fields of `Delivery`.
+//! Bodies are only logged when enabled with `Constructor::log_payloads`, truncated to `MAX_LOGGED_BODY_LEN`.
+
+use std::fmt;
+
+use super::handler;
+
+/// Headers carrying signatures, tokens or credentials
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "circleci-signature",
+    "linear-signature",
+    "token",
+    "x-forgejo-signature",
+    "x-gerrit-signature",
+    "x-gitea-signature",
+    "x-gitee-token",
+    "x-gitlab-token",
+    "x-hub-signature",
+    "x-hub-signature-256",
+    "x-signature-ed25519",
+    "x-telegram-bot-api-secret-token",
+];
+
+/// Maximum number of characters of bodies in logs
+pub(crate) const MAX_LOGGED_BODY_LEN: usize = 1024;
+
+/// Secret value, formatted as `<redacted>`
+pub(crate) struct Secret;
+
+/// Headers with the values of sensitive ones redacted
+pub(crate) struct Headers<'a>(pub &'a handler::Headers);
+
+/// Body formatted as its length in bytes
+pub(crate) struct Length<'a>(pub &'a str);
+
+/// Body truncated to `MAX_LOGGED_BODY_LEN` characters
+pub(crate) struct Truncated<'a>(pub &'a str);
+
+/// Test if the header carries a secret, the name must be lower cased
+pub(crate) fn is_sensitive(name: &str) -> bool {
+    SENSITIVE_HEADERS.contains(&name)
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl<'a> fmt::Debug for Headers<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.0.iter() {
+            if is_sensitive(name) {
+                map.entry(&name, &Secret);
+            } else {
+                map.entry(&name, &value);
+            }
+        }
+        map.finish()
+    }
+}
+
+impl<'a> fmt::Debug for Length<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} bytes>", self.0.len())
+    }
+}
+
+impl<'a> fmt::Display for Truncated<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.char_indices().nth(MAX_LOGGED_BODY_LEN) {
+            Some((index, _)) => write!(
+                f,
+                "{}... ({} bytes truncated)",
+                &self.0[..index],
+                self.0.len() - index
+            ),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Test redacting headers and truncating bodies
+    #[test]
+    fn redaction() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-token".to_string(), "secret".to_string());
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        let formatted = format!("{:?}", Headers(&headers.into()));
+        assert!(!formatted.contains("secret"));
+        assert!(formatted.contains("Push Hook"));
+        let body = "a".repeat(MAX_LOGGED_BODY_LEN + 10);
+        assert!(format!("{}", Truncated(&body)).ends_with("a... (10 bytes truncated)"));
+        assert_eq!(format!("{}", Truncated("{}")), "{}");
+    }
+}
@@ -0,0 +1,129 @@
+//! Reloading hooks
+//!
+//! The hooks of a `Constructor` can be replaced at runtime with the handle returned by `Constructor::reload_handle`,
+//! e.g. after re-reading the configuration of the application when it receives `SIGHUP`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.register(Hook::new("push", Some("old secret".to_string()), |_: &Delivery| ()));
+//! let reload = cons.reload_handle();
+//! // Later
+//! reload.reload(vec![
+//!     Hook::new("push", Some("new secret".to_string()), |_: &Delivery| ()),
+//!     Hook::new("issues", None, |_: &Delivery| ()),
+//! ]);
+//! ```
+//!
+//! The registry is swapped atomically for every `Handler` created from the constructor, requests being handled keep
+//! the hooks they matched. Reading configuration files is left to the application, and so is handling signals unless
+//! the `reload-signal` feature is enabled: `ReloadHandle::reload_on_sighup` rebuilds the hooks with a function of the
+//! application on every `SIGHUP` (Unix only).
+
+#[cfg(all(unix, feature = "reload-signal"))]
+use signal_hook::consts::SIGHUP;
+#[cfg(all(unix, feature = "reload-signal"))]
+use signal_hook::iterator::Signals;
+
+#[cfg(all(unix, feature = "reload-signal"))]
+use std::fmt::Display;
+#[cfg(all(unix, feature = "reload-signal"))]
+use std::io;
+use std::sync::{Arc, RwLock};
+#[cfg(all(unix, feature = "reload-signal"))]
+use std::thread;
+
+use super::handler::{EventNormalization, HookRegistry};
+use super::hook::Hook;
+
+/// Hooks replacing those of the constructor, and how to normalize their events
+#[derive(Default)]
+struct State {
+    normalization: EventNormalization,
+    registry: Option<Arc<HookRegistry>>,
+}
+
+/// Handle to replace the hooks of a `Constructor` and its `Handler`s, cheap to clone
+#[derive(Clone, Default)]
+pub struct ReloadHandle {
+    state: Arc<RwLock<State>>,
+}
+
+/// The main impl clause of `ReloadHandle`
+impl ReloadHandle {
+    /// Replace all the registered hooks
+    pub fn reload(&self, hooks: impl IntoIterator<Item = Hook>) {
+        let mut state = match self.state.write() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let registry = state.normalization.registry(hooks);
+        info!(
+            "Reloaded {} hook(s)",
+            registry.values().map(Vec::len).sum::<usize>()
+        );
+        state.registry = Some(Arc::new(registry));
+    }
+
+    /// Replace all the registered hooks with those built by the function whenever the process receives `SIGHUP`,
+    /// from a thread waiting for it
+    ///
+    /// The hooks are kept when the function fails (e.g. when the configuration can't be read), the error is logged:
+    ///
+    /// ```no_run
+    /// extern crate rifling;
+    ///
+    /// use rifling::{Constructor, Delivery, Hook};
+    ///
+    /// let mut cons = Constructor::new();
+    /// cons.reload_handle()
+    ///     .reload_on_sighup(|| -> Result<Vec<Hook>, std::io::Error> {
+    ///         let secret = std::fs::read_to_string("/etc/rifling/secret")?;
+    ///         Ok(vec![Hook::new("push", Some(secret), |_: &Delivery| ())])
+    ///     })
+    ///     .unwrap();
+    /// ```
+    #[cfg(all(unix, feature = "reload-signal"))]
+    pub fn reload_on_sighup<F, E>(&self, mut rebuild: F) -> io::Result<()>
+    where
+        F: FnMut() -> Result<Vec<Hook>, E> + Send + 'static,
+        E: Display,
+    {
+        let mut signals = Signals::new([SIGHUP])?;
+        let handle = self.clone();
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                info!("Received SIGHUP, reloading hooks");
+                match rebuild() {
+                    Ok(hooks) => handle.reload(hooks),
+                    Err(error) => error!("Unable to reload hooks: {}", error),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Hooks replacing those of the constructor, if reloaded
+    pub(crate) fn registry(&self) -> Option<Arc<HookRegistry>> {
+        match self.state.read() {
+            Ok(state) => state.registry.clone(),
+            Err(poisoned) => poisoned.into_inner().registry.clone(),
+        }
+    }
+
+    /// Set how events of reloaded hooks are normalized
+    pub(crate) fn set_normalization(&self, normalization: EventNormalization) {
+        let mut state = match self.state.write() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        state.normalization = normalization;
+        if let Some(registry) = state.registry.take() {
+            let hooks = registry.values().flatten().cloned().collect::<Vec<_>>();
+            state.registry = Some(Arc::new(state.normalization.registry(hooks)));
+        }
+    }
+}
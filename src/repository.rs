@@ -0,0 +1,253 @@
+//! Repository routing
+//!
+//! `RepositoryRoutes` runs a different hook function for each repository, so one listener can deploy many
+//! repositories, e.g. with a `CommandHook` or a `GitSyncHook` per repository:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::command::CommandHook;
+//! use rifling::repository::RepositoryRoutes;
+//! use rifling::Hook;
+//!
+//! let routes = RepositoryRoutes::new()
+//!     .route("RedL0tus/rifling", CommandHook::shell("make deploy").current_dir("/srv/rifling"))
+//!     .route("RedL0tus/*", CommandHook::shell("make").current_dir("/srv/{repository}"));
+//! let hook = Hook::new("push", Some("secret".to_string()), routes);
+//! ```
+//!
+//! Repositories are matched by their full name (e.g. `RedL0tus/rifling`, see `Delivery::repository`) ignoring case,
+//! `*` in patterns matches any part of a name without `/`. Exact names take precedence over patterns, which are
+//! tried in the order they were added. Deliveries of other repositories are ignored.
+//!
+//! Routes can be collected from an iterator, e.g. of the entries of a configuration file read by the application:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::command::CommandHook;
+//! use rifling::repository::RepositoryRoutes;
+//!
+//! let config = vec![("RedL0tus/rifling", "/srv/rifling", "make deploy")];
+//! let routes: RepositoryRoutes<CommandHook> = config
+//!     .into_iter()
+//!     .map(|(name, dir, script)| (name, CommandHook::shell(script).current_dir(dir)))
+//!     .collect();
+//! ```
+//!
+//! The working directories of `CommandHook` and `GitSyncHook` may contain `{repository}`, replaced by the full name
+//! of the repository of the delivery (e.g. `/srv/{repository}` is `/srv/RedL0tus/rifling`).
+
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+
+use super::context::Context;
+use super::error::Error;
+use super::handler::Delivery;
+use super::hook::HookFunc;
+
+/// Placeholder of working directories replaced by the full name of the repository
+const PLACEHOLDER: &str = "{repository}";
+
+/// Hook functions run for deliveries of different repositories
+#[derive(Clone, Debug)]
+pub struct RepositoryRoutes<H> {
+    routes: Vec<(String, H)>,
+}
+
+/// Test if the full name of a repository matches the pattern, ignoring case
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    // Position in the pattern after the last `*` and in the name it was matched up to, to backtrack to
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, n));
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else {
+            match backtrack {
+                Some((after_star, matched)) if name[matched] != '/' => {
+                    p = after_star;
+                    n = matched + 1;
+                    backtrack = Some((after_star, n));
+                }
+                _ => return false,
+            }
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Working directory for the delivery, replacing `{repository}` with the full name of its repository
+pub(crate) fn working_dir(dir: &Path, delivery: &Delivery) -> Result<PathBuf, Error> {
+    let path = dir.to_string_lossy();
+    if !path.contains(PLACEHOLDER) {
+        return Ok(dir.to_path_buf());
+    }
+    let repository = delivery
+        .repository()
+        .ok_or_else(|| Error::HookFailed("No repository in the delivery".to_string()))?;
+    // Names come from the payload, they must not escape the directory
+    let valid = repository.split('/').all(|segment| {
+        !segment.is_empty()
+            && segment != "."
+            && segment != ".."
+            && !segment.contains(|c: char| c == '\\' || c.is_control())
+    });
+    if !valid {
+        return Err(Error::HookFailed(format!(
+            "Invalid repository name {:?}",
+            repository
+        )));
+    }
+    Ok(PathBuf::from(path.replace(PLACEHOLDER, &repository)))
+}
+
+/// The main impl clause of `RepositoryRoutes`
+impl<H: HookFunc> RepositoryRoutes<H> {
+    /// Create new, empty routes
+    pub fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    /// Run the hook function for the repositories matching the pattern (a full name, possibly with `*`)
+    pub fn route(mut self, pattern: &str, func: H) -> Self {
+        self.routes.push((pattern.to_string(), func));
+        self
+    }
+
+    /// Hook function for the repository
+    pub fn get(&self, repository: &str) -> Option<&H> {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.eq_ignore_ascii_case(repository))
+            .or_else(|| {
+                self.routes
+                    .iter()
+                    .find(|(pattern, _)| pattern.contains('*') && matches(pattern, repository))
+            })
+            .map(|(_, func)| func)
+    }
+
+    /// Hook function for the repository of the delivery
+    fn route_of(&self, delivery: &Delivery) -> Option<&H> {
+        let repository = delivery.repository()?;
+        let func = self.get(&repository);
+        if func.is_none() {
+            debug!("No route for repository {}", &repository);
+        }
+        func
+    }
+}
+
+/// Implement `Default` to `RepositoryRoutes`
+impl<H: HookFunc> Default for RepositoryRoutes<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implement `FromIterator` to `RepositoryRoutes`
+impl<H: HookFunc, P: AsRef<str>> FromIterator<(P, H)> for RepositoryRoutes<H> {
+    fn from_iter<I: IntoIterator<Item = (P, H)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |routes, (pattern, func)| {
+                routes.route(pattern.as_ref(), func)
+            })
+    }
+}
+
+/// Implement `HookFunc` to `RepositoryRoutes`
+impl<H: HookFunc> HookFunc for RepositoryRoutes<H> {
+    fn run(&self, delivery: &Delivery) -> Result<(), Error> {
+        match self.route_of(delivery) {
+            Some(func) => func.run(delivery),
+            None => Ok(()),
+        }
+    }
+
+    fn run_with_context(&self, context: &Context, delivery: &Delivery) -> Result<(), Error> {
+        match self.route_of(delivery) {
+            Some(func) => func.run_with_context(context, delivery),
+            None => Ok(()),
+        }
+    }
+
+    fn validate(&self, delivery: &Delivery) -> Result<(), Error> {
+        match self.route_of(delivery) {
+            Some(func) => func.validate(delivery),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn delivery(repository: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        let request_body = format!(r#"{{"repository": {{"full_name": "{}"}}}}"#, repository);
+        Delivery::new(headers, Some(request_body)).unwrap()
+    }
+
+    /// Test matching repositories with patterns
+    #[test]
+    fn patterns() {
+        assert!(matches("RedL0tus/rifling", "redl0tus/Rifling"));
+        assert!(matches("RedL0tus/*", "RedL0tus/rifling"));
+        assert!(matches("*/rifling", "RedL0tus/rifling"));
+        assert!(matches("RedL0tus/rif*g", "RedL0tus/rifling"));
+        assert!(matches("*", "rifling"));
+        assert!(!matches("*", "RedL0tus/rifling"));
+        assert!(!matches("RedL0tus/*", "RedL0tus/group/rifling"));
+        assert!(!matches("RedL0tus/rif*", "octocat/rifling"));
+        assert!(matches("*/*/*", "RedL0tus/group/rifling"));
+    }
+
+    /// Test running the hook function of the repository of deliveries
+    #[test]
+    fn routes() {
+        let runs: Vec<Arc<AtomicUsize>> = (0..2).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let counter = |index: usize| {
+            let runs = runs[index].clone();
+            move |_: &Delivery| {
+                runs.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+        let routes: RepositoryRoutes<_> =
+            vec![("RedL0tus/*", counter(0)), ("RedL0tus/rifling", counter(1))]
+                .into_iter()
+                .collect();
+        routes.run(&delivery("RedL0tus/rifling")).unwrap();
+        routes.run(&delivery("RedL0tus/trigger")).unwrap();
+        routes.run(&delivery("octocat/rifling")).unwrap();
+        assert_eq!(runs[0].load(Ordering::SeqCst), 1);
+        assert_eq!(runs[1].load(Ordering::SeqCst), 1);
+    }
+
+    /// Test replacing the placeholder of working directories
+    #[test]
+    fn working_dirs() {
+        let dir = Path::new("/srv/{repository}/current");
+        assert_eq!(
+            working_dir(dir, &delivery("RedL0tus/rifling")).unwrap(),
+            PathBuf::from("/srv/RedL0tus/rifling/current")
+        );
+        assert!(working_dir(dir, &delivery("RedL0tus/..")).is_err());
+        assert!(working_dir(dir, &delivery("/etc")).is_err());
+        assert_eq!(
+            working_dir(Path::new("/srv"), &delivery("RedL0tus/..")).unwrap(),
+            PathBuf::from("/srv")
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Request IDs
+//!
+//! With `Constructor::request_id(true)`, every delivery gets an ID, taken from the `X-Request-Id` header set by
+//! proxies in front of the handler or generated, it's available as `Delivery::request_id`, in the access log and
+//! sent back in the `X-Request-Id` header of the response.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::handler::Headers;
+
+/// Longest ID accepted from the request
+const MAX_LENGTH: usize = 128;
+
+/// Number of generated IDs
+static GENERATED: AtomicU64 = AtomicU64::new(0);
+
+/// Generate a random ID of 32 hexadecimal digits
+pub(crate) fn generate() -> String {
+    let count = GENERATED.fetch_add(1, Ordering::Relaxed);
+    let mut id = String::with_capacity(32);
+    for half in 0..2 {
+        // Every `RandomState` is keyed differently
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        hasher.write_u8(half);
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id
+}
+
+/// ID of the request from its headers (lower cased) if acceptable, or a generated one
+pub(crate) fn from_headers(headers: &Headers) -> String {
+    match headers.get("x-request-id").map(|id| id.trim()) {
+        Some(id) if is_valid(id) => id.to_string(),
+        _ => generate(),
+    }
+}
+
+/// Test if the ID can be sent back safely, i.e. printable ASCII without spaces and not too long
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_LENGTH && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Test propagating and generating IDs
+    #[test]
+    fn request_ids() {
+        let (first, second) = (generate(), generate());
+        assert_eq!(first.len(), 32);
+        assert!(first.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(first, second);
+        let mut headers = HashMap::new();
+        headers.insert("x-request-id".to_string(), "req-42".to_string());
+        assert_eq!(from_headers(&headers.clone().into()), "req-42");
+        headers.insert(
+            "x-request-id".to_string(),
+            "evil\r\nSet-Cookie: a=b".to_string(),
+        );
+        assert_eq!(from_headers(&headers.into()).len(), 32);
+    }
+}
@@ -0,0 +1,163 @@
+//! Retry queue
+//!
+//! Hooks configured with `Hook::retries` are re-executed in the background when they fail:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Delivery, Hook};
+//!
+//! use std::time::Duration;
+//!
+//! let hook = Hook::new("push", None, |_: &Delivery| -> Result<(), String> {
+//!     Err("Build server is down".to_string())
+//! })
+//! .retries(3)
+//! .backoff(Duration::from_secs(30));
+//! ```
+//!
+//! The delay before a retry doubles after every attempt (30s, 60s, 120s in the example above).
+//! When all retries have failed, `Error::RetriesExhausted` is passed to the error callback and the
+//! delivery store.
+//!
+//! Hooks scheduled with `Hook::delay`, `Hook::debounce` and `Hook::throttle`, and hooks queued by
+//! `ConcurrencyPolicy::Queue`, are scheduled the same way. A single timer thread waits for the jobs to be due, every
+//! job is then run on a thread of its own so that slow hooks don't hold up the others. The number of jobs running
+//! at once is bounded by `Constructor::max_concurrent_hooks`, if set.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::handler::{Delivery, Settings};
+use super::hook::Hook;
+
+/// A scheduled execution of a hook
+pub(crate) struct Job {
+    pub due: Instant,
+    pub attempt: u32,
+    pub hook: Hook,
+    pub delivery: Delivery,
+    pub settings: Settings,
+    /// Key and ID of the pending run of a coalesced hook, its last delivery is run instead
+    pub pending: Option<(String, u64)>,
+    /// Whether the run is queued by `ConcurrencyPolicy::Queue`, holding a place in the queue until it starts
+    pub queued: bool,
+}
+
+/// Scheduled jobs and whether there is a worker running them
+#[derive(Default)]
+struct State {
+    jobs: BinaryHeap<Job>,
+    worker_running: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    updated: Condvar,
+}
+
+/// Queue of jobs, shared by handlers created from the same `Constructor`
+#[derive(Clone, Default)]
+pub(crate) struct RetryQueue {
+    inner: Arc<Inner>,
+}
+
+/// Delay before the given attempt (starting from 2, the first retry)
+pub(crate) fn delay(backoff: Duration, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(2).min(16);
+    backoff * 2u32.pow(exponent)
+}
+
+/// Jobs with earlier due time come first in the heap
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.due.cmp(&self.due)
+    }
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.due == other.due
+    }
+}
+
+impl Eq for Job {}
+
+/// The main impl clause of `RetryQueue`
+impl RetryQueue {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        match self.inner.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Schedule a job, starting a worker if there isn't one
+    pub fn schedule(&self, job: Job) {
+        let mut state = self.lock();
+        state.jobs.push(job);
+        self.inner.updated.notify_one();
+        if !state.worker_running {
+            state.worker_running = true;
+            let queue = self.clone();
+            thread::spawn(move || queue.work());
+        }
+    }
+
+    /// Start the jobs on threads of their own when they are due, until there is no job left
+    fn work(self) {
+        debug!("Retry worker started");
+        loop {
+            let mut state = self.lock();
+            let now = Instant::now();
+            let due = match state.jobs.peek() {
+                Some(job) => job.due,
+                None => {
+                    state.worker_running = false;
+                    debug!("Retry worker stopped");
+                    return;
+                }
+            };
+            if due > now {
+                let _ = self.inner.updated.wait_timeout(state, due - now);
+                continue;
+            }
+            if let Some(job) = state.jobs.pop() {
+                drop(state);
+                debug!(
+                    "Running scheduled {} (attempt {})",
+                    job.hook.label(),
+                    job.attempt
+                );
+                thread::spawn(move || {
+                    let settings = job.settings.clone();
+                    settings.run_job(job);
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the delay growing exponentially
+    #[test]
+    fn exponential_delay() {
+        let backoff = Duration::from_secs(30);
+        assert_eq!(delay(backoff, 2), Duration::from_secs(30));
+        assert_eq!(delay(backoff, 3), Duration::from_secs(60));
+        assert_eq!(delay(backoff, 4), Duration::from_secs(120));
+    }
+}
@@ -0,0 +1,188 @@
+//! Router
+//!
+//! `Router` composes multiple `Constructor`s by path, so one process can serve several independent webhook
+//! configurations with isolated secrets and hooks.
+//!
+//! Example:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Router};
+//!
+//! let github = Constructor::new();
+//! let gitlab = Constructor::new();
+//! let router = Router::new().mount("/github", github).mount("/gitlab", gitlab);
+//! ```
+//!
+//! Requests are dispatched to the constructor mounted at the longest matching path,
+//! requests that match no path are answered with `404 Not Found`.
+//!
+//! `Router` implements the service traits of hyper (0.12, with `hyper-support`), like `Constructor`. There is no
+//! integration with other frameworks (e.g. axum), they can find the handler for the path of a request with
+//! `RouterHandler::route` and pass it the request with `Handler::handle`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::router::RouterHandler;
+//! use rifling::{Constructor, Delivery, Hook, Router};
+//!
+//! let mut github = Constructor::new();
+//! github.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+//! let router = Router::new().mount("/github", github);
+//! if let Some(handler) = RouterHandler::from(&router).route("/github") {
+//!     let response = handler.handle(vec![("X-GitHub-Event", "push")], b"{}");
+//!     assert_eq!(response.status(), 200);
+//! }
+//! ```
+
+use std::net::SocketAddr;
+
+use super::handler::{Constructor, Handler};
+
+/// Composition of `Constructor`s mounted at different paths
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<(String, Constructor)>,
+}
+
+/// Handlers created from a `Router`
+pub struct RouterHandler {
+    routes: Vec<(String, Handler)>,
+}
+
+/// Normalize the path by removing the trailing slash
+fn normalize(path: &str) -> &str {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        "/"
+    } else {
+        trimmed
+    }
+}
+
+/// Test if the path is the mount point or below it
+fn is_mounted_at(path: &str, mount_point: &str) -> bool {
+    mount_point == "/"
+        || path == mount_point
+        || (path.starts_with(mount_point) && path[mount_point.len()..].starts_with('/'))
+}
+
+/// The main impl clause of `Router`
+impl Router {
+    /// Create a new, empty `Router`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mount the constructor at the path, replacing the one previously mounted there
+    pub fn mount(mut self, path: &str, constructor: Constructor) -> Self {
+        let path = normalize(path).to_string();
+        self.routes.retain(|(mount_point, _)| mount_point != &path);
+        self.routes.push((path, constructor));
+        // Longest paths first, so the most specific mount point matches first
+        self.routes
+            .sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        self
+    }
+
+    /// Create handlers for every mounted constructor
+    pub(crate) fn handler(&self, remote_addr: Option<SocketAddr>) -> RouterHandler {
+        RouterHandler {
+            routes: self
+                .routes
+                .iter()
+                .map(|(path, constructor)| {
+                    let handler = Handler::from(constructor);
+                    let handler = match remote_addr {
+                        Some(remote_addr) => handler.with_remote_addr(remote_addr),
+                        None => handler,
+                    };
+                    (path.clone(), handler)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The main impl clause of `RouterHandler`
+impl RouterHandler {
+    /// Find the mount point matching the path
+    fn position(&self, path: &str) -> Option<usize> {
+        let path = normalize(path);
+        self.routes
+            .iter()
+            .position(|(mount_point, _)| is_mounted_at(path, mount_point))
+    }
+
+    /// Take the handler for the path, `None` if no constructor is mounted there
+    pub fn route(mut self, path: &str) -> Option<Handler> {
+        let position = self.position(path)?;
+        Some(self.routes.swap_remove(position).1)
+    }
+
+    /// Find the handler for the path, keeping the others for the next requests
+    #[cfg(feature = "hyper-support")]
+    pub(crate) fn route_mut(&mut self, path: &str) -> Option<&mut Handler> {
+        let position = self.position(path)?;
+        Some(&mut self.routes[position].1)
+    }
+}
+
+/// Create handlers from the router
+impl From<&Router> for RouterHandler {
+    fn from(router: &Router) -> Self {
+        router.handler(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::Outcome;
+    use crate::{Delivery, Hook};
+
+    /// Test matching paths against mount points
+    #[test]
+    fn routing() {
+        let router = Router::new()
+            .mount("/github/", Constructor::new())
+            .mount("/github/enterprise", Constructor::new());
+        let handler = RouterHandler::from(&router);
+        let mount_point = |path: &str| {
+            handler
+                .position(path)
+                .map(|position| handler.routes[position].0.as_str())
+        };
+        assert_eq!(mount_point("/github"), Some("/github"));
+        assert_eq!(mount_point("/github/hook"), Some("/github"));
+        assert_eq!(
+            mount_point("/github/enterprise/"),
+            Some("/github/enterprise")
+        );
+        assert_eq!(mount_point("/githubx"), None);
+        assert_eq!(mount_point("/"), None);
+    }
+
+    /// Test taking the handler mounted at the path of a request
+    #[test]
+    fn route() {
+        let mut github = Constructor::new();
+        github.register(Hook::new("push", None, |_: &Delivery| ()));
+        let router = Router::new()
+            .mount("/github", github)
+            .mount("/gitlab", Constructor::new());
+        let handler = RouterHandler::from(&router).route("/github/").unwrap();
+        assert_eq!(
+            handler
+                .handle(vec![("X-GitHub-Event", "push")], b"{}")
+                .outcome,
+            Outcome::Handled {
+                hooks: 1,
+                unverified: 1
+            }
+        );
+        assert!(RouterHandler::from(&router).route("/stripe").is_none());
+    }
+}
@@ -0,0 +1,163 @@
+//! Scheduled hooks
+//!
+//! Hooks can be run some time after the delivery instead of while handling it, in the background like retries (see
+//! `rifling::retry`). Successive deliveries can be coalesced per hook and repository, e.g. to start a single build
+//! after a burst of pushes:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Delivery, Hook};
+//!
+//! use std::time::Duration;
+//!
+//! // Run 10 seconds after a delivery
+//! let notify = Hook::new("issues", None, |_: &Delivery| println!("Issue updated")).delay(Duration::from_secs(10));
+//! // Run once pushes to the repository have stopped for a minute, with the last delivery
+//! let build = Hook::new("push", None, |_: &Delivery| println!("Building")).debounce(Duration::from_secs(60));
+//! // Run at most every 5 minutes per repository, with the last delivery received meanwhile
+//! let deploy = Hook::new("push", None, |_: &Delivery| println!("Deploying")).throttle(Duration::from_secs(300));
+//! ```
+//!
+//! Hooks created with `Hook::batch` receive the deliveries gathered over a window at once.
+//!
+//! Deliveries are counted as handled by scheduled hooks once they're authenticated, failures of the hooks are
+//! reported when they run.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::handler::Delivery;
+use super::hook::Hook;
+
+/// When to run a hook, see `Hook::delay`, `Hook::debounce` and `Hook::throttle`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// Run every delivery after the duration
+    Delay(Duration),
+    /// Run the last delivery once no other delivery is received for the duration
+    Debounce(Duration),
+    /// Run the last delivery received within the duration after the first one
+    Throttle(Duration),
+    /// Run the deliveries received within the window after the first one at once, or as soon as there are `max` of
+    /// them, see `Hook::batch`
+    Batch { window: Duration, max: usize },
+}
+
+/// Result of adding a delivery to a batch
+pub(crate) enum Batched {
+    /// The delivery started a batch with the ID
+    Started(u64),
+    /// The delivery was added to the batch
+    Added,
+    /// The batch is full and should be run now
+    Full(Vec<Delivery>),
+}
+
+/// Pending deliveries of coalesced hooks, by hook and repository
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    pending: HashMap<String, (u64, Delivery)>,
+    batches: HashMap<String, (u64, Vec<Delivery>)>,
+}
+
+/// Deliveries waiting for coalesced hooks to run, shared by handlers created from the same `Constructor`
+#[derive(Clone, Default)]
+pub(crate) struct PendingRuns {
+    state: Arc<Mutex<State>>,
+}
+
+/// Key of coalesced deliveries, the hook and the repository of the delivery
+pub(crate) fn key(hook: &Hook, delivery: &Delivery) -> String {
+    format!(
+        "{:x}:{}:{}",
+        hook.identity(),
+        hook.event,
+        delivery.repository().unwrap_or_default()
+    )
+}
+
+/// Key of batched deliveries, the hook
+pub(crate) fn batch_key(hook: &Hook) -> String {
+    format!("{:x}:{}", hook.identity(), hook.event)
+}
+
+/// The main impl clause of `PendingRuns`
+impl PendingRuns {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Replace the pending delivery of the key, returns the ID of the new run
+    pub fn replace(&self, key: String, delivery: Delivery) -> u64 {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let id = state.next_id;
+        state.pending.insert(key, (id, delivery));
+        id
+    }
+
+    /// Update the pending delivery of the key, returns the ID of the new run if none was pending
+    pub fn update(&self, key: String, delivery: Delivery) -> Option<u64> {
+        let mut state = self.lock();
+        if let Some(pending) = state.pending.get_mut(&key) {
+            pending.1 = delivery;
+            return None;
+        }
+        state.next_id += 1;
+        let id = state.next_id;
+        state.pending.insert(key, (id, delivery));
+        Some(id)
+    }
+
+    /// Add the delivery to the batch of the key, taking the batch if it reaches `max` deliveries
+    pub fn push(&self, key: String, delivery: Delivery, max: usize) -> Batched {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let next_id = state.next_id;
+        let batch = state
+            .batches
+            .entry(key.clone())
+            .or_insert((next_id, Vec::new()));
+        batch.1.push(delivery);
+        let (id, length) = (batch.0, batch.1.len());
+        if length >= max {
+            state
+                .batches
+                .remove(&key)
+                .map(|(_, batch)| Batched::Full(batch))
+                .unwrap_or(Batched::Added)
+        } else if length == 1 {
+            Batched::Started(id)
+        } else {
+            Batched::Added
+        }
+    }
+
+    /// Take the batch of the key, unless it was already run
+    pub fn take_batch(&self, key: &str, id: u64) -> Option<Vec<Delivery>> {
+        let mut state = self.lock();
+        match state.batches.get(key) {
+            Some((batch_id, _)) if *batch_id == id => {
+                state.batches.remove(key).map(|(_, batch)| batch)
+            }
+            _ => None,
+        }
+    }
+
+    /// Take the pending delivery of the key, unless the run was superseded by another
+    pub fn take(&self, key: &str, id: u64) -> Option<Delivery> {
+        let mut state = self.lock();
+        match state.pending.get(key) {
+            Some((pending_id, _)) if *pending_id == id => {
+                state.pending.remove(key).map(|(_, delivery)| delivery)
+            }
+            _ => None,
+        }
+    }
+}
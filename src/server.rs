@@ -0,0 +1,375 @@
+//! Server
+//!
+//! `Builder` sets up a hyper server with defaults suited to webhooks, instead of configuring hyper directly:
+//!
+//! ```no_run
+//! extern crate rifling;
+//!
+//! use rifling::server::Builder;
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+//! // Listen on `RIFLING_ADDR`, or on `HOST` and `PORT` (0.0.0.0:4567 by default)
+//! Builder::from_env().unwrap().run(cons).unwrap();
+//! ```
+//!
+//! Defaults:
+//!
+//!  - HTTP/1.1 only, most webhooks are not sent with HTTP/2. Set `http1_only(false)` to accept HTTP/2 too (e.g. h2c
+//!    with prior knowledge from service meshes), or `http2_only(true)` to only accept HTTP/2.
+//!  - Headers and buffers of at most 16 KiB.
+//!  - Bodies of at most 25 MiB (the limit of GitHub), larger ones are answered with `413 Payload Too Large`.
+//!  - Keep-alive enabled, with TCP keep-alive probes after 60 seconds.
+//!  - Unlimited concurrent streams of HTTP/2 connections, see `http2_max_concurrent_streams`.
+//!
+//! The address of the sender is available to the handlers.
+//!
+//! `serve_multi` serves a constructor on several addresses at once, and `run_multi` serves each constructor with its
+//! own builder, e.g. clones of a constructor sharing its hooks, hardened for the public address only:
+//!
+//! ```no_run
+//! extern crate rifling;
+//!
+//! use rifling::server::{run_multi, Builder};
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut internal = Constructor::new();
+//! internal.register(Hook::new("push", None, |_: &Delivery| println!("Pushed!")));
+//! let mut public = internal.clone();
+//! public.strict_github(true);
+//! run_multi(vec![
+//!     (Builder::new().addr("10.0.0.2:4567".parse().unwrap()).http1_only(false), internal),
+//!     (Builder::new().addr("0.0.0.0:8443".parse().unwrap()), public),
+//! ])
+//! .unwrap();
+//! ```
+
+use futures::{future, Future};
+use hyper::server::conn::AddrIncoming;
+use hyper::{Error, Server};
+#[cfg(feature = "tls-server")]
+use rustls::ServerConfig;
+#[cfg(feature = "tls-server")]
+use tokio::net::TcpListener;
+
+use std::env;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(feature = "tls-server")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::handler::{Constructor, WithRemoteAddr};
+#[cfg(feature = "tls-server")]
+use super::tls::{self, TlsIncoming};
+
+/// Address listened on by default
+const DEFAULT_ADDR: ([u8; 4], u16) = ([0, 0, 0, 0], 4567);
+
+/// Builder of a hyper server serving a `Constructor`
+#[derive(Clone, Debug)]
+pub struct Builder {
+    addr: SocketAddr,
+    http1_only: bool,
+    http2_only: bool,
+    http2_max_concurrent_streams: Option<u32>,
+    keep_alive: bool,
+    tcp_keep_alive: Option<Duration>,
+    max_header_size: usize,
+    max_body_size: usize,
+}
+
+/// Parse the address from `RIFLING_ADDR`, or `HOST` and `PORT`
+fn parse_addr(
+    addr: Option<&str>,
+    host: Option<&str>,
+    port: Option<&str>,
+) -> Result<SocketAddr, String> {
+    if let Some(addr) = addr {
+        return addr
+            .parse()
+            .map_err(|_| format!("Invalid address: {}", addr));
+    }
+    let default = SocketAddr::from(DEFAULT_ADDR);
+    let ip = match host {
+        Some(host) => host
+            .parse::<IpAddr>()
+            .map_err(|_| format!("Invalid host: {}", host))?,
+        None => default.ip(),
+    };
+    let port = match port {
+        Some(port) => port
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid port: {}", port))?,
+        None => default.port(),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Listen on 0.0.0.0:4567 with the defaults
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(DEFAULT_ADDR),
+            http1_only: true,
+            http2_only: false,
+            http2_max_concurrent_streams: None,
+            keep_alive: true,
+            tcp_keep_alive: Some(Duration::from_secs(60)),
+            max_header_size: 16 * 1024,
+            max_body_size: 25 * 1024 * 1024,
+        }
+    }
+}
+
+/// The main impl clause of `Builder`
+impl Builder {
+    /// Create a builder listening on 0.0.0.0:4567
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder listening on the address in the `RIFLING_ADDR` environment variable (e.g. `127.0.0.1:8080`),
+    /// or on the IP address in `HOST` and the port in `PORT`, defaulting to 0.0.0.0 and 4567
+    pub fn from_env() -> io::Result<Self> {
+        let addr = parse_addr(
+            env::var("RIFLING_ADDR").ok().as_deref(),
+            env::var("HOST").ok().as_deref(),
+            env::var("PORT").ok().as_deref(),
+        )
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        Ok(Self::new().addr(addr))
+    }
+
+    /// Set the address to listen on
+    pub fn addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    /// Set whether HTTP/2 is refused (true by default), both HTTP/1.1 and HTTP/2 are accepted if `false`
+    pub fn http1_only(mut self, http1_only: bool) -> Self {
+        self.http1_only = http1_only;
+        self
+    }
+
+    /// Set whether HTTP/1.1 is refused (false by default), takes precedence over `http1_only`
+    pub fn http2_only(mut self, http2_only: bool) -> Self {
+        self.http2_only = http2_only;
+        self
+    }
+
+    /// Set the maximum number of concurrent streams of HTTP/2 connections, or remove the limit with `None` (the
+    /// default)
+    pub fn http2_max_concurrent_streams(mut self, max: Option<u32>) -> Self {
+        self.http2_max_concurrent_streams = max;
+        self
+    }
+
+    /// Set whether connections are kept alive between requests (true by default)
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Set the idle time before sending TCP keep-alive probes, or disable them with `None` (60 seconds by default)
+    pub fn tcp_keep_alive(mut self, tcp_keep_alive: Option<Duration>) -> Self {
+        self.tcp_keep_alive = tcp_keep_alive;
+        self
+    }
+
+    /// Set the size of the buffer of connections, limiting the size of headers (16 KiB by default, at least 8 KiB)
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.max_header_size = max_header_size.max(8192);
+        self
+    }
+
+    /// Set the maximum size of bodies in bytes (25 MiB by default), see `Constructor::max_body_size`
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Bind the address and serve the constructor, the server has to be run by a tokio runtime
+    pub fn serve(
+        self,
+        mut constructor: Constructor,
+    ) -> Result<Server<AddrIncoming, WithRemoteAddr<Constructor>>, Error> {
+        constructor.max_body_size(self.max_body_size);
+        let builder = Server::try_bind(&self.addr)?;
+        // Both set the protocols of connections, the last one wins
+        let builder = if self.http2_only {
+            builder.http2_only(true)
+        } else {
+            builder.http1_only(self.http1_only)
+        };
+        let server = builder
+            .http2_max_concurrent_streams(self.http2_max_concurrent_streams)
+            .http1_keepalive(self.keep_alive)
+            .http1_max_buf_size(self.max_header_size)
+            .tcp_keepalive(self.tcp_keep_alive)
+            .serve(constructor.with_remote_addr());
+        info!("Listening on {}", server.local_addr());
+        Ok(server)
+    }
+
+    /// Bind the address and serve the constructor with a new tokio runtime, blocks forever
+    pub fn run(self, constructor: Constructor) -> Result<(), Error> {
+        let server = self.serve(constructor)?;
+        hyper::rt::run(server.map_err(|error| error!("Server error: {}", error)));
+        Ok(())
+    }
+
+    /// Bind the address and serve the constructor over TLS with the configuration (see `rifling::tls`), the server
+    /// has to be run by a tokio runtime
+    ///
+    /// Only HTTP/1.1 is served, `http1_only` and `http2_only` are ignored.
+    #[cfg(feature = "tls-server")]
+    pub fn serve_tls(
+        self,
+        mut constructor: Constructor,
+        config: Arc<ServerConfig>,
+    ) -> io::Result<Server<TlsIncoming, WithRemoteAddr<Constructor>>> {
+        constructor.max_body_size(self.max_body_size);
+        let listener = TcpListener::bind(&self.addr)?;
+        info!("Listening on {} with TLS", listener.local_addr()?);
+        let server = Server::builder(tls::incoming(listener, config, self.tcp_keep_alive))
+            .http1_only(true)
+            .http1_keepalive(self.keep_alive)
+            .http1_max_buf_size(self.max_header_size)
+            .serve(constructor.with_remote_addr());
+        Ok(server)
+    }
+
+    /// Bind the address and serve the constructor over TLS with a new tokio runtime, blocks forever
+    #[cfg(feature = "tls-server")]
+    pub fn run_tls(self, constructor: Constructor, config: Arc<ServerConfig>) -> io::Result<()> {
+        let server = self.serve_tls(constructor, config)?;
+        hyper::rt::run(server.map_err(|error| error!("Server error: {}", error)));
+        Ok(())
+    }
+}
+
+/// Bind every address with the defaults and serve the constructor on all of them with a new tokio runtime, blocks
+/// forever
+pub fn serve_multi(addrs: &[SocketAddr], constructor: Constructor) -> Result<(), Error> {
+    run_multi(
+        addrs
+            .iter()
+            .map(|addr| (Builder::new().addr(*addr), constructor.clone())),
+    )
+}
+
+/// Bind the address of each builder and serve its constructor with a new tokio runtime, blocks forever
+///
+/// All the addresses are bound before serving, the error of the first one which can't be bound is returned. The
+/// servers keep running if one of them fails.
+pub fn run_multi(listeners: impl IntoIterator<Item = (Builder, Constructor)>) -> Result<(), Error> {
+    let servers = listeners
+        .into_iter()
+        .map(|(builder, constructor)| builder.serve(constructor))
+        .collect::<Result<Vec<_>, _>>()?;
+    hyper::rt::run(future::lazy(move || {
+        for server in servers {
+            hyper::rt::spawn(server.map_err(|error| error!("Server error: {}", error)));
+        }
+        Ok(())
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Delivery, Hook};
+    use hyper::{Body, Client, Request, StatusCode, Version};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Test parsing the address from the environment
+    #[test]
+    fn addr_from_env() {
+        assert_eq!(
+            parse_addr(None, None, None),
+            Ok(SocketAddr::from(DEFAULT_ADDR))
+        );
+        assert_eq!(
+            parse_addr(Some("127.0.0.1:8080"), Some("::1"), Some("80")),
+            Ok(SocketAddr::from(([127, 0, 0, 1], 8080)))
+        );
+        assert_eq!(
+            parse_addr(None, Some("127.0.0.1"), Some("8080")),
+            Ok(SocketAddr::from(([127, 0, 0, 1], 8080)))
+        );
+        assert!(parse_addr(None, None, Some("http")).is_err());
+    }
+
+    /// Test serving deliveries sent with HTTP/2
+    #[test]
+    fn http2() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let server = Builder::new()
+            .addr(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .http2_only(true)
+            .http2_max_concurrent_streams(Some(4))
+            .serve(cons)
+            .unwrap();
+        let addr = server.local_addr();
+        thread::spawn(move || hyper::rt::run(server.map_err(|_| ())));
+        let (sender, receiver) = mpsc::channel();
+        let request = Request::post(format!("http://{}/", addr))
+            .header("X-GitHub-Event", "push")
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = Client::builder()
+            .http2_only(true)
+            .build_http::<Body>()
+            .request(request)
+            .map(move |response| {
+                sender
+                    .send((response.version(), response.status()))
+                    .unwrap()
+            })
+            .map_err(|error| panic!("{}", error));
+        hyper::rt::run(response);
+        assert_eq!(receiver.recv().unwrap(), (Version::HTTP_2, StatusCode::OK));
+    }
+
+    /// Test serving a constructor on several addresses
+    #[test]
+    fn multiple_addrs() {
+        let mut cons = Constructor::new();
+        cons.register(Hook::new("push", None, |_: &Delivery| ()));
+        let addrs: Vec<SocketAddr> = (0..2)
+            .map(|_| {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                listener.local_addr().unwrap()
+            })
+            .collect();
+        // Binding the same address twice fails before serving
+        assert!(serve_multi(&[addrs[0], addrs[0]], cons.clone()).is_err());
+        let addrs_inner = addrs.clone();
+        thread::spawn(move || serve_multi(&addrs_inner, cons).unwrap());
+        for addr in addrs {
+            let mut stream = (0..50)
+                .find_map(|_| {
+                    TcpStream::connect(addr)
+                        .map_err(|_| thread::sleep(Duration::from_millis(20)))
+                        .ok()
+                })
+                .unwrap();
+            write!(
+                stream,
+                "POST / HTTP/1.1\r\nHost: localhost\r\nX-GitHub-Event: push\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200"));
+        }
+    }
+}
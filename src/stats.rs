@@ -0,0 +1,338 @@
+//! Statistics
+//!
+//! Counters of the deliveries handled since the `Constructor` was created, shared by every `Handler` created from
+//! it. They can be read with `Handler::stats`, or with the handle returned by `Constructor::stats_handle`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//!
+//! let cons = Constructor::new();
+//! let stats = cons.stats_handle();
+//! // Later, e.g. in the health check endpoint of the application
+//! let snapshot = stats.snapshot();
+//! println!("{} deliveries, {} failed to authenticate", snapshot.deliveries, snapshot.auth_failures);
+//! ```
+//!
+//! Totals are atomic counters, the counts per event and per provider are kept in maps behind a lock.
+//!
+//! To spot what generates load, deliveries are also counted per repository (up to 1000 repositories, others are not
+//! counted) and by the size of their payload:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::stats::PAYLOAD_SIZE_BUCKETS;
+//! use rifling::Constructor;
+//!
+//! let snapshot = Constructor::new().stats_handle().snapshot();
+//! for (repository, count) in snapshot.top_repositories(10) {
+//!     println!("{}: {} deliveries", repository, count);
+//! }
+//! for (bound, count) in PAYLOAD_SIZE_BUCKETS.iter().zip(&snapshot.payload_sizes) {
+//!     println!("Up to {} bytes: {}", bound, count);
+//! }
+//! ```
+//!
+//! The health of named hooks (see `Hook::named`) is tracked too: their pending retries, the deliveries they failed
+//! for good, their last successful run and their consecutive failures. Supervising code can alert on a single
+//! failing hook with `StatsHandle::hook_health`:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//!
+//! let stats = Constructor::new().stats_handle();
+//! if let Some(health) = stats.hook_health("deploy-prod") {
+//!     if health.consecutive_failures >= 3 {
+//!         eprintln!("deploy-prod failed {} times in a row", health.consecutive_failures);
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::handler::Delivery;
+
+/// Upper bounds in bytes of the buckets of `Stats.payload_sizes` (1 KiB, 10 KiB, 100 KiB, 1 MiB and 10 MiB), larger
+/// payloads are counted in an additional last bucket
+pub const PAYLOAD_SIZE_BUCKETS: [usize; 5] = [1 << 10, 10 << 10, 100 << 10, 1 << 20, 10 << 20];
+
+/// Maximum number of repositories counted in `Stats.per_repository`, names come from payloads
+const MAX_REPOSITORIES: usize = 1000;
+
+/// Counters of the deliveries
+#[derive(Debug)]
+struct Counters {
+    started: Instant,
+    deliveries: AtomicU64,
+    auth_failures: AtomicU64,
+    hook_runs: AtomicU64,
+    hook_nanos: AtomicU64,
+    payload_bytes: AtomicU64,
+    payload_sizes: [AtomicU64; PAYLOAD_SIZE_BUCKETS.len() + 1],
+    events: Mutex<HashMap<String, u64>>,
+    providers: Mutex<HashMap<String, u64>>,
+    repositories: Mutex<HashMap<String, u64>>,
+    metrics: Mutex<HashMap<String, u64>>,
+    hooks: Mutex<HashMap<String, u64>>,
+    hook_failures: Mutex<HashMap<String, u64>>,
+    health: Mutex<HashMap<String, HookHealth>>,
+}
+
+/// Handle to the statistics of a `Constructor` and its `Handler`s, cheap to clone
+#[derive(Clone, Debug)]
+pub struct StatsHandle {
+    counters: Arc<Counters>,
+}
+
+/// Health of a named hook, see `StatsHandle::hook_health`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HookHealth {
+    /// Number of retries scheduled and not run yet
+    pub pending_retries: u64,
+    /// Number of deliveries the hook failed for good: retries exhausted, failed batches and deliveries dead-lettered
+    /// outside its active window
+    pub dead_lettered: u64,
+    /// Time of the last successful run
+    pub last_success: Option<SystemTime>,
+    /// Number of failed runs since the last successful one, including retries
+    pub consecutive_failures: u64,
+}
+
+/// Statistics at a point in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// Time elapsed since the `Constructor` was created
+    pub uptime: Duration,
+    /// Number of requests recognized as deliveries
+    pub deliveries: u64,
+    /// Number of deliveries per event
+    pub per_event: HashMap<String, u64>,
+    /// Number of deliveries per provider (e.g. `GitHub`)
+    pub per_provider: HashMap<String, u64>,
+    /// Number of deliveries per repository (e.g. `RedL0tus/rifling`), see `Delivery::repository`
+    pub per_repository: HashMap<String, u64>,
+    /// Total size of the bodies of deliveries in bytes
+    pub payload_bytes: u64,
+    /// Number of deliveries per size of their body, in the buckets of `PAYLOAD_SIZE_BUCKETS` and then larger ones
+    pub payload_sizes: Vec<u64>,
+    /// Number of deliveries failing to authenticate with every matched hook
+    pub auth_failures: u64,
+    /// Number of hook runs, including retries
+    pub hook_runs: u64,
+    /// Average duration of hook runs
+    pub average_hook_latency: Option<Duration>,
+    /// Number of runs per named hook, see `Hook::named`
+    pub per_hook: HashMap<String, u64>,
+    /// Number of failed runs per named hook
+    pub per_hook_failures: HashMap<String, u64>,
+    /// Health per named hook
+    pub per_hook_health: HashMap<String, HookHealth>,
+    /// Custom metrics counted by hooks with `Context::count`
+    pub metrics: HashMap<String, u64>,
+}
+
+/// Increase the count of the key by the value
+fn increase(counts: &Mutex<HashMap<String, u64>>, key: String, value: u64) {
+    let mut counts = match counts.lock() {
+        Ok(counts) => counts,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *counts.entry(key).or_insert(0) += value;
+}
+
+/// Copy the counts
+fn copy(counts: &Mutex<HashMap<String, u64>>) -> HashMap<String, u64> {
+    match counts.lock() {
+        Ok(counts) => counts.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// The keys with the highest counts, ties broken by key
+fn top(counts: &HashMap<String, u64>, n: usize) -> Vec<(&str, u64)> {
+    let mut top: Vec<(&str, u64)> = counts
+        .iter()
+        .map(|(key, count)| (key.as_str(), *count))
+        .collect();
+    top.sort_by(|(key_a, count_a), (key_b, count_b)| {
+        count_b.cmp(count_a).then_with(|| key_a.cmp(key_b))
+    });
+    top.truncate(n);
+    top
+}
+
+/// The main impl clause of `Stats`
+impl Stats {
+    /// The `n` events with the most deliveries, with their counts
+    pub fn top_events(&self, n: usize) -> Vec<(&str, u64)> {
+        top(&self.per_event, n)
+    }
+
+    /// The `n` repositories with the most deliveries, with their counts
+    pub fn top_repositories(&self, n: usize) -> Vec<(&str, u64)> {
+        top(&self.per_repository, n)
+    }
+}
+
+/// Statistics start when created
+impl Default for StatsHandle {
+    fn default() -> Self {
+        Self {
+            counters: Arc::new(Counters {
+                started: Instant::now(),
+                deliveries: AtomicU64::new(0),
+                auth_failures: AtomicU64::new(0),
+                hook_runs: AtomicU64::new(0),
+                hook_nanos: AtomicU64::new(0),
+                payload_bytes: AtomicU64::new(0),
+                payload_sizes: Default::default(),
+                events: Mutex::new(HashMap::new()),
+                providers: Mutex::new(HashMap::new()),
+                repositories: Mutex::new(HashMap::new()),
+                metrics: Mutex::new(HashMap::new()),
+                hooks: Mutex::new(HashMap::new()),
+                hook_failures: Mutex::new(HashMap::new()),
+                health: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+/// The main impl clause of `StatsHandle`
+impl StatsHandle {
+    /// Read the current statistics
+    pub fn snapshot(&self) -> Stats {
+        let counters = &self.counters;
+        let hook_runs = counters.hook_runs.load(Ordering::Relaxed);
+        let hook_nanos = counters.hook_nanos.load(Ordering::Relaxed);
+        Stats {
+            uptime: counters.started.elapsed(),
+            deliveries: counters.deliveries.load(Ordering::Relaxed),
+            per_event: copy(&counters.events),
+            per_provider: copy(&counters.providers),
+            per_repository: copy(&counters.repositories),
+            payload_bytes: counters.payload_bytes.load(Ordering::Relaxed),
+            payload_sizes: counters
+                .payload_sizes
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            auth_failures: counters.auth_failures.load(Ordering::Relaxed),
+            hook_runs,
+            average_hook_latency: hook_nanos.checked_div(hook_runs).map(Duration::from_nanos),
+            per_hook: copy(&counters.hooks),
+            per_hook_failures: copy(&counters.hook_failures),
+            per_hook_health: self.lock_health().clone(),
+            metrics: copy(&counters.metrics),
+        }
+    }
+
+    /// Health of the named hook, `None` if it never ran nor failed
+    pub fn hook_health(&self, name: &str) -> Option<HookHealth> {
+        self.lock_health().get(name).cloned()
+    }
+
+    fn lock_health(&self) -> MutexGuard<'_, HashMap<String, HookHealth>> {
+        match self.counters.health.lock() {
+            Ok(health) => health,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Update the health of the hook with the name, unnamed hooks are not tracked
+    fn update_health(&self, name: Option<&str>, update: impl FnOnce(&mut HookHealth)) {
+        if let Some(name) = name {
+            update(self.lock_health().entry(name.to_string()).or_default());
+        }
+    }
+
+    /// Count a retry of the hook with the name being scheduled
+    pub(crate) fn record_retry_scheduled(&self, name: Option<&str>) {
+        self.update_health(name, |health| health.pending_retries += 1);
+    }
+
+    /// Count a scheduled retry of the hook with the name being run
+    pub(crate) fn record_retry_started(&self, name: Option<&str>) {
+        self.update_health(name, |health| {
+            health.pending_retries = health.pending_retries.saturating_sub(1)
+        });
+    }
+
+    /// Count a delivery the hook with the name failed for good
+    pub(crate) fn record_dead_letter(&self, name: Option<&str>) {
+        self.update_health(name, |health| health.dead_lettered += 1);
+    }
+
+    /// Count the delivery
+    pub(crate) fn record_delivery(&self, delivery: &Delivery) {
+        let counters = &self.counters;
+        counters.deliveries.fetch_add(1, Ordering::Relaxed);
+        increase(&counters.events, delivery.event.clone(), 1);
+        increase(
+            &counters.providers,
+            format!("{:?}", delivery.delivery_type),
+            1,
+        );
+        let size = delivery.request_body.as_ref().map_or(0, String::len);
+        let bucket = PAYLOAD_SIZE_BUCKETS
+            .iter()
+            .position(|bound| size <= *bound)
+            .unwrap_or(PAYLOAD_SIZE_BUCKETS.len());
+        counters
+            .payload_bytes
+            .fetch_add(size as u64, Ordering::Relaxed);
+        counters.payload_sizes[bucket].fetch_add(1, Ordering::Relaxed);
+        if let Some(repository) = delivery.repository() {
+            let mut repositories = match counters.repositories.lock() {
+                Ok(repositories) => repositories,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let len = repositories.len();
+            match repositories.get_mut(&repository) {
+                Some(count) => *count += 1,
+                None if len < MAX_REPOSITORIES => {
+                    repositories.insert(repository, 1);
+                }
+                None => debug!("Too many repositories to count {}", repository),
+            }
+        }
+    }
+
+    /// Count a delivery failing to authenticate
+    pub(crate) fn record_auth_failure(&self) {
+        self.counters.auth_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Add the value to the custom metric
+    pub(crate) fn record_metric(&self, metric: &str, value: u64) {
+        increase(&self.counters.metrics, metric.to_string(), value);
+    }
+
+    /// Count a run of the hook with the name taking the duration
+    pub(crate) fn record_hook(&self, name: Option<&str>, duration: Duration, ok: bool) {
+        let nanos = duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos());
+        self.counters.hook_runs.fetch_add(1, Ordering::Relaxed);
+        self.counters.hook_nanos.fetch_add(nanos, Ordering::Relaxed);
+        if let Some(name) = name {
+            increase(&self.counters.hooks, name.to_string(), 1);
+            if !ok {
+                increase(&self.counters.hook_failures, name.to_string(), 1);
+            }
+        }
+        self.update_health(name, |health| {
+            if ok {
+                health.last_success = Some(SystemTime::now());
+                health.consecutive_failures = 0;
+            } else {
+                health.consecutive_failures += 1;
+            }
+        });
+    }
+}
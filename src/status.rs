@@ -0,0 +1,198 @@
+//! Commit status
+//!
+//! `CommitStatus` reports the outcome of a hook back to GitHub as a status of the commit in the delivery,
+//! so CI-style hooks show up next to the commit and in pull requests:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::status::CommitStatus;
+//! use rifling::{Delivery, Hook};
+//!
+//! let status = CommitStatus::new("<token>")
+//!     .context("ci/build")
+//!     .target_url("https://ci.example.com/");
+//! let hook = Hook::new("push", None, |_: &Delivery| -> Result<(), String> {
+//!     Ok(()) // Build the project
+//! })
+//! .report_status(status);
+//! ```
+//!
+//! The status is `success` when the hook returns `Ok`, and `failure` when it returns `Err` (after all the
+//! retries). The repository and the commit are taken from the payload, deliveries without them are
+//! not reported. Statuses are posted in a separate thread, errors are passed to the error callback.
+//!
+//! The token needs the `repo:status` scope. Check runs require a GitHub App and are not supported.
+
+use futures::{Future, Stream};
+use hyper::client::connect::HttpConnector;
+use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+use hyper::{Body, Client, Method, Request};
+use hyper_rustls::HttpsConnector;
+use serde_json::json;
+use tokio::runtime::current_thread::Runtime;
+
+use super::error::Error;
+use super::handler::Delivery;
+
+/// Maximum length of the description accepted by GitHub
+const MAX_DESCRIPTION_LEN: usize = 140;
+
+/// Reporter of hook outcomes as GitHub commit statuses
+#[derive(Clone, Debug)]
+pub struct CommitStatus {
+    token: String,
+    context: String,
+    target_url: Option<String>,
+    api_url: String,
+}
+
+/// The main impl clause of `CommitStatus`
+impl CommitStatus {
+    /// Create a new reporter authenticating with the token
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_string(),
+            context: "rifling".to_string(),
+            target_url: None,
+            api_url: "https://api.github.com".to_string(),
+        }
+    }
+
+    /// Set the label of the status (`rifling` by default)
+    pub fn context(mut self, context: &str) -> Self {
+        self.context = context.to_string();
+        self
+    }
+
+    /// Set the URL linked from the status
+    pub fn target_url(mut self, target_url: &str) -> Self {
+        self.target_url = Some(target_url.to_string());
+        self
+    }
+
+    /// Set the URL of the API (e.g. `https://github.example.com/api/v3` for GitHub Enterprise)
+    pub fn api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Build the request creating the status, `None` if the repository or the commit is unknown
+    fn request(
+        &self,
+        event: &str,
+        delivery: &Delivery,
+        result: &Result<(), Error>,
+    ) -> Option<Request<Body>> {
+        let repository = delivery.repository()?;
+        let sha = delivery.commit_sha()?;
+        let (state, mut description) = match result {
+            Ok(()) => ("success", format!("Hook for '{}' event succeeded", event)),
+            Err(Error::HookFailed(message)) => (
+                "failure",
+                format!("Hook for '{}' event failed: {}", event, message),
+            ),
+            Err(error) => ("failure", format!("Hook for '{}' event: {}", event, error)),
+        };
+        if description.chars().count() > MAX_DESCRIPTION_LEN {
+            description = description.chars().take(MAX_DESCRIPTION_LEN - 3).collect();
+            description.push_str("...");
+        }
+        let body = json!({
+            "state": state,
+            "context": &self.context,
+            "description": description,
+            "target_url": &self.target_url,
+        });
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!(
+                "{}/repos/{}/statuses/{}",
+                &self.api_url, repository, sha
+            ))
+            .header(AUTHORIZATION, format!("token {}", &self.token))
+            .header(USER_AGENT, "rifling")
+            .header(ACCEPT, "application/vnd.github.v3+json")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .ok()
+    }
+
+    /// Post the outcome of the hook for the event, blocking until GitHub answers
+    pub fn post(
+        &self,
+        event: &str,
+        delivery: &Delivery,
+        result: &Result<(), Error>,
+    ) -> Result<(), Error> {
+        let request = match self.request(event, delivery, result) {
+            Some(request) => request,
+            None => {
+                debug!("No repository or commit in the delivery, status not reported");
+                return Ok(());
+            }
+        };
+        debug!("Reporting commit status to {}", request.uri());
+        let client: Client<HttpsConnector<HttpConnector>> =
+            Client::builder().build(HttpsConnector::new(1));
+        let response = client.request(request).and_then(|response| {
+            let status = response.status();
+            response
+                .into_body()
+                .concat2()
+                .map(move |body| (status, body))
+        });
+        let mut runtime = Runtime::new().map_err(|error| Error::StatusReport(error.to_string()))?;
+        let (status, body) = runtime
+            .block_on(response)
+            .map_err(|error| Error::StatusReport(error.to_string()))?;
+        if !status.is_success() {
+            return Err(Error::StatusReport(format!(
+                "{} {}",
+                status,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn delivery(payload: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        Delivery::new(headers, Some(payload.to_string())).unwrap()
+    }
+
+    /// Test building the request from the delivery and the outcome
+    #[test]
+    fn status_request() {
+        let status = CommitStatus::new("secret").api_url("https://github.example.com/api/v3/");
+        let delivery =
+            delivery(r#"{"after": "abc123", "repository": {"full_name": "RedL0tus/rifling"}}"#);
+        let request = status
+            .request(
+                "push",
+                &delivery,
+                &Err(Error::HookFailed("Nope".to_string())),
+            )
+            .unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://github.example.com/api/v3/repos/RedL0tus/rifling/statuses/abc123"
+        );
+        assert_eq!(request.headers()[AUTHORIZATION], "token secret");
+        let body = request.into_body().concat2().wait().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["state"], "failure");
+        assert_eq!(body["context"], "rifling");
+        assert_eq!(body["description"], "Hook for 'push' event failed: Nope");
+        assert!(status
+            .request("push", &self::delivery(r#"{"after": "abc123"}"#), &Ok(()))
+            .is_none());
+    }
+}
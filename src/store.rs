@@ -0,0 +1,268 @@
+//! Delivery store
+//!
+//! A `DeliveryStore` keeps track of received deliveries and what happened to them, configured on the
+//! `Constructor` with `Constructor::store`.
+//!
+//! `MemoryStore` keeps the most recent deliveries in memory, it can be cloned to query the store while the
+//! server is running:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::Constructor;
+//! use rifling::store::MemoryStore;
+//!
+//! let store = MemoryStore::new(100);
+//! let mut cons = Constructor::new();
+//! cons.store(store.clone());
+//! // ...
+//! for record in store.records() {
+//!     println!("{}: {} failure(s)", record.delivery.event(), record.failures.len());
+//! }
+//! ```
+//!
+//! The result of each hook run for a delivery is kept too, e.g. to check if the deploy hook ran:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::store::{DeliveryStore, MemoryStore};
+//!
+//! let store = MemoryStore::new(100);
+//! if let Some(status) = store.status("72d3162e-cc78-11e3-81ab-4c9367dc0958") {
+//!     match status.hook("deploy") {
+//!         Some(result) if result.ok => println!("Deployed in {:?}", result.duration),
+//!         Some(result) => println!("Deploy failed after {} attempt(s): {:?}", result.attempts, result.error),
+//!         None => println!("Deploy didn't run (yet)"),
+//!     }
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::error::Error;
+use super::handler::Delivery;
+
+/// Storage of deliveries
+pub trait DeliveryStore: Send + Sync {
+    /// Record a received delivery
+    fn record(&self, delivery: &Delivery);
+
+    /// Record a failure of hooks handling the delivery
+    fn record_failure(&self, delivery: &Delivery, error: &Error);
+
+    /// Record the result of an attempt to run a hook with the delivery, replacing the result of its previous attempt
+    fn record_hook(&self, _delivery: &Delivery, _result: &HookRun) {}
+
+    /// Status of the hooks run for the delivery with the ID, if it's still stored
+    fn status(&self, _id: &str) -> Option<DeliveryStatus> {
+        None
+    }
+}
+
+/// Result of the last attempt to run a hook with a delivery
+#[derive(Clone, Debug, PartialEq)]
+pub struct HookRun {
+    pub event: &'static str,
+    pub name: Option<String>, // set by `Hook::named`
+    pub ok: bool,
+    /// Message of the error returned by the hook, if it failed
+    pub error: Option<String>,
+    /// Time taken by the last attempt
+    pub duration: Duration,
+    /// Number of attempts so far, including retries (see `Hook::retry`)
+    pub attempts: u32,
+    /// Output recorded by the last attempt with `Context::record_output`, e.g. of commands
+    pub output: Option<String>,
+    /// The hook function was skipped by a dry run, see `Constructor::dry_run`
+    pub dry_run: bool,
+}
+
+/// Status of the hooks run for a delivery, see `DeliveryStore::status`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeliveryStatus {
+    pub delivery_id: String,
+    pub event: String,
+    pub hooks: Vec<HookRun>,
+}
+
+/// A delivery and what happened to it
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub delivery: Delivery,
+    pub failures: Vec<Error>,
+    pub hooks: Vec<HookRun>,
+}
+
+/// The main impl clause of `DeliveryStatus`
+impl DeliveryStatus {
+    /// Result of the hook with the name, or registered for the event if unnamed
+    pub fn hook(&self, name: &str) -> Option<&HookRun> {
+        self.hooks
+            .iter()
+            .find(|result| result.name.as_deref() == Some(name))
+            .or_else(|| {
+                self.hooks
+                    .iter()
+                    .find(|result| result.name.is_none() && result.event == name)
+            })
+    }
+}
+
+/// In-memory store of the most recent deliveries
+#[derive(Clone, Debug)]
+pub struct MemoryStore {
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<Record>>>,
+}
+
+/// The main impl clause of `MemoryStore`
+impl MemoryStore {
+    /// Create a new store keeping at most `capacity` deliveries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Record>> {
+        match self.records.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// All records, oldest first
+    pub fn records(&self) -> Vec<Record> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Find the record of the delivery
+    fn find<'a>(records: &'a mut VecDeque<Record>, delivery: &Delivery) -> Option<&'a mut Record> {
+        records.iter_mut().rev().find(|record| {
+            record.delivery.id == delivery.id
+                && record.delivery.event == delivery.event
+                && record.delivery.request_body == delivery.request_body
+        })
+    }
+
+    /// Find the record of a delivery by its ID
+    pub fn get(&self, id: &str) -> Option<Record> {
+        self.lock()
+            .iter()
+            .rev()
+            .find(|record| record.delivery.id.as_deref() == Some(id))
+            .cloned()
+    }
+}
+
+/// Implement `DeliveryStore` to `MemoryStore`
+impl DeliveryStore for MemoryStore {
+    fn record(&self, delivery: &Delivery) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.lock();
+        while records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(Record {
+            delivery: delivery.clone(),
+            failures: Vec::new(),
+            hooks: Vec::new(),
+        });
+    }
+
+    fn record_failure(&self, delivery: &Delivery, error: &Error) {
+        if let Some(record) = Self::find(&mut self.lock(), delivery) {
+            record.failures.push(error.clone());
+        }
+    }
+
+    fn record_hook(&self, delivery: &Delivery, result: &HookRun) {
+        let mut records = self.lock();
+        let record = match Self::find(&mut records, delivery) {
+            Some(record) => record,
+            None => return,
+        };
+        let previous = record
+            .hooks
+            .iter_mut()
+            .find(|previous| previous.event == result.event && previous.name == result.name);
+        match previous {
+            Some(previous) => *previous = result.clone(),
+            None => record.hooks.push(result.clone()),
+        }
+    }
+
+    fn status(&self, id: &str) -> Option<DeliveryStatus> {
+        let record = self.get(id)?;
+        Some(DeliveryStatus {
+            delivery_id: id.to_string(),
+            event: record.delivery.event,
+            hooks: record.hooks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn delivery(id: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        headers.insert("x-github-delivery".to_string(), id.to_string());
+        Delivery::new(headers, Some("{}".to_string())).unwrap()
+    }
+
+    /// Test recording deliveries and failures
+    #[test]
+    fn memory_store() {
+        let store = MemoryStore::new(2);
+        store.record(&delivery("1"));
+        store.record(&delivery("2"));
+        store.record(&delivery("3"));
+        assert!(store.get("1").is_none());
+        store.record_failure(&delivery("2"), &Error::InvalidSignature);
+        assert_eq!(
+            store.get("2").unwrap().failures,
+            vec![Error::InvalidSignature]
+        );
+        assert!(store.get("3").unwrap().failures.is_empty());
+    }
+
+    /// Test recording the results of hooks
+    #[test]
+    fn hook_results() {
+        let store = MemoryStore::new(2);
+        store.record(&delivery("1"));
+        let result = |name: Option<&str>, ok: bool, attempts: u32| HookRun {
+            event: "push",
+            name: name.map(str::to_string),
+            ok,
+            error: if ok { None } else { Some("Failed".to_string()) },
+            duration: Duration::from_millis(1),
+            attempts,
+            output: None,
+            dry_run: false,
+        };
+        store.record_hook(&delivery("1"), &result(Some("deploy"), false, 1));
+        store.record_hook(&delivery("1"), &result(None, true, 1));
+        store.record_hook(&delivery("1"), &result(Some("deploy"), true, 2));
+        let status = store.status("1").unwrap();
+        assert_eq!(status.event, "push");
+        assert_eq!(status.hooks.len(), 2);
+        assert_eq!(
+            status.hook("deploy"),
+            Some(&result(Some("deploy"), true, 2))
+        );
+        assert_eq!(status.hook("push"), Some(&result(None, true, 1)));
+        assert!(status.hook("notify").is_none());
+        assert!(store.status("2").is_none());
+    }
+}
@@ -0,0 +1,127 @@
+//! systemd integration
+//!
+//! With the `socket-activation` feature, the listening socket can be passed by systemd (socket activation, see
+//! `sd_listen_fds(3)`) instead of being bound by the application, and systemd can be notified once the service is
+//! ready (`Type=notify` services, see `sd_notify(3)`). Both are Unix only.
+//!
+//! With hyper:
+//!
+//! ```no_run
+//! extern crate hyper;
+//! extern crate rifling;
+//!
+//! use hyper::rt::Future;
+//! use rifling::{systemd, Constructor};
+//!
+//! let listener = systemd::listener().unwrap().expect("Not started by systemd");
+//! let server = hyper::Server::from_tcp(listener).unwrap().serve(Constructor::new());
+//! systemd::notify_ready().unwrap();
+//! hyper::rt::run(server.map_err(|error| eprintln!("Error: {}", error)));
+//! ```
+//!
+//! With the `sync-server` feature, `Constructor::serve_activated` serves the inherited socket, falling back to
+//! binding the address when the process wasn't started by systemd.
+
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::process;
+
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+
+/// First file descriptor passed by systemd
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Number of file descriptors passed to the process with the given ID
+fn listen_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> usize {
+    match (listen_pid, listen_fds) {
+        (Some(listen_pid), Some(listen_fds)) if listen_pid.parse() == Ok(pid) => {
+            listen_fds.parse().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+/// Take the listening sockets passed by systemd, the environment variables passing them are removed
+///
+/// The sockets must be TCP sockets (`ListenStream=` with an address or port in the socket unit).
+pub fn listeners() -> Vec<TcpListener> {
+    let count = listen_fds(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        process::id(),
+    );
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    debug!("{} socket(s) passed by systemd", count);
+    (0..count)
+        .map(|index| unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START + index as RawFd) })
+        .collect()
+}
+
+/// Take the first listening socket passed by systemd, if any
+pub fn listener() -> io::Result<Option<TcpListener>> {
+    match listeners().into_iter().next() {
+        Some(listener) => {
+            // Sockets may be passed in non-blocking mode (`NonBlocking=true`), which tiny_http doesn't expect
+            listener.set_nonblocking(false)?;
+            Ok(Some(listener))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Send the state to the socket at the path, abstract sockets start with `@`
+fn notify(path: &str, state: &str) -> io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Notify systemd that the service is ready (`READY=1`), does nothing if the service is not of `Type=notify`
+pub fn notify_ready() -> io::Result<()> {
+    match env::var("NOTIFY_SOCKET") {
+        Ok(path) => notify(&path, "READY=1"),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test counting the sockets passed to the process
+    #[test]
+    fn listen_fds_for_process() {
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42), 2);
+        assert_eq!(listen_fds(Some("41"), Some("2"), 42), 0);
+        assert_eq!(listen_fds(None, Some("2"), 42), 0);
+        assert_eq!(listen_fds(Some("42"), Some("many"), 42), 0);
+    }
+
+    /// Test notifying the socket
+    #[test]
+    fn notify_socket() {
+        let path = env::temp_dir().join(format!("rifling-notify-{}", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        notify(path.to_str().unwrap(), "READY=1").unwrap();
+        let mut buffer = [0; 16];
+        let length = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], b"READY=1");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,119 @@
+//! Templates
+//!
+//! `Template` renders fields of the payload of deliveries into strings, e.g. the messages of notification hooks (see
+//! `rifling::notify`):
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::template::Template;
+//! use rifling::{Delivery, Hook};
+//!
+//! let template = Template::new("{{repository.full_name}} pushed to {{ref}} by {{sender.login}}");
+//! let hook = Hook::new("push", None, move |delivery: &Delivery| {
+//!     println!("{}", template.render(delivery).unwrap_or_default());
+//! });
+//! ```
+//!
+//! Placeholders are paths of fields with dots (e.g. `{{repository.full_name}}`, `{{commits.0.message}}`). Missing
+//! fields are rendered empty, fields which are not strings are rendered as JSON.
+//!
+//! With `template-handlebars`, templates are rendered with [`handlebars`](https://crates.io/crates/handlebars),
+//! supporting its whole syntax, e.g. `{{#each commits}}- {{message}}{{/each}}` or `{{#if forced}}(forced){{/if}}`.
+//! Values are not HTML-escaped.
+
+#[cfg(feature = "template-handlebars")]
+use handlebars::{no_escape, Handlebars};
+use serde_json::Value;
+
+use super::error::Error;
+use super::handler::Delivery;
+
+/// Template of a string rendered with the fields of the payload of deliveries
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    source: String,
+}
+
+/// The main impl clause of `Template`
+impl Template {
+    /// Create a new template from its source
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+        }
+    }
+
+    /// Source of the template
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Render the template with the fields of the payload of the delivery
+    #[cfg(not(feature = "template-handlebars"))]
+    pub fn render(&self, delivery: &Delivery) -> Result<String, Error> {
+        let mut rendered = String::with_capacity(self.source.len());
+        let mut rest = self.source.as_str();
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+            rendered.push_str(&rest[..start]);
+            let path = rest[start + 2..end].trim();
+            match delivery.field(&format!("/{}", path.replace('.', "/"))) {
+                Some(Value::String(string)) => rendered.push_str(string),
+                Some(Value::Null) | None => (),
+                Some(value) => rendered.push_str(&value.to_string()),
+            }
+            rest = &rest[end + 2..];
+        }
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+
+    /// Render the template with the fields of the payload of the delivery
+    #[cfg(feature = "template-handlebars")]
+    pub fn render(&self, delivery: &Delivery) -> Result<String, Error> {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(no_escape);
+        let payload = delivery.payload.as_ref().unwrap_or(&Value::Null);
+        registry
+            .render_template(&self.source, payload)
+            .map_err(|error| Error::Template(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Test rendering templates with fields of the payload
+    #[test]
+    fn render() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "push".to_string());
+        let request_body = r#"{"ref": "refs/heads/master", "forced": false, "repository": {"full_name": "RedL0tus/rifling"}, "sender": {"login": "octocat"}, "commits": [{"message": "Fix <it>"}]}"#;
+        let delivery = Delivery::new(headers, Some(request_body.to_string())).unwrap();
+        let render = |source: &str| Template::new(source).render(&delivery).unwrap();
+        assert_eq!(
+            render("{{repository.full_name}} pushed to {{ ref }} by {{sender.login}}"),
+            "RedL0tus/rifling pushed to refs/heads/master by octocat"
+        );
+        assert_eq!(
+            render("{{commits.0.message}} (forced: {{forced}}){{missing}}"),
+            "Fix <it> (forced: false)"
+        );
+        #[cfg(not(feature = "template-handlebars"))]
+        assert_eq!(render("Unclosed {{ref"), "Unclosed {{ref");
+        #[cfg(feature = "template-handlebars")]
+        {
+            assert_eq!(
+                render("{{#each commits}}- {{message}}{{/each}}{{#if forced}} (forced){{/if}}"),
+                "- Fix <it>"
+            );
+            assert!(Template::new("Unclosed {{ref").render(&delivery).is_err());
+        }
+    }
+}
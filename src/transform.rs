@@ -0,0 +1,122 @@
+//! Transformation of deliveries
+//!
+//! Transformers added with `Constructor::transform` rewrite deliveries before hooks are matched, e.g. to give
+//! synchronized pull requests an event of their own, or to fill in fields of payloads for compatibility with other
+//! senders:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::filter::when_eq;
+//! use rifling::transform::RenameEvent;
+//! use rifling::{Constructor, Delivery, Hook};
+//!
+//! let mut cons = Constructor::new();
+//! cons.transform(RenameEvent::new("pull_request", "pr_updated").when(when_eq("/action", "synchronize")));
+//! cons.register(Hook::new("pr_updated", None, |delivery: &Delivery| {
+//!     println!("Pull request updated: {:?}", delivery.field("/number"));
+//! }));
+//! ```
+//!
+//! Any `Fn(&mut Delivery)` can be used as a transformer too, they're run in the order they were added. Events set by
+//! transformers are normalized like those of hooks (see `Constructor::event_normalization`), and events are matched
+//! by transformers as normalized (e.g. `merge_request_hook`).
+//!
+//! Transformers run before deliveries are authenticated, on what the sender claims. Signatures are verified against
+//! the body as received (`Delivery::request_body`), rewriting the payload doesn't affect them. The event sent by the
+//! provider is still available in the headers of the delivery (e.g. `x-github-event`).
+
+use std::sync::Arc;
+
+use super::filter::Condition;
+use super::handler::Delivery;
+
+/// Rewrite of deliveries before hooks are matched
+/// You can implement this trait to your own struct
+pub trait Transformer: Sync + Send {
+    fn transform(&self, delivery: &mut Delivery);
+}
+
+/// Transformer renaming an event, for deliveries satisfying all its conditions
+#[derive(Clone)]
+pub struct RenameEvent {
+    from: String,
+    to: String,
+    conditions: Vec<Arc<dyn Condition>>,
+}
+
+/// The main impl clause of `RenameEvent`
+impl RenameEvent {
+    /// Rename the event (as normalized) of deliveries
+    pub fn new(from: &str, to: &str) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Only rename deliveries satisfying the condition, see `rifling::filter`
+    pub fn when(mut self, condition: impl Condition + 'static) -> Self {
+        self.conditions.push(Arc::new(condition));
+        self
+    }
+}
+
+/// Implement `Transformer` to `RenameEvent`
+impl Transformer for RenameEvent {
+    fn transform(&self, delivery: &mut Delivery) {
+        if delivery.event != self.from {
+            return;
+        }
+        if self
+            .conditions
+            .iter()
+            .all(|condition| condition.matches(delivery))
+        {
+            debug!("Renamed event '{}' to '{}'", &self.from, &self.to);
+            delivery.event = self.to.clone();
+        }
+    }
+}
+
+/// Implement `Transformer` to `Fn(&mut Delivery)`.
+impl<F> Transformer for F
+where
+    F: Fn(&mut Delivery) + Sync + Send,
+{
+    fn transform(&self, delivery: &mut Delivery) {
+        self(delivery)
+    }
+}
+
+#[cfg(all(test, feature = "parse"))]
+mod tests {
+    use super::*;
+    use crate::filter::when_eq;
+    use std::collections::HashMap;
+
+    fn delivery(action: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-github-event".to_string(), "pull_request".to_string());
+        let request_body = format!(r#"{{"action": "{}"}}"#, action);
+        Delivery::new(headers, Some(request_body)).unwrap()
+    }
+
+    /// Test renaming events of deliveries satisfying the conditions
+    #[test]
+    fn rename_event() {
+        let transformer =
+            RenameEvent::new("pull_request", "pr_updated").when(when_eq("/action", "synchronize"));
+        let mut synchronized = delivery("synchronize");
+        transformer.transform(&mut synchronized);
+        assert_eq!(synchronized.event, "pr_updated");
+        let mut opened = delivery("opened");
+        transformer.transform(&mut opened);
+        assert_eq!(opened.event, "pull_request");
+        let mut pushed = delivery("synchronize");
+        pushed.event = "push".to_string();
+        transformer.transform(&mut pushed);
+        assert_eq!(pushed.event, "push");
+    }
+}
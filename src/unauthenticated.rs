@@ -0,0 +1,270 @@
+//! Providers sending no signatures
+//!
+//! Some senders (e.g. PyPI) neither sign their deliveries nor send a token, anyone knowing the URL of the hooks could
+//! trigger them. `UnauthenticatedProvider` registers hooks for such senders, and refuses to do so until at least one
+//! compensating control is attached to it:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::unauthenticated::UnauthenticatedProvider;
+//! use rifling::{Constructor, Delivery, DeliveryType, Hook};
+//!
+//! let pypi = UnauthenticatedProvider::new("pypi")
+//!     .allow_from(&["10.0.0.0/8"])
+//!     .unwrap()
+//!     .query_token("token", "s3cr3t")
+//!     .hook(Hook::new("*", None, |_: &Delivery| println!("Released!")));
+//! let mut cons = Constructor::new();
+//! cons.provider_hint(DeliveryType::Form);
+//! cons.register_unauthenticated(pypi).unwrap();
+//! ```
+//!
+//! Controls:
+//!
+//! - `allow_from`: the address of the sender (see `Constructor::trust_proxies`) is in one of the ranges.
+//! - `query_token`: the parameter of the query string is the token, e.g. `/hooks?token=s3cr3t`.
+//! - `basic_auth`: the `Authorization` header carries the credentials of basic authentication.
+//!
+//! Deliveries have to pass all the controls attached, they replace the secrets and authenticators of the hooks. Such
+//! deliveries still have to be identified, e.g. with `Constructor::provider_hint`.
+
+use std::error;
+use std::fmt;
+
+use super::crypto::SignatureEncoding;
+use super::error::Error;
+use super::handler::{query_param, Delivery};
+use super::hook::{Authenticator, Hook};
+use super::proxy::{Cidr, InvalidCidr};
+use super::verify;
+
+/// Control compensating the lack of signatures
+#[derive(Clone)]
+enum Control {
+    Clients(Vec<Cidr>),
+    QueryToken { param: String, token: String },
+    BasicAuth { user: String, password: String },
+}
+
+/// Controls deliveries to the hooks of a provider have to pass
+#[derive(Clone)]
+struct Controls {
+    provider: String,
+    controls: Vec<Control>,
+}
+
+/// Hooks of a sender without signatures, registered with `Constructor::register_unauthenticated`
+#[derive(Clone)]
+pub struct UnauthenticatedProvider {
+    name: String,
+    controls: Vec<Control>,
+    hooks: Vec<Hook>,
+}
+
+/// Error registering an `UnauthenticatedProvider` without any control, naming the provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingControl(pub String);
+
+/// Implement `Display` for `MissingControl`
+impl fmt::Display for MissingControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "No control attached to the unauthenticated provider '{}'",
+            self.0
+        )
+    }
+}
+
+impl error::Error for MissingControl {}
+
+/// The main impl clause of `Control`
+impl Control {
+    /// Check the delivery, returns the reason if it's rejected
+    fn verify(&self, delivery: &Delivery) -> Result<(), Error> {
+        match self {
+            Control::Clients(cidrs) => match delivery.remote_addr {
+                Some(addr) if cidrs.iter().any(|cidr| cidr.contains(&addr)) => Ok(()),
+                _ => Err(Error::UntrustedClient),
+            },
+            Control::QueryToken { param, token } => {
+                let sent = delivery
+                    .query
+                    .as_deref()
+                    .and_then(|query| query_param(query, param))
+                    .ok_or(Error::MissingSignature)?;
+                verify::token(token, &sent)
+            }
+            Control::BasicAuth { user, password } => {
+                let credentials = delivery
+                    .headers
+                    .get("authorization")
+                    .and_then(|value| value.strip_prefix("Basic "))
+                    .ok_or(Error::MissingSignature)?;
+                let credentials = SignatureEncoding::Base64
+                    .decode(credentials.trim())
+                    .and_then(|credentials| String::from_utf8(credentials).ok())
+                    .ok_or(Error::InvalidSignature)?;
+                verify::token(&format!("{}:{}", user, password), &credentials)
+            }
+        }
+    }
+}
+
+/// Implement `Authenticator` to `Controls`
+impl Authenticator for Controls {
+    fn verify(&self, delivery: &Delivery) -> Result<(), Error> {
+        for control in self.controls.iter() {
+            if let Err(error) = control.verify(delivery) {
+                debug!("Delivery of '{}' refused by its controls", &self.provider);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The main impl clause of `UnauthenticatedProvider`
+impl UnauthenticatedProvider {
+    /// Create a new provider without controls nor hooks, the name is used in logs and errors
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            controls: Vec::new(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Only accept deliveries sent from the ranges of IP addresses (e.g. `10.0.0.0/8`), see `rifling::proxy`
+    pub fn allow_from(mut self, cidrs: &[&str]) -> Result<Self, InvalidCidr> {
+        let cidrs = cidrs
+            .iter()
+            .map(|cidr| cidr.parse())
+            .collect::<Result<_, _>>()?;
+        self.controls.push(Control::Clients(cidrs));
+        Ok(self)
+    }
+
+    /// Only accept deliveries carrying the token in the parameter of their query string
+    pub fn query_token(mut self, param: &str, token: &str) -> Self {
+        self.controls.push(Control::QueryToken {
+            param: param.to_string(),
+            token: token.to_string(),
+        });
+        self
+    }
+
+    /// Only accept deliveries carrying the credentials of basic authentication
+    pub fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.controls.push(Control::BasicAuth {
+            user: user.to_string(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    /// Add the hook, its secret is ignored
+    pub fn hook(mut self, hook: Hook) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Name of the provider
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The hooks, authenticated by the controls, or the error if there's no control
+    pub(crate) fn into_hooks(self) -> Result<Vec<Hook>, MissingControl> {
+        if self.controls.is_empty() {
+            return Err(MissingControl(self.name));
+        }
+        let controls = Controls {
+            provider: self.name,
+            controls: self.controls,
+        };
+        Ok(self
+            .hooks
+            .into_iter()
+            .map(|hook| hook.with_authenticator(controls.clone()))
+            .collect())
+    }
+}
+
+/// Implement `Debug` to `UnauthenticatedProvider`, the controls are elided
+impl fmt::Debug for UnauthenticatedProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UnauthenticatedProvider")
+            .field("name", &self.name)
+            .field("hooks", &self.hooks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn delivery(remote_addr: &str, query: &str, authorization: &str) -> Delivery {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert("x-gitlab-event".to_string(), "Push Hook".to_string());
+        headers.insert("authorization".to_string(), authorization.to_string());
+        let mut delivery = Delivery::new(headers, Some("{}".to_string())).unwrap();
+        delivery.remote_addr = Some(remote_addr.parse().unwrap());
+        delivery.query = Some(query.to_string());
+        delivery
+    }
+
+    /// Test refusing to register providers without controls
+    #[test]
+    fn missing_control() {
+        let provider =
+            UnauthenticatedProvider::new("pypi").hook(Hook::new("*", None, |_: &Delivery| ()));
+        assert_eq!(
+            provider.into_hooks().err(),
+            Some(MissingControl("pypi".to_string()))
+        );
+    }
+
+    /// Test authenticating deliveries with all the controls
+    #[test]
+    fn controls() {
+        let hooks = UnauthenticatedProvider::new("pypi")
+            .allow_from(&["10.0.0.0/8"])
+            .unwrap()
+            .query_token("token", "s3cr3t")
+            .basic_auth("admin", "secret")
+            .hook(Hook::new(
+                "*",
+                Some("ignored".to_string()),
+                |_: &Delivery| (),
+            ))
+            .into_hooks()
+            .unwrap();
+        let hook = &hooks[0];
+        // admin:secret
+        let basic = "Basic YWRtaW46c2VjcmV0";
+        assert_eq!(
+            hook.verify(&delivery("10.1.2.3", "token=s3cr3t", basic)),
+            Ok(())
+        );
+        assert_eq!(
+            hook.verify(&delivery("192.168.1.1", "token=s3cr3t", basic)),
+            Err(Error::UntrustedClient)
+        );
+        assert_eq!(
+            hook.verify(&delivery("10.1.2.3", "token=secret", basic)),
+            Err(Error::InvalidSignature)
+        );
+        assert_eq!(
+            hook.verify(&delivery("10.1.2.3", "event=push", basic)),
+            Err(Error::MissingSignature)
+        );
+        assert_eq!(
+            hook.verify(&delivery("10.1.2.3", "token=s3cr3t", "Basic YWRtaW46")),
+            Err(Error::InvalidSignature)
+        );
+    }
+}
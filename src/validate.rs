@@ -0,0 +1,275 @@
+//! Validation
+//!
+//! Validators guard hooks against malformed or unexpected payloads, they are attached to events with
+//! `Constructor::validate`. Deliveries failing validation are rejected with `422 Unprocessable Entity` before
+//! running any hook, and the error is passed to the error callback.
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{Constructor, Delivery};
+//!
+//! let mut cons = Constructor::new();
+//! cons.validate("push", |delivery: &Delivery| -> Result<(), String> {
+//!     match delivery.payload() {
+//!         Some(_) => Ok(()),
+//!         None => Err("Payload is not JSON".to_string()),
+//!     }
+//! });
+//! ```
+//!
+//! With the `schema` feature, payloads can be validated against a JSON Schema with `Schema`. Only a subset of the
+//! validation keywords is supported: `type`, `enum`, `const`, `required`, `properties`, `additionalProperties`,
+//! `items`, `minItems`, `maxItems`, `minLength`, `maxLength`, `minimum`, `maximum`, `allOf` and `anyOf`.
+
+#[cfg(feature = "schema")]
+use serde_json::Value;
+
+use super::handler::Delivery;
+
+/// Validator of deliveries
+/// You can implement this trait to your own struct
+pub trait Validator: Sync + Send {
+    /// Validate the delivery, returns the reason if it's invalid
+    fn validate(&self, delivery: &Delivery) -> Result<(), String>;
+}
+
+/// Implement `Validator` to `Fn(&Delivery) -> Result<(), String>`.
+impl<F> Validator for F
+where
+    F: Fn(&Delivery) -> Result<(), String> + Sync + Send + 'static,
+{
+    fn validate(&self, delivery: &Delivery) -> Result<(), String> {
+        self(delivery)
+    }
+}
+
+/// JSON Schema validating the payload of deliveries
+///
+/// ```
+/// extern crate rifling;
+///
+/// use rifling::validate::Schema;
+/// use rifling::Constructor;
+///
+/// let schema = Schema::from_str(r#"{"type": "object", "required": ["ref", "after"]}"#).unwrap();
+/// let mut cons = Constructor::new();
+/// cons.validate("push", schema);
+/// ```
+#[cfg(feature = "schema")]
+#[derive(Clone, Debug)]
+pub struct Schema {
+    schema: Value,
+}
+
+/// Name of the JSON type of the value
+#[cfg(feature = "schema")]
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// The main impl clause of `Schema`
+#[cfg(feature = "schema")]
+impl Schema {
+    /// Create a schema from its JSON value
+    pub fn new(schema: Value) -> Self {
+        Self { schema }
+    }
+
+    /// Parse the schema
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(schema: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(schema).map(Self::new)
+    }
+
+    /// Validate the value, returns the reason of the first violation found
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        check(&self.schema, value, "")
+    }
+}
+
+/// Validate the value at the JSON pointer against the schema
+#[cfg(feature = "schema")]
+fn check(schema: &Value, value: &Value, pointer: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: not allowed", pointer)),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+    let keyword = |name: &str| schema.get(name);
+    let number = |name: &str| keyword(name).and_then(Value::as_f64);
+    let count = |name: &str| {
+        keyword(name)
+            .and_then(Value::as_u64)
+            .map(|count| count as usize)
+    };
+    if let Some(expected) = keyword("type") {
+        let actual = type_name(value);
+        let matches = |expected: &Value| {
+            expected.as_str().is_some_and(|expected| {
+                expected == actual || (expected == "number" && actual == "integer")
+            })
+        };
+        let valid = match expected {
+            Value::Array(expected) => expected.iter().any(matches),
+            expected => matches(expected),
+        };
+        if !valid {
+            return Err(format!(
+                "{}: expected {}, found {}",
+                pointer, expected, actual
+            ));
+        }
+    }
+    if let Some(Value::Array(allowed)) = keyword("enum") {
+        if !allowed.contains(value) {
+            return Err(format!(
+                "{}: {} is not one of {:?}",
+                pointer, value, allowed
+            ));
+        }
+    }
+    if let Some(constant) = keyword("const") {
+        if constant != value {
+            return Err(format!("{}: expected {}", pointer, constant));
+        }
+    }
+    if let Some(Value::Array(schemas)) = keyword("allOf") {
+        for schema in schemas {
+            check(schema, value, pointer)?;
+        }
+    }
+    if let Some(Value::Array(schemas)) = keyword("anyOf") {
+        if !schemas
+            .iter()
+            .any(|schema| check(schema, value, pointer).is_ok())
+        {
+            return Err(format!("{}: no schema in anyOf matched", pointer));
+        }
+    }
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = keyword("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        return Err(format!("{}: missing property '{}'", pointer, name));
+                    }
+                }
+            }
+            let properties = keyword("properties").and_then(Value::as_object);
+            for (name, property) in object {
+                let property_pointer = format!("{}/{}", pointer, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(schema) => check(schema, property, &property_pointer)?,
+                    None => {
+                        if let Some(schema) = keyword("additionalProperties") {
+                            check(schema, property, &property_pointer)?;
+                        }
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            if count("minItems").is_some_and(|min| items.len() < min) {
+                return Err(format!("{}: too few items", pointer));
+            }
+            if count("maxItems").is_some_and(|max| items.len() > max) {
+                return Err(format!("{}: too many items", pointer));
+            }
+            if let Some(schema) = keyword("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(schema, item, &format!("{}/{}", pointer, index))?;
+                }
+            }
+        }
+        Value::String(string) => {
+            let length = string.chars().count();
+            if count("minLength").is_some_and(|min| length < min) {
+                return Err(format!("{}: string is too short", pointer));
+            }
+            if count("maxLength").is_some_and(|max| length > max) {
+                return Err(format!("{}: string is too long", pointer));
+            }
+        }
+        Value::Number(value) => {
+            let value = value.as_f64().unwrap_or_default();
+            if number("minimum").is_some_and(|min| value < min) {
+                return Err(format!("{}: {} is less than the minimum", pointer, value));
+            }
+            if number("maximum").is_some_and(|max| value > max) {
+                return Err(format!(
+                    "{}: {} is greater than the maximum",
+                    pointer, value
+                ));
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Implement `Validator` to `Schema`, deliveries without parsed payload are invalid
+#[cfg(feature = "schema")]
+impl Validator for Schema {
+    fn validate(&self, delivery: &Delivery) -> Result<(), String> {
+        match &delivery.payload {
+            Some(payload) => self.check(payload),
+            None => Err("Payload is not JSON".to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test validating values against schemas
+    #[test]
+    fn schema_check() {
+        let schema = Schema::from_str(
+            r#"{
+                "type": "object",
+                "required": ["ref", "commits"],
+                "properties": {
+                    "ref": {"type": "string", "minLength": 1},
+                    "commits": {"type": "array", "items": {"type": "object", "required": ["id"]}},
+                    "forced": {"type": "boolean"}
+                },
+                "additionalProperties": {"type": ["string", "integer"]}
+            }"#,
+        )
+        .unwrap();
+        let check = |value: &str| schema.check(&serde_json::from_str(value).unwrap());
+        assert_eq!(
+            check(r#"{"ref": "refs/heads/master", "commits": [{"id": "abc123"}], "size": 1}"#),
+            Ok(())
+        );
+        assert_eq!(
+            check(r#"{"ref": "refs/heads/master"}"#),
+            Err(": missing property 'commits'".to_string())
+        );
+        assert_eq!(
+            check(r#"{"ref": "", "commits": []}"#),
+            Err("/ref: string is too short".to_string())
+        );
+        assert_eq!(
+            check(r#"{"ref": "refs/heads/master", "commits": [{}]}"#),
+            Err("/commits/0: missing property 'id'".to_string())
+        );
+        assert_eq!(
+            check(r#"{"ref": "refs/heads/master", "commits": [], "forced": "yes"}"#),
+            Err(r#"/forced: expected "boolean", found string"#.to_string())
+        );
+        assert!(check(r#"{"ref": "refs/heads/master", "commits": [], "size": 1.5}"#).is_err());
+    }
+}
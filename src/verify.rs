@@ -0,0 +1,305 @@
+//! Verification of deliveries
+//!
+//! The checks hooks authenticate deliveries with, usable on their own, e.g. for bodies received through a queue or
+//! forwarded by a proxy. Each takes the secret, the body as received and the value of the header carrying the
+//! signature or token:
+//!
+//! ```
+//! extern crate rifling;
+//!
+//! use rifling::{verify, Error};
+//!
+//! let body = b"{\"zen\": \"Keep it logically awesome.\"}";
+//! let signature = "sha256=0000000000000000000000000000000000000000000000000000000000000000";
+//! # if rifling::crypto::is_available() {
+//! assert_eq!(verify::github("secret", body, signature), Err(Error::InvalidSignature));
+//! # }
+//! assert_eq!(verify::gitlab("secret", "secret"), Ok(()));
+//! ```
+//!
+//! They fail with `Error::MissingSignature` if the header is empty, `Error::InvalidSignature` if it doesn't match
+//! the secret, or `Error::CryptoUnavailable` if no cryptography library able to verify it is enabled (see
+//! `rifling::crypto`).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::crypto::{self, Algorithm, SignatureEncoding};
+use super::error::Error;
+
+/// Fail with `Error::MissingSignature` if the header is empty
+fn present(header: &str) -> Result<&str, Error> {
+    match header.trim() {
+        "" => Err(Error::MissingSignature),
+        header => Ok(header),
+    }
+}
+
+/// Fail with `Error::CryptoUnavailable` if signatures can't be verified
+fn hmac_available() -> Result<(), Error> {
+    if crypto::is_available() {
+        Ok(())
+    } else {
+        warn!("Unable to authenticate signed payload due to lack of cryptography support");
+        Err(Error::CryptoUnavailable)
+    }
+}
+
+/// Compare the token with the secret in constant time
+fn token_matches(secret: &str, token: &str) -> bool {
+    secret.len() == token.len()
+        && secret
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Verify the HMAC digest (without prefix) of the body with any of the algorithms
+pub fn hmac(
+    algorithms: &[Algorithm],
+    encoding: SignatureEncoding,
+    secret: &str,
+    body: &[u8],
+    digest: &str,
+) -> Result<(), Error> {
+    hmac_available()?;
+    if let Some(signature) = encoding.decode(digest) {
+        debug!("Validating payload with given secret");
+        let valid = algorithms
+            .iter()
+            .any(|algorithm| crypto::verify_hmac(*algorithm, secret.as_bytes(), body, &signature));
+        if valid {
+            return Ok(());
+        }
+    }
+    debug!("Invalid signature");
+    Err(Error::InvalidSignature)
+}
+
+/// Verify the `X-Hub-Signature-256` (or `X-Hub-Signature`) header of GitHub, e.g. `sha256=<hex digest>`
+pub fn github(secret: &str, body: &[u8], signature: &str) -> Result<(), Error> {
+    hmac_available()?;
+    match crypto::split_signature(present(signature)?) {
+        (Some(algorithm), digest) => {
+            hmac(&[algorithm], SignatureEncoding::Hex, secret, body, digest)
+        }
+        (None, _) => {
+            debug!("Signature without algorithm");
+            Err(Error::InvalidSignature)
+        }
+    }
+}
+
+/// Verify the `X-Npm-Signature` header of npm hooks, e.g. `sha256=<hex digest>`
+pub fn npm(secret: &str, body: &[u8], signature: &str) -> Result<(), Error> {
+    github(secret, body, signature)
+}
+
+/// Verify the `X-Gitea-Signature` header of Gitea (or `X-Forgejo-Signature` of Forgejo), a hex HMAC-SHA256 digest
+pub fn gitea(secret: &str, body: &[u8], signature: &str) -> Result<(), Error> {
+    hmac_available()?;
+    let signature = present(signature)?;
+    hmac(
+        &[Algorithm::Sha256],
+        SignatureEncoding::Hex,
+        secret,
+        body,
+        signature,
+    )
+}
+
+/// Verify the token sent as is by the provider (e.g. in the `X-Gitlab-Token` header), it does not require any
+/// cryptography library
+pub fn token(secret: &str, token: &str) -> Result<(), Error> {
+    if token_matches(secret, present(token)?) {
+        Ok(())
+    } else {
+        debug!("Invalid token");
+        Err(Error::InvalidSignature)
+    }
+}
+
+/// Verify the `X-Gitlab-Token` header of GitLab
+pub fn gitlab(secret: &str, token_header: &str) -> Result<(), Error> {
+    token(secret, token_header)
+}
+
+/// Verify the `X-JFrog-Event-Auth` header of JFrog Artifactory
+pub fn artifactory(secret: &str, token_header: &str) -> Result<(), Error> {
+    token(secret, token_header)
+}
+
+/// Verify the `X-Gitee-Token` header of Gitee sent at the time (`X-Gitee-Timestamp`)
+///
+/// The token is either the password itself, or the base64 encoded HMAC-SHA256 signature of
+/// `"{timestamp}\n{secret}"` when signing with a secret.
+pub fn gitee(secret: &str, token: &str, timestamp: Option<SystemTime>) -> Result<(), Error> {
+    let token = present(token)?;
+    if token_matches(secret, token) {
+        return Ok(());
+    }
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => {
+            debug!("Invalid token");
+            return Err(Error::InvalidSignature);
+        }
+    };
+    hmac_available()?;
+    let millis = timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    let string_to_sign = format!("{}\n{}", millis, secret);
+    // The signature may be URL encoded
+    let token = token
+        .replace("%2B", "+")
+        .replace("%2F", "/")
+        .replace("%3D", "=");
+    hmac(
+        &[Algorithm::Sha256],
+        SignatureEncoding::Base64,
+        secret,
+        string_to_sign.as_bytes(),
+        &token,
+    )
+}
+
+/// Verify the `Circleci-Signature` header of CircleCI, `v1=<hex encoded HMAC-SHA256>`
+///
+/// The header may contain multiple comma separated signatures, one of them has to be valid.
+pub fn circleci(secret: &str, body: &[u8], signature: &str) -> Result<(), Error> {
+    hmac_available()?;
+    let valid = present(signature)?
+        .split(',')
+        .filter_map(|signature| signature.trim().strip_prefix("v1="))
+        .any(|digest| {
+            hmac(
+                &[Algorithm::Sha256],
+                SignatureEncoding::Hex,
+                secret,
+                body,
+                digest,
+            )
+            .is_ok()
+        });
+    if valid {
+        Ok(())
+    } else {
+        debug!("Invalid signature");
+        Err(Error::InvalidSignature)
+    }
+}
+
+/// Verify the `X-Signature-Ed25519` header of Discord, signing the `X-Signature-Timestamp` header and the body with
+/// the hex encoded public key of the application (requires `crypto-use-ring`)
+pub fn discord(
+    public_key: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+) -> Result<(), Error> {
+    if !crypto::is_ed25519_available() {
+        warn!("Unable to authenticate signed payload due to lack of Ed25519 support");
+        return Err(Error::CryptoUnavailable);
+    }
+    let signature = present(signature)?;
+    let timestamp = present(timestamp)?;
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    let valid = match (
+        SignatureEncoding::Hex.decode(public_key),
+        SignatureEncoding::Hex.decode(signature),
+    ) {
+        (Some(public_key), Some(signature)) => {
+            crypto::verify_ed25519(&public_key, &message, &signature)
+        }
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        debug!("Invalid signature");
+        Err(Error::InvalidSignature)
+    }
+}
+
+/// Verify the `Authorization` header of Grafana
+///
+/// The secret is either the bearer token, or the credentials of basic authentication: `user:password`
+/// or the password alone.
+pub fn grafana(secret: &str, authorization: &str) -> Result<(), Error> {
+    let authorization = present(authorization)?;
+    let valid = if let Some(token) = authorization.strip_prefix("Bearer ") {
+        token_matches(secret, token.trim())
+    } else if let Some(credentials) = authorization.strip_prefix("Basic ") {
+        SignatureEncoding::Base64
+            .decode(credentials)
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .is_some_and(|credentials| {
+                token_matches(secret, &credentials)
+                    || credentials
+                        .split_once(':')
+                        .is_some_and(|(_, password)| token_matches(secret, password))
+            })
+    } else {
+        false
+    };
+    if valid {
+        Ok(())
+    } else {
+        debug!("Invalid credentials");
+        Err(Error::InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test verifying signatures of GitHub, Gitea and CircleCI
+    #[cfg(any(feature = "crypto-use-rustcrypto", feature = "crypto-use-ring"))]
+    #[test]
+    fn signatures() {
+        let body = b"The quick brown fox jumps over the lazy dog";
+        let digest = "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8";
+        assert_eq!(github("key", body, &format!("sha256={}", digest)), Ok(()));
+        assert_eq!(
+            github("secret", body, &format!("sha256={}", digest)),
+            Err(Error::InvalidSignature)
+        );
+        assert_eq!(github("key", body, digest), Err(Error::InvalidSignature));
+        assert_eq!(github("key", body, ""), Err(Error::MissingSignature));
+        assert_eq!(gitea("key", body, digest), Ok(()));
+        assert_eq!(npm("key", body, &format!("sha256={}", digest)), Ok(()));
+        assert_eq!(
+            circleci("key", body, &format!("v1=00, v1={}", digest)),
+            Ok(())
+        );
+        assert_eq!(circleci("key", body, "v1=00"), Err(Error::InvalidSignature));
+        let timestamp = UNIX_EPOCH + std::time::Duration::from_millis(1_576_800_000_000);
+        let signature =
+            crypto::sign_hmac(Algorithm::Sha256, b"secret", b"1576800000000\nsecret").unwrap();
+        let token = base64::encode(&signature);
+        assert_eq!(gitee("secret", &token, Some(timestamp)), Ok(()));
+        assert_eq!(gitee("secret", &token, None), Err(Error::InvalidSignature));
+    }
+
+    /// Test verifying tokens and credentials
+    #[test]
+    fn tokens() {
+        assert_eq!(gitlab("secret", "secret"), Ok(()));
+        assert_eq!(gitlab("secret", "secreT"), Err(Error::InvalidSignature));
+        assert_eq!(gitlab("secret", "secret2"), Err(Error::InvalidSignature));
+        assert_eq!(gitlab("secret", ""), Err(Error::MissingSignature));
+        assert_eq!(artifactory("secret", "secret"), Ok(()));
+        assert_eq!(gitee("secret", "secret", None), Ok(()));
+        assert_eq!(grafana("secret", "Bearer secret"), Ok(()));
+        // admin:secret
+        assert_eq!(grafana("secret", "Basic YWRtaW46c2VjcmV0"), Ok(()));
+        assert_eq!(
+            grafana("secret", "Basic YWRtaW46"),
+            Err(Error::InvalidSignature)
+        );
+    }
+}
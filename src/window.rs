@@ -0,0 +1,210 @@
+//! Active windows of hooks
+//!
+//! Hooks can be restricted to a window of the week with `Hook::active_between`, e.g. to deploy automatically during
+//! business hours only:
+//!
+//! ```
+//! extern crate chrono;
+//! extern crate rifling;
+//!
+//! use chrono::{Utc, Weekday};
+//! use rifling::window::OutOfWindow;
+//! use rifling::{Delivery, Hook};
+//!
+//! let hook = Hook::new("push", None, |_: &Delivery| println!("Deploying"))
+//!     .active_between("09:00".."18:00", Weekday::Mon..=Weekday::Fri, Utc)
+//!     .unwrap()
+//!     .outside_window(OutOfWindow::Queue);
+//! ```
+//!
+//! Times are in the time zone given, any `chrono::TimeZone` (e.g. `Utc`, `Local`, a `FixedOffset` or a zone of
+//! `chrono-tz`). Windows ending before they start span midnight (e.g. `"22:00".."06:00"`, the days being those the
+//! windows open on), windows starting and ending at the same time last the whole days.
+//!
+//! Deliveries received outside the window are dropped by default, they can be queued until the window opens again
+//! (in the background like retries) or dead-lettered: reported as `Error::OutsideWindow` to the
+//! error callback and kept as a failure in the delivery store. Deliveries are counted as handled by such hooks in
+//! all cases.
+
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+
+use super::error::Error;
+
+/// What happens to deliveries received outside the active window of a hook, see `Hook::outside_window`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutOfWindow {
+    /// Ignore the delivery
+    #[default]
+    Drop,
+    /// Run the hook with the delivery once the window opens
+    Queue,
+    /// Report the delivery as `Error::OutsideWindow` and keep it as a failure in the delivery store
+    DeadLetter,
+}
+
+/// Times of the week a hook is active, see `Hook::active_between`
+#[derive(Clone)]
+pub struct ActiveWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    days: [bool; 7], // from Monday
+    local: Arc<dyn Fn(DateTime<Utc>) -> NaiveDateTime + Send + Sync>,
+}
+
+/// Parse the time of a window
+fn parse_time(time: &str) -> Result<NaiveTime, Error> {
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| Error::InvalidWindow(time.to_string()))
+}
+
+/// The main impl clause of `ActiveWindow`
+impl ActiveWindow {
+    /// Create a window opening between the times (`HH:MM`) on the days, in the time zone
+    ///
+    /// Panics if the times are not `HH:MM`, see `ActiveWindow::try_new`.
+    pub fn new<Tz>(hours: Range<&str>, days: RangeInclusive<Weekday>, tz: Tz) -> Self
+    where
+        Tz: TimeZone + Send + Sync + 'static,
+    {
+        Self::try_new(hours, days, tz).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Create a window opening between the times (`HH:MM`) on the days, in the time zone, failing with
+    /// `Error::InvalidWindow` if the times are not `HH:MM`
+    pub fn try_new<Tz>(
+        hours: Range<&str>,
+        days: RangeInclusive<Weekday>,
+        tz: Tz,
+    ) -> Result<Self, Error>
+    where
+        Tz: TimeZone + Send + Sync + 'static,
+    {
+        let mut active = [false; 7];
+        let mut day = *days.start();
+        loop {
+            active[day.num_days_from_monday() as usize] = true;
+            if day == *days.end() {
+                break;
+            }
+            day = day.succ();
+        }
+        Ok(Self {
+            start: parse_time(hours.start)?,
+            end: parse_time(hours.end)?,
+            days: active,
+            local: Arc::new(move |now: DateTime<Utc>| now.with_timezone(&tz).naive_local()),
+        })
+    }
+
+    /// Test if the window is open at the time
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let local = (self.local)(now);
+        let (time, day) = (local.time(), local.weekday());
+        let opened_on = |day: Weekday| self.days[day.num_days_from_monday() as usize];
+        if self.start < self.end {
+            opened_on(day) && time >= self.start && time < self.end
+        } else if self.start > self.end {
+            // Spanning midnight, the end belongs to the window opened the day before
+            (opened_on(day) && time >= self.start) || (opened_on(day.pred()) && time < self.end)
+        } else {
+            opened_on(day)
+        }
+    }
+
+    /// Time until the window opens, zero if it's open
+    pub fn opens_in(&self, now: DateTime<Utc>) -> Duration {
+        if self.contains(now) {
+            return Duration::from_secs(0);
+        }
+        let local = (self.local)(now);
+        (0..=7)
+            .filter_map(|days| {
+                let opening = (local.date() + chrono::Duration::days(days)).and_time(self.start);
+                let day = opening.weekday().num_days_from_monday() as usize;
+                Some(opening).filter(|opening| *opening > local && self.days[day])
+            })
+            .next()
+            .and_then(|opening| (opening - local).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Implement `Debug` to `ActiveWindow`, the time zone is elided
+impl fmt::Debug for ActiveWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ActiveWindow")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .field("days", &self.days)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Test windows within business hours, in another time zone
+    #[test]
+    fn business_hours() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let window = ActiveWindow::new("09:00".."18:00", Weekday::Mon..=Weekday::Fri, tz);
+        // Monday 2019-12-16, 10:00 and 20:00 in UTC+2
+        assert!(window.contains(at("2019-12-16T08:00:00Z")));
+        assert!(!window.contains(at("2019-12-16T18:00:00Z")));
+        assert_eq!(
+            window.opens_in(at("2019-12-16T18:00:00Z")),
+            Duration::from_secs(13 * 3600)
+        );
+        // Saturday, opens on Monday
+        assert!(!window.contains(at("2019-12-21T08:00:00Z")));
+        assert_eq!(
+            window.opens_in(at("2019-12-21T07:00:00Z")),
+            Duration::from_secs(48 * 3600)
+        );
+        assert_eq!(
+            window.opens_in(at("2019-12-16T08:00:00Z")),
+            Duration::from_secs(0)
+        );
+    }
+
+    /// Test windows spanning midnight and the weekend
+    #[test]
+    fn overnight() {
+        let window = ActiveWindow::new("22:00".."06:00", Weekday::Fri..=Weekday::Sun, Utc);
+        // Friday 23:00, Saturday 05:00, Monday 05:00 and Friday 05:00
+        assert!(window.contains(at("2019-12-20T23:00:00Z")));
+        assert!(window.contains(at("2019-12-21T05:00:00Z")));
+        assert!(window.contains(at("2019-12-23T05:00:00Z")));
+        assert!(!window.contains(at("2019-12-20T05:00:00Z")));
+        let all_day = ActiveWindow::new("00:00".."00:00", Weekday::Sat..=Weekday::Sat, Utc);
+        assert!(all_day.contains(at("2019-12-21T23:59:00Z")));
+        assert!(!all_day.contains(at("2019-12-22T00:00:00Z")));
+    }
+
+    /// Test rejecting times which are not `HH:MM`
+    #[test]
+    fn invalid_times() {
+        for hours in ["9am".."18:00", "09:00".."24:00", "".."18:00"] {
+            let error = ActiveWindow::try_new(hours.clone(), Weekday::Mon..=Weekday::Fri, Utc)
+                .map(|_| ())
+                .unwrap_err();
+            assert!(matches!(error, Error::InvalidWindow(_)), "{:?}", hours);
+        }
+        assert_eq!(
+            ActiveWindow::try_new("09:00".."6pm", Weekday::Mon..=Weekday::Fri, Utc).map(|_| ()),
+            Err(Error::InvalidWindow("6pm".to_string()))
+        );
+    }
+}